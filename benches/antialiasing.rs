@@ -0,0 +1,132 @@
+use std::sync::Arc;
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use gamezap::{
+    post_process::{PostEffect, ShaderEffect},
+    renderer::Renderer,
+};
+use smaa::{SmaaMode, SmaaTarget};
+
+const WIDTH: u32 = 1920;
+const HEIGHT: u32 = 1080;
+
+/// Slanted black and white cells, so both passes have plenty of aliased edges to smooth
+const SCENE_SHADER: &str = "
+    @fragment
+    fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+        let cell = vec2<u32>(in.uv * 64.0 + in.uv.yx * 8.0);
+        return vec4<f32>(vec3<f32>(f32((cell.x + cell.y) % 2u)), 1.0);
+    }
+";
+
+struct Target {
+    device: Arc<wgpu::Device>,
+    queue: Arc<wgpu::Queue>,
+    scene: ShaderEffect,
+    fxaa: ShaderEffect,
+    smaa: SmaaTarget,
+    scene_view: wgpu::TextureView,
+    output_view: wgpu::TextureView,
+}
+
+/// `None` without an adapter
+fn target() -> Option<Target> {
+    let instance = wgpu::Instance::default();
+    let adapter =
+        pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions::default()))?;
+    let (device, queue) = pollster::block_on(adapter.request_device(
+        &wgpu::DeviceDescriptor {
+            label: Some("Bench Device"),
+            required_features: wgpu::Features::empty(),
+            required_limits: wgpu::Limits::default(),
+            memory_hints: wgpu::MemoryHints::default(),
+        },
+        None,
+    ))
+    .ok()?;
+    let (device, queue) = (Arc::new(device), Arc::new(queue));
+
+    let format = Renderer::HEADLESS_FORMAT;
+    let create_view = |label: &str| {
+        device
+            .create_texture(&wgpu::TextureDescriptor {
+                label: Some(label),
+                size: wgpu::Extent3d {
+                    width: WIDTH,
+                    height: HEIGHT,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format,
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                    | wgpu::TextureUsages::TEXTURE_BINDING,
+                view_formats: &[],
+            })
+            .create_view(&wgpu::TextureViewDescriptor::default())
+    };
+    let scene_view = create_view("Bench Scene Target");
+    let output_view = create_view("Bench Output Target");
+
+    Some(Target {
+        scene: ShaderEffect::new(device.clone(), format, "Bench Scene", SCENE_SHADER),
+        fxaa: ShaderEffect::fxaa(device.clone(), format),
+        smaa: SmaaTarget::new(&device, &queue, WIDTH, HEIGHT, format, SmaaMode::Smaa1X),
+        device,
+        queue,
+        scene_view,
+        output_view,
+    })
+}
+
+/// Draws the scene into the target's scene texture and runs FXAA from it onto the output, the
+/// same way [gamezap::post_process::PostProcessStack] does
+fn fxaa_frame(target: &Target) {
+    let mut encoder = target
+        .device
+        .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Bench Encoder"),
+        });
+    target
+        .scene
+        .apply(&mut encoder, &target.output_view, &target.scene_view);
+    target
+        .fxaa
+        .apply(&mut encoder, &target.scene_view, &target.output_view);
+    target.queue.submit(Some(encoder.finish()));
+    target.device.poll(wgpu::Maintain::Wait);
+}
+
+/// Draws the scene into SMAA's frame and resolves it onto the output
+fn smaa_frame(target: &mut Target) {
+    let frame = target
+        .smaa
+        .start_frame(&target.device, &target.queue, &target.output_view);
+    let mut encoder = target
+        .device
+        .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Bench Encoder"),
+        });
+    target.scene.apply(&mut encoder, &target.scene_view, &frame);
+    target.queue.submit(Some(encoder.finish()));
+    frame.resolve();
+    target.device.poll(wgpu::Maintain::Wait);
+}
+
+fn antialiasing(c: &mut Criterion) {
+    let Some(mut target) = target() else {
+        eprintln!("No adapter found, skipping the antialiasing benchmark");
+        return;
+    };
+    let mut group = c.benchmark_group("antialiasing_1080p");
+    group.sample_size(20);
+
+    group.bench_function("fxaa", |b| b.iter(|| fxaa_frame(&target)));
+    group.bench_function("smaa", |b| b.iter(|| smaa_frame(&mut target)));
+
+    group.finish();
+}
+
+criterion_group!(benches, antialiasing);
+criterion_main!(benches);
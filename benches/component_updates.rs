@@ -0,0 +1,131 @@
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    rc::Rc,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use gamezap::{
+    ecs::{component::Component, entity::EntityId, scene::Scene},
+    EngineDetails, EngineSystems,
+};
+
+const COMPONENT_COUNT: u32 = 10_000;
+
+/// Counts allocations, so the bench can show that updating doesn't copy the components
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+mod counter {
+    use gamezap::{ecs::scene::TextParams, new_component};
+
+    new_component!(Counter { count: u64 });
+
+    impl Counter {
+        pub fn boxed() -> Box<Self> {
+            Box::new(Counter {
+                count: 0,
+                parent: EntityId::MAX,
+                id: (EntityId::MAX, TypeId::of::<Self>(), 0),
+            })
+        }
+    }
+
+    impl ComponentSystem for Counter {
+        fn update(
+            &mut self,
+            _device: Arc<Device>,
+            _queue: Arc<Queue>,
+            _component_map: &mut AllComponents,
+            _engine_details: Rc<Mutex<EngineDetails>>,
+            _engine_systems: Rc<Mutex<EngineSystems>>,
+            _concept_manager: Rc<Mutex<ConceptManager>>,
+            _active_camera_id: Option<EntityId>,
+            _entities: &mut Vec<Entity>,
+            _materials: Option<&mut (Vec<Material>, usize)>,
+            _compute_pipelines: &mut [ComputePipeline],
+            _text_items: &mut Vec<TextParams>,
+        ) {
+            self.count += 1;
+        }
+    }
+}
+
+fn device() -> Option<(Arc<wgpu::Device>, Arc<wgpu::Queue>)> {
+    let instance = wgpu::Instance::default();
+    let adapter =
+        pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions::default()))?;
+    let (device, queue) = pollster::block_on(adapter.request_device(
+        &wgpu::DeviceDescriptor {
+            label: Some("Bench Device"),
+            required_features: wgpu::Features::empty(),
+            required_limits: wgpu::Limits::default(),
+            memory_hints: wgpu::MemoryHints::default(),
+        },
+        None,
+    ))
+    .ok()?;
+    Some((Arc::new(device), Arc::new(queue)))
+}
+
+fn component_updates(c: &mut Criterion) {
+    std::env::set_var("SDL_VIDEODRIVER", "dummy");
+    let Some(engine_systems) = sdl2::init()
+        .and_then(EngineSystems::new)
+        .ok()
+        .map(|engine_systems| Rc::new(Mutex::new(engine_systems)))
+    else {
+        eprintln!("Skipping the component update bench, SDL failed to start");
+        return;
+    };
+    let Some((device, queue)) = device() else {
+        eprintln!("Skipping the component update bench, no adapter");
+        return;
+    };
+    let engine_details = Rc::new(Mutex::new(EngineDetails::new((64, 64))));
+
+    let mut scene = Scene::default();
+    for _ in 0..COMPONENT_COUNT {
+        let components: Vec<Component> = vec![counter::Counter::boxed()];
+        scene.create_entity(EntityId::MAX, true, components, None);
+    }
+    let update = |scene: &mut Scene| {
+        scene.update(
+            device.clone(),
+            queue.clone(),
+            engine_details.clone(),
+            engine_systems.clone(),
+        )
+    };
+
+    update(&mut scene);
+    let allocations_before = ALLOCATIONS.load(Ordering::Relaxed);
+    update(&mut scene);
+    eprintln!(
+        "Allocations per update of {COMPONENT_COUNT} components: {}",
+        ALLOCATIONS.load(Ordering::Relaxed) - allocations_before
+    );
+
+    c.bench_function("update_10k_components", |b| b.iter(|| update(&mut scene)));
+}
+
+criterion_group!(benches, component_updates);
+criterion_main!(benches);
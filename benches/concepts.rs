@@ -0,0 +1,95 @@
+use std::any::{Any, TypeId};
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use gamezap::ecs::{
+    component::ComponentId,
+    concepts::{ConceptHandle, ConceptManager},
+};
+use nalgebra as na;
+
+const BODY_COUNT: u32 = 10_000;
+
+struct Body;
+
+/// Physics-like bodies with the same concepts as a `PhysicsComponent`
+fn bodies() -> (ConceptManager, Vec<ComponentId>) {
+    let mut concept_manager = ConceptManager::default();
+    let ids = (0..BODY_COUNT)
+        .map(|entity_id| {
+            let id = (entity_id, TypeId::of::<Body>(), 0);
+            let concepts: [(&str, Box<dyn Any>); 4] = [
+                ("velocity", Box::new(na::Vector3::<f32>::zeros())),
+                ("net_force", Box::new(na::Vector3::new(0.0_f32, 1.0, 0.0))),
+                ("mass", Box::new(2.0_f32)),
+                ("gravity", Box::new(na::Vector3::new(0.0_f32, -9.81, 0.0))),
+            ];
+            concept_manager.register_component_concepts(
+                id,
+                concepts
+                    .into_iter()
+                    .map(|(name, concept)| (name.to_string(), concept))
+                    .collect(),
+            );
+            id
+        })
+        .collect();
+    (concept_manager, ids)
+}
+
+fn velocity_step(c: &mut Criterion) {
+    let (mut concept_manager, ids) = bodies();
+    let mut group = c.benchmark_group("velocity_step_10k");
+
+    group.bench_function("names", |b| {
+        b.iter(|| {
+            for id in &ids {
+                let mass = *concept_manager
+                    .get_concept::<f32>(*id, "mass".to_string())
+                    .unwrap();
+                let net_force = *concept_manager
+                    .get_concept::<na::Vector3<f32>>(*id, "net_force".to_string())
+                    .unwrap();
+                let gravity = *concept_manager
+                    .get_concept::<na::Vector3<f32>>(*id, "gravity".to_string())
+                    .unwrap();
+                *concept_manager
+                    .get_concept_mut::<na::Vector3<f32>>(*id, "velocity".to_string())
+                    .unwrap() += (net_force / mass + gravity) * black_box(1.0 / 60.0);
+            }
+        })
+    });
+
+    type Handles = (
+        ConceptHandle<f32>,
+        ConceptHandle<na::Vector3<f32>>,
+        ConceptHandle<na::Vector3<f32>>,
+        ConceptHandle<na::Vector3<f32>>,
+    );
+    let handles: Vec<Handles> = ids
+        .iter()
+        .map(|id| {
+            (
+                concept_manager.concept_handle(*id, "mass").unwrap(),
+                concept_manager.concept_handle(*id, "net_force").unwrap(),
+                concept_manager.concept_handle(*id, "gravity").unwrap(),
+                concept_manager.concept_handle(*id, "velocity").unwrap(),
+            )
+        })
+        .collect();
+    group.bench_function("handles", |b| {
+        b.iter(|| {
+            for (mass, net_force, gravity, velocity) in &handles {
+                let mass = *concept_manager.get(*mass).unwrap();
+                let net_force = *concept_manager.get(*net_force).unwrap();
+                let gravity = *concept_manager.get(*gravity).unwrap();
+                *concept_manager.get_mut(*velocity).unwrap() +=
+                    (net_force / mass + gravity) * black_box(1.0 / 60.0);
+            }
+        })
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, velocity_step);
+criterion_main!(benches);
@@ -0,0 +1,190 @@
+use criterion::{criterion_group, criterion_main, Criterion};
+use gamezap::indirect::IndirectDraws;
+use wgpu::util::{DeviceExt, DrawIndexedIndirectArgs};
+
+const OBJECT_COUNT: u32 = 10_000;
+
+const SHADER: &str = "
+    @vertex
+    fn vs_main(@location(0) position: vec3<f32>) -> @builtin(position) vec4<f32> {
+        return vec4<f32>(position, 1.0);
+    }
+
+    @fragment
+    fn fs_main() -> @location(0) vec4<f32> {
+        return vec4<f32>(1.0);
+    }
+";
+
+struct Target {
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    pipeline: wgpu::RenderPipeline,
+    view: wgpu::TextureView,
+    vertex_buffer: wgpu::Buffer,
+    index_buffer: wgpu::Buffer,
+    indirect_buffer: wgpu::Buffer,
+}
+
+/// One small triangle drawn once per object, with the same buffer binds per draw as the scene's
+/// regular and indirect paths. `None` without an adapter
+fn target() -> Option<Target> {
+    let instance = wgpu::Instance::default();
+    let adapter =
+        pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions::default()))?;
+    let (device, queue) = pollster::block_on(adapter.request_device(
+        &wgpu::DeviceDescriptor {
+            label: Some("Bench Device"),
+            required_features: wgpu::Features::empty(),
+            required_limits: wgpu::Limits::default(),
+            memory_hints: wgpu::MemoryHints::default(),
+        },
+        None,
+    ))
+    .ok()?;
+
+    let format = wgpu::TextureFormat::Rgba8Unorm;
+    let view = device
+        .create_texture(&wgpu::TextureDescriptor {
+            label: Some("Bench Target"),
+            size: wgpu::Extent3d {
+                width: 64,
+                height: 64,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        })
+        .create_view(&wgpu::TextureViewDescriptor::default());
+
+    let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some("Bench Shader"),
+        source: wgpu::ShaderSource::Wgsl(SHADER.into()),
+    });
+    let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("Bench Pipeline"),
+        layout: None,
+        vertex: wgpu::VertexState {
+            module: &shader,
+            entry_point: "vs_main",
+            compilation_options: wgpu::PipelineCompilationOptions::default(),
+            buffers: &[wgpu::VertexBufferLayout {
+                array_stride: 12,
+                step_mode: wgpu::VertexStepMode::Vertex,
+                attributes: &wgpu::vertex_attr_array![0 => Float32x3],
+            }],
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: &shader,
+            entry_point: "fs_main",
+            compilation_options: wgpu::PipelineCompilationOptions::default(),
+            targets: &[Some(format.into())],
+        }),
+        primitive: wgpu::PrimitiveState::default(),
+        depth_stencil: None,
+        multisample: wgpu::MultisampleState::default(),
+        multiview: None,
+        cache: None,
+    });
+
+    let vertices: [[f32; 3]; 3] = [[0.0, 0.0, 0.0], [0.01, 0.0, 0.0], [0.0, 0.01, 0.0]];
+    let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: Some("Bench Vertex Buffer"),
+        contents: bytemuck::cast_slice(&vertices),
+        usage: wgpu::BufferUsages::VERTEX,
+    });
+    let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: Some("Bench Index Buffer"),
+        contents: bytemuck::cast_slice(&[0_u32, 1, 2]),
+        usage: wgpu::BufferUsages::INDEX,
+    });
+    let indirect_args = (0..OBJECT_COUNT)
+        .flat_map(|_| {
+            DrawIndexedIndirectArgs {
+                index_count: 3,
+                instance_count: 1,
+                first_index: 0,
+                base_vertex: 0,
+                first_instance: 0,
+            }
+            .as_bytes()
+            .to_vec()
+        })
+        .collect::<Vec<u8>>();
+    let indirect_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: Some("Bench Indirect Buffer"),
+        contents: &indirect_args,
+        usage: wgpu::BufferUsages::INDIRECT,
+    });
+
+    Some(Target {
+        device,
+        queue,
+        pipeline,
+        view,
+        vertex_buffer,
+        index_buffer,
+        indirect_buffer,
+    })
+}
+
+/// Encodes one pass drawing every object, submits it and waits for the GPU
+fn draw_all(target: &Target, indirect: bool) {
+    let mut encoder = target
+        .device
+        .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Bench Encoder"),
+        });
+    {
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Bench Render Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: &target.view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        render_pass.set_pipeline(&target.pipeline);
+        for object in 0..OBJECT_COUNT {
+            render_pass.set_vertex_buffer(0, target.vertex_buffer.slice(..));
+            render_pass.set_index_buffer(target.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+            if indirect {
+                render_pass.draw_indexed_indirect(
+                    &target.indirect_buffer,
+                    object as u64 * IndirectDraws::STRIDE,
+                );
+            } else {
+                render_pass.draw_indexed(0..3, 0, 0..1);
+            }
+        }
+    }
+    target.queue.submit(Some(encoder.finish()));
+    target.device.poll(wgpu::Maintain::Wait);
+}
+
+fn draws(c: &mut Criterion) {
+    let Some(target) = target() else {
+        eprintln!("No adapter found, skipping the indirect draw benchmark");
+        return;
+    };
+    let mut group = c.benchmark_group("draws_10k");
+    group.sample_size(20);
+
+    group.bench_function("regular", |b| b.iter(|| draw_all(&target, false)));
+    group.bench_function("indirect", |b| b.iter(|| draw_all(&target, true)));
+
+    group.finish();
+}
+
+criterion_group!(benches, draws);
+criterion_main!(benches);
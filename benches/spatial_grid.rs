@@ -0,0 +1,70 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use gamezap::ecs::{entity::EntityId, spatial_grid::SpatialGrid};
+use nalgebra as na;
+
+const COLLIDER_COUNT: u32 = 5_000;
+
+/// Boxes scattered over a cube with a fixed seed, so every run compares the same scene
+fn colliders() -> Vec<(EntityId, na::Vector3<f32>, na::Vector3<f32>)> {
+    let mut state = 0x2545_f491_u32;
+    let mut next = || {
+        state ^= state << 13;
+        state ^= state >> 17;
+        state ^= state << 5;
+        state as f32 / u32::MAX as f32
+    };
+    (0..COLLIDER_COUNT)
+        .map(|entity_id| {
+            let center = na::Vector3::new(next(), next(), next()) * 200.0;
+            let half_extents = na::Vector3::repeat(0.5 + next());
+            (entity_id, center - half_extents, center + half_extents)
+        })
+        .collect()
+}
+
+fn overlaps(
+    a: &(EntityId, na::Vector3<f32>, na::Vector3<f32>),
+    b: &(EntityId, na::Vector3<f32>, na::Vector3<f32>),
+) -> bool {
+    (0..3).all(|axis| a.1[axis] <= b.2[axis] && b.1[axis] <= a.2[axis])
+}
+
+fn broad_phase(c: &mut Criterion) {
+    let colliders = colliders();
+    let mut group = c.benchmark_group("broad_phase_5k");
+
+    group.bench_function("brute_force", |b| {
+        b.iter(|| {
+            let mut count = 0;
+            for (i, a) in colliders.iter().enumerate() {
+                for other in &colliders[i + 1..] {
+                    if overlaps(a, other) {
+                        count += 1;
+                    }
+                }
+            }
+            black_box(count)
+        })
+    });
+
+    let mut grid = SpatialGrid::default();
+    group.bench_function("spatial_grid", |b| {
+        b.iter(|| {
+            grid.clear();
+            for (entity_id, min, max) in &colliders {
+                grid.insert(*entity_id, *min, *max);
+            }
+            let count = grid
+                .candidate_pairs()
+                .into_iter()
+                .filter(|(a, b)| overlaps(&colliders[*a as usize], &colliders[*b as usize]))
+                .count();
+            black_box(count)
+        })
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, broad_phase);
+criterion_main!(benches);
@@ -0,0 +1,54 @@
+use gamezap::{
+    ecs::{
+        components::transform_component::TransformComponent,
+        scene::{Scene, TextParams},
+    },
+    new_component,
+};
+
+use nalgebra as na;
+
+new_component!(GamepadInputComponent { speed: f32 });
+
+impl GamepadInputComponent {
+    pub fn new(speed: f32) -> Self {
+        GamepadInputComponent {
+            parent: EntityId::MAX,
+            id: (EntityId::MAX, TypeId::of::<Self>(), 0),
+            speed,
+        }
+    }
+}
+
+impl ComponentSystem for GamepadInputComponent {
+    fn update(
+        &mut self,
+        _device: Arc<Device>,
+        _queue: Arc<Queue>,
+        component_map: &mut AllComponents,
+        engine_details: Rc<Mutex<EngineDetails>>,
+        _engine_systems: Rc<Mutex<EngineSystems>>,
+        concept_manager: Rc<Mutex<ConceptManager>>,
+        _active_camera_id: Option<EntityId>,
+        _entities: &mut Vec<Entity>,
+        _materials: Option<&mut (Vec<Material>, usize)>,
+        _compute_pipelines: &mut [ComputePipeline],
+        _text_items: &mut Vec<TextParams>,
+    ) {
+        let details = engine_details.lock().unwrap();
+        let Some(gamepad) = details.gamepads.first() else {
+            return;
+        };
+
+        let distance = self.speed * details.last_frame_duration.as_secs_f32();
+        // The stick's y axis points down
+        let translation =
+            na::Vector3::new(gamepad.left_stick.x, 0.0, -gamepad.left_stick.y) * distance;
+
+        if let Some(transform) =
+            Scene::get_component::<TransformComponent>(&component_map[&self.parent])
+        {
+            transform.apply_translation(concept_manager, translation);
+        }
+    }
+}
@@ -1,12 +1,11 @@
 use gamezap::{
     ecs::{
         component::Component,
-        components::{
-            physics_component::PhysicsComponent, transform_component::TransformComponent,
-        },
+        components::{physics_component::ImpulseEvent, transform_component::TransformComponent},
         scene::{Scene, TextParams},
     },
     new_component,
+    renderer::FrameCaptured,
 };
 
 use nalgebra as na;
@@ -56,7 +55,7 @@ impl ComponentSystem for KeyboardInputComponent {
         _queue: Arc<Queue>,
         component_map: &mut AllComponents,
         engine_details: Rc<Mutex<EngineDetails>>,
-        _engine_systems: Rc<Mutex<EngineSystems>>,
+        engine_systems: Rc<Mutex<EngineSystems>>,
         concept_manager: Rc<Mutex<ConceptManager>>,
         _active_camera_id: Option<EntityId>,
         _entities: &mut Vec<Entity>,
@@ -71,9 +70,6 @@ impl ComponentSystem for KeyboardInputComponent {
             Some(transform) => transform.create_rotation_matrix(&concept_manager),
             None => na::Matrix4::identity(),
         };
-        let physics_component =
-        Scene::get_component_mut::<PhysicsComponent>(component_map.get_mut(&1).unwrap())
-            .unwrap();
 
         let position_concept = concept_manager
             .get_concept_mut::<na::Vector3<f32>>(
@@ -82,9 +78,9 @@ impl ComponentSystem for KeyboardInputComponent {
             )
             .unwrap();
 
-        let details = engine_details.lock().unwrap();
+        let mut details = engine_details.lock().unwrap();
 
-        let speed = 10.0 / (details.last_frame_duration.as_micros() as f32);
+        let speed = 10.0 / (details.real_frame_duration.as_micros() as f32);
 
         let forward_vector = (camera_rotation_matrix
             * na::Vector3::new(0.0, 0.0, 1.0).to_homogeneous())
@@ -113,15 +109,33 @@ impl ComponentSystem for KeyboardInputComponent {
                 Scancode::Space => {
                     position_concept.y += speed;
                 }
-                Scancode::B => {
-                    physics_component.add_impulse(
-                        na::Vector3::new(-0.00001, 0.0, 0.0),
-                        std::time::Duration::from_secs(1),
-                    );
-                }
                 _ => {}
             }
         }
+
+        if details.input.was_key_pressed(Scancode::B) {
+            engine_systems.lock().unwrap().events.emit(ImpulseEvent {
+                target: None,
+                force: na::Vector3::new(-0.00001, 0.0, 0.0),
+                duration: std::time::Duration::from_secs(1),
+            });
+        }
+
+        if details.input.was_key_pressed(Scancode::F12) {
+            details.capture_frame = true;
+        }
+        for FrameCaptured(image) in engine_systems
+            .lock()
+            .unwrap()
+            .events
+            .events::<FrameCaptured>()
+        {
+            let path = format!("screenshot_{}.png", details.frame_number);
+            match image.save(&path) {
+                Ok(()) => println!("Saved {path}"),
+                Err(err) => println!("Failed to save {path}: {err}"),
+            }
+        }
     }
 
     fn on_event(
@@ -0,0 +1,57 @@
+use bytemuck::{Pod, Zeroable};
+use gamezap::{ecs::scene::TextParams, new_component};
+
+/// Matches `Params` in `examples/shaders/mandelbrot.wgsl`
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod, Zeroable)]
+pub struct MandelbrotParams {
+    pub time: f32,
+    pub max_iterations: u32,
+    pub size: [u32; 2],
+}
+
+new_component!(MandelbrotComponent {
+    pipeline_index: usize,
+    size: (u32, u32)
+});
+
+impl MandelbrotComponent {
+    pub fn new(pipeline_index: usize, size: (u32, u32)) -> Self {
+        MandelbrotComponent {
+            pipeline_index,
+            size,
+            parent: EntityId::MAX,
+            id: (EntityId::MAX, TypeId::of::<Self>(), 0),
+        }
+    }
+}
+
+impl ComponentSystem for MandelbrotComponent {
+    fn update(
+        &mut self,
+        _device: Arc<Device>,
+        queue: Arc<Queue>,
+        _component_map: &mut AllComponents,
+        engine_details: Rc<Mutex<EngineDetails>>,
+        _engine_systems: Rc<Mutex<EngineSystems>>,
+        _concept_manager: Rc<Mutex<ConceptManager>>,
+        _active_camera_id: Option<EntityId>,
+        _entities: &mut Vec<Entity>,
+        _materials: Option<&mut (Vec<Material>, usize)>,
+        compute_pipelines: &mut [ComputePipeline],
+        _text_items: &mut Vec<TextParams>,
+    ) {
+        let time = engine_details.lock().unwrap().time_elapsed.as_secs_f32();
+
+        compute_pipelines[self.pipeline_index]
+            .set_params(
+                &queue,
+                MandelbrotParams {
+                    time,
+                    max_iterations: 256,
+                    size: [self.size.0, self.size.1],
+                },
+            )
+            .unwrap();
+    }
+}
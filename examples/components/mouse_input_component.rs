@@ -60,7 +60,7 @@ impl ComponentSystem for MouseInputComponent {
         let mouse = sdl_context.mouse();
         let is_hidden = mouse.relative_mouse_mode();
 
-        let speed = (details.last_frame_duration.as_micros() as f32) / 1000000.0;
+        let speed = (details.real_frame_duration.as_micros() as f32) / 1000000.0;
         // let speed = 100.0 * details.last_frame_duration.as_micros() as f32;
         if is_hidden {
             if let Some(mouse_state) = details.mouse_state.0 {
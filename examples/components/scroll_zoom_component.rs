@@ -0,0 +1,67 @@
+use gamezap::{
+    ecs::{components::camera_component::CameraComponent, scene::TextParams},
+    new_component,
+};
+
+new_component!(ScrollZoomComponent { speed: f32 });
+
+impl ScrollZoomComponent {
+    /// Zooms the orthographic camera it's attached to by `speed` of its view per wheel step
+    pub fn new(speed: f32) -> Self {
+        ScrollZoomComponent {
+            parent: EntityId::MAX,
+            id: (EntityId::MAX, TypeId::of::<Self>(), 0),
+            speed,
+        }
+    }
+}
+
+impl ComponentSystem for ScrollZoomComponent {
+    fn update(
+        &mut self,
+        _device: Arc<Device>,
+        _queue: Arc<Queue>,
+        _component_map: &mut AllComponents,
+        engine_details: Rc<Mutex<EngineDetails>>,
+        _engine_systems: Rc<Mutex<EngineSystems>>,
+        concept_manager: Rc<Mutex<ConceptManager>>,
+        _active_camera_id: Option<EntityId>,
+        _entities: &mut Vec<Entity>,
+        _materials: Option<&mut (Vec<Material>, usize)>,
+        _compute_pipelines: &mut [ComputePipeline],
+        _text_items: &mut Vec<TextParams>,
+    ) {
+        let details = engine_details.lock().unwrap();
+        let scroll = details.scroll_delta.1 as f32;
+        if scroll == 0.0 {
+            return;
+        }
+
+        let mut concept_manager = concept_manager.lock().unwrap();
+        let camera_id = (self.parent, TypeId::of::<CameraComponent>(), 0);
+        let concept = |concept_manager: &ConceptManager, name: &str| {
+            *concept_manager
+                .get_concept::<f32>(camera_id, name.to_string())
+                .unwrap()
+        };
+
+        // Scrolling away from the user zooms in, showing less of the world
+        let height = (concept(&concept_manager, "ortho_height") * (1.0 - self.speed).powf(scroll))
+            .max(f32::EPSILON);
+        let projection = CameraComponent::orthographic_matrix(
+            height * details.window_aspect_ratio,
+            height,
+            concept(&concept_manager, "near_plane"),
+            concept(&concept_manager, "far_plane"),
+        );
+        *concept_manager
+            .get_concept_mut::<f32>(camera_id, "ortho_height".to_string())
+            .unwrap() = height;
+        *concept_manager
+            .get_concept_mut::<nalgebra::Matrix4<f32>>(
+                camera_id,
+                "view_to_projected_mat".to_string(),
+            )
+            .unwrap() = projection;
+    }
+}
@@ -33,8 +33,10 @@ impl ComponentSystem for TransparencyComponent {
 
         let materials = materials.unwrap();
         let selected_material = &mut materials.0[materials.1];
-        if let Some((_, buffer)) = &selected_material.uniform_buffer_bind_group() {
-            queue.write_buffer(buffer, 0, bytemuck::cast_slice(&[time % 2.0]));
+        if selected_material.uniform_buffer_bind_group().is_some() {
+            selected_material
+                .update_uniform(&queue, time % 2.0)
+                .unwrap();
         }
 
         let output_data = &compute_pipelines[0].pipeline_assets[1];
@@ -50,7 +50,10 @@ impl ComponentSystem for UiComponent {
             &mut renderer,
             "assets\\testing_textures\\dude.png".to_string(),
         );
-        self.image_details = Some(details);
+        match details {
+            Ok(details) => self.image_details = Some(details),
+            Err(err) => println!("ERROR: {:?}", err),
+        }
     }
 
     fn update(
@@ -116,11 +119,15 @@ impl ComponentSystem for UiComponent {
                         engine_details
                             .lock()
                             .unwrap()
-                            .last_frame_duration
+                            .real_frame_duration
                             .as_micros()
                     ));
-                    imgui::Image::new(self.image_details.unwrap().0, self.image_details.unwrap().1)
-                        .build(ui_frame);
+                    match self.image_details {
+                        Some((texture_id, size)) => {
+                            imgui::Image::new(texture_id, size).build(ui_frame)
+                        }
+                        None => ui_frame.text("Image unavailable"),
+                    }
                 });
             _inter.pop();
 
@@ -3,20 +3,26 @@ use std::rc::Rc;
 use algoe::{bivector::Bivector, rotor::Rotor3};
 use components::{
     compute_monitor_component::ComputeMonitorComponent,
-    keyboard_input_component::KeyboardInputComponent, mouse_input_component::MouseInputComponent,
+    gamepad_input_component::GamepadInputComponent,
+    keyboard_input_component::KeyboardInputComponent,
+    mandelbrot_component::{MandelbrotComponent, MandelbrotParams},
+    mouse_input_component::MouseInputComponent,
     transparency_component::TransparencyComponent, ui_component::UiComponent,
 };
 use gamezap::{
+    bloom::Bloom,
     compute::{ComputeData, ComputePipelineType, ComputeTextureData},
     ecs::{
         components::{
+            billboard_component::{BillboardComponent, BillboardMode},
             camera_component::CameraComponent, mesh_component::MeshComponent,
             physics_component::PhysicsComponent, transform_component::TransformComponent, text_component::TextComponent,
         },
         material::Material,
-        scene::{Scene, TextParams},
+        scene::{Scene, TextAnchor, TextParams},
     },
     model::Vertex,
+    post_process::ShaderEffect,
     texture::Texture,
     GameZap,
 };
@@ -27,7 +33,9 @@ extern crate gamezap;
 
 pub mod components {
     pub mod compute_monitor_component;
+    pub mod gamepad_input_component;
     pub mod keyboard_input_component;
+    pub mod mandelbrot_component;
     pub mod mouse_input_component;
     pub mod transparency_component;
     pub mod ui_component;
@@ -60,9 +68,11 @@ async fn main() {
             },
         )
         .antialiasing()
+        .hdr()
         .hide_cursor()
         .build()
-        .await;
+        .await
+        .unwrap();
 
     let mut scene = Scene::default();
     let concept_manager = scene.get_concept_manager();
@@ -70,6 +80,13 @@ async fn main() {
     let device = engine.renderer.device.clone();
     let queue = engine.renderer.queue.clone();
 
+    let scene_format = engine.renderer.scene_format;
+    let bloom = Bloom::new(device.clone(), scene_format, engine.window_size, 1.0, 0.6);
+    let vignette = ShaderEffect::vignette(device.clone(), scene_format, 0.5);
+    let post_process = &mut engine.renderer.post_process;
+    post_process.push(&device, Box::new(bloom));
+    post_process.push(&device, Box::new(vignette));
+
     let mesh_component = MeshComponent::new(
         concept_manager.clone(),
         vec![
@@ -191,11 +208,12 @@ async fn main() {
             Box::new(sword_mesh),
             Box::new(sword_transform),
             Box::new(sword_physics),
+            Box::new(GamepadInputComponent::new(5.0)),
         ],
         Some((vec![sword_material], 0)),
     );
 
-    let cube_material = Material::new(
+    let cube_material = Material::new_with_uniform(
         "examples/shaders/vert.wgsl",
         "examples/shaders/frag2.wgsl",
         vec![Rc::new(
@@ -209,7 +227,7 @@ async fn main() {
             .await
             .unwrap(),
         )],
-        Some(bytemuck::cast_slice(&[0.0_f32])),
+        0.0_f32,
         true,
         device.clone(),
     );
@@ -279,15 +297,13 @@ async fn main() {
                 ComputeData::TextureData((ComputeTextureData::Dimensions((1000, 1000)), true)),
                 ComputeData::TextureData((ComputeTextureData::Dimensions((200, 200)), true)),
             ],
-            output_data_type: vec![gamezap::compute::ComputeOutput::Array(std::mem::size_of::<
-                [f32; 128],
-            >()
-                as u64)],
+            output_data_type: vec![gamezap::compute::ComputeOutput::array::<f32>(128)],
             // output_data_type: vec![gamezap::compute::ComputeOutput::Texture((200, 200))],
+            params: None,
         },
         /* "examples/shaders/compute_2.wgsl",
         (6,1,1),
-        ComputeData::ArrayData([5.0, 6.0, 10.0, 4.0, 0.1, 12.0_f32]), */
+        ComputeData::array(&[5.0, 6.0, 10.0, 4.0, 0.1, 12.0_f32]), */
     );
 
     let compute_monitor_component =
@@ -296,6 +312,85 @@ async fn main() {
     let _compute_entity =
         scene.create_entity(0, true, vec![Box::new(compute_monitor_component)], None);
 
+    let mandelbrot_size = (400, 400);
+    let mandelbrot_pipeline_index = scene
+        .create_compute_pipeline(
+            device.clone(),
+            queue.clone(),
+            "examples/shaders/mandelbrot.wgsl",
+            (mandelbrot_size.0, mandelbrot_size.1, 1),
+            ComputePipelineType {
+                input_data: vec![ComputeData::TextureData((
+                    ComputeTextureData::Dimensions(mandelbrot_size),
+                    true,
+                ))],
+                output_data_type: Vec::new(),
+                params: Some(bytemuck::bytes_of(&MandelbrotParams {
+                    time: 0.0,
+                    max_iterations: 256,
+                    size: [mandelbrot_size.0, mandelbrot_size.1],
+                })),
+            },
+        )
+        .unwrap();
+    // Two diffusion steps that feed each other's input every frame
+    let diffusion_cells = 1000;
+    let mut initial_cells = vec![0.0_f32; diffusion_cells];
+    initial_cells[diffusion_cells / 2] = 1000.0;
+    let diffusion_pipeline_indices =
+        [&initial_cells, &vec![0.0_f32; diffusion_cells]].map(|cells| {
+            scene
+                .create_compute_pipeline(
+                    device.clone(),
+                    queue.clone(),
+                    "examples/shaders/diffusion.wgsl",
+                    (
+                        gamezap::compute::ComputePipeline::workgroups_for(
+                            diffusion_cells as u32,
+                            64,
+                        ),
+                        1,
+                        1,
+                    ),
+                    ComputePipelineType {
+                        input_data: vec![ComputeData::array(cells)],
+                        output_data_type: vec![gamezap::compute::ComputeOutput::array::<f32>(
+                            diffusion_cells,
+                        )],
+                        params: None,
+                    },
+                )
+                .unwrap()
+        });
+    scene
+        .link_compute_outputs(
+            device.clone(),
+            diffusion_pipeline_indices[0],
+            1,
+            diffusion_pipeline_indices[1],
+            0,
+        )
+        .unwrap();
+    scene
+        .link_compute_outputs(
+            device.clone(),
+            diffusion_pipeline_indices[1],
+            1,
+            diffusion_pipeline_indices[0],
+            0,
+        )
+        .unwrap();
+
+    let _mandelbrot_entity = scene.create_entity(
+        0,
+        true,
+        vec![Box::new(MandelbrotComponent::new(
+            mandelbrot_pipeline_index,
+            mandelbrot_size,
+        ))],
+        None,
+    );
+
     let mesh_component = MeshComponent::new(
         concept_manager.clone(),
         vec![
@@ -330,11 +425,11 @@ async fn main() {
         na::Vector3::new(2.0, 2.0, 2.0),
     );
 
-    let test_material = Material::new(
+    let test_material = Material::new_with_uniform(
         "examples/shaders/vert.wgsl",
         "examples/shaders/frag3.wgsl",
         Vec::new(),
-        Some(bytemuck::cast_slice(&[1.0])),
+        1.0_f32,
         true,
         device.clone(),
     );
@@ -346,8 +441,93 @@ async fn main() {
         Some((vec![test_material], 0)),
     );
 
+    let billboard_material = Material::new_with_uniform(
+        "examples/shaders/vert.wgsl",
+        "examples/shaders/frag2.wgsl",
+        vec![Rc::new(
+            Texture::load_texture(
+                "assets/testing_textures/dude.png",
+                false,
+                &device.clone(),
+                &queue,
+                false,
+            )
+            .await
+            .unwrap(),
+        )],
+        0.0_f32,
+        true,
+        device.clone(),
+    );
+
+    let billboard_mesh = MeshComponent::new(
+        concept_manager.clone(),
+        vec![
+            Vertex {
+                position: [-1.0, -1.0, 0.0],
+                tex_coords: [0.0, 1.0],
+                normal: [0.0, 0.0, -1.0],
+            },
+            Vertex {
+                position: [-1.0, 1.0, 0.0],
+                tex_coords: [0.0, 0.0],
+                normal: [0.0, 0.0, -1.0],
+            },
+            Vertex {
+                position: [1.0, -1.0, 0.0],
+                tex_coords: [1.0, 1.0],
+                normal: [0.0, 0.0, -1.0],
+            },
+            Vertex {
+                position: [1.0, 1.0, 0.0],
+                tex_coords: [1.0, 0.0],
+                normal: [0.0, 0.0, -1.0],
+            },
+        ],
+        vec![0, 1, 2, 1, 2, 3],
+    );
+
+    let billboard_transform = TransformComponent::new(
+        concept_manager.clone(),
+        na::Vector3::new(-3.0, 0.0, 4.0),
+        Rotor3::default(),
+        na::Vector3::new(0.5, 0.5, 0.5),
+    );
+
+    scene.create_entity(
+        0,
+        true,
+        vec![
+            Box::new(BillboardComponent::new(BillboardMode::Cylindrical)),
+            Box::new(billboard_transform),
+            Box::new(billboard_mesh),
+        ],
+        Some((vec![billboard_material], 0)),
+    );
+
     scene.set_active_camera(camera);
 
+    let skybox_cubemap = Texture::load_cubemap(
+        &device,
+        &queue,
+        [
+            "assets/skybox/right.png",
+            "assets/skybox/left.png",
+            "assets/skybox/top.png",
+            "assets/skybox/bottom.png",
+            "assets/skybox/front.png",
+            "assets/skybox/back.png",
+        ],
+    )
+    .unwrap();
+    scene.set_skybox(
+        device.clone(),
+        engine.renderer.scene_format,
+        engine.renderer.sample_count,
+        engine.renderer.depth_format,
+        skybox_cubemap,
+    );
+
     let ui_component = UiComponent::new("assets/fonts/inter.ttf");
 
     let _ui_entity = scene.create_entity(0, true, vec![Box::new(ui_component)], None);
@@ -363,6 +543,7 @@ async fn main() {
         weight: glyphon::Weight(200),
         fancy_render: true,
         top_left_position: (0.0, 20.0),
+        anchor: TextAnchor::Screen,
         text_scale: 1.0,
         bounds: glyphon::TextBounds {
             left: 0,
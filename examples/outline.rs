@@ -0,0 +1,162 @@
+use std::rc::Rc;
+
+use algoe::rotor::Rotor3;
+use gamezap::{
+    ecs::{
+        components::{
+            camera_component::CameraComponent, mesh_component::MeshComponent,
+            transform_component::TransformComponent,
+        },
+        material::Material,
+        scene::Scene,
+    },
+    pipeline::{PipelineConfig, StencilConfig},
+    texture::Texture,
+    GameZap,
+};
+
+use nalgebra as na;
+
+extern crate gamezap;
+
+#[tokio::main]
+async fn main() {
+    let sdl_context = sdl2::init().unwrap();
+    let video_subsystem = sdl_context.video().unwrap();
+    let event_pump = sdl_context.event_pump().unwrap();
+    let window_size = (800, 600);
+    let window = video_subsystem
+        .window("Outline", window_size.0, window_size.1)
+        .resizable()
+        .build()
+        .unwrap();
+
+    let mut engine = GameZap::builder()
+        .window_and_renderer(
+            sdl_context,
+            video_subsystem,
+            event_pump,
+            window,
+            wgpu::Color {
+                r: 0.1,
+                g: 0.1,
+                b: 0.15,
+                a: 1.0,
+            },
+        )
+        .stencil()
+        .build()
+        .await
+        .unwrap();
+
+    let mut scene = Scene::default();
+    let concept_manager = scene.get_concept_manager();
+
+    let device = engine.renderer.device.clone();
+    let queue = engine.renderer.queue.clone();
+
+    let camera = scene.create_entity(
+        0,
+        true,
+        vec![
+            Box::new(CameraComponent::new_3d(
+                concept_manager.clone(),
+                window_size,
+                60.0,
+                0.01,
+                200.0,
+            )),
+            Box::new(TransformComponent::new(
+                concept_manager.clone(),
+                na::Vector3::new(0.0, 0.0, -2.0),
+                Rotor3::default(),
+                na::Vector3::new(1.0, 1.0, 1.0),
+            )),
+        ],
+        None,
+    );
+    scene.set_active_camera(camera);
+
+    // The cube marks its pixels in the stencil buffer
+    let cube_material = Material::new(
+        "examples/shaders/vert.wgsl",
+        "examples/shaders/frag.wgsl",
+        vec![Rc::new(
+            Texture::load_texture(
+                "assets/testing_textures/dude.png",
+                false,
+                &device.clone(),
+                &queue,
+                false,
+            )
+            .await
+            .unwrap(),
+        )],
+        None,
+        true,
+        device.clone(),
+    )
+    .with_pipeline_config(PipelineConfig::imported_mesh().with_stencil(StencilConfig::write(1)));
+
+    scene.create_entity(
+        0,
+        true,
+        vec![
+            Box::new(
+                MeshComponent::from_obj(
+                    concept_manager.clone(),
+                    "assets/models/basic_cube.obj",
+                    false,
+                )
+                .unwrap(),
+            ),
+            Box::new(TransformComponent::new(
+                concept_manager.clone(),
+                na::Vector3::new(0.0, 0.0, 0.0),
+                Rotor3::default(),
+                na::Vector3::new(0.3, 0.3, 0.3),
+            )),
+        ],
+        Some((vec![cube_material], 0)),
+    );
+
+    // A slightly larger copy of the cube is only drawn where the cube isn't, leaving its outline
+    let outline_material = Material::new_with_uniform(
+        "examples/shaders/vert.wgsl",
+        "examples/shaders/outline_frag.wgsl",
+        Vec::new(),
+        [1.0_f32, 0.6, 0.0, 1.0],
+        true,
+        device.clone(),
+    )
+    .with_pipeline_config(PipelineConfig {
+        depth_write: false,
+        ..PipelineConfig::imported_mesh().with_stencil(StencilConfig::not_equal(1))
+    });
+
+    scene.create_entity(
+        0,
+        true,
+        vec![
+            Box::new(
+                MeshComponent::from_obj(
+                    concept_manager.clone(),
+                    "assets/models/basic_cube.obj",
+                    false,
+                )
+                .unwrap(),
+            ),
+            Box::new(TransformComponent::new(
+                concept_manager.clone(),
+                na::Vector3::new(0.0, 0.0, 0.0),
+                Rotor3::default(),
+                na::Vector3::new(0.32, 0.32, 0.32),
+            )),
+        ],
+        Some((vec![outline_material], 0)),
+    );
+
+    engine.create_scene(scene);
+
+    engine.main_loop();
+}
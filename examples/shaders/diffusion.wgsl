@@ -0,0 +1,15 @@
+@group(0) @binding(0) var<storage, read_write> current: array<f32>;
+@group(0) @binding(1) var<storage, read_write> next: array<f32>;
+
+@compute @workgroup_size(64)
+fn main(@builtin(global_invocation_id) global_id: vec3<u32>) {
+    let count = arrayLength(&current);
+    let i = global_id.x;
+    if i >= count {
+        return;
+    }
+
+    let left = current[max(i, 1u) - 1u];
+    let right = current[min(i + 1u, count - 1u)];
+    next[i] = (left + current[i] + right) / 3.0;
+}
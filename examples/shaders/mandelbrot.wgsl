@@ -0,0 +1,37 @@
+struct Params {
+    time: f32,
+    max_iterations: u32,
+    size: vec2<u32>,
+}
+
+@group(0) @binding(0) var output: texture_storage_2d<rgba8unorm, read_write>;
+
+#ifdef PUSH_CONSTANT_PARAMS
+var<push_constant> params: Params;
+#else
+@group(0) @binding(1) var<uniform> params: Params;
+#endif
+
+@compute @workgroup_size(1)
+fn main(@builtin(global_invocation_id) global_id: vec3<u32>) {
+    if global_id.x >= params.size.x || global_id.y >= params.size.y {
+        return;
+    }
+
+    // Slowly zoom towards a point on the set's boundary
+    let zoom = exp(-0.2 * (params.time % 30.0));
+    let center = vec2f(-0.743643887, 0.131825904);
+    let uv = vec2f(global_id.xy) / vec2f(params.size) * 2.0 - 1.0;
+    let c = center + uv * 1.5 * zoom;
+
+    var z = vec2f(0.0);
+    var iteration = 0u;
+    while iteration < params.max_iterations && dot(z, z) < 4.0 {
+        z = vec2f(z.x * z.x - z.y * z.y, 2.0 * z.x * z.y) + c;
+        iteration++;
+    }
+
+    let t = f32(iteration) / f32(params.max_iterations);
+    let color = vec3f(t, t * t, sqrt(t)) * select(1.0, 0.0, iteration == params.max_iterations);
+    textureStore(output, vec2i(global_id.xy), vec4f(color, 1.0));
+}
@@ -0,0 +1,36 @@
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>,
+    @location(1) world_normal: vec3<f32>,
+    @location(2) world_tangent: vec3<f32>,
+    @location(3) world_bitangent: vec3<f32>,
+}
+
+@group(0) @binding(0)
+var diffuse_texture: texture_2d<f32>;
+@group(0) @binding(1)
+var diffuse_sampler: sampler;
+@group(0) @binding(2)
+var normal_texture: texture_2d<f32>;
+@group(0) @binding(3)
+var normal_sampler: sampler;
+
+const LIGHT_DIRECTION: vec3<f32> = vec3<f32>(0.4, 1.0, 0.6);
+const AMBIENT: f32 = 0.15;
+
+@fragment
+fn main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let diffuse_color = textureSample(diffuse_texture, diffuse_sampler, in.tex_coords);
+
+    // The normal map is stored in tangent space, remapped from [-1, 1] to [0, 1]
+    let tangent_normal = textureSample(normal_texture, normal_sampler, in.tex_coords).xyz * 2.0 - 1.0;
+    let tbn = mat3x3<f32>(
+        normalize(in.world_tangent),
+        normalize(in.world_bitangent),
+        normalize(in.world_normal),
+    );
+    let normal = normalize(tbn * tangent_normal);
+
+    let diffuse_strength = max(dot(normal, normalize(LIGHT_DIRECTION)), 0.0);
+    return vec4<f32>(diffuse_color.rgb * (AMBIENT + diffuse_strength), diffuse_color.a);
+}
@@ -0,0 +1,37 @@
+// Soft particle fragment shader: fades a particle out where it gets close to the geometry behind
+// it. The scene's depth is bound with `Texture::depth_bind_group_layout` and read in a pass that
+// runs after the scene pass, since the depth buffer can't be sampled while it is being written.
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) color: vec4<f32>,
+    @location(1) tex_coords: vec2<f32>,
+}
+
+struct Camera {
+    view_pos: vec4<f32>,
+    view_proj: mat4x4<f32>,
+};
+@group(1) @binding(0)
+var<uniform> camera: Camera;
+
+@group(2) @binding(0)
+var scene_depth: texture_depth_2d;
+
+const NEAR_PLANE: f32 = 0.1;
+const FAR_PLANE: f32 = 100.0;
+const FADE_DISTANCE: f32 = 0.5;
+
+fn linearize_depth(depth: f32) -> f32 {
+    let ndc_depth = depth * 2.0 - 1.0;
+    return (2.0 * NEAR_PLANE * FAR_PLANE) / (FAR_PLANE + NEAR_PLANE - ndc_depth * (FAR_PLANE - NEAR_PLANE));
+}
+
+@fragment
+fn main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let scene_depth = textureLoad(scene_depth, vec2<i32>(in.clip_position.xy), 0);
+    let distance_to_scene = linearize_depth(scene_depth) - linearize_depth(in.clip_position.z);
+    let fade = clamp(distance_to_scene / FADE_DISTANCE, 0.0, 1.0);
+
+    return vec4<f32>(in.color.rgb, in.color.a * fade);
+}
@@ -0,0 +1,169 @@
+use std::rc::Rc;
+
+use algoe::rotor::Rotor3;
+use components::scroll_zoom_component::ScrollZoomComponent;
+use gamezap::{
+    ecs::{
+        components::{
+            camera_component::CameraComponent,
+            sprite_component::{SpriteComponent, SpriteSize},
+            transform_component::TransformComponent,
+        },
+        scene::Scene,
+    },
+    texture::Texture,
+    GameZap,
+};
+
+use nalgebra as na;
+
+extern crate gamezap;
+
+pub mod components {
+    pub mod scroll_zoom_component;
+}
+
+#[tokio::main]
+async fn main() {
+    let sdl_context = sdl2::init().unwrap();
+    let video_subsystem = sdl_context.video().unwrap();
+    let event_pump = sdl_context.event_pump().unwrap();
+    let window_size = (800, 600);
+    let window = video_subsystem
+        .window("Sprites", window_size.0, window_size.1)
+        .resizable()
+        .build()
+        .unwrap();
+
+    let mut engine = GameZap::builder()
+        .window_and_renderer(
+            sdl_context,
+            video_subsystem,
+            event_pump,
+            window,
+            wgpu::Color {
+                r: 0.1,
+                g: 0.1,
+                b: 0.15,
+                a: 1.0,
+            },
+        )
+        .build()
+        .await
+        .unwrap();
+
+    let mut scene = Scene::default();
+    let concept_manager = scene.get_concept_manager();
+
+    let device = engine.renderer.device.clone();
+    let queue = engine.renderer.queue.clone();
+
+    let dude_texture = Rc::new(
+        Texture::load_texture(
+            "assets/testing_textures/dude.png",
+            false,
+            &device.clone(),
+            &queue,
+            false,
+        )
+        .await
+        .unwrap(),
+    );
+    let sheet_texture = Rc::new(
+        Texture::load_texture(
+            "assets/testing_textures/texture.png",
+            false,
+            &device.clone(),
+            &queue,
+            false,
+        )
+        .await
+        .unwrap(),
+    );
+
+    // One world unit per pixel at the starting window size, the wheel zooms in and out
+    let camera = scene.create_entity(
+        0,
+        true,
+        vec![
+            Box::new(CameraComponent::new_ortho(
+                concept_manager.clone(),
+                window_size.0 as f32,
+                window_size.1 as f32,
+                0.1,
+                100.0,
+            )),
+            Box::new(TransformComponent::new(
+                concept_manager.clone(),
+                na::Vector3::new(0.0, 0.0, -10.0),
+                Rotor3::default(),
+                na::Vector3::new(1.0, 1.0, 1.0),
+            )),
+            Box::new(ScrollZoomComponent::new(0.1)),
+        ],
+        None,
+    );
+    scene.set_active_camera(camera);
+
+    // Stays 128 pixels wide however the window is resized
+    for (i, x) in [-250.0, 0.0, 250.0].into_iter().enumerate() {
+        scene.create_entity(
+            0,
+            true,
+            vec![
+                Box::new(TransformComponent::new(
+                    concept_manager.clone(),
+                    na::Vector3::new(x, 100.0, i as f32),
+                    Rotor3::default(),
+                    na::Vector3::new(1.0, 1.0, 1.0),
+                )),
+                Box::new(SpriteComponent::new(SpriteSize::Pixels(na::Vector2::new(
+                    128.0, 128.0,
+                )))),
+            ],
+            Some((
+                vec![SpriteComponent::material(
+                    "examples/shaders/vert.wgsl",
+                    "examples/shaders/frag.wgsl",
+                    dude_texture.clone(),
+                    device.clone(),
+                )],
+                0,
+            )),
+        );
+    }
+
+    // Each quarter of the texture as a frame of a 2x2 sprite sheet
+    for frame in 0..4 {
+        let uv_min = na::Vector2::new((frame % 2) as f32 * 0.5, (frame / 2) as f32 * 0.5);
+        scene.create_entity(
+            0,
+            true,
+            vec![
+                Box::new(TransformComponent::new(
+                    concept_manager.clone(),
+                    na::Vector3::new(-225.0 + frame as f32 * 150.0, -150.0, 0.0),
+                    Rotor3::default(),
+                    na::Vector3::new(1.0, 1.0, 1.0),
+                )),
+                Box::new(
+                    SpriteComponent::new(SpriteSize::World(na::Vector2::new(100.0, 100.0)))
+                        .with_uv_rect(uv_min, na::Vector2::new(0.5, 0.5)),
+                ),
+            ],
+            Some((
+                vec![SpriteComponent::material(
+                    "examples/shaders/vert.wgsl",
+                    "examples/shaders/frag.wgsl",
+                    sheet_texture.clone(),
+                    device.clone(),
+                )],
+                0,
+            )),
+        );
+    }
+
+    engine.create_scene(scene);
+
+    engine.main_loop();
+}
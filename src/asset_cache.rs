@@ -0,0 +1,63 @@
+use std::{collections::HashMap, path::PathBuf, rc::Rc};
+
+use wgpu::{Device, Queue};
+
+use crate::texture::Texture;
+
+/// Shares textures between everything that loads the same file, so each image is only uploaded
+/// to the GPU once. Entries are keyed by canonicalized path and whether the texture is a normal
+/// map, since that changes its format.
+///
+/// Textures are handed out as [Rc], so the cache is single-threaded like the rest of the scene
+/// data. It lives in [crate::EngineSystems] and is reached through its mutex.
+#[derive(Debug, Default)]
+pub struct AssetCache {
+    textures: HashMap<(PathBuf, bool), Rc<Texture>>,
+}
+
+impl AssetCache {
+    /// Returns the cached texture for `path`, loading it from disk first if needed. Relative
+    /// paths are resolved against the current directory
+    pub fn get_or_load(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        path: &str,
+        is_normal_map: bool,
+    ) -> anyhow::Result<Rc<Texture>> {
+        let full_path = std::path::Path::new(&std::env::current_dir()?)
+            .join(path)
+            .canonicalize()?;
+        let key = (full_path, is_normal_map);
+
+        if let Some(texture) = self.textures.get(&key) {
+            return Ok(texture.clone());
+        }
+
+        let bytes = std::fs::read(&key.0)?;
+        let texture = Rc::new(Texture::from_bytes(
+            device,
+            queue,
+            &bytes,
+            path,
+            is_normal_map,
+        )?);
+        self.textures.insert(key, texture.clone());
+
+        Ok(texture)
+    }
+
+    /// Drops the cache's references. Textures still used by materials stay alive until those
+    /// are dropped as well
+    pub fn clear(&mut self) {
+        self.textures.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.textures.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.textures.is_empty()
+    }
+}
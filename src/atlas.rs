@@ -0,0 +1,330 @@
+use std::rc::Rc;
+
+use image::RgbaImage;
+use nalgebra::Vector2;
+use wgpu::{Device, Queue};
+
+use crate::texture::Texture;
+
+#[derive(Debug)]
+pub enum AtlasError {
+    /// The image added at `index` is wider or taller than the atlas can be, padding included
+    ImageTooLarge {
+        index: usize,
+        size: (u32, u32),
+        max_size: u32,
+    },
+    /// The images fit one by one, but not all together
+    DoesNotFit {
+        max_size: u32,
+    },
+    Upload(anyhow::Error),
+}
+
+/// Where an image was packed, in pixels and without its padding
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AtlasRect {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Packs rectangles of `sizes` into shelves of an atlas at most `max_size` pixels wide and tall,
+/// with `padding` pixels kept free around each of them. Returns their rects in the order of
+/// `sizes`, and the atlas size. The width is the smallest power of two that keeps the atlas
+/// about square
+pub fn pack_shelves(
+    sizes: &[(u32, u32)],
+    padding: u32,
+    max_size: u32,
+) -> Result<(Vec<AtlasRect>, (u32, u32)), AtlasError> {
+    let padded = |size: u32| size + padding * 2;
+    for (index, size) in sizes.iter().enumerate() {
+        if padded(size.0) > max_size || padded(size.1) > max_size {
+            return Err(AtlasError::ImageTooLarge {
+                index,
+                size: *size,
+                max_size,
+            });
+        }
+    }
+
+    // Tallest first, so each shelf wastes little space above its shorter images
+    let mut order = (0..sizes.len()).collect::<Vec<_>>();
+    order.sort_by_key(|index| std::cmp::Reverse(sizes[*index].1));
+
+    let widest = sizes.iter().map(|size| padded(size.0)).max().unwrap_or(1);
+    let mut width = widest.max(1).next_power_of_two().min(max_size);
+    loop {
+        let packed = pack_into_width(sizes, &order, padding, width);
+        match packed {
+            Some((rects, height)) if height <= width || width == max_size => {
+                if height > max_size {
+                    return Err(AtlasError::DoesNotFit { max_size });
+                }
+                return Ok((rects, (width, height.max(1))));
+            }
+            _ => width = (width * 2).min(max_size),
+        }
+    }
+}
+
+/// Shelf packs `sizes` in `order` into an atlas `width` pixels wide, returns the rects and the
+/// height they take up
+fn pack_into_width(
+    sizes: &[(u32, u32)],
+    order: &[usize],
+    padding: u32,
+    width: u32,
+) -> Option<(Vec<AtlasRect>, u32)> {
+    let mut rects = vec![
+        AtlasRect {
+            x: 0,
+            y: 0,
+            width: 0,
+            height: 0,
+        };
+        sizes.len()
+    ];
+    let (mut x, mut shelf_y, mut shelf_height) = (0, 0, 0);
+    for index in order {
+        let (image_width, image_height) = sizes[*index];
+        let padded_width = image_width + padding * 2;
+        let padded_height = image_height + padding * 2;
+        if padded_width > width {
+            return None;
+        }
+        if x + padded_width > width {
+            shelf_y += shelf_height;
+            x = 0;
+            shelf_height = 0;
+        }
+        rects[*index] = AtlasRect {
+            x: x + padding,
+            y: shelf_y + padding,
+            width: image_width,
+            height: image_height,
+        };
+        x += padded_width;
+        shelf_height = shelf_height.max(padded_height);
+    }
+    Some((rects, shelf_y + shelf_height))
+}
+
+/// Packs many small images into one texture, so sprites can share a material and only differ
+/// in their UV rects. See [TextureAtlas::uv_rect]
+#[derive(Debug)]
+pub struct AtlasBuilder {
+    images: Vec<RgbaImage>,
+    padding: u32,
+    max_size: u32,
+}
+
+impl Default for AtlasBuilder {
+    fn default() -> Self {
+        AtlasBuilder {
+            images: Vec::new(),
+            padding: 1,
+            max_size: 4096,
+        }
+    }
+}
+
+impl AtlasBuilder {
+    /// Pixels around each image filled with copies of its edge, so filtering and mipmaps don't
+    /// pull in the neighbouring images. `1` by default
+    pub fn padding(mut self, padding: u32) -> Self {
+        self.padding = padding;
+        self
+    }
+
+    /// Largest width and height the atlas may have, `4096` by default
+    pub fn max_size(mut self, max_size: u32) -> Self {
+        self.max_size = max_size;
+        self
+    }
+
+    /// Returns the index to look the image up with in the [TextureAtlas]
+    pub fn add(&mut self, image: RgbaImage) -> usize {
+        self.images.push(image);
+        self.images.len() - 1
+    }
+
+    /// Packs the images without uploading them, along with where each of them went
+    pub fn pack(&self) -> Result<(RgbaImage, Vec<AtlasRect>), AtlasError> {
+        let sizes = self
+            .images
+            .iter()
+            .map(|image| image.dimensions())
+            .collect::<Vec<_>>();
+        let (rects, (width, height)) = pack_shelves(&sizes, self.padding, self.max_size)?;
+
+        let mut atlas = RgbaImage::new(width, height);
+        for (image, rect) in self.images.iter().zip(&rects) {
+            if rect.width == 0 || rect.height == 0 {
+                continue;
+            }
+            let padding = self.padding as i64;
+            for y in -padding..rect.height as i64 + padding {
+                for x in -padding..rect.width as i64 + padding {
+                    let source_x = x.clamp(0, rect.width as i64 - 1) as u32;
+                    let source_y = y.clamp(0, rect.height as i64 - 1) as u32;
+                    atlas.put_pixel(
+                        (rect.x as i64 + x) as u32,
+                        (rect.y as i64 + y) as u32,
+                        *image.get_pixel(source_x, source_y),
+                    );
+                }
+            }
+        }
+        Ok((atlas, rects))
+    }
+
+    pub fn build(&self, device: &Device, queue: &Queue) -> Result<TextureAtlas, AtlasError> {
+        let (image, rects) = self.pack()?;
+        let texture =
+            Texture::from_rgba(device, queue, &image, Some("Texture Atlas"), false, false)
+                .map_err(AtlasError::Upload)?;
+        Ok(TextureAtlas {
+            texture: Rc::new(texture),
+            rects,
+            size: image.dimensions(),
+        })
+    }
+}
+
+/// The packed texture of an [AtlasBuilder] and where each of its images is
+#[derive(Debug)]
+pub struct TextureAtlas {
+    pub texture: Rc<Texture>,
+    rects: Vec<AtlasRect>,
+    size: (u32, u32),
+}
+
+impl TextureAtlas {
+    pub fn rect(&self, index: usize) -> Option<AtlasRect> {
+        self.rects.get(index).copied()
+    }
+
+    /// Start and size of the image in texture coordinates, as taken by
+    /// [crate::ecs::components::sprite_component::SpriteComponent::with_uv_rect]
+    pub fn uv_rect(&self, index: usize) -> Option<(Vector2<f32>, Vector2<f32>)> {
+        let rect = self.rects.get(index)?;
+        let size = Vector2::new(self.size.0 as f32, self.size.1 as f32);
+        Some((
+            Vector2::new(rect.x as f32, rect.y as f32).component_div(&size),
+            Vector2::new(rect.width as f32, rect.height as f32).component_div(&size),
+        ))
+    }
+
+    pub fn size(&self) -> (u32, u32) {
+        self.size
+    }
+
+    pub fn len(&self) -> usize {
+        self.rects.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rects.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use image::Rgba;
+
+    use super::*;
+
+    /// The rect grown by `padding` on every side
+    fn padded(rect: &AtlasRect, padding: u32) -> (u32, u32, u32, u32) {
+        (
+            rect.x - padding,
+            rect.y - padding,
+            rect.x + rect.width + padding,
+            rect.y + rect.height + padding,
+        )
+    }
+
+    #[test]
+    fn padded_rects_never_overlap() {
+        let sizes = (0..40)
+            .map(|i| (3 + i * 7 % 29, 2 + i * 11 % 23))
+            .collect::<Vec<_>>();
+        let padding = 2;
+        let (rects, (width, height)) = pack_shelves(&sizes, padding, 512).unwrap();
+
+        for (rect, size) in rects.iter().zip(&sizes) {
+            assert_eq!((rect.width, rect.height), *size);
+            let (_, _, right, bottom) = padded(rect, padding);
+            assert!(right <= width && bottom <= height);
+        }
+        for (i, a) in rects.iter().enumerate() {
+            for b in &rects[i + 1..] {
+                let (a_left, a_top, a_right, a_bottom) = padded(a, padding);
+                let (b_left, b_top, b_right, b_bottom) = padded(b, padding);
+                let overlaps =
+                    a_left < b_right && b_left < a_right && a_top < b_bottom && b_top < a_bottom;
+                assert!(!overlaps, "{a:?} and {b:?} overlap");
+            }
+        }
+    }
+
+    #[test]
+    fn images_too_large_with_their_padding_are_rejected() {
+        assert!(pack_shelves(&[(10, 10), (60, 5)], 2, 64).is_ok());
+        assert!(matches!(
+            pack_shelves(&[(10, 10), (61, 5)], 2, 64),
+            Err(AtlasError::ImageTooLarge {
+                index: 1,
+                size: (61, 5),
+                max_size: 64
+            })
+        ));
+        assert!(matches!(
+            pack_shelves(&[(5, 61)], 2, 64),
+            Err(AtlasError::ImageTooLarge { index: 0, .. })
+        ));
+    }
+
+    #[test]
+    fn images_that_only_fit_one_by_one_do_not_fit() {
+        assert!(pack_shelves(&[(16, 16); 4], 0, 32).is_ok());
+        assert!(matches!(
+            pack_shelves(&[(16, 16); 5], 0, 32),
+            Err(AtlasError::DoesNotFit { max_size: 32 })
+        ));
+    }
+
+    #[test]
+    fn padding_repeats_the_edge_pixels() {
+        let colors = [
+            Rgba([255, 0, 0, 255]),
+            Rgba([0, 255, 0, 255]),
+            Rgba([0, 0, 255, 255]),
+            Rgba([255, 255, 0, 255]),
+        ];
+        let image = RgbaImage::from_fn(2, 2, |x, y| colors[(y * 2 + x) as usize]);
+        let mut builder = AtlasBuilder::default().padding(2);
+        let index = builder.add(image.clone());
+        let (atlas, rects) = builder.pack().unwrap();
+        let rect = rects[index];
+
+        // Pixel of the atlas at an offset from the image's top left corner
+        let atlas_pixel = |x: i64, y: i64| {
+            *atlas.get_pixel((rect.x as i64 + x) as u32, (rect.y as i64 + y) as u32)
+        };
+        for y in 0..2 {
+            for x in 0..2 {
+                assert_eq!(atlas_pixel(x, y), *image.get_pixel(x as u32, y as u32));
+            }
+        }
+        assert_eq!(atlas_pixel(-2, -2), colors[0]);
+        assert_eq!(atlas_pixel(3, -1), colors[1]);
+        assert_eq!(atlas_pixel(-1, 3), colors[2]);
+        assert_eq!(atlas_pixel(3, 3), colors[3]);
+        assert_eq!(atlas_pixel(-2, 1), colors[2]);
+        assert_eq!(atlas_pixel(1, -2), colors[1]);
+    }
+}
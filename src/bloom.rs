@@ -0,0 +1,275 @@
+use std::sync::Arc;
+
+use bytemuck::{Pod, Zeroable};
+use wgpu::{
+    util::{BufferInitDescriptor, DeviceExt},
+    BindGroup, BindGroupLayout, Buffer, Device, Queue, RenderPipeline, Sampler, TextureView,
+};
+
+use crate::post_process::{
+    create_fullscreen_pipeline, create_post_process_sampler, create_post_process_shader,
+    draw_fullscreen, input_layout_entries, texture_layout_entry, PostEffect,
+};
+
+#[repr(C)]
+#[derive(Pod, Zeroable, Clone, Copy, Debug)]
+struct RawBloomParams {
+    threshold: f32,
+    knee: f32,
+    intensity: f32,
+    _padding: f32,
+}
+
+/// Makes bright pixels glow. Pixels above the threshold are blurred through a chain of
+/// half-size mip levels and added back on top of the scene.
+/// Only an HDR scene (see [crate::renderer::RendererSettings::hdr]) has pixels brighter than
+/// `1.0`, without one use a threshold below that
+#[derive(Debug)]
+pub struct Bloom {
+    device: Arc<Device>,
+    prefilter_pipeline: RenderPipeline,
+    downsample_pipeline: RenderPipeline,
+    upsample_pipeline: RenderPipeline,
+    composite_pipeline: RenderPipeline,
+    bind_group_layout: BindGroupLayout,
+    composite_bind_group_layout: BindGroupLayout,
+    sampler: Sampler,
+    params_buffer: Buffer,
+    color_format: wgpu::TextureFormat,
+    /// Half the window's size and smaller
+    mip_views: Vec<TextureView>,
+    /// Reads each mip level, used to downsample from it and upsample it into the level above
+    mip_bind_groups: Vec<BindGroup>,
+}
+
+impl Bloom {
+    pub const MAX_MIP_LEVELS: u32 = 6;
+
+    /// `threshold` is the brightness above which pixels glow, `intensity` scales the glow
+    pub fn new(
+        device: Arc<Device>,
+        color_format: wgpu::TextureFormat,
+        size: (u32, u32),
+        threshold: f32,
+        intensity: f32,
+    ) -> Self {
+        let shader = create_post_process_shader(
+            &device,
+            "Bloom",
+            include_str!("default-shaders/bloom.wgsl"),
+        );
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Bloom Bind Group Layout"),
+            entries: &input_layout_entries(true),
+        });
+        let mut composite_entries = input_layout_entries(true);
+        composite_entries.push(texture_layout_entry(3));
+        let composite_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Bloom Composite Bind Group Layout"),
+                entries: &composite_entries,
+            });
+
+        let create_pipeline = |entry_point, layout, blend| {
+            create_fullscreen_pipeline(
+                &device,
+                "Bloom",
+                &shader,
+                entry_point,
+                layout,
+                color_format,
+                blend,
+            )
+        };
+        let prefilter_pipeline = create_pipeline("fs_prefilter", &bind_group_layout, None);
+        let downsample_pipeline = create_pipeline("fs_downsample", &bind_group_layout, None);
+        // Each level is added onto the downsampled level above it
+        let upsample_pipeline = create_pipeline(
+            "fs_upsample",
+            &bind_group_layout,
+            Some(wgpu::BlendState {
+                color: wgpu::BlendComponent {
+                    src_factor: wgpu::BlendFactor::One,
+                    dst_factor: wgpu::BlendFactor::One,
+                    operation: wgpu::BlendOperation::Add,
+                },
+                alpha: wgpu::BlendComponent::OVER,
+            }),
+        );
+        let composite_pipeline =
+            create_pipeline("fs_composite", &composite_bind_group_layout, None);
+
+        let params_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("Bloom Params Buffer"),
+            contents: bytemuck::bytes_of(&Self::raw_params(threshold, intensity)),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let sampler = create_post_process_sampler(&device, "Bloom");
+
+        let mut bloom = Bloom {
+            device: device.clone(),
+            prefilter_pipeline,
+            downsample_pipeline,
+            upsample_pipeline,
+            composite_pipeline,
+            bind_group_layout,
+            composite_bind_group_layout,
+            sampler,
+            params_buffer,
+            color_format,
+            mip_views: Vec::new(),
+            mip_bind_groups: Vec::new(),
+        };
+        bloom.resize(&device, size);
+        bloom
+    }
+
+    pub fn set_params(&self, queue: &Queue, threshold: f32, intensity: f32) {
+        queue.write_buffer(
+            &self.params_buffer,
+            0,
+            bytemuck::bytes_of(&Self::raw_params(threshold, intensity)),
+        );
+    }
+
+    fn raw_params(threshold: f32, intensity: f32) -> RawBloomParams {
+        RawBloomParams {
+            threshold,
+            knee: threshold * 0.5,
+            intensity,
+            _padding: 0.0,
+        }
+    }
+
+    fn create_bind_group(
+        &self,
+        input_view: &TextureView,
+        bloom_view: Option<&TextureView>,
+    ) -> BindGroup {
+        let mut entries = vec![
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(input_view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::Sampler(&self.sampler),
+            },
+            wgpu::BindGroupEntry {
+                binding: 2,
+                resource: self.params_buffer.as_entire_binding(),
+            },
+        ];
+        if let Some(bloom_view) = bloom_view {
+            entries.push(wgpu::BindGroupEntry {
+                binding: 3,
+                resource: wgpu::BindingResource::TextureView(bloom_view),
+            });
+        }
+        self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Bloom Bind Group"),
+            layout: if bloom_view.is_some() {
+                &self.composite_bind_group_layout
+            } else {
+                &self.bind_group_layout
+            },
+            entries: &entries,
+        })
+    }
+}
+
+impl PostEffect for Bloom {
+    fn apply(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        input_view: &TextureView,
+        output_view: &TextureView,
+    ) {
+        let clear = wgpu::LoadOp::Clear(wgpu::Color::BLACK);
+
+        let prefilter_bind_group = self.create_bind_group(input_view, None);
+        draw_fullscreen(
+            encoder,
+            "Bloom Prefilter Pass",
+            &self.prefilter_pipeline,
+            &prefilter_bind_group,
+            &self.mip_views[0],
+            clear,
+        );
+
+        for level in 1..self.mip_views.len() {
+            draw_fullscreen(
+                encoder,
+                "Bloom Downsample Pass",
+                &self.downsample_pipeline,
+                &self.mip_bind_groups[level - 1],
+                &self.mip_views[level],
+                clear,
+            );
+        }
+
+        for level in (1..self.mip_views.len()).rev() {
+            draw_fullscreen(
+                encoder,
+                "Bloom Upsample Pass",
+                &self.upsample_pipeline,
+                &self.mip_bind_groups[level],
+                &self.mip_views[level - 1],
+                wgpu::LoadOp::Load,
+            );
+        }
+
+        let composite_bind_group = self.create_bind_group(input_view, Some(&self.mip_views[0]));
+        draw_fullscreen(
+            encoder,
+            "Bloom Composite Pass",
+            &self.composite_pipeline,
+            &composite_bind_group,
+            output_view,
+            wgpu::LoadOp::Load,
+        );
+    }
+
+    fn resize(&mut self, device: &Device, size: (u32, u32)) {
+        let base_size = ((size.0 / 2).max(1), (size.1 / 2).max(1));
+        // Stop before the smallest side goes below a couple of pixels
+        let mip_level_count = base_size
+            .0
+            .min(base_size.1)
+            .ilog2()
+            .clamp(1, Self::MAX_MIP_LEVELS);
+
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Bloom Texture"),
+            size: wgpu::Extent3d {
+                width: base_size.0,
+                height: base_size.1,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: self.color_format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+
+        self.mip_views = (0..mip_level_count)
+            .map(|level| {
+                texture.create_view(&wgpu::TextureViewDescriptor {
+                    label: Some("Bloom Mip View"),
+                    base_mip_level: level,
+                    mip_level_count: Some(1),
+                    ..Default::default()
+                })
+            })
+            .collect();
+        self.mip_bind_groups = self
+            .mip_views
+            .iter()
+            .map(|view| self.create_bind_group(view, None))
+            .collect();
+    }
+}
@@ -1,5 +1,6 @@
 use std::{fmt::Debug, rc::Rc, sync::Arc};
 
+use bytemuck::Pod;
 use enum_as_inner::EnumAsInner;
 use wgpu::{util::DeviceExt, Buffer, Device, Queue};
 
@@ -10,26 +11,85 @@ pub enum ComputeError {
     InvalidCast,
     BufferMapError,
     AssetIsNotBuffer,
+    AssetIsNotTexture,
+    NoParams,
+    /// Sizes in bytes
+    ParamsSizeMismatch {
+        expected: u64,
+        actual: u64,
+    },
 }
 
 #[derive(Debug)]
 pub struct ComputePipelineType<'a> {
     pub input_data: Vec<ComputeData<'a>>,
     pub output_data_type: Vec<ComputeOutput>,
+    /// Initial value of a small params block that can change every dispatch, see
+    /// [ComputePipeline::set_params]. It's passed as push constants when the device supports
+    /// them, and the shader is compiled with [ComputePipeline::PUSH_CONSTANT_PARAMS_DEFINE] in
+    /// that case. Otherwise it's a uniform buffer bound right after the last output
+    pub params: Option<&'a [u8]>,
 }
 
 #[derive(Debug)]
 pub enum ComputeOutput {
+    /// Size in bytes
     Array(u64),
     Texture((u32, u32)),
 }
 
+impl ComputeOutput {
+    /// Buffer for `len` elements of `T`, read back with [ComputePipeline::grab_array_data]
+    pub fn array<T: Pod>(len: usize) -> Self {
+        ComputeOutput::Array((len * std::mem::size_of::<T>()) as u64)
+    }
+}
+
+/// Bytes of one storage buffer input along with the size of one element, so every input can
+/// have its own element type
+#[derive(Debug, Clone, Copy)]
+pub struct ComputeArray<'a> {
+    pub bytes: &'a [u8],
+    /// Size of one element in bytes
+    pub stride: u64,
+}
+
+impl<'a> ComputeArray<'a> {
+    pub fn new<T: Pod>(data: &'a [T]) -> Self {
+        ComputeArray {
+            bytes: bytemuck::cast_slice(data),
+            stride: std::mem::size_of::<T>() as u64,
+        }
+    }
+
+    /// Raw bytes where every element is one byte
+    pub fn from_bytes(bytes: &'a [u8]) -> Self {
+        ComputeArray { bytes, stride: 1 }
+    }
+
+    pub fn len(&self) -> usize {
+        self.bytes.len() / self.stride.max(1) as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+}
+
 #[derive(Debug, EnumAsInner)]
 pub enum ComputeData<'a> {
-    ArrayData(&'a [u8]),
+    ArrayData(ComputeArray<'a>),
     TextureData((ComputeTextureData, bool)),
 }
 
+impl<'a> ComputeData<'a> {
+    /// Storage buffer input holding `data`, e.g. `ComputeData::array(&[1.0_f32, 2.0])` next to
+    /// `ComputeData::array(&[3_u32, 4])` in the same pipeline
+    pub fn array<T: Pod>(data: &'a [T]) -> Self {
+        ComputeData::ArrayData(ComputeArray::new(data))
+    }
+}
+
 #[derive(Debug)]
 pub enum ComputeTextureData {
     Path(String),
@@ -42,6 +102,12 @@ pub enum ComputePackagedData {
     Texture(Rc<Texture>),
 }
 
+#[derive(Debug)]
+enum ComputeParams {
+    PushConstants(Vec<u8>),
+    Uniform { buffer: Buffer, size: u64 },
+}
+
 #[derive(Debug)]
 pub struct ComputePipeline {
     pub pipeline: wgpu::ComputePipeline,
@@ -49,10 +115,25 @@ pub struct ComputePipeline {
     pub bind_group: wgpu::BindGroup,
     pub pipeline_assets: Vec<ComputePackagedData>,
     pub workgroup_counts: (u32, u32, u32),
+    /// The shader's `@workgroup_size`, see [ComputePipeline::dispatch_for]
+    pub local_size: (u32, u32, u32),
     pub compute_shader_index: usize,
+    params: Option<ComputeParams>,
 }
 
 impl ComputePipeline {
+    /// Defined for shaders whose params are push constants, e.g.
+    /// `#ifdef PUSH_CONSTANT_PARAMS` around `var<push_constant> params: Params;`
+    pub const PUSH_CONSTANT_PARAMS_DEFINE: &'static str = "PUSH_CONSTANT_PARAMS";
+
+    /// Whether a params block of `params_size` bytes is passed as push constants on this device,
+    /// rather than through a uniform buffer
+    pub fn uses_push_constants(device: &Device, params_size: usize) -> bool {
+        device.features().contains(wgpu::Features::PUSH_CONSTANTS)
+            && params_size % wgpu::PUSH_CONSTANT_ALIGNMENT as usize == 0
+            && params_size <= device.limits().max_push_constant_size as usize
+    }
+
     pub fn new(
         device: Arc<Device>,
         queue: Arc<Queue>,
@@ -63,10 +144,33 @@ impl ComputePipeline {
     ) -> Self {
         let shader_module = device.create_shader_module(shader_module_descriptor);
 
+        let params = pipeline_type.params.map(|data| {
+            if Self::uses_push_constants(&device, data.len()) {
+                ComputeParams::PushConstants(data.to_vec())
+            } else {
+                // Uniform buffer bindings are sized in multiples of 16 bytes
+                let size = (data.len() as u64).div_ceil(16) * 16;
+                let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+                    label: Some(&format!(
+                        "Compute shader #{compute_shader_index} params buffer"
+                    )),
+                    size,
+                    usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                    mapped_at_creation: false,
+                });
+                queue.write_buffer(&buffer, 0, data);
+                ComputeParams::Uniform {
+                    buffer,
+                    size: data.len() as u64,
+                }
+            }
+        });
+
         let (bind_group_layout, pipeline) = Self::create_bind_group_layout_and_pipeline(
             device.clone(),
             shader_module,
             &pipeline_type,
+            params.as_ref(),
             compute_shader_index,
         );
 
@@ -77,7 +181,8 @@ impl ComputePipeline {
             compute_shader_index,
         );
 
-        let bind_group_entries = Self::create_pipeline_bind_group_entries(&pipeline_assets);
+        let bind_group_entries =
+            Self::create_pipeline_bind_group_entries(&pipeline_assets, params.as_ref());
 
         let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
             label: Some(&format!(
@@ -94,7 +199,61 @@ impl ComputePipeline {
             bind_group,
             pipeline_assets,
             workgroup_counts,
+            local_size: (1, 1, 1),
             compute_shader_index,
+            params,
+        }
+    }
+
+    /// Number of workgroups of `local_size` invocations needed to cover `element_count`
+    /// elements. Rounds up, so the shader should skip invocations past the end
+    pub fn workgroups_for(element_count: u32, local_size: u32) -> u32 {
+        element_count.div_ceil(local_size.max(1))
+    }
+
+    /// Dispatches enough workgroups to cover `element_count` elements from now on, `local_size`
+    /// has to match the shader's `@workgroup_size`
+    pub fn dispatch_for(&mut self, element_count: u32, local_size: u32) {
+        self.local_size = (local_size, 1, 1);
+        self.workgroup_counts = (Self::workgroups_for(element_count, local_size), 1, 1);
+    }
+
+    /// Same as [ComputePipeline::dispatch_for] for two dimensional data such as textures
+    pub fn dispatch_for_2d(&mut self, element_counts: (u32, u32), local_size: (u32, u32)) {
+        self.local_size = (local_size.0, local_size.1, 1);
+        self.workgroup_counts = (
+            Self::workgroups_for(element_counts.0, local_size.0),
+            Self::workgroups_for(element_counts.1, local_size.1),
+            1,
+        );
+    }
+
+    /// Replaces the params block, applied from the next dispatch on. `T` must be the size of
+    /// the params the pipeline was created with
+    pub fn set_params<T: Pod>(&mut self, queue: &Queue, value: T) -> Result<(), ComputeError> {
+        let actual = std::mem::size_of::<T>() as u64;
+        match &mut self.params {
+            None => Err(ComputeError::NoParams),
+            Some(ComputeParams::PushConstants(data)) => {
+                if data.len() as u64 != actual {
+                    return Err(ComputeError::ParamsSizeMismatch {
+                        expected: data.len() as u64,
+                        actual,
+                    });
+                }
+                data.copy_from_slice(bytemuck::bytes_of(&value));
+                Ok(())
+            }
+            Some(ComputeParams::Uniform { buffer, size }) => {
+                if *size != actual {
+                    return Err(ComputeError::ParamsSizeMismatch {
+                        expected: *size,
+                        actual,
+                    });
+                }
+                queue.write_buffer(buffer, 0, bytemuck::bytes_of(&value));
+                Ok(())
+            }
         }
     }
 
@@ -102,6 +261,7 @@ impl ComputePipeline {
         device: Arc<Device>,
         shader_module: wgpu::ShaderModule,
         pipeline_type: &ComputePipelineType,
+        params: Option<&ComputeParams>,
         compute_shader_index: usize,
     ) -> (wgpu::BindGroupLayout, wgpu::ComputePipeline) {
         let input_data = &pipeline_type.input_data;
@@ -129,7 +289,32 @@ impl ComputePipeline {
                 }
             });
 
-        let entries = input_entries.chain(output_entries).collect::<Vec<_>>();
+        let params_entry = match params {
+            Some(ComputeParams::Uniform { .. }) => Some(wgpu::BindGroupLayoutEntry {
+                binding: (input_len + output_data.len()) as u32,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }),
+            _ => None,
+        };
+
+        let push_constant_ranges = match params {
+            Some(ComputeParams::PushConstants(data)) => vec![wgpu::PushConstantRange {
+                stages: wgpu::ShaderStages::COMPUTE,
+                range: 0..data.len() as u32,
+            }],
+            _ => Vec::new(),
+        };
+
+        let entries = input_entries
+            .chain(output_entries)
+            .chain(params_entry)
+            .collect::<Vec<_>>();
 
         let pipeline_bind_group_layouts =
             device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
@@ -147,7 +332,7 @@ impl ComputePipeline {
                         "Compute shader #{compute_shader_index} pipeline layout"
                     )),
                     bind_group_layouts: &[&pipeline_bind_group_layouts],
-                    push_constant_ranges: &[],
+                    push_constant_ranges: &push_constant_ranges,
                 }),
             ),
             module: &shader_module,
@@ -169,7 +354,7 @@ impl ComputePipeline {
 
         let packaged_input_data = input_data.iter().enumerate().map(|(i, entry)| match entry {
             ComputeData::ArrayData(arr) => ComputePackagedData::Buffer(Rc::new(
-                Self::create_array_buffer(device.clone(), arr, compute_shader_index, i),
+                Self::create_array_buffer(device.clone(), arr.bytes, compute_shader_index, i),
             )),
             ComputeData::TextureData((tex_data, _)) => {
                 ComputePackagedData::Texture(Rc::new(match tex_data {
@@ -232,9 +417,18 @@ impl ComputePipeline {
         packaged_input_data.chain(packaged_output_data).collect()
     }
 
-    fn create_pipeline_bind_group_entries(
-        pipeline_assets: &[ComputePackagedData],
-    ) -> Vec<wgpu::BindGroupEntry> {
+    fn create_pipeline_bind_group_entries<'a>(
+        pipeline_assets: &'a [ComputePackagedData],
+        params: Option<&'a ComputeParams>,
+    ) -> Vec<wgpu::BindGroupEntry<'a>> {
+        let params_entry = match params {
+            Some(ComputeParams::Uniform { buffer, .. }) => Some(wgpu::BindGroupEntry {
+                binding: pipeline_assets.len() as u32,
+                resource: buffer.as_entire_binding(),
+            }),
+            _ => None,
+        };
+
         pipeline_assets
             .iter()
             .enumerate()
@@ -248,6 +442,7 @@ impl ComputePipeline {
                     resource: wgpu::BindingResource::TextureView(&tex.view),
                 },
             })
+            .chain(params_entry)
             .collect()
     }
 
@@ -260,7 +455,8 @@ impl ComputePipeline {
             self.pipeline_assets[index] = new_asset;
         }
 
-        let bind_group_entries = Self::create_pipeline_bind_group_entries(&self.pipeline_assets);
+        let bind_group_entries =
+            Self::create_pipeline_bind_group_entries(&self.pipeline_assets, self.params.as_ref());
 
         self.bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
             label: Some(&format!(
@@ -402,6 +598,9 @@ impl ComputePipeline {
 
             compute_pass.set_pipeline(&self.pipeline);
             compute_pass.set_bind_group(0, &self.bind_group, &[]);
+            if let Some(ComputeParams::PushConstants(data)) = &self.params {
+                compute_pass.set_push_constants(0, data);
+            }
             compute_pass.dispatch_workgroups(
                 self.workgroup_counts.0,
                 self.workgroup_counts.1,
@@ -412,6 +611,7 @@ impl ComputePipeline {
         queue.submit(Some(encoder.finish()));
     }
 
+    /// Reads an output buffer back as elements of `T`, which can differ between outputs
     pub fn grab_array_data<
         T: bytemuck::Pod + bytemuck::Zeroable + std::marker::Sync + std::marker::Send,
     >(
@@ -424,24 +624,14 @@ impl ComputePipeline {
 
             let buffer_slice = buf.slice(..);
             let (sender, receiver) = flume::bounded(1);
-            buffer_slice.map_async(wgpu::MapMode::Read, move |v| sender.send(v).unwrap());
+            buffer_slice.map_async(wgpu::MapMode::Read, move |v| {
+                let _ = sender.send(v);
+            });
 
             device.poll(wgpu::Maintain::Wait);
 
             if let Ok(Ok(())) = receiver.recv() {
-                let data_buffer = buffer_slice.get_mapped_range();
-
-                let data_result: Result<&[T], bytemuck::PodCastError> =
-                    bytemuck::try_cast_slice(&data_buffer);
-
-                if let Ok(result) = data_result {
-                    let vec = result.to_vec();
-                    drop(data_buffer);
-                    buf.unmap();
-                    Ok(vec)
-                } else {
-                    Err(ComputeError::InvalidCast)
-                }
+                Self::read_mapped_buffer(&buf)
             } else {
                 Err(ComputeError::BufferMapError)
             }
@@ -449,4 +639,192 @@ impl ComputePipeline {
             Err(ComputeError::AssetIsNotBuffer)
         }
     }
+
+    /// Same as [ComputePipeline::grab_array_data], but yields to the async runtime while the
+    /// GPU finishes instead of blocking the thread. Every readback waits on its own channel, so
+    /// several can be in flight at once. Reading the same buffer again before the previous
+    /// readback finished fails with [ComputeError::BufferMapError]
+    pub fn grab_array_data_async<
+        T: bytemuck::Pod + bytemuck::Zeroable + std::marker::Sync + std::marker::Send,
+    >(
+        &self,
+        device: Arc<Device>,
+        asset_index: usize,
+    ) -> impl std::future::Future<Output = Result<Vec<T>, ComputeError>> {
+        let buf = self.pipeline_assets[asset_index].as_buffer().cloned();
+
+        async move {
+            let buf = buf.ok_or(ComputeError::AssetIsNotBuffer)?;
+
+            let (sender, receiver) = flume::bounded(1);
+            buf.slice(..).map_async(wgpu::MapMode::Read, move |v| {
+                let _ = sender.send(v);
+            });
+
+            let map_result = loop {
+                device.poll(wgpu::Maintain::Poll);
+                match receiver.try_recv() {
+                    Ok(map_result) => break map_result,
+                    Err(flume::TryRecvError::Empty) => tokio::task::yield_now().await,
+                    Err(flume::TryRecvError::Disconnected) => {
+                        return Err(ComputeError::BufferMapError)
+                    }
+                }
+            };
+
+            match map_result {
+                Ok(()) => Self::read_mapped_buffer(&buf),
+                Err(_) => Err(ComputeError::BufferMapError),
+            }
+        }
+    }
+
+    /// Reads a storage texture back into an image, e.g. to save a generated image. The texture
+    /// is copied through a staging buffer, blocking until the GPU is done
+    pub fn grab_texture_data(
+        &self,
+        device: Arc<Device>,
+        queue: &Queue,
+        asset_index: usize,
+    ) -> Result<image::RgbaImage, ComputeError> {
+        let texture = self.pipeline_assets[asset_index]
+            .as_texture()
+            .ok_or(ComputeError::AssetIsNotTexture)?;
+        let size = texture.texture.size();
+
+        // Rows in the staging buffer are padded to the copy alignment
+        let unpadded_bytes_per_row = size.width * 4;
+        let padded_bytes_per_row = unpadded_bytes_per_row
+            .div_ceil(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT)
+            * wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+
+        let staging_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some(&format!(
+                "Compute shader #{} asset #{asset_index} readback buffer",
+                self.compute_shader_index
+            )),
+            size: (padded_bytes_per_row * size.height) as u64,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some(&format!(
+                "Compute shader #{} texture readback encoder",
+                self.compute_shader_index
+            )),
+        });
+        encoder.copy_texture_to_buffer(
+            texture.texture.as_image_copy(),
+            wgpu::ImageCopyBuffer {
+                buffer: &staging_buffer,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(padded_bytes_per_row),
+                    rows_per_image: Some(size.height),
+                },
+            },
+            wgpu::Extent3d {
+                depth_or_array_layers: 1,
+                ..size
+            },
+        );
+        queue.submit(Some(encoder.finish()));
+
+        let buffer_slice = staging_buffer.slice(..);
+        let (sender, receiver) = flume::bounded(1);
+        buffer_slice.map_async(wgpu::MapMode::Read, move |v| {
+            let _ = sender.send(v);
+        });
+        device.poll(wgpu::Maintain::Wait);
+        if !matches!(receiver.recv(), Ok(Ok(()))) {
+            return Err(ComputeError::BufferMapError);
+        }
+
+        let padded_data = buffer_slice.get_mapped_range();
+        let pixels = padded_data
+            .chunks_exact(padded_bytes_per_row as usize)
+            .flat_map(|row| &row[..unpadded_bytes_per_row as usize])
+            .copied()
+            .collect::<Vec<u8>>();
+        drop(padded_data);
+        staging_buffer.unmap();
+
+        image::RgbaImage::from_raw(size.width, size.height, pixels).ok_or(ComputeError::InvalidCast)
+    }
+
+    /// Copies out and unmaps a buffer that was mapped for reading
+    fn read_mapped_buffer<T: bytemuck::Pod>(buf: &Buffer) -> Result<Vec<T>, ComputeError> {
+        let data_buffer = buf.slice(..).get_mapped_range();
+        let result = bytemuck::try_cast_slice::<u8, T>(&data_buffer)
+            .map(|result| result.to_vec())
+            .map_err(|_| ComputeError::InvalidCast);
+        drop(data_buffer);
+        buf.unmap();
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::headless_device;
+
+    const MIXED_TYPES_SHADER: &str = "
+        @group(0) @binding(0) var<storage, read_write> floats: array<f32>;
+        @group(0) @binding(1) var<storage, read_write> uints: array<u32>;
+        @group(0) @binding(2) var<storage, read_write> doubled: array<f32>;
+        @group(0) @binding(3) var<storage, read_write> incremented: array<u32>;
+
+        @compute @workgroup_size(4)
+        fn main(@builtin(global_invocation_id) id: vec3<u32>) {
+            doubled[id.x] = floats[id.x] * 2.0;
+            incremented[id.x] = uints[id.x] + 1u;
+        }
+    ";
+
+    #[test]
+    fn mixed_element_types_are_read_back_with_their_own_type() {
+        // Output buffers are both mappable and storage buffers
+        let Some((device, queue)) = headless_device(wgpu::Features::MAPPABLE_PRIMARY_BUFFERS)
+        else {
+            return;
+        };
+
+        let floats = [0.5_f32, 1.5, -2.0, 8.0];
+        let uints = [0_u32, 7, 41, u32::MAX - 1];
+        let pipeline = ComputePipeline::new(
+            device.clone(),
+            queue.clone(),
+            wgpu::ShaderModuleDescriptor {
+                label: Some("Mixed types test shader"),
+                source: wgpu::ShaderSource::Wgsl(MIXED_TYPES_SHADER.into()),
+            },
+            ComputePipelineType {
+                input_data: vec![ComputeData::array(&floats), ComputeData::array(&uints)],
+                output_data_type: vec![
+                    ComputeOutput::array::<f32>(floats.len()),
+                    ComputeOutput::array::<u32>(uints.len()),
+                ],
+                params: None,
+            },
+            0,
+            (1, 1, 1),
+        );
+        pipeline.run_compute_shader(&device, &queue);
+
+        let doubled = pipeline.grab_array_data::<f32>(device.clone(), 2).unwrap();
+        let incremented = pipeline.grab_array_data::<u32>(device.clone(), 3).unwrap();
+        assert_eq!(doubled, [1.0, 3.0, -4.0, 16.0]);
+        assert_eq!(incremented, [1, 8, 42, u32::MAX]);
+    }
+
+    #[test]
+    fn workgroups_cover_every_element() {
+        assert_eq!(ComputePipeline::workgroups_for(1000, 64), 16);
+        assert_eq!(ComputePipeline::workgroups_for(1024, 64), 16);
+        assert_eq!(ComputePipeline::workgroups_for(1025, 64), 17);
+        assert_eq!(ComputePipeline::workgroups_for(0, 64), 0);
+        assert_eq!(ComputePipeline::workgroups_for(5, 0), 5);
+    }
 }
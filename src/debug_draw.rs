@@ -0,0 +1,240 @@
+use std::sync::Arc;
+
+use bytemuck::{Pod, Zeroable};
+use nalgebra as na;
+use wgpu::{
+    util::{BufferInitDescriptor, DeviceExt},
+    BindGroup, Buffer, Device, RenderPass, RenderPipeline,
+};
+
+use crate::{ecs::components::camera_component::CameraComponent, model::VertexData};
+
+#[repr(C)]
+#[derive(Pod, Zeroable, Clone, Copy, Debug)]
+struct DebugVertex {
+    position: [f32; 3],
+    color: [f32; 4],
+}
+
+impl VertexData for DebugVertex {
+    fn desc() -> wgpu::VertexBufferLayout<'static> {
+        const ATTRIBUTES: [wgpu::VertexAttribute; 2] =
+            wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x4];
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<DebugVertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &ATTRIBUTES,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct DebugDrawPipelines {
+    /// Color format, sample count and depth format the pipelines were built for
+    target: (wgpu::TextureFormat, u32, wgpu::TextureFormat),
+    depth_tested: RenderPipeline,
+    on_top: RenderPipeline,
+}
+
+/// Immediate mode lines for visualizing normals, bounds, raycasts and the like. Lines are
+/// collected until the scene is next rendered, drawn unlit with the active camera after the
+/// scene's geometry and then cleared, so they have to be added again every frame
+#[derive(Debug, Default)]
+pub struct DebugDraw {
+    vertices: Vec<DebugVertex>,
+    on_top: bool,
+    pipelines: Option<DebugDrawPipelines>,
+    /// This frame's lines, along with their vertex count
+    vertex_buffer: Option<(Buffer, u32)>,
+}
+
+impl DebugDraw {
+    pub fn line(&mut self, a: na::Vector3<f32>, b: na::Vector3<f32>, color: [f32; 4]) {
+        self.vertices.push(DebugVertex {
+            position: a.into(),
+            color,
+        });
+        self.vertices.push(DebugVertex {
+            position: b.into(),
+            color,
+        });
+    }
+
+    /// The line from `origin` to `origin + direction`, scale `direction` to change its length
+    pub fn ray(&mut self, origin: na::Vector3<f32>, direction: na::Vector3<f32>, color: [f32; 4]) {
+        self.line(origin, origin + direction, color);
+    }
+
+    /// The edges of an axis aligned box
+    pub fn aabb(&mut self, min: na::Vector3<f32>, max: na::Vector3<f32>, color: [f32; 4]) {
+        let corner = |x: bool, y: bool, z: bool| {
+            na::Vector3::new(
+                if x { max.x } else { min.x },
+                if y { max.y } else { min.y },
+                if z { max.z } else { min.z },
+            )
+        };
+        for a in [false, true] {
+            for b in [false, true] {
+                self.line(corner(false, a, b), corner(true, a, b), color);
+                self.line(corner(a, false, b), corner(a, true, b), color);
+                self.line(corner(a, b, false), corner(a, b, true), color);
+            }
+        }
+    }
+
+    /// The x, y and z axes in red, green and blue
+    pub fn axes(&mut self, origin: na::Vector3<f32>, length: f32) {
+        self.ray(origin, na::Vector3::x() * length, [1.0, 0.0, 0.0, 1.0]);
+        self.ray(origin, na::Vector3::y() * length, [0.0, 1.0, 0.0, 1.0]);
+        self.ray(origin, na::Vector3::z() * length, [0.0, 0.0, 1.0, 1.0]);
+    }
+
+    /// Draws the lines over the scene's geometry instead of testing them against its depth
+    pub fn set_on_top(&mut self, on_top: bool) {
+        self.on_top = on_top;
+    }
+
+    pub fn on_top(&self) -> bool {
+        self.on_top
+    }
+
+    /// Drops the lines added since the last render
+    pub fn clear(&mut self) {
+        self.vertices.clear();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.vertices.is_empty()
+    }
+
+    /// Uploads the collected lines and clears them, called by the scene before its render pass
+    pub(crate) fn prepare(
+        &mut self,
+        device: &Arc<Device>,
+        color_format: wgpu::TextureFormat,
+        sample_count: u32,
+        depth_format: wgpu::TextureFormat,
+    ) {
+        if self.vertices.is_empty() {
+            self.vertex_buffer = None;
+            return;
+        }
+
+        if self
+            .pipelines
+            .as_ref()
+            .filter(|pipelines| pipelines.target == (color_format, sample_count, depth_format))
+            .is_none()
+        {
+            self.pipelines = Some(Self::create_pipelines(
+                device,
+                color_format,
+                sample_count,
+                depth_format,
+            ));
+        }
+
+        let buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("Debug Draw Vertex Buffer"),
+            contents: bytemuck::cast_slice(&self.vertices),
+            usage: wgpu::BufferUsages::VERTEX,
+        });
+        self.vertex_buffer = Some((buffer, self.vertices.len() as u32));
+        self.vertices.clear();
+    }
+
+    pub(crate) fn render<'a: 'b, 'b>(
+        &'a self,
+        render_pass: &mut RenderPass<'b>,
+        camera_bind_group: &'a BindGroup,
+    ) {
+        let (Some(pipelines), Some((buffer, vertex_count))) =
+            (&self.pipelines, &self.vertex_buffer)
+        else {
+            return;
+        };
+
+        render_pass.set_pipeline(if self.on_top {
+            &pipelines.on_top
+        } else {
+            &pipelines.depth_tested
+        });
+        render_pass.set_bind_group(0, camera_bind_group, &[]);
+        render_pass.set_vertex_buffer(0, buffer.slice(..));
+        render_pass.draw(0..*vertex_count, 0..1);
+    }
+
+    fn create_pipelines(
+        device: &Arc<Device>,
+        color_format: wgpu::TextureFormat,
+        sample_count: u32,
+        depth_format: wgpu::TextureFormat,
+    ) -> DebugDrawPipelines {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Debug Draw Shader"),
+            source: wgpu::ShaderSource::Wgsl(std::borrow::Cow::Borrowed(include_str!(
+                "default-shaders/debug_lines.wgsl"
+            ))),
+        });
+
+        let camera_bind_group_layout = CameraComponent::camera_bind_group_layout(device.clone());
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Debug Draw Pipeline Layout"),
+            bind_group_layouts: &[&camera_bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let create_pipeline = |depth_compare| {
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("Debug Draw Pipeline"),
+                layout: Some(&layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: "vs_main",
+                    buffers: &[DebugVertex::desc()],
+                    compilation_options: wgpu::PipelineCompilationOptions::default(),
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: "fs_main",
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format: color_format,
+                        blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                    compilation_options: wgpu::PipelineCompilationOptions::default(),
+                }),
+                primitive: wgpu::PrimitiveState {
+                    topology: wgpu::PrimitiveTopology::LineList,
+                    strip_index_format: None,
+                    front_face: wgpu::FrontFace::Ccw,
+                    cull_mode: None,
+                    unclipped_depth: false,
+                    polygon_mode: wgpu::PolygonMode::Fill,
+                    conservative: false,
+                },
+                depth_stencil: Some(wgpu::DepthStencilState {
+                    format: depth_format,
+                    depth_write_enabled: false,
+                    depth_compare,
+                    stencil: wgpu::StencilState::default(),
+                    bias: wgpu::DepthBiasState::default(),
+                }),
+                multisample: wgpu::MultisampleState {
+                    count: sample_count,
+                    mask: !0,
+                    alpha_to_coverage_enabled: false,
+                },
+                multiview: None,
+                cache: None,
+            })
+        };
+
+        DebugDrawPipelines {
+            target: (color_format, sample_count, depth_format),
+            depth_tested: create_pipeline(wgpu::CompareFunction::LessEqual),
+            on_top: create_pipeline(wgpu::CompareFunction::Always),
+        }
+    }
+}
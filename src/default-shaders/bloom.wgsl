@@ -0,0 +1,61 @@
+struct BloomParams {
+    threshold: f32,
+    // Width of the soft transition around the threshold
+    knee: f32,
+    intensity: f32,
+    _padding: f32,
+}
+@group(0) @binding(2)
+var<uniform> params: BloomParams;
+
+// Only bound for the composite pass
+@group(0) @binding(3)
+var bloom_texture: texture_2d<f32>;
+
+// Average of a 4x4 block around `uv` from 4 bilinear samples
+fn box_sample(uv: vec2<f32>) -> vec3<f32> {
+    let texel = 1.0 / vec2<f32>(textureDimensions(input_texture));
+    let a = textureSample(input_texture, input_sampler, uv + texel * vec2<f32>(-1.0, -1.0)).rgb;
+    let b = textureSample(input_texture, input_sampler, uv + texel * vec2<f32>(1.0, -1.0)).rgb;
+    let c = textureSample(input_texture, input_sampler, uv + texel * vec2<f32>(-1.0, 1.0)).rgb;
+    let d = textureSample(input_texture, input_sampler, uv + texel * vec2<f32>(1.0, 1.0)).rgb;
+    return (a + b + c + d) * 0.25;
+}
+
+@fragment
+fn fs_prefilter(in: VertexOutput) -> @location(0) vec4<f32> {
+    let color = box_sample(in.uv);
+    let brightness = max(color.r, max(color.g, color.b));
+    var soft = clamp(brightness - params.threshold + params.knee, 0.0, 2.0 * params.knee);
+    soft = soft * soft / (4.0 * params.knee + 0.00001);
+    let contribution = max(soft, brightness - params.threshold) / max(brightness, 0.00001);
+    return vec4<f32>(color * contribution, 1.0);
+}
+
+@fragment
+fn fs_downsample(in: VertexOutput) -> @location(0) vec4<f32> {
+    return vec4<f32>(box_sample(in.uv), 1.0);
+}
+
+// 3x3 tent filter, blended additively onto the larger level
+@fragment
+fn fs_upsample(in: VertexOutput) -> @location(0) vec4<f32> {
+    let texel = 1.0 / vec2<f32>(textureDimensions(input_texture));
+    var color = textureSample(input_texture, input_sampler, in.uv).rgb * 4.0;
+    color += textureSample(input_texture, input_sampler, in.uv + vec2<f32>(-texel.x, 0.0)).rgb * 2.0;
+    color += textureSample(input_texture, input_sampler, in.uv + vec2<f32>(texel.x, 0.0)).rgb * 2.0;
+    color += textureSample(input_texture, input_sampler, in.uv + vec2<f32>(0.0, -texel.y)).rgb * 2.0;
+    color += textureSample(input_texture, input_sampler, in.uv + vec2<f32>(0.0, texel.y)).rgb * 2.0;
+    color += textureSample(input_texture, input_sampler, in.uv + vec2<f32>(-texel.x, -texel.y)).rgb;
+    color += textureSample(input_texture, input_sampler, in.uv + vec2<f32>(texel.x, -texel.y)).rgb;
+    color += textureSample(input_texture, input_sampler, in.uv + vec2<f32>(-texel.x, texel.y)).rgb;
+    color += textureSample(input_texture, input_sampler, in.uv + vec2<f32>(texel.x, texel.y)).rgb;
+    return vec4<f32>(color / 16.0, 1.0);
+}
+
+@fragment
+fn fs_composite(in: VertexOutput) -> @location(0) vec4<f32> {
+    let scene = textureSample(input_texture, input_sampler, in.uv);
+    let bloom = textureSample(bloom_texture, input_sampler, in.uv).rgb;
+    return vec4<f32>(scene.rgb + bloom * params.intensity, scene.a);
+}
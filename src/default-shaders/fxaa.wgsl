@@ -0,0 +1,133 @@
+const EDGE_THRESHOLD_MIN: f32 = 0.0312;
+const EDGE_THRESHOLD_MAX: f32 = 0.125;
+const SUBPIXEL_QUALITY: f32 = 0.75;
+const SEARCH_STEPS: i32 = 12;
+
+// Perceptual luma, the input is linear
+fn luma(color: vec4<f32>) -> f32 {
+    return sqrt(dot(color.rgb, vec3<f32>(0.299, 0.587, 0.114)));
+}
+
+fn sample_luma(uv: vec2<f32>) -> f32 {
+    return luma(textureSampleLevel(input_texture, input_sampler, uv, 0.0));
+}
+
+fn search_step(i: i32) -> f32 {
+    if i < 4 {
+        return 1.0;
+    }
+    if i < 8 {
+        return 2.0;
+    }
+    return 4.0;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let texel = 1.0 / vec2<f32>(textureDimensions(input_texture));
+
+    let center = textureSampleLevel(input_texture, input_sampler, in.uv, 0.0);
+    let luma_center = luma(center);
+    let luma_up = sample_luma(in.uv + vec2<f32>(0.0, -texel.y));
+    let luma_down = sample_luma(in.uv + vec2<f32>(0.0, texel.y));
+    let luma_left = sample_luma(in.uv + vec2<f32>(-texel.x, 0.0));
+    let luma_right = sample_luma(in.uv + vec2<f32>(texel.x, 0.0));
+
+    let luma_min = min(luma_center, min(min(luma_up, luma_down), min(luma_left, luma_right)));
+    let luma_max = max(luma_center, max(max(luma_up, luma_down), max(luma_left, luma_right)));
+    let luma_range = luma_max - luma_min;
+
+    // Not an edge
+    if luma_range < max(EDGE_THRESHOLD_MIN, luma_max * EDGE_THRESHOLD_MAX) {
+        return center;
+    }
+
+    let luma_up_left = sample_luma(in.uv + vec2<f32>(-texel.x, -texel.y));
+    let luma_up_right = sample_luma(in.uv + vec2<f32>(texel.x, -texel.y));
+    let luma_down_left = sample_luma(in.uv + vec2<f32>(-texel.x, texel.y));
+    let luma_down_right = sample_luma(in.uv + vec2<f32>(texel.x, texel.y));
+
+    let luma_up_down = luma_up + luma_down;
+    let luma_left_right = luma_left + luma_right;
+    let luma_left_corners = luma_up_left + luma_down_left;
+    let luma_right_corners = luma_up_right + luma_down_right;
+    let luma_up_corners = luma_up_left + luma_up_right;
+    let luma_down_corners = luma_down_left + luma_down_right;
+
+    let edge_horizontal = abs(-2.0 * luma_left + luma_left_corners)
+        + abs(-2.0 * luma_center + luma_up_down) * 2.0
+        + abs(-2.0 * luma_right + luma_right_corners);
+    let edge_vertical = abs(-2.0 * luma_up + luma_up_corners)
+        + abs(-2.0 * luma_center + luma_left_right) * 2.0
+        + abs(-2.0 * luma_down + luma_down_corners);
+    let is_horizontal = edge_horizontal >= edge_vertical;
+
+    // Which side of the edge the pixel blends towards
+    let luma_negative = select(luma_left, luma_up, is_horizontal);
+    let luma_positive = select(luma_right, luma_down, is_horizontal);
+    let gradient_negative = luma_negative - luma_center;
+    let gradient_positive = luma_positive - luma_center;
+    let is_negative_steepest = abs(gradient_negative) >= abs(gradient_positive);
+    let gradient_scaled = 0.25 * max(abs(gradient_negative), abs(gradient_positive));
+
+    var step_length = select(texel.x, texel.y, is_horizontal);
+    var luma_local_average = 0.5 * (luma_positive + luma_center);
+    if is_negative_steepest {
+        step_length = -step_length;
+        luma_local_average = 0.5 * (luma_negative + luma_center);
+    }
+
+    // Walk along the edge in both directions until its ends are found
+    var edge_uv = in.uv;
+    if is_horizontal {
+        edge_uv.y += step_length * 0.5;
+    } else {
+        edge_uv.x += step_length * 0.5;
+    }
+    let offset = select(vec2<f32>(0.0, texel.y), vec2<f32>(texel.x, 0.0), is_horizontal);
+
+    var uv_negative = edge_uv - offset;
+    var uv_positive = edge_uv + offset;
+    var luma_end_negative = sample_luma(uv_negative) - luma_local_average;
+    var luma_end_positive = sample_luma(uv_positive) - luma_local_average;
+    var reached_negative = abs(luma_end_negative) >= gradient_scaled;
+    var reached_positive = abs(luma_end_positive) >= gradient_scaled;
+
+    for (var i = 0; i < SEARCH_STEPS && !(reached_negative && reached_positive); i++) {
+        if !reached_negative {
+            uv_negative -= offset * search_step(i);
+            luma_end_negative = sample_luma(uv_negative) - luma_local_average;
+            reached_negative = abs(luma_end_negative) >= gradient_scaled;
+        }
+        if !reached_positive {
+            uv_positive += offset * search_step(i);
+            luma_end_positive = sample_luma(uv_positive) - luma_local_average;
+            reached_positive = abs(luma_end_positive) >= gradient_scaled;
+        }
+    }
+
+    let distance_negative = select(in.uv.y - uv_negative.y, in.uv.x - uv_negative.x, is_horizontal);
+    let distance_positive = select(uv_positive.y - in.uv.y, uv_positive.x - in.uv.x, is_horizontal);
+    let is_negative_closer = distance_negative < distance_positive;
+    let edge_length = distance_negative + distance_positive;
+    let pixel_offset = -min(distance_negative, distance_positive) / edge_length + 0.5;
+
+    // Only blend if the closer end varies in the same direction as the center
+    let is_luma_center_smaller = luma_center < luma_local_average;
+    let end_is_smaller = select(luma_end_positive < 0.0, luma_end_negative < 0.0, is_negative_closer);
+    var final_offset = select(0.0, pixel_offset, end_is_smaller != is_luma_center_smaller);
+
+    // Subpixel aliasing, e.g. thin lines that the edge search misses
+    let luma_average = (2.0 * (luma_up_down + luma_left_right) + luma_left_corners + luma_right_corners) / 12.0;
+    let subpixel_offset = clamp(abs(luma_average - luma_center) / luma_range, 0.0, 1.0);
+    let subpixel_smoothed = (-2.0 * subpixel_offset + 3.0) * subpixel_offset * subpixel_offset;
+    final_offset = max(final_offset, subpixel_smoothed * subpixel_smoothed * SUBPIXEL_QUALITY);
+
+    var final_uv = in.uv;
+    if is_horizontal {
+        final_uv.y += final_offset * step_length;
+    } else {
+        final_uv.x += final_offset * step_length;
+    }
+    return textureSampleLevel(input_texture, input_sampler, final_uv, 0.0);
+}
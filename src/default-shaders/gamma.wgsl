@@ -0,0 +1,9 @@
+// x is the gamma, values above 1 brighten the image
+@group(0) @binding(2)
+var<uniform> params: vec4<f32>;
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let color = textureSample(input_texture, input_sampler, in.uv);
+    return vec4<f32>(pow(color.rgb, vec3<f32>(1.0 / params.x)), color.a);
+}
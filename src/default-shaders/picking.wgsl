@@ -0,0 +1,39 @@
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+    @location(1) tex_coords: vec2<f32>,
+    @location(2) normal: vec3<f32>,
+}
+
+struct ModelData {
+    @location(5) model_matrix_0: vec4<f32>,
+    @location(6) model_matrix_1: vec4<f32>,
+    @location(7) model_matrix_2: vec4<f32>,
+    @location(8) model_matrix_3: vec4<f32>,
+}
+
+struct Camera {
+    view_pos: vec4<f32>,
+    view_proj: mat4x4<f32>,
+};
+@group(0) @binding(0)
+var<uniform> camera: Camera;
+
+@group(1) @binding(0)
+var<uniform> entity_id: vec4<u32>;
+
+@vertex
+fn vs_main(model: VertexInput, model_data: ModelData) -> @builtin(position) vec4<f32> {
+    let model_matrix = mat4x4<f32>(
+        model_data.model_matrix_0,
+        model_data.model_matrix_1,
+        model_data.model_matrix_2,
+        model_data.model_matrix_3,
+    );
+
+    return camera.view_proj * model_matrix * vec4<f32>(model.position, 1.0);
+}
+
+@fragment
+fn fs_main() -> @location(0) u32 {
+    return entity_id.x;
+}
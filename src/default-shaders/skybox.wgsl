@@ -0,0 +1,35 @@
+struct Skybox {
+    inverse_view_proj: mat4x4<f32>,
+    camera_position: vec4<f32>,
+};
+@group(0) @binding(0)
+var<uniform> skybox: Skybox;
+
+@group(0) @binding(1)
+var cubemap: texture_cube<f32>;
+@group(0) @binding(2)
+var cubemap_sampler: sampler;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) ndc: vec2<f32>,
+}
+
+// Single triangle covering the screen, placed on the far plane
+@vertex
+fn vs_main(@builtin(vertex_index) vertex_index: u32) -> VertexOutput {
+    let uv = vec2<f32>(f32((vertex_index << 1u) & 2u), f32(vertex_index & 2u));
+    let ndc = uv * 2.0 - 1.0;
+
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(ndc, 1.0, 1.0);
+    out.ndc = ndc;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let world_position = skybox.inverse_view_proj * vec4<f32>(in.ndc, 1.0, 1.0);
+    let direction = world_position.xyz / world_position.w - skybox.camera_position.xyz;
+    return textureSample(cubemap, cubemap_sampler, direction);
+}
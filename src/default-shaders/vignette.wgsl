@@ -0,0 +1,12 @@
+// x is the strength, 0 leaves the image untouched
+@group(0) @binding(2)
+var<uniform> params: vec4<f32>;
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let color = textureSample(input_texture, input_sampler, in.uv);
+    // 0 in the center, 1 in the corners
+    let distance = length(in.uv - 0.5) * sqrt(2.0);
+    let darkening = 1.0 - params.x * smoothstep(0.4, 1.0, distance);
+    return vec4<f32>(color.rgb * darkening, color.a);
+}
@@ -61,6 +61,25 @@ pub trait ComponentSystem: Debug + dyn_clone::DynClone + ComponentSystemCore {
     ) {
     }
 
+    /// Called [crate::EngineDetails::fixed_timestep] apart in simulated time, any number of
+    /// times per frame before [ComponentSystem::update]. For simulation that has to behave the
+    /// same at every frame rate, like physics
+    fn fixed_update(
+        &mut self,
+        device: Arc<Device>,
+        queue: Arc<Queue>,
+        component_map: &mut AllComponents,
+        engine_details: Rc<Mutex<EngineDetails>>,
+        engine_systems: Rc<Mutex<EngineSystems>>,
+        concept_manager: Rc<Mutex<ConceptManager>>,
+        active_camera_id: Option<EntityId>,
+        entities: &mut Vec<Entity>,
+        materials: Option<&mut (Vec<Material>, usize)>,
+        compute_pipelines: &mut [ComputePipeline],
+        text_items: &mut Vec<TextParams>,
+    ) {
+    }
+
     fn ui_draw(
         &mut self,
         device: Arc<Device>,
@@ -96,6 +115,14 @@ pub trait ComponentSystem: Debug + dyn_clone::DynClone + ComponentSystemCore {
         engine_systems: &EngineSystems,
     ) {
     }
+
+    /// Called when the component is removed from its entity, before its concepts are dropped
+    fn on_remove(
+        &mut self,
+        component_map: &AllComponents,
+        concept_manager: Rc<Mutex<ConceptManager>>,
+    ) {
+    }
 }
 
 pub trait ComponentSystemCore {
@@ -110,6 +137,10 @@ pub trait ComponentSystemCore {
     fn render_order(&self) -> usize {
         0
     }
+
+    fn type_name(&self) -> &'static str {
+        std::any::type_name::<Self>()
+    }
 }
 
 #[macro_export]
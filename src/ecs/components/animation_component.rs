@@ -0,0 +1,464 @@
+use algoe::rotor::Rotor3;
+use na::{Vector2, Vector3, Vector4};
+use nalgebra as na;
+
+use crate::{ecs::scene::TextParams, new_component, ui_manager::UiManager};
+
+use super::transform_component::TransformComponent;
+
+/// Shapes the progress between two keyframes
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Easing {
+    #[default]
+    Linear,
+    /// Starts slow and speeds up
+    EaseIn,
+    /// Starts fast and slows down
+    EaseOut,
+    /// Slow at both ends
+    EaseInOut,
+    /// Like [Easing::EaseInOut] with a sharper middle
+    Cubic,
+}
+
+impl Easing {
+    /// Maps progress `t` from `0` to `1` onto the eased progress
+    pub fn apply(&self, t: f32) -> f32 {
+        let t = t.clamp(0.0, 1.0);
+        match self {
+            Easing::Linear => t,
+            Easing::EaseIn => t * t,
+            Easing::EaseOut => 1.0 - (1.0 - t) * (1.0 - t),
+            Easing::EaseInOut => {
+                if t < 0.5 {
+                    2.0 * t * t
+                } else {
+                    1.0 - 2.0 * (1.0 - t) * (1.0 - t)
+                }
+            }
+            Easing::Cubic => {
+                if t < 0.5 {
+                    4.0 * t * t * t
+                } else {
+                    1.0 - 4.0 * (1.0 - t).powi(3)
+                }
+            }
+        }
+    }
+}
+
+/// What happens once the last keyframe is reached
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PlaybackMode {
+    /// Holds the last keyframe's value
+    #[default]
+    Once,
+    /// Jumps back to the first keyframe
+    Loop,
+    /// Plays backwards to the first keyframe, then forwards again
+    PingPong,
+}
+
+/// A value that can be animated. The concept it's written to must have the matching type
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AnimationValue {
+    Float(f32),
+    Vector2(Vector2<f32>),
+    Vector3(Vector3<f32>),
+    Vector4(Vector4<f32>),
+}
+
+impl AnimationValue {
+    /// `None` if the values are of different kinds
+    pub fn lerp(&self, other: &AnimationValue, t: f32) -> Option<AnimationValue> {
+        match (self, other) {
+            (AnimationValue::Float(a), AnimationValue::Float(b)) => {
+                Some(AnimationValue::Float(a + (b - a) * t))
+            }
+            (AnimationValue::Vector2(a), AnimationValue::Vector2(b)) => {
+                Some(AnimationValue::Vector2(a.lerp(b, t)))
+            }
+            (AnimationValue::Vector3(a), AnimationValue::Vector3(b)) => {
+                Some(AnimationValue::Vector3(a.lerp(b, t)))
+            }
+            (AnimationValue::Vector4(a), AnimationValue::Vector4(b)) => {
+                Some(AnimationValue::Vector4(a.lerp(b, t)))
+            }
+            _ => None,
+        }
+    }
+}
+
+impl From<f32> for AnimationValue {
+    fn from(value: f32) -> Self {
+        AnimationValue::Float(value)
+    }
+}
+
+impl From<Vector2<f32>> for AnimationValue {
+    fn from(value: Vector2<f32>) -> Self {
+        AnimationValue::Vector2(value)
+    }
+}
+
+impl From<Vector3<f32>> for AnimationValue {
+    fn from(value: Vector3<f32>) -> Self {
+        AnimationValue::Vector3(value)
+    }
+}
+
+impl From<Vector4<f32>> for AnimationValue {
+    fn from(value: Vector4<f32>) -> Self {
+        AnimationValue::Vector4(value)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Keyframe {
+    /// Seconds from the start of the animation
+    pub time: f32,
+    pub value: AnimationValue,
+    /// Eases the way from the previous keyframe to this one
+    pub easing: Easing,
+}
+
+/// Where an [AnimationComponent] writes its value
+#[derive(Debug, Clone, PartialEq)]
+pub enum AnimationTarget {
+    /// A concept of any component. Animating a [TransformComponent]'s `position` or `scale` also
+    /// rebuilds its matrix
+    Concept {
+        component: ComponentId,
+        name: String,
+    },
+    /// The uniform of one of the animation's own entity's materials, see
+    /// [Material::update_uniform]. Vectors are written as arrays of `f32`
+    MaterialUniform { material_index: usize },
+}
+
+new_component!(AnimationComponent {
+    target: AnimationTarget,
+    keyframes: Vec<Keyframe>,
+    mode: PlaybackMode,
+    elapsed: f32,
+    playing: bool
+});
+
+impl AnimationComponent {
+    /// An animation without keyframes, add them with [AnimationComponent::keyframe]. It starts
+    /// playing once the scene updates
+    pub fn new(target: AnimationTarget, mode: PlaybackMode) -> Self {
+        AnimationComponent {
+            parent: EntityId::MAX,
+            id: (EntityId::MAX, TypeId::of::<Self>(), 0),
+            target,
+            keyframes: Vec::new(),
+            mode,
+            elapsed: 0.0,
+            playing: true,
+        }
+    }
+
+    /// Adds a keyframe at `time` seconds, keyframes can be added in any order
+    pub fn keyframe(mut self, time: f32, value: impl Into<AnimationValue>, easing: Easing) -> Self {
+        let keyframe = Keyframe {
+            time,
+            value: value.into(),
+            easing,
+        };
+        let index = self
+            .keyframes
+            .partition_point(|existing| existing.time <= time);
+        self.keyframes.insert(index, keyframe);
+        self
+    }
+
+    pub fn keyframes(&self) -> &[Keyframe] {
+        &self.keyframes
+    }
+
+    /// Time of the last keyframe
+    pub fn duration(&self) -> f32 {
+        self.keyframes.last().map_or(0.0, |keyframe| keyframe.time)
+    }
+
+    pub fn play(&mut self) {
+        self.playing = true;
+    }
+
+    pub fn pause(&mut self) {
+        self.playing = false;
+    }
+
+    /// Jumps back to the start, keeping whether it's playing
+    pub fn restart(&mut self) {
+        self.elapsed = 0.0;
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.mode == PlaybackMode::Once && self.elapsed >= self.duration()
+    }
+
+    /// Maps the time since the animation started onto the keyframes' timeline
+    fn local_time(&self, elapsed: f32) -> f32 {
+        let duration = self.duration();
+        if duration <= 0.0 {
+            return 0.0;
+        }
+        match self.mode {
+            PlaybackMode::Once => elapsed.min(duration),
+            // The end of a cycle still shows the last keyframe, the next one starts over
+            PlaybackMode::Loop => {
+                let time = elapsed % duration;
+                if time == 0.0 && elapsed > 0.0 {
+                    duration
+                } else {
+                    time
+                }
+            }
+            PlaybackMode::PingPong => {
+                let time = elapsed % (duration * 2.0);
+                if time > duration {
+                    duration * 2.0 - time
+                } else {
+                    time
+                }
+            }
+        }
+    }
+
+    /// The value `elapsed` seconds after the animation started, `None` without keyframes or if
+    /// neighbouring keyframes hold different kinds of values
+    pub fn sample(&self, elapsed: f32) -> Option<AnimationValue> {
+        let time = self.local_time(elapsed);
+        let next_index = self
+            .keyframes
+            .partition_point(|keyframe| keyframe.time <= time);
+        if next_index == 0 {
+            return self.keyframes.first().map(|keyframe| keyframe.value);
+        }
+        let previous = &self.keyframes[next_index - 1];
+        let Some(next) = self.keyframes.get(next_index) else {
+            return Some(previous.value);
+        };
+
+        let t = (time - previous.time) / (next.time - previous.time);
+        previous.value.lerp(&next.value, next.easing.apply(t))
+    }
+
+    fn write_concept(
+        concept_manager: &mut ConceptManager,
+        component: ComponentId,
+        name: &str,
+        value: AnimationValue,
+    ) -> bool {
+        let name = name.to_string();
+        let written = match value {
+            AnimationValue::Float(value) => concept_manager.modify_concept(component, name, value),
+            AnimationValue::Vector2(value) => {
+                concept_manager.modify_concept(component, name, value)
+            }
+            AnimationValue::Vector3(value) => {
+                concept_manager.modify_concept(component, name, value)
+            }
+            AnimationValue::Vector4(value) => {
+                concept_manager.modify_concept(component, name, value)
+            }
+        };
+        written.is_ok()
+    }
+}
+
+impl ComponentSystem for AnimationComponent {
+    fn update(
+        &mut self,
+        _device: Arc<Device>,
+        queue: Arc<Queue>,
+        _component_map: &mut AllComponents,
+        engine_details: Rc<Mutex<EngineDetails>>,
+        _engine_systems: Rc<Mutex<EngineSystems>>,
+        concept_manager: Rc<Mutex<ConceptManager>>,
+        _active_camera_id: Option<EntityId>,
+        _entities: &mut Vec<Entity>,
+        materials: Option<&mut (Vec<Material>, usize)>,
+        _compute_pipelines: &mut [ComputePipeline],
+        _text_items: &mut Vec<TextParams>,
+    ) {
+        if !self.playing {
+            return;
+        }
+        self.elapsed += engine_details
+            .lock()
+            .unwrap()
+            .last_frame_duration
+            .as_secs_f32();
+        let Some(value) = self.sample(self.elapsed) else {
+            return;
+        };
+
+        match &self.target {
+            AnimationTarget::Concept { component, name } => {
+                let mut concept_manager = concept_manager.lock().unwrap();
+                if !Self::write_concept(&mut concept_manager, *component, name, value) {
+                    log::warn!("Animation can't write {value:?} to the concept {name}, pausing it");
+                    self.playing = false;
+                    return;
+                }
+
+                let is_transform = component.1 == TypeId::of::<TransformComponent>();
+                if is_transform && ["position", "scale"].contains(&name.as_str()) {
+                    let position = *concept_manager
+                        .get_concept::<Vector3<f32>>(*component, "position".to_string())
+                        .unwrap();
+                    let rotation = *concept_manager
+                        .get_concept::<Rotor3>(*component, "rotation".to_string())
+                        .unwrap();
+                    TransformComponent::set_position_and_rotation(
+                        &mut concept_manager,
+                        *component,
+                        position,
+                        rotation,
+                    )
+                    .unwrap();
+                }
+            }
+            AnimationTarget::MaterialUniform { material_index } => {
+                let Some(material) =
+                    materials.and_then(|(materials, _)| materials.get(*material_index))
+                else {
+                    return;
+                };
+                let result = match value {
+                    AnimationValue::Float(value) => material.update_uniform(&queue, value),
+                    AnimationValue::Vector2(value) => {
+                        material.update_uniform::<[f32; 2]>(&queue, value.into())
+                    }
+                    AnimationValue::Vector3(value) => {
+                        material.update_uniform::<[f32; 3]>(&queue, value.into())
+                    }
+                    AnimationValue::Vector4(value) => {
+                        material.update_uniform::<[f32; 4]>(&queue, value.into())
+                    }
+                };
+                if let Err(err) = result {
+                    log::warn!("Animation can't write {value:?} to the material uniform, pausing it: {err:?}");
+                    self.playing = false;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EASINGS: [Easing; 5] = [
+        Easing::Linear,
+        Easing::EaseIn,
+        Easing::EaseOut,
+        Easing::EaseInOut,
+        Easing::Cubic,
+    ];
+
+    /// Goes from `0` to `20` over two seconds
+    fn ramp(mode: PlaybackMode) -> AnimationComponent {
+        AnimationComponent::new(AnimationTarget::MaterialUniform { material_index: 0 }, mode)
+            .keyframe(0.0, 0.0, Easing::Linear)
+            .keyframe(1.0, 10.0, Easing::Linear)
+            .keyframe(2.0, 20.0, Easing::Linear)
+    }
+
+    fn sample_float(animation: &AnimationComponent, elapsed: f32) -> f32 {
+        match animation.sample(elapsed) {
+            Some(AnimationValue::Float(value)) => value,
+            other => panic!("Expected a float, got {other:?}"),
+        }
+    }
+
+    fn assert_close(actual: f32, expected: f32) {
+        assert!(
+            (actual - expected).abs() < 1e-5,
+            "Expected {expected}, got {actual}"
+        );
+    }
+
+    #[test]
+    fn easings_keep_their_endpoints() {
+        for easing in EASINGS {
+            assert_close(easing.apply(0.0), 0.0);
+            assert_close(easing.apply(1.0), 1.0);
+            assert_close(easing.apply(-1.0), 0.0);
+            assert_close(easing.apply(2.0), 1.0);
+        }
+    }
+
+    #[test]
+    fn easing_midpoints() {
+        let midpoints = [0.5, 0.25, 0.75, 0.5, 0.5];
+        for (easing, midpoint) in EASINGS.into_iter().zip(midpoints) {
+            assert_close(easing.apply(0.5), midpoint);
+        }
+        assert_close(Easing::Cubic.apply(0.25), 0.0625);
+        assert_close(Easing::EaseInOut.apply(0.75), 0.875);
+    }
+
+    #[test]
+    fn once_holds_the_last_keyframe() {
+        let animation = ramp(PlaybackMode::Once);
+        assert_close(sample_float(&animation, 0.0), 0.0);
+        assert_close(sample_float(&animation, 0.5), 5.0);
+        assert_close(sample_float(&animation, 1.5), 15.0);
+        assert_close(sample_float(&animation, 2.0), 20.0);
+        assert_close(sample_float(&animation, 7.0), 20.0);
+    }
+
+    #[test]
+    fn loop_ends_each_cycle_on_the_last_keyframe() {
+        let animation = ramp(PlaybackMode::Loop);
+        assert_close(sample_float(&animation, 0.0), 0.0);
+        assert_close(sample_float(&animation, 2.0), 20.0);
+        assert_close(sample_float(&animation, 2.5), 5.0);
+        assert_close(sample_float(&animation, 4.0), 20.0);
+    }
+
+    #[test]
+    fn ping_pong_plays_backwards_after_the_end() {
+        let animation = ramp(PlaybackMode::PingPong);
+        assert_close(sample_float(&animation, 2.0), 20.0);
+        assert_close(sample_float(&animation, 2.5), 15.0);
+        assert_close(sample_float(&animation, 4.0), 0.0);
+        assert_close(sample_float(&animation, 4.5), 5.0);
+    }
+
+    #[test]
+    fn keyframe_easing_applies_on_the_way_to_it() {
+        let animation = AnimationComponent::new(
+            AnimationTarget::MaterialUniform { material_index: 0 },
+            PlaybackMode::Once,
+        )
+        .keyframe(0.0, 0.0, Easing::Linear)
+        .keyframe(1.0, 10.0, Easing::EaseIn);
+        assert_close(sample_float(&animation, 0.5), 2.5);
+    }
+
+    #[test]
+    fn keyframes_added_out_of_order_are_sorted() {
+        let animation = AnimationComponent::new(
+            AnimationTarget::MaterialUniform { material_index: 0 },
+            PlaybackMode::Once,
+        )
+        .keyframe(2.0, 20.0, Easing::Linear)
+        .keyframe(0.0, 0.0, Easing::Linear)
+        .keyframe(1.0, 10.0, Easing::Linear);
+
+        let times = animation
+            .keyframes()
+            .iter()
+            .map(|keyframe| keyframe.time)
+            .collect::<Vec<_>>();
+        assert_eq!(times, [0.0, 1.0, 2.0]);
+        assert_eq!(animation.duration(), 2.0);
+        assert_close(sample_float(&animation, 1.5), 15.0);
+    }
+}
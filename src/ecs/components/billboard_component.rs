@@ -0,0 +1,119 @@
+use algoe::rotor::Rotor3;
+use na::Vector3;
+use nalgebra as na;
+
+use crate::{
+    ecs::scene::{Scene, TextParams},
+    new_component,
+    ui_manager::UiManager,
+};
+
+use super::{camera_component::CameraComponent, transform_component::TransformComponent};
+
+/// How a [BillboardComponent] turns towards the camera
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum BillboardMode {
+    /// Copies the camera's rotation, so the quad lies flat on the screen. For particles and
+    /// sprites
+    #[default]
+    Full,
+    /// Only turns around the y axis to face the camera's position, so the quad stays upright.
+    /// For trees, characters and health bars
+    Cylindrical,
+}
+
+new_component!(BillboardComponent {
+    mode: BillboardMode
+});
+
+impl BillboardComponent {
+    /// Turns the entity's [TransformComponent] to face the active camera every update. The quad
+    /// should face `-z`, towards a camera looking down `+z` at it. Place the billboard before the
+    /// transform on the entity, or the transform uploads the new rotation a frame late
+    pub fn new(mode: BillboardMode) -> Self {
+        BillboardComponent {
+            parent: EntityId::MAX,
+            id: (EntityId::MAX, TypeId::of::<Self>(), 0),
+            mode,
+        }
+    }
+
+    /// The rotation facing a camera at `camera_position` with `camera_rotation` from `position`
+    fn facing_rotation(
+        &self,
+        position: Vector3<f32>,
+        camera_position: Vector3<f32>,
+        camera_rotation: Rotor3,
+    ) -> Option<Rotor3> {
+        match self.mode {
+            BillboardMode::Full => Some(camera_rotation),
+            BillboardMode::Cylindrical => {
+                let away_from_camera = position - camera_position;
+                CameraComponent::look_rotation(
+                    Vector3::new(away_from_camera.x, 0.0, away_from_camera.z),
+                    Vector3::y(),
+                )
+            }
+        }
+    }
+}
+
+impl ComponentSystem for BillboardComponent {
+    fn initialize(
+        &mut self,
+        _device: Arc<Device>,
+        _queue: Arc<Queue>,
+        component_map: &AllComponents,
+        _concept_manager: Rc<Mutex<ConceptManager>>,
+        _engine_details: Option<Rc<Mutex<EngineDetails>>>,
+        _engine_systems: Option<Rc<Mutex<EngineSystems>>>,
+        _ui_manager: Rc<Mutex<UiManager>>,
+        _text_items: &mut Vec<TextParams>,
+    ) {
+        let _transform_component =
+            Scene::get_component::<TransformComponent>(&component_map[&self.parent])
+                .expect("Billboard component expects a transform component on this entity");
+    }
+
+    fn update(
+        &mut self,
+        _device: Arc<Device>,
+        _queue: Arc<Queue>,
+        _component_map: &mut AllComponents,
+        _engine_details: Rc<Mutex<EngineDetails>>,
+        _engine_systems: Rc<Mutex<EngineSystems>>,
+        concept_manager: Rc<Mutex<ConceptManager>>,
+        active_camera_id: Option<EntityId>,
+        _entities: &mut Vec<Entity>,
+        _materials: Option<&mut (Vec<Material>, usize)>,
+        _compute_pipelines: &mut [ComputePipeline],
+        _text_items: &mut Vec<TextParams>,
+    ) {
+        let Some(camera_id) = active_camera_id else {
+            return;
+        };
+        let mut concept_manager = concept_manager.lock().unwrap();
+        let transform_id = (self.parent, TypeId::of::<TransformComponent>(), 0);
+        let camera_transform_id = (camera_id, TypeId::of::<TransformComponent>(), 0);
+
+        let (Ok(position), Ok(camera_position), Ok(camera_rotation)) = (
+            concept_manager.get_concept::<Vector3<f32>>(transform_id, "position".to_string()),
+            concept_manager
+                .get_concept::<Vector3<f32>>(camera_transform_id, "position".to_string()),
+            concept_manager.get_concept::<Rotor3>(camera_transform_id, "rotation".to_string()),
+        ) else {
+            return;
+        };
+        let position = *position;
+
+        if let Some(rotation) = self.facing_rotation(position, *camera_position, *camera_rotation) {
+            TransformComponent::set_position_and_rotation(
+                &mut concept_manager,
+                transform_id,
+                position,
+                rotation,
+            )
+            .unwrap();
+        }
+    }
+}
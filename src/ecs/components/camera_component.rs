@@ -1,4 +1,4 @@
-use std::fmt::Debug;
+use std::{fmt::Debug, sync::MutexGuard};
 
 use bytemuck::{Pod, Zeroable};
 use wgpu::{
@@ -7,9 +7,17 @@ use wgpu::{
     BindingType, Buffer, BufferBindingType, BufferUsages, ShaderStages,
 };
 
+use algoe::rotor::Rotor3;
 use nalgebra as na;
 
-use crate::{ecs::scene::{Scene, TextParams}, new_component, ui_manager::UiManager};
+use crate::{
+    ecs::{
+        concepts::ConceptManagerError,
+        scene::{Scene, TextParams},
+    },
+    new_component,
+    ui_manager::UiManager,
+};
 
 use super::transform_component::TransformComponent;
 
@@ -29,11 +37,47 @@ impl Default for RawCameraData {
     }
 }
 
+/// Rectangle of the window a camera draws to, in pixels from its top left corner
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Viewport {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+impl Viewport {
+    pub fn new(x: f32, y: f32, width: f32, height: f32) -> Self {
+        Viewport {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    /// Covers the whole window
+    pub fn from_window_size(window_size: (u32, u32)) -> Self {
+        Viewport::new(0.0, 0.0, window_size.0 as f32, window_size.1 as f32)
+    }
+}
+
+/// How a camera's `view_to_projected_mat` concept is built
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CameraProjection {
+    /// [CameraComponent::new_2d], positions are already in clip space
+    Identity,
+    Perspective,
+    /// Rebuilt from the window's aspect ratio whenever it changes, keeping the view's height
+    Orthographic,
+}
+
 new_component!(
     CameraComponent {
         concept_ids: Vec<String>,
         buf: Arc<Option<Buffer>>,
-        raw_data: RawCameraData
+        raw_data: RawCameraData,
+        projection: CameraProjection
     }
 );
 
@@ -45,6 +89,7 @@ impl CameraComponent {
             id: (EntityId::MAX, TypeId::of::<Self>(), 0),
             buf: Arc::new(None),
             raw_data: RawCameraData::default(),
+            projection: CameraProjection::Identity,
         };
 
         let mut concepts: HashMap<String, Box<dyn Any>> = HashMap::new();
@@ -87,6 +132,7 @@ impl CameraComponent {
             id: (EntityId::MAX, TypeId::of::<Self>(), 0),
             buf: Arc::new(None),
             raw_data: RawCameraData::default(),
+            projection: CameraProjection::Perspective,
         };
 
         let mut concepts: HashMap<String, Box<dyn Any>> = HashMap::new();
@@ -105,6 +151,136 @@ impl CameraComponent {
         component
     }
 
+    /// Parallel projection for 2D, UI and isometric views. `width` and `height` are the extents
+    /// of the visible area in world units, centered on the camera. On resize the height is kept
+    /// and the width follows the window's aspect ratio
+    pub fn new_ortho(
+        concept_manager: Rc<Mutex<ConceptManager>>,
+        width: f32,
+        height: f32,
+        near_plane: f32,
+        far_plane: f32,
+    ) -> Self {
+        let mut component = CameraComponent {
+            parent: EntityId::MAX,
+            concept_ids: Vec::new(),
+            id: (EntityId::MAX, TypeId::of::<Self>(), 0),
+            buf: Arc::new(None),
+            raw_data: RawCameraData::default(),
+            projection: CameraProjection::Orthographic,
+        };
+
+        let mut concepts: HashMap<String, Box<dyn Any>> = HashMap::new();
+
+        concepts.insert(
+            "view_to_projected_mat".to_string(),
+            Box::new(Self::orthographic_matrix(
+                width, height, near_plane, far_plane,
+            )),
+        );
+        concepts.insert("aspect_ratio".to_string(), Box::new(width / height));
+        concepts.insert("fov".to_string(), Box::new(0.0_f32));
+        concepts.insert("near_plane".to_string(), Box::new(near_plane));
+        concepts.insert("far_plane".to_string(), Box::new(far_plane));
+        concepts.insert("ortho_height".to_string(), Box::new(height));
+
+        component.register_component(concept_manager, concepts);
+
+        component
+    }
+
+    /// Maps the box of `width` by `height` around the view axis, between the near and far planes,
+    /// to clip space. Looks down `+z` like [CameraComponent::new_3d], with depth from `0` to `1`
+    #[rustfmt::skip]
+    pub fn orthographic_matrix(
+        width: f32,
+        height: f32,
+        near_plane: f32,
+        far_plane: f32,
+    ) -> na::Matrix4<f32> {
+        let depth = far_plane - near_plane;
+        na::Matrix4::new(
+            2.0 / width, 0.0, 0.0, 0.0,
+            0.0, 2.0 / height, 0.0, 0.0,
+            0.0, 0.0, 1.0 / depth, -near_plane / depth,
+            0.0, 0.0, 0.0, 1.0
+        )
+    }
+
+    pub fn projection(&self) -> CameraProjection {
+        self.projection
+    }
+
+    /// Turns the camera's transform to face `target`, with its up as close to `up` as possible.
+    /// Does nothing if `target` is at the camera's position
+    pub fn look_at(
+        &self,
+        concept_manager: Rc<Mutex<ConceptManager>>,
+        target: na::Vector3<f32>,
+        up: na::Vector3<f32>,
+    ) -> Result<(), ConceptManagerError> {
+        let mut concept_manager = concept_manager.lock().unwrap();
+        let transform_id = (self.parent, TypeId::of::<TransformComponent>(), 0);
+        let position = *concept_manager
+            .get_concept::<na::Vector3<f32>>(transform_id, "position".to_string())?;
+
+        match Self::look_rotation(target - position, up) {
+            Some(rotation) => TransformComponent::set_position_and_rotation(
+                &mut concept_manager,
+                transform_id,
+                position,
+                rotation,
+            ),
+            None => Ok(()),
+        }
+    }
+
+    /// Moves the camera onto the sphere of `radius` around `target` and faces it, for orbit
+    /// cameras. `yaw` turns around the y axis, starting behind the target on `-z`, and `pitch`
+    /// raises the camera above the xz plane, both in radians. Pitch is kept just short of
+    /// straight up or down, where the camera's up would be undefined
+    pub fn orbit(
+        &self,
+        concept_manager: Rc<Mutex<ConceptManager>>,
+        target: na::Vector3<f32>,
+        radius: f32,
+        yaw: f32,
+        pitch: f32,
+    ) -> Result<(), ConceptManagerError> {
+        let max_pitch = std::f32::consts::FRAC_PI_2 - 0.001;
+        let pitch = pitch.clamp(-max_pitch, max_pitch);
+        let offset = na::Vector3::new(
+            yaw.sin() * pitch.cos(),
+            pitch.sin(),
+            -yaw.cos() * pitch.cos(),
+        ) * radius;
+
+        let rotation = Self::look_rotation(-offset, na::Vector3::y()).unwrap_or_default();
+        TransformComponent::set_position_and_rotation(
+            &mut concept_manager.lock().unwrap(),
+            (self.parent, TypeId::of::<TransformComponent>(), 0),
+            target + offset,
+            rotation,
+        )
+    }
+
+    /// Rotation turning the camera's forward (`+z`) towards `direction` and its up (`+y`) towards
+    /// `up`. `None` if `direction` is zero
+    pub fn look_rotation(direction: na::Vector3<f32>, up: na::Vector3<f32>) -> Option<Rotor3> {
+        let forward = direction.try_normalize(f32::EPSILON)?;
+        // Any right vector works when looking straight along `up`
+        let right = up
+            .cross(&forward)
+            .try_normalize(f32::EPSILON)
+            .or_else(|| na::Vector3::x().cross(&forward).try_normalize(f32::EPSILON))
+            .or_else(|| na::Vector3::z().cross(&forward).try_normalize(f32::EPSILON))?;
+        let up = forward.cross(&right);
+
+        Some(TransformComponent::rotor_from_matrix(
+            na::Matrix3::from_columns(&[right, up, forward]),
+        ))
+    }
+
     pub fn camera_bind_group_layout(device: Arc<Device>) -> BindGroupLayout {
         device.create_bind_group_layout(&BindGroupLayoutDescriptor {
             label: Some("Default Camera Bind Group Layout"),
@@ -130,6 +306,101 @@ impl CameraComponent {
         camera_buffer
     }
 
+    /// World to view space matrix, the inverse of the parent entity's position and rotation
+    pub fn view_matrix(
+        &self,
+        component_map: &AllComponents,
+        concept_manager: &MutexGuard<ConceptManager>,
+    ) -> na::Matrix4<f32> {
+        let position = concept_manager
+            .get_concept::<na::Vector3<f32>>(
+                (self.parent, TypeId::of::<TransformComponent>(), 0),
+                "position".to_string(),
+            )
+            .copied()
+            .unwrap_or_else(|_| na::Vector3::zeros());
+        let transform_component = component_map
+            .get(&self.parent)
+            .and_then(|components| Scene::get_component::<TransformComponent>(components));
+        let rotation_matrix = match transform_component {
+            Some(transform) => transform.create_rotation_matrix(concept_manager),
+            None => na::Matrix4::identity(),
+        };
+        let world_to_view_mat = na::Matrix4::new_translation(&position) * rotation_matrix;
+        world_to_view_mat
+            .try_inverse()
+            .unwrap_or_else(na::Matrix4::identity)
+    }
+
+    /// View to clip space matrix
+    pub fn projection_matrix(
+        &self,
+        concept_manager: &MutexGuard<ConceptManager>,
+    ) -> na::Matrix4<f32> {
+        *concept_manager
+            .get_concept::<na::Matrix4<f32>>(self.id, "view_to_projected_mat".to_string())
+            .unwrap()
+    }
+
+    /// Pixel position of `point` within `viewport`, `None` if it's behind the camera
+    pub fn world_to_screen(
+        &self,
+        component_map: &AllComponents,
+        concept_manager: &MutexGuard<ConceptManager>,
+        point: na::Vector3<f32>,
+        viewport: Viewport,
+    ) -> Option<(f32, f32)> {
+        let clip = self.projection_matrix(concept_manager)
+            * self.view_matrix(component_map, concept_manager)
+            * point.push(1.0);
+        if clip.w <= f32::EPSILON {
+            return None;
+        }
+
+        let ndc = clip.xy() / clip.w;
+        Some((
+            viewport.x + (ndc.x + 1.0) * 0.5 * viewport.width,
+            viewport.y + (1.0 - ndc.y) * 0.5 * viewport.height,
+        ))
+    }
+
+    /// World space ray through the pixel at `pixel` within `viewport`, as its origin and
+    /// normalized direction. Perspective rays start at the camera, others on the view plane
+    pub fn screen_to_ray(
+        &self,
+        component_map: &AllComponents,
+        concept_manager: &MutexGuard<ConceptManager>,
+        pixel: (f32, f32),
+        viewport: Viewport,
+    ) -> (na::Vector3<f32>, na::Vector3<f32>) {
+        let view_matrix = self.view_matrix(component_map, concept_manager);
+        let inverse_view_proj = (self.projection_matrix(concept_manager) * view_matrix)
+            .try_inverse()
+            .unwrap_or_else(na::Matrix4::identity);
+
+        let ndc_x = (pixel.0 - viewport.x) / viewport.width * 2.0 - 1.0;
+        let ndc_y = 1.0 - (pixel.1 - viewport.y) / viewport.height * 2.0;
+        let unproject = |ndc_z: f32| {
+            let world = inverse_view_proj * na::Vector4::new(ndc_x, ndc_y, ndc_z, 1.0);
+            world.xyz() / world.w
+        };
+
+        let near_point = unproject(0.0);
+        let direction = (unproject(0.5) - near_point)
+            .try_normalize(f32::EPSILON)
+            .unwrap_or_else(na::Vector3::z);
+        let origin = match self.projection {
+            CameraProjection::Perspective => view_matrix
+                .try_inverse()
+                .unwrap_or_else(na::Matrix4::identity)
+                .column(3)
+                .xyz(),
+            CameraProjection::Identity | CameraProjection::Orthographic => near_point,
+        };
+
+        (origin, direction)
+    }
+
     pub fn create_camera_bind_group(&self, device: Arc<Device>) -> BindGroup {
         let buf_clone = self.buf.clone();
         let buffer = buf_clone.as_ref();
@@ -201,7 +472,27 @@ impl ComponentSystem for CameraComponent {
         let aspect_ratio = concept_manager
             .get_concept_mut::<f32>(self.id, "aspect_ratio".to_string())
             .unwrap();
-        *aspect_ratio = engine_details.lock().unwrap().window_aspect_ratio;
+        let window_aspect_ratio = engine_details.lock().unwrap().window_aspect_ratio;
+        let aspect_ratio_changed = *aspect_ratio != window_aspect_ratio;
+        *aspect_ratio = window_aspect_ratio;
+
+        if self.projection == CameraProjection::Orthographic && aspect_ratio_changed {
+            let concept = |name: &str| {
+                *concept_manager
+                    .get_concept::<f32>(self.id, name.to_string())
+                    .unwrap()
+            };
+            let height = concept("ortho_height");
+            let projection = Self::orthographic_matrix(
+                height * window_aspect_ratio,
+                height,
+                concept("near_plane"),
+                concept("far_plane"),
+            );
+            *concept_manager
+                .get_concept_mut::<na::Matrix4<f32>>(self.id, "view_to_projected_mat".to_string())
+                .unwrap() = projection;
+        }
 
         let position = concept_manager
             .get_concept::<na::Vector3<f32>>(
@@ -211,20 +502,8 @@ impl ComponentSystem for CameraComponent {
             .unwrap();
         self.raw_data.cam_pos = position.to_homogeneous().into();
 
-        let view_to_projected_mat = concept_manager
-            .get_concept::<na::Matrix4<f32>>(self.id, "view_to_projected_mat".to_string())
-            .unwrap();
-        let transform_component =
-            Scene::get_component::<TransformComponent>(component_map.get(&self.parent).unwrap());
-        let rotation_matrix = match transform_component {
-            Some(transform) => transform.create_rotation_matrix(&concept_manager),
-            None => na::Matrix4::identity(),
-        };
-        // println!("rotation: {rotation_matrix}");
-        let world_to_view_mat = na::Matrix4::new_translation(position) * rotation_matrix;
-        // println!("{world_to_view_mat}");
-        let cam_mat = view_to_projected_mat * world_to_view_mat.try_inverse().unwrap();
-        // println!("{cam_mat}");
+        let cam_mat = self.projection_matrix(&concept_manager)
+            * self.view_matrix(component_map, &concept_manager);
         self.raw_data.cam_mat = cam_mat.into();
         let buf_clone = self.buf.clone();
         let buffer = buf_clone.as_ref();
@@ -236,3 +515,171 @@ impl ComponentSystem for CameraComponent {
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::ecs::component::Component;
+
+    use super::*;
+
+    /// A scene with one entity holding a transform at `position` and the camera
+    fn camera_scene(
+        position: na::Vector3<f32>,
+        camera: impl FnOnce(Rc<Mutex<ConceptManager>>) -> CameraComponent,
+    ) -> (Scene, EntityId) {
+        let mut scene = Scene::default();
+        let concept_manager = scene.get_concept_manager();
+        let components: Vec<Component> = vec![
+            Box::new(TransformComponent::new(
+                concept_manager.clone(),
+                position,
+                Rotor3::default(),
+                na::Vector3::repeat(1.0),
+            )),
+            Box::new(camera(concept_manager)),
+        ];
+        let entity = scene.create_entity(EntityId::MAX, true, components, None);
+        (scene, entity)
+    }
+
+    fn rotation(scene: &Scene, entity: EntityId) -> Rotor3 {
+        *scene
+            .get_concept_manager()
+            .lock()
+            .unwrap()
+            .get_concept::<Rotor3>(
+                (entity, TypeId::of::<TransformComponent>(), 0),
+                "rotation".to_string(),
+            )
+            .unwrap()
+    }
+
+    fn assert_faces(rotation: Rotor3, direction: na::Vector3<f32>, up: na::Vector3<f32>) {
+        let forward = rotation * na::Vector3::z();
+        let rotated_up = rotation * na::Vector3::y();
+        let right = rotation * na::Vector3::x();
+        assert!(
+            (forward - direction.normalize()).norm() < 1e-4,
+            "{forward:?} should face {direction:?}"
+        );
+        assert!(forward.dot(&rotated_up).abs() < 1e-4);
+        // Unless looking along `up` the camera isn't rolled, so its up leans towards `up`
+        if up.cross(&direction).norm() > f32::EPSILON {
+            assert!(rotated_up.dot(&up) > 0.0);
+            assert!(right.dot(&up).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn orthographic_matrix_is_nalgebras_with_depth_from_zero_to_one() {
+        let (width, height, near_plane, far_plane) = (8.0, 6.0, 0.5, 40.0);
+        let opengl = na::Orthographic3::new(
+            -width / 2.0,
+            width / 2.0,
+            -height / 2.0,
+            height / 2.0,
+            near_plane,
+            far_plane,
+        )
+        .to_homogeneous();
+        // nalgebra looks down -z, with depth from -1 to 1
+        let flip_z = na::Matrix4::from_diagonal(&na::Vector4::new(1.0, 1.0, -1.0, 1.0));
+        #[rustfmt::skip]
+        let remap_depth = na::Matrix4::new(
+            1.0, 0.0, 0.0, 0.0,
+            0.0, 1.0, 0.0, 0.0,
+            0.0, 0.0, 0.5, 0.5,
+            0.0, 0.0, 0.0, 1.0
+        );
+
+        let matrix = CameraComponent::orthographic_matrix(width, height, near_plane, far_plane);
+        assert!((matrix - remap_depth * opengl * flip_z).amax() < 1e-6);
+
+        let depth = |z: f32| (matrix * na::Vector4::new(0.0, 0.0, z, 1.0)).z;
+        assert!(depth(near_plane).abs() < 1e-6);
+        assert!((depth(far_plane) - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn look_rotation_faces_the_direction() {
+        let up = na::Vector3::y();
+        for direction in [
+            na::Vector3::new(1.0, 2.0, 3.0),
+            na::Vector3::new(-4.0, 0.0, -1.0),
+            -na::Vector3::z(),
+            na::Vector3::new(0.0, 5.0, 0.0),
+            -na::Vector3::y(),
+        ] {
+            let rotation = CameraComponent::look_rotation(direction, up).unwrap();
+            assert_faces(rotation, direction, up);
+        }
+        assert!(CameraComponent::look_rotation(na::Vector3::zeros(), up).is_none());
+    }
+
+    #[test]
+    fn look_at_turns_the_camera_to_the_target() {
+        let position = na::Vector3::new(3.0, 4.0, -5.0);
+        let (scene, entity) = camera_scene(position, |concept_manager| {
+            CameraComponent::new_3d(concept_manager, (64, 48), 1.0, 0.1, 100.0)
+        });
+        let camera =
+            Scene::get_component::<CameraComponent>(&scene.get_components()[&entity]).unwrap();
+        let concept_manager = scene.get_concept_manager();
+        let up = na::Vector3::y();
+
+        for target in [na::Vector3::zeros(), na::Vector3::new(3.0, 10.0, -5.0)] {
+            camera.look_at(concept_manager.clone(), target, up).unwrap();
+            assert_faces(rotation(&scene, entity), target - position, up);
+        }
+
+        let before = rotation(&scene, entity);
+        camera.look_at(concept_manager, position, up).unwrap();
+        assert_faces(rotation(&scene, entity), before * na::Vector3::z(), up);
+    }
+
+    #[test]
+    fn screen_rays_pass_through_the_projected_point() {
+        let viewport = Viewport::new(10.0, 20.0, 64.0, 48.0);
+        let cameras: [fn(Rc<Mutex<ConceptManager>>) -> CameraComponent; 2] = [
+            |concept_manager| CameraComponent::new_3d(concept_manager, (64, 48), 1.0, 0.1, 100.0),
+            |concept_manager| CameraComponent::new_ortho(concept_manager, 8.0, 6.0, 0.1, 100.0),
+        ];
+
+        for camera in cameras {
+            let (scene, entity) = camera_scene(na::Vector3::new(1.0, 2.0, -5.0), camera);
+            let components = scene.get_components();
+            let camera = Scene::get_component::<CameraComponent>(&components[&entity]).unwrap();
+            let concept_manager = scene.get_concept_manager();
+            camera
+                .look_at(
+                    concept_manager.clone(),
+                    na::Vector3::zeros(),
+                    na::Vector3::y(),
+                )
+                .unwrap();
+            let concept_manager = concept_manager.lock().unwrap();
+
+            for point in [
+                na::Vector3::zeros(),
+                na::Vector3::new(1.5, -1.0, 0.5),
+                na::Vector3::new(-2.0, 0.5, 3.0),
+            ] {
+                let pixel = camera
+                    .world_to_screen(components, &concept_manager, point, viewport)
+                    .unwrap();
+                let (origin, direction) =
+                    camera.screen_to_ray(components, &concept_manager, pixel, viewport);
+
+                let along = (point - origin).dot(&direction);
+                let closest = origin + direction * along;
+                assert!(along > 0.0, "{:?} is behind the ray", camera.projection());
+                assert!(
+                    (closest - point).norm() < 1e-3,
+                    "{:?} ray misses {point:?} by {}",
+                    camera.projection(),
+                    (closest - point).norm()
+                );
+            }
+        }
+    }
+}
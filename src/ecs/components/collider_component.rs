@@ -0,0 +1,253 @@
+use na::Vector3;
+use nalgebra as na;
+
+use crate::{
+    ecs::scene::{Scene, TextParams},
+    new_component,
+    ui_manager::UiManager,
+};
+
+use super::transform_component::TransformComponent;
+
+/// Collision volume centered on the entity's position. Rotation and scale are ignored
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ColliderShape {
+    Aabb { half_extents: Vector3<f32> },
+    Sphere { radius: f32 },
+}
+
+impl ColliderShape {
+    /// Min and max corners of the box around the shape
+    pub fn bounds(&self, center: Vector3<f32>) -> (Vector3<f32>, Vector3<f32>) {
+        let half_extents = match self {
+            ColliderShape::Aabb { half_extents } => *half_extents,
+            ColliderShape::Sphere { radius } => Vector3::repeat(*radius),
+        };
+        (center - half_extents, center + half_extents)
+    }
+
+    /// Distance along the ray and surface normal of the first hit, `direction` must be
+    /// normalized. A ray starting inside the shape hits where it exits
+    pub fn ray_intersection(
+        &self,
+        center: Vector3<f32>,
+        origin: Vector3<f32>,
+        direction: Vector3<f32>,
+    ) -> Option<(f32, Vector3<f32>)> {
+        match self {
+            ColliderShape::Aabb { half_extents } => {
+                let (min, max) = (center - half_extents, center + half_extents);
+                let (mut t_near, mut t_far) = (f32::NEG_INFINITY, f32::INFINITY);
+                let (mut near_axis, mut far_axis) = (0, 0);
+                // Slab method, clip the ray against each pair of parallel faces
+                for axis in 0..3 {
+                    if direction[axis].abs() < f32::EPSILON {
+                        if origin[axis] < min[axis] || origin[axis] > max[axis] {
+                            return None;
+                        }
+                        continue;
+                    }
+                    let t_0 = (min[axis] - origin[axis]) / direction[axis];
+                    let t_1 = (max[axis] - origin[axis]) / direction[axis];
+                    let (t_0, t_1) = if t_0 > t_1 { (t_1, t_0) } else { (t_0, t_1) };
+                    if t_0 > t_near {
+                        t_near = t_0;
+                        near_axis = axis;
+                    }
+                    if t_1 < t_far {
+                        t_far = t_1;
+                        far_axis = axis;
+                    }
+                    if t_near > t_far {
+                        return None;
+                    }
+                }
+
+                let mut normal = Vector3::zeros();
+                if t_near >= 0.0 {
+                    normal[near_axis] = -direction[near_axis].signum();
+                    Some((t_near, normal))
+                } else if t_far >= 0.0 {
+                    normal[far_axis] = direction[far_axis].signum();
+                    Some((t_far, normal))
+                } else {
+                    None
+                }
+            }
+            ColliderShape::Sphere { radius } => {
+                let offset = origin - center;
+                let b = offset.dot(&direction);
+                let c = offset.norm_squared() - radius * radius;
+                let discriminant = b * b - c;
+                if discriminant < 0.0 {
+                    return None;
+                }
+                let root = discriminant.sqrt();
+                let t = if -b - root >= 0.0 {
+                    -b - root
+                } else {
+                    -b + root
+                };
+                if t < 0.0 {
+                    return None;
+                }
+                let normal = (origin + direction * t - center) / *radius;
+                Some((t, normal))
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RaycastHit {
+    pub entity: EntityId,
+    pub point: Vector3<f32>,
+    pub distance: f32,
+    pub normal: Vector3<f32>,
+}
+
+/// An overlap between two colliders found during [crate::ecs::scene::Scene::fixed_update]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CollisionEvent {
+    pub entities: (EntityId, EntityId),
+    /// Points from the first entity to the second
+    pub normal: Vector3<f32>,
+    pub penetration: f32,
+}
+
+new_component!(ColliderComponent {
+    concept_ids: Vec<String>
+});
+
+impl ColliderComponent {
+    /// `restitution` is how much of the approaching speed is kept after a bounce, `1.0` fully
+    /// reflects the velocity. Entities without a [super::physics_component::PhysicsComponent]
+    /// are static and never moved by collisions.
+    /// The collisions the entity was part of during the last fixed update are stored in the
+    /// `collisions` concept
+    pub fn new(
+        concept_manager: Rc<Mutex<ConceptManager>>,
+        shape: ColliderShape,
+        restitution: f32,
+    ) -> Self {
+        let mut component = ColliderComponent {
+            parent: EntityId::MAX,
+            concept_ids: Vec::new(),
+            id: (EntityId::MAX, TypeId::of::<Self>(), 0),
+        };
+
+        let mut concepts: HashMap<String, Box<dyn Any>> = HashMap::new();
+
+        concepts.insert("shape".to_string(), Box::new(shape));
+        concepts.insert("restitution".to_string(), Box::new(restitution));
+        concepts.insert(
+            "collisions".to_string(),
+            Box::new(Vec::<CollisionEvent>::new()),
+        );
+
+        component.register_component(concept_manager, concepts);
+
+        component
+    }
+
+    /// Finds the contact normal (from `a` to `b`) and penetration depth of two overlapping shapes
+    pub fn contact(
+        a: (ColliderShape, Vector3<f32>),
+        b: (ColliderShape, Vector3<f32>),
+    ) -> Option<(Vector3<f32>, f32)> {
+        match (a.0, b.0) {
+            (
+                ColliderShape::Aabb {
+                    half_extents: a_half,
+                },
+                ColliderShape::Aabb {
+                    half_extents: b_half,
+                },
+            ) => {
+                let delta = b.1 - a.1;
+                let overlap = a_half + b_half - delta.abs();
+                if overlap.iter().any(|axis_overlap| *axis_overlap <= 0.0) {
+                    return None;
+                }
+                // Push out along the axis of least overlap
+                let axis = overlap.imin();
+                let mut normal = Vector3::zeros();
+                normal[axis] = if delta[axis] < 0.0 { -1.0 } else { 1.0 };
+                Some((normal, overlap[axis]))
+            }
+            (
+                ColliderShape::Sphere { radius: a_radius },
+                ColliderShape::Sphere { radius: b_radius },
+            ) => {
+                let delta = b.1 - a.1;
+                let distance = delta.norm();
+                let penetration = a_radius + b_radius - distance;
+                if penetration <= 0.0 {
+                    return None;
+                }
+                let normal = if distance > 0.0 {
+                    delta / distance
+                } else {
+                    Vector3::y()
+                };
+                Some((normal, penetration))
+            }
+            (ColliderShape::Aabb { half_extents }, ColliderShape::Sphere { radius }) => {
+                let closest_point = b.1.sup(&(a.1 - half_extents)).inf(&(a.1 + half_extents));
+                let delta = b.1 - closest_point;
+                let distance = delta.norm();
+                if distance >= radius {
+                    return None;
+                }
+                if distance > 0.0 {
+                    Some((delta / distance, radius - distance))
+                } else {
+                    // The sphere's center is inside the box, treat it as a box of the same size
+                    Self::contact(
+                        a,
+                        (
+                            ColliderShape::Aabb {
+                                half_extents: Vector3::repeat(radius),
+                            },
+                            b.1,
+                        ),
+                    )
+                }
+            }
+            (ColliderShape::Sphere { .. }, ColliderShape::Aabb { .. }) => {
+                Self::contact(b, a).map(|(normal, penetration)| (-normal, penetration))
+            }
+        }
+    }
+}
+
+impl ComponentSystem for ColliderComponent {
+    fn register_component(
+        &mut self,
+        concept_manager: Rc<Mutex<ConceptManager>>,
+        data: HashMap<String, Box<dyn Any>>,
+    ) {
+        self.concept_ids = data.keys().cloned().collect();
+
+        concept_manager
+            .lock()
+            .unwrap()
+            .register_component_concepts(self.id, data);
+    }
+
+    fn initialize(
+        &mut self,
+        _device: Arc<Device>,
+        _queue: Arc<Queue>,
+        component_map: &AllComponents,
+        _concept_manager: Rc<Mutex<ConceptManager>>,
+        _engine_details: Option<Rc<Mutex<EngineDetails>>>,
+        _engine_systems: Option<Rc<Mutex<EngineSystems>>>,
+        _ui_manager: Rc<Mutex<UiManager>>,
+        _text_items: &mut Vec<TextParams>,
+    ) {
+        let _transform_component =
+            Scene::get_component::<TransformComponent>(&component_map[&self.parent])
+                .expect("Collider component expects a transform component on this entity");
+    }
+}
@@ -1,28 +1,47 @@
 #![allow(unused_imports)]
 use std::fmt::Debug;
 
+use nalgebra as na;
 use wgpu::{
     util::{BufferInitDescriptor, DeviceExt},
     Buffer, RenderPass,
 };
 
 use crate::{
-    model::Vertex,
+    ecs::scene::TextParams,
+    model::{TangentVertex, Vertex, VertexType},
     new_component,
-    ui_manager::UiManager, ecs::scene::TextParams,
+    pipeline::{BlendMode, PipelineConfig},
+    primitives,
+    texture::Texture,
+    ui_manager::UiManager,
 };
 
 #[derive(Debug)]
 pub enum MeshComponentError {
     FailedToLoadObj,
     FailedToLoadMtl,
+    FailedToLoadTexture(String),
+    FailedToLoadGltf,
+    /// The mesh has fewer submeshes than the index given
+    SubmeshOutOfRange(usize),
 }
 
+/// Added to every vertex and index buffer so updates can write to them in place. Tests also read
+/// them back
+#[cfg(not(test))]
+const BUFFER_USAGES: wgpu::BufferUsages = wgpu::BufferUsages::COPY_DST;
+#[cfg(test)]
+const BUFFER_USAGES: wgpu::BufferUsages =
+    wgpu::BufferUsages::COPY_DST.union(wgpu::BufferUsages::COPY_SRC);
+
 new_component!(MeshComponent {
     concept_ids: Vec<String>,
     mesh_count: usize,
-    vertex_buffers: Arc<[Option<Buffer>]>,
-    index_buffers: Arc<[Option<Buffer>]>
+    vertex_buffers: Vec<Option<Arc<Buffer>>>,
+    index_buffers: Vec<Option<Arc<Buffer>>>,
+    material_indices: Vec<usize>,
+    vertex_type: VertexType
 }, render_order: usize::MAX);
 
 impl MeshComponent {
@@ -36,8 +55,10 @@ impl MeshComponent {
             concept_ids: Vec::new(),
             id: (EntityId::MAX, TypeId::of::<Self>(), 0),
             mesh_count: 1,
-            vertex_buffers: Arc::from(vec![None].into_boxed_slice()),
-            index_buffers: Arc::from(vec![None].into_boxed_slice()),
+            vertex_buffers: vec![None],
+            index_buffers: vec![None],
+            material_indices: Vec::new(),
+            vertex_type: VertexType::Standard,
         };
 
         let mut concepts: HashMap<String, Box<dyn Any>> = HashMap::new();
@@ -49,68 +70,536 @@ impl MeshComponent {
         component
     }
 
+    /// See [primitives::quad]
+    pub fn quad(concept_manager: Rc<Mutex<ConceptManager>>) -> Self {
+        let (vertices, indices) = primitives::quad();
+        Self::new(concept_manager, vertices, indices)
+    }
+
+    /// See [primitives::cube]
+    pub fn cube(concept_manager: Rc<Mutex<ConceptManager>>) -> Self {
+        let (vertices, indices) = primitives::cube();
+        Self::new(concept_manager, vertices, indices)
+    }
+
+    /// See [primitives::plane]
+    pub fn plane(concept_manager: Rc<Mutex<ConceptManager>>, subdivisions: u32) -> Self {
+        let (vertices, indices) = primitives::plane(subdivisions);
+        Self::new(concept_manager, vertices, indices)
+    }
+
+    /// See [primitives::sphere]
+    pub fn sphere(concept_manager: Rc<Mutex<ConceptManager>>, segments: u32) -> Self {
+        let (vertices, indices) = primitives::sphere(segments);
+        Self::new(concept_manager, vertices, indices)
+    }
+
+    /// See [primitives::cylinder]
+    pub fn cylinder(concept_manager: Rc<Mutex<ConceptManager>>, segments: u32) -> Self {
+        let (vertices, indices) = primitives::cylinder(segments);
+        Self::new(concept_manager, vertices, indices)
+    }
+
     pub fn from_obj(
         concept_manager: Rc<Mutex<ConceptManager>>,
         obj_path: &str,
         expect_material: bool,
     ) -> Result<Self, MeshComponentError> {
+        let (models, materials_res) = Self::load_obj(obj_path)?;
+        if materials_res.is_err() && expect_material {
+            return Err(MeshComponentError::FailedToLoadMtl);
+        }
+
+        Ok(Self::from_obj_models(concept_manager, models, Vec::new()))
+    }
+
+    /// Loads an OBJ along with one [Material] per entry of its MTL file. Each submesh draws with
+    /// its own material, so the returned materials should be passed to `create_entity` as is.
+    /// Texture paths are resolved relative to the OBJ's directory. The diffuse texture is bound
+    /// first and the normal map second (when present), the base color is passed to the shaders as
+    /// a `vec4<f32>` uniform. The materials cull back faces, use
+    /// [Material::with_flipped_winding] on them if the OBJ has clockwise winding
+    pub fn from_obj_with_materials(
+        concept_manager: Rc<Mutex<ConceptManager>>,
+        obj_path: &str,
+        vertex_shader_path: &str,
+        fragment_shader_path: &str,
+        device: Arc<Device>,
+        queue: Arc<Queue>,
+    ) -> Result<(Self, Vec<Material>), MeshComponentError> {
+        let (models, materials_res) = Self::load_obj(obj_path)?;
+        let obj_materials = materials_res.map_err(|_| MeshComponentError::FailedToLoadMtl)?;
+
+        let obj_directory = std::path::Path::new(&std::env::current_dir().unwrap())
+            .join(obj_path)
+            .parent()
+            .map(|dir| dir.to_path_buf())
+            .unwrap_or_default();
+
+        let mut materials = obj_materials
+            .iter()
+            .map(|obj_material| {
+                Self::create_obj_material(
+                    obj_material,
+                    &obj_directory,
+                    vertex_shader_path,
+                    fragment_shader_path,
+                    device.clone(),
+                    &queue,
+                )
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let default_material_index = materials.len();
+        let material_indices = models
+            .iter()
+            .map(|m| m.mesh.material_id.unwrap_or(default_material_index))
+            .collect::<Vec<_>>();
+
+        if material_indices.contains(&default_material_index) {
+            materials.push(Self::create_obj_material(
+                &tobj::Material::default(),
+                &obj_directory,
+                vertex_shader_path,
+                fragment_shader_path,
+                device,
+                &queue,
+            )?);
+        }
+
+        Ok((
+            Self::from_obj_models(concept_manager, models, material_indices),
+            materials,
+        ))
+    }
+
+    fn load_obj(
+        obj_path: &str,
+    ) -> Result<(Vec<tobj::Model>, tobj::MTLLoadResult), MeshComponentError> {
         let path = std::path::Path::new(&std::env::current_dir().unwrap()).join(obj_path);
-        let obj_load_res = tobj::load_obj(
+        tobj::load_obj(
             path,
             &tobj::LoadOptions {
                 single_index: true,
                 triangulate: true,
                 ..Default::default()
             },
-        );
+        )
+        .map_err(|_| MeshComponentError::FailedToLoadObj)
+    }
 
-        if let Ok((models, materials_res)) = obj_load_res {
-            if materials_res.is_err() && expect_material {
-                return Err(MeshComponentError::FailedToLoadMtl);
-            }
+    fn from_obj_models(
+        concept_manager: Rc<Mutex<ConceptManager>>,
+        models: Vec<tobj::Model>,
+        material_indices: Vec<usize>,
+    ) -> Self {
+        let meshes = models.into_iter().map(|m| {
+            let generated_normals = if m.mesh.normals.is_empty() {
+                Self::compute_vertex_normals(&m.mesh.positions, &m.mesh.indices)
+            } else {
+                Vec::new()
+            };
 
-            // let materials = materials_res.unwrap_or(vec![tobj::Material::default()]);
-
-            let meshes = models.into_iter().map(|m| {
-                let vertices = (0..m.mesh.positions.len() / 3)
-                    .map(|i| Vertex {
-                        position: [
-                            m.mesh.positions[i * 3],
-                            m.mesh.positions[i * 3 + 1],
-                            m.mesh.positions[i * 3 + 2],
-                        ],
-                        normal: [
+            let vertices = (0..m.mesh.positions.len() / 3)
+                .map(|i| Vertex {
+                    position: [
+                        m.mesh.positions[i * 3],
+                        m.mesh.positions[i * 3 + 1],
+                        m.mesh.positions[i * 3 + 2],
+                    ],
+                    normal: if m.mesh.normals.is_empty() {
+                        generated_normals[i]
+                    } else {
+                        [
                             m.mesh.normals[i * 3],
                             m.mesh.normals[i * 3 + 1],
                             m.mesh.normals[i * 3 + 2],
-                        ],
-                        tex_coords: [m.mesh.texcoords[i * 2], 1.0 - m.mesh.texcoords[i * 2 + 1]],
+                        ]
+                    },
+                    tex_coords: if m.mesh.texcoords.is_empty() {
+                        [0.0, 0.0]
+                    } else {
+                        [m.mesh.texcoords[i * 2], 1.0 - m.mesh.texcoords[i * 2 + 1]]
+                    },
+                })
+                .collect::<Vec<_>>();
+
+            (vertices, m.mesh.indices)
+        });
+
+        Self::from_submeshes(concept_manager, meshes.collect(), material_indices)
+    }
+
+    /// Loads every triangle primitive of every mesh in a glTF/GLB file as one submesh each. Node
+    /// transforms and materials are ignored, use [crate::ecs::scene::Scene::load_gltf_scene] to
+    /// keep them
+    pub fn from_gltf(
+        concept_manager: Rc<Mutex<ConceptManager>>,
+        gltf_path: &str,
+    ) -> Result<Self, MeshComponentError> {
+        let path = std::path::Path::new(&std::env::current_dir().unwrap()).join(gltf_path);
+        let (document, buffers, _) =
+            gltf::import(path).map_err(|_| MeshComponentError::FailedToLoadGltf)?;
+
+        let submeshes = document
+            .meshes()
+            .flat_map(|mesh| Self::read_gltf_primitives(&mesh, &buffers))
+            .map(|(vertices, indices, _)| (vertices, indices))
+            .collect();
+
+        Ok(Self::from_submeshes(concept_manager, submeshes, Vec::new()))
+    }
+
+    /// Reads the triangle primitives of a glTF mesh into the [Vertex] layout, along with the
+    /// index of the glTF material each primitive uses
+    pub fn read_gltf_primitives(
+        mesh: &gltf::Mesh,
+        buffers: &[gltf::buffer::Data],
+    ) -> Vec<(Vec<Vertex>, Vec<u32>, Option<usize>)> {
+        mesh.primitives()
+            .filter(|primitive| primitive.mode() == gltf::mesh::Mode::Triangles)
+            .filter_map(|primitive| {
+                let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()]));
+                let positions = reader.read_positions()?.collect::<Vec<_>>();
+
+                let indices = match reader.read_indices() {
+                    Some(indices) => indices.into_u32().collect::<Vec<_>>(),
+                    None => (0..positions.len() as u32).collect(),
+                };
+
+                let normals = match reader.read_normals() {
+                    Some(normals) => normals.collect::<Vec<_>>(),
+                    None => Self::compute_vertex_normals(&positions.concat(), &indices),
+                };
+
+                let tex_coords = match reader.read_tex_coords(0) {
+                    Some(tex_coords) => tex_coords.into_f32().collect::<Vec<_>>(),
+                    None => vec![[0.0, 0.0]; positions.len()],
+                };
+
+                let vertices = positions
+                    .into_iter()
+                    .zip(normals)
+                    .zip(tex_coords)
+                    .map(|((position, normal), tex_coords)| Vertex {
+                        position,
+                        normal,
+                        tex_coords,
                     })
-                    .collect::<Vec<_>>();
+                    .collect();
 
-                (vertices, m.mesh.indices)
-            });
+                Some((vertices, indices, primitive.material().index()))
+            })
+            .collect()
+    }
+
+    /// Creates a mesh with one submesh per `(vertices, indices)` pair. `material_indices` holds
+    /// the entity material of each submesh, or is empty to draw everything with the active one
+    pub fn from_submeshes(
+        concept_manager: Rc<Mutex<ConceptManager>>,
+        submeshes: Vec<(Vec<Vertex>, Vec<u32>)>,
+        material_indices: Vec<usize>,
+    ) -> Self {
+        let (vertices, indices): (Vec<_>, Vec<_>) = submeshes.into_iter().unzip();
+
+        let mut component = MeshComponent {
+            parent: EntityId::MAX,
+            concept_ids: Vec::new(),
+            id: (EntityId::MAX, TypeId::of::<Self>(), 0),
+            mesh_count: vertices.len(),
+            vertex_buffers: vec![None],
+            index_buffers: vec![None],
+            material_indices,
+            vertex_type: VertexType::Standard,
+        };
 
-            let (vertices, indices): (Vec<_>, Vec<_>) = meshes.unzip();
+        let mut concepts: HashMap<String, Box<dyn Any>> = HashMap::new();
+        concepts.insert("vertices".to_string(), Box::new(vertices));
+        concepts.insert("indices".to_string(), Box::new(indices));
 
-            let mut component = MeshComponent {
-                parent: EntityId::MAX,
-                concept_ids: Vec::new(),
-                id: (EntityId::MAX, TypeId::of::<Self>(), 0),
-                mesh_count: vertices.len(),
-                vertex_buffers: Arc::from(vec![None].into_boxed_slice()),
-                index_buffers: Arc::from(vec![None].into_boxed_slice()),
-            };
+        component.register_component(concept_manager, concepts);
 
-            let mut concepts: HashMap<String, Box<dyn Any>> = HashMap::new();
-            concepts.insert("vertices".to_string(), Box::new(vertices));
-            concepts.insert("indices".to_string(), Box::new(indices));
+        component
+    }
+
+    fn create_obj_material(
+        obj_material: &tobj::Material,
+        obj_directory: &std::path::Path,
+        vertex_shader_path: &str,
+        fragment_shader_path: &str,
+        device: Arc<Device>,
+        queue: &Queue,
+    ) -> Result<Material, MeshComponentError> {
+        let textures = [
+            (&obj_material.diffuse_texture, false),
+            (&obj_material.normal_texture, true),
+        ]
+        .into_iter()
+        .filter_map(|(texture_path, is_normal_map)| {
+            texture_path
+                .as_ref()
+                .map(|texture_path| (texture_path, is_normal_map))
+        })
+        .map(|(texture_path, is_normal_map)| {
+            let full_path = obj_directory.join(texture_path);
+            let load_error =
+                || MeshComponentError::FailedToLoadTexture(full_path.display().to_string());
+            let bytes = std::fs::read(&full_path).map_err(|_| load_error())?;
+            Texture::from_bytes(&device, queue, &bytes, texture_path, is_normal_map)
+                .map(Rc::new)
+                .map_err(|_| load_error())
+        })
+        .collect::<Result<Vec<_>, _>>()?;
 
-            component.register_component(concept_manager, concepts);
+        let diffuse = obj_material.diffuse.unwrap_or([1.0; 3]);
+        let base_color = [
+            diffuse[0],
+            diffuse[1],
+            diffuse[2],
+            obj_material.dissolve.unwrap_or(1.0),
+        ];
 
-            return Ok(component);
+        let mut pipeline_config = PipelineConfig::imported_mesh();
+        if base_color[3] < 1.0 {
+            pipeline_config.blend_mode = BlendMode::AlphaBlend;
+            pipeline_config.depth_write = false;
         }
-        Err(MeshComponentError::FailedToLoadObj)
+
+        Ok(Material::new(
+            vertex_shader_path,
+            fragment_shader_path,
+            textures,
+            Some(bytemuck::cast_slice(&base_color)),
+            true,
+            device,
+        )
+        .with_pipeline_config(pipeline_config))
+    }
+
+    /// Uploads [TangentVertex]s generated from the mesh's texture coordinates instead of plain
+    /// vertices, for normal mapping. The entity's materials need
+    /// [Material::with_vertex_type] with [VertexType::Tangent]. Call before the mesh is
+    /// initialized
+    pub fn with_tangents(mut self) -> Self {
+        self.vertex_type = VertexType::Tangent;
+        self
+    }
+
+    /// Number of submeshes
+    pub fn mesh_count(&self) -> usize {
+        self.mesh_count
+    }
+
+    pub fn vertex_type(&self) -> VertexType {
+        self.vertex_type
+    }
+
+    /// Index into the entity's materials for every submesh. Empty when the whole mesh is drawn
+    /// with the entity's active material
+    pub fn material_indices(&self) -> &[usize] {
+        &self.material_indices
+    }
+
+    /// Replaces the vertices of a submesh, e.g. for deformable meshes or terrain edits. When the
+    /// vertex count stays the same the existing buffer is overwritten in place, which is cheap
+    /// enough to do every frame. A different count allocates a new buffer, which is slower and
+    /// is better kept to occasional edits. Before the mesh is initialized only the stored
+    /// vertices change
+    pub fn update_vertices(
+        &mut self,
+        concept_manager: Rc<Mutex<ConceptManager>>,
+        device: Arc<Device>,
+        queue: Arc<Queue>,
+        mesh_index: usize,
+        vertices: Vec<Vertex>,
+    ) -> Result<(), MeshComponentError> {
+        if mesh_index >= self.mesh_count {
+            return Err(MeshComponentError::SubmeshOutOfRange(mesh_index));
+        }
+
+        let mut concept_manager = concept_manager.lock().unwrap();
+        concept_manager
+            .get_concept_mut::<Vec<Vec<Vertex>>>(self.id, "vertices".to_string())
+            .unwrap()[mesh_index] = vertices;
+        self.write_vertex_buffer(&concept_manager, &device, &queue, mesh_index);
+
+        Ok(())
+    }
+
+    /// Replaces the indices of a submesh, reusing its index buffer when the index count stays
+    /// the same like [MeshComponent::update_vertices]. Tangent meshes also rewrite their
+    /// vertices, as the tangents depend on the faces
+    pub fn update_indices(
+        &mut self,
+        concept_manager: Rc<Mutex<ConceptManager>>,
+        device: Arc<Device>,
+        queue: Arc<Queue>,
+        mesh_index: usize,
+        indices: Vec<u32>,
+    ) -> Result<(), MeshComponentError> {
+        if mesh_index >= self.mesh_count {
+            return Err(MeshComponentError::SubmeshOutOfRange(mesh_index));
+        }
+
+        let mut concept_manager = concept_manager.lock().unwrap();
+        let stored_indices = &mut concept_manager
+            .get_concept_mut::<Vec<Vec<u32>>>(self.id, "indices".to_string())
+            .unwrap()[mesh_index];
+        *stored_indices = indices;
+
+        if let Some(index_buffer) = self.index_buffers.get_mut(mesh_index) {
+            if index_buffer.is_some() {
+                Self::write_buffer(
+                    &device,
+                    &queue,
+                    index_buffer,
+                    bytemuck::cast_slice(stored_indices),
+                    wgpu::BufferUsages::INDEX,
+                    "Entity Index Buffer",
+                );
+            }
+        }
+        if self.vertex_type == VertexType::Tangent {
+            self.write_vertex_buffer(&concept_manager, &device, &queue, mesh_index);
+        }
+
+        Ok(())
+    }
+
+    /// Uploads the stored vertices of a submesh, if its buffers have been created
+    fn write_vertex_buffer(
+        &mut self,
+        concept_manager: &ConceptManager,
+        device: &Device,
+        queue: &Queue,
+        mesh_index: usize,
+    ) {
+        let Some(vertex_buffer) = self.vertex_buffers.get_mut(mesh_index) else {
+            return;
+        };
+        if vertex_buffer.is_none() {
+            return;
+        }
+
+        let vertices = &concept_manager
+            .get_concept::<Vec<Vec<Vertex>>>(self.id, "vertices".to_string())
+            .unwrap()[mesh_index];
+        match self.vertex_type {
+            VertexType::Standard | VertexType::Custom(_) => Self::write_buffer(
+                device,
+                queue,
+                vertex_buffer,
+                bytemuck::cast_slice(vertices),
+                wgpu::BufferUsages::VERTEX,
+                "Entity Vertex Buffer",
+            ),
+            VertexType::Tangent => {
+                let indices = &concept_manager
+                    .get_concept::<Vec<Vec<u32>>>(self.id, "indices".to_string())
+                    .unwrap()[mesh_index];
+                Self::write_buffer(
+                    device,
+                    queue,
+                    vertex_buffer,
+                    bytemuck::cast_slice(&TangentVertex::from_vertices(vertices, indices)),
+                    wgpu::BufferUsages::VERTEX,
+                    "Entity Vertex Buffer",
+                )
+            }
+        }
+    }
+
+    /// Writes `contents` into `buffer` when they're the same size, otherwise replaces it with a
+    /// new buffer holding them
+    fn write_buffer(
+        device: &Device,
+        queue: &Queue,
+        buffer: &mut Option<Arc<Buffer>>,
+        contents: &[u8],
+        usage: wgpu::BufferUsages,
+        label: &str,
+    ) {
+        match buffer {
+            Some(existing) if existing.size() == contents.len() as u64 && !contents.is_empty() => {
+                queue.write_buffer(existing, 0, contents);
+            }
+            _ => {
+                *buffer = Some(Arc::new(device.create_buffer_init(&BufferInitDescriptor {
+                    label: Some(label),
+                    contents,
+                    usage: usage | BUFFER_USAGES,
+                })));
+            }
+        }
+    }
+
+    pub fn render_submesh<'a: 'b, 'b>(
+        &'a self,
+        render_pass: &mut RenderPass<'b>,
+        concept_manager: &ConceptManager,
+        submesh_index: usize,
+    ) {
+        let vertex_buffer_opt = self.vertex_buffers[submesh_index].as_ref();
+        if let Some(vertex_buffer) = &vertex_buffer_opt {
+            render_pass.set_vertex_buffer(0, vertex_buffer.slice(..));
+        }
+
+        let index_buffer_opt = self.index_buffers[submesh_index].as_ref();
+        let indices = &concept_manager
+            .get_concept::<Vec<Vec<u32>>>(self.id, "indices".to_string())
+            .unwrap()[submesh_index];
+
+        if let Some(index_buffer) = index_buffer_opt {
+            render_pass.set_index_buffer(index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+            render_pass.draw_indexed(0..indices.len() as u32, 0, 0..1);
+        }
+    }
+
+    /// Same as [MeshComponent::render_submesh], with the draw's arguments read from
+    /// `indirect_buffer` at `indirect_offset`, see [crate::indirect::IndirectDraws]
+    pub fn render_submesh_indirect<'a: 'b, 'b>(
+        &'a self,
+        render_pass: &mut RenderPass<'b>,
+        submesh_index: usize,
+        indirect_buffer: &'b Buffer,
+        indirect_offset: u64,
+    ) {
+        if let (Some(vertex_buffer), Some(index_buffer)) = (
+            self.vertex_buffers[submesh_index].as_ref(),
+            self.index_buffers[submesh_index].as_ref(),
+        ) {
+            render_pass.set_vertex_buffer(0, vertex_buffer.slice(..));
+            render_pass.set_index_buffer(index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+            render_pass.draw_indexed_indirect(indirect_buffer, indirect_offset);
+        }
+    }
+
+    /// Generates smooth normals by accumulating the normal of every face touching a vertex
+    pub fn compute_vertex_normals(positions: &[f32], indices: &[u32]) -> Vec<[f32; 3]> {
+        let mut normals = vec![na::Vector3::<f32>::zeros(); positions.len() / 3];
+        let position = |i: u32| {
+            let i = i as usize;
+            na::Vector3::new(positions[i * 3], positions[i * 3 + 1], positions[i * 3 + 2])
+        };
+
+        for face in indices.chunks_exact(3) {
+            let edge_a = position(face[1]) - position(face[0]);
+            let edge_b = position(face[2]) - position(face[0]);
+            let face_normal = edge_a.cross(&edge_b);
+            for index in face {
+                normals[*index as usize] += face_normal;
+            }
+        }
+
+        normals
+            .into_iter()
+            .map(|normal| {
+                normal
+                    .try_normalize(f32::EPSILON)
+                    .unwrap_or(na::Vector3::y())
+                    .into()
+            })
+            .collect()
     }
 }
 
@@ -152,24 +641,35 @@ impl ComponentSystem for MeshComponent {
             let current_vertices = &vertices[i];
             let current_indices = &indices[i];
 
-            let vert_buf = device.create_buffer_init(&BufferInitDescriptor {
-                label: Some("Entity Vertex Buffer"),
-                contents: bytemuck::cast_slice(current_vertices),
-                usage: wgpu::BufferUsages::VERTEX,
-            });
+            // Meshes only ever hold standard or tangent vertices, see [MeshComponent::with_tangents]
+            let vert_buf = match self.vertex_type {
+                VertexType::Standard | VertexType::Custom(_) => {
+                    device.create_buffer_init(&BufferInitDescriptor {
+                        label: Some("Entity Vertex Buffer"),
+                        contents: bytemuck::cast_slice(current_vertices),
+                        usage: wgpu::BufferUsages::VERTEX | BUFFER_USAGES,
+                    })
+                }
+                VertexType::Tangent => device.create_buffer_init(&BufferInitDescriptor {
+                    label: Some("Entity Vertex Buffer"),
+                    contents: bytemuck::cast_slice(&TangentVertex::from_vertices(
+                        current_vertices,
+                        current_indices,
+                    )),
+                    usage: wgpu::BufferUsages::VERTEX | BUFFER_USAGES,
+                }),
+            };
 
             let ind_buf = device.create_buffer_init(&BufferInitDescriptor {
                 label: Some("Entity Index Buffer"),
                 contents: bytemuck::cast_slice(current_indices),
-                usage: wgpu::BufferUsages::INDEX,
+                usage: wgpu::BufferUsages::INDEX | BUFFER_USAGES,
             });
 
-            (Some(vert_buf), Some(ind_buf))
+            (Some(Arc::new(vert_buf)), Some(Arc::new(ind_buf)))
         });
 
-        let (vert_bufs, ind_bufs): (Vec<_>, Vec<_>) = buffers.unzip();
-        self.vertex_buffers = Arc::from(vert_bufs.into_boxed_slice());
-        self.index_buffers = Arc::from(ind_bufs.into_boxed_slice());
+        (self.vertex_buffers, self.index_buffers) = buffers.unzip();
     }
 
     fn render<'a: 'b, 'b>(
@@ -185,20 +685,142 @@ impl ComponentSystem for MeshComponent {
         let concept_manager = concept_manager.lock().unwrap();
 
         for i in 0..self.mesh_count {
-            let vertex_buffer_opt = self.vertex_buffers[i].as_ref();
-            if let Some(vertex_buffer) = &vertex_buffer_opt {
-                render_pass.set_vertex_buffer(0, vertex_buffer.slice(..));
-            }
+            self.render_submesh(render_pass, &concept_manager, i);
+        }
+    }
+}
 
-            let index_buffer_opt = self.index_buffers[i].as_ref();
-            let indices = &concept_manager
-                .get_concept::<Vec<Vec<u32>>>(self.id, "indices".to_string())
-                .unwrap()[i];
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{headless_device, read_buffer, shader_dir};
 
-            if let Some(index_buffer) = index_buffer_opt {
-                render_pass.set_index_buffer(index_buffer.slice(..), wgpu::IndexFormat::Uint32);
-                render_pass.draw_indexed(0..indices.len() as u32, 0, 0..1);
-            }
+    #[test]
+    fn updates_reach_the_gpu_buffers() {
+        let Some((device, queue)) = headless_device(wgpu::Features::empty()) else {
+            return;
+        };
+        let concept_manager = Rc::new(Mutex::new(ConceptManager::default()));
+        let mut mesh = MeshComponent::quad(concept_manager.clone());
+        let ui_manager = Rc::new(Mutex::new(UiManager::without_window(
+            wgpu::TextureFormat::Rgba8Unorm,
+            device.clone(),
+            queue.clone(),
+        )));
+        mesh.initialize(
+            device.clone(),
+            queue.clone(),
+            &AllComponents::new(),
+            concept_manager.clone(),
+            None,
+            None,
+            ui_manager,
+            &mut Vec::new(),
+        );
+        let (mut vertices, indices) = primitives::quad();
+        let (cube_vertices, cube_indices) = primitives::cube();
+
+        // The same counts are written into the existing buffers
+        let vertex_buffer = mesh.vertex_buffers[0].clone().unwrap();
+        vertices[0].position = [9.0, 8.0, 7.0];
+        mesh.update_vertices(
+            concept_manager.clone(),
+            device.clone(),
+            queue.clone(),
+            0,
+            vertices.clone(),
+        )
+        .unwrap();
+        assert!(Arc::ptr_eq(
+            &vertex_buffer,
+            mesh.vertex_buffers[0].as_ref().unwrap()
+        ));
+        assert_eq!(
+            read_buffer(&device, &queue, &vertex_buffer),
+            bytemuck::cast_slice::<Vertex, u8>(&vertices)
+        );
+
+        let index_buffer = mesh.index_buffers[0].clone().unwrap();
+        let reversed_indices = indices.iter().rev().copied().collect::<Vec<_>>();
+        mesh.update_indices(
+            concept_manager.clone(),
+            device.clone(),
+            queue.clone(),
+            0,
+            reversed_indices.clone(),
+        )
+        .unwrap();
+        assert!(Arc::ptr_eq(
+            &index_buffer,
+            mesh.index_buffers[0].as_ref().unwrap()
+        ));
+        assert_eq!(
+            read_buffer(&device, &queue, &index_buffer),
+            bytemuck::cast_slice::<u32, u8>(&reversed_indices)
+        );
+
+        // Other counts get new buffers
+        mesh.update_vertices(
+            concept_manager.clone(),
+            device.clone(),
+            queue.clone(),
+            0,
+            cube_vertices.clone(),
+        )
+        .unwrap();
+        let new_vertex_buffer = mesh.vertex_buffers[0].clone().unwrap();
+        assert!(!Arc::ptr_eq(&vertex_buffer, &new_vertex_buffer));
+        assert_eq!(
+            read_buffer(&device, &queue, &new_vertex_buffer),
+            bytemuck::cast_slice::<Vertex, u8>(&cube_vertices)
+        );
+
+        mesh.update_indices(
+            concept_manager.clone(),
+            device.clone(),
+            queue.clone(),
+            0,
+            cube_indices.clone(),
+        )
+        .unwrap();
+        let new_index_buffer = mesh.index_buffers[0].clone().unwrap();
+        assert!(!Arc::ptr_eq(&index_buffer, &new_index_buffer));
+        assert_eq!(
+            read_buffer(&device, &queue, &new_index_buffer),
+            bytemuck::cast_slice::<u32, u8>(&cube_indices)
+        );
+
+        assert!(matches!(
+            mesh.update_indices(concept_manager, device, queue, 1, cube_indices),
+            Err(MeshComponentError::SubmeshOutOfRange(1))
+        ));
+    }
+
+    #[test]
+    fn positions_only_objs_get_unit_normals() {
+        let dir = shader_dir(
+            "positions_only_obj",
+            &[(
+                "quad.obj",
+                "v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nf 1 2 3\nf 1 3 4\n",
+            )],
+        );
+        let concept_manager = Rc::new(Mutex::new(ConceptManager::default()));
+        let mesh = MeshComponent::from_obj(
+            concept_manager.clone(),
+            dir.join("quad.obj").to_str().unwrap(),
+            false,
+        )
+        .unwrap();
+
+        let concept_manager = concept_manager.lock().unwrap();
+        let vertices = &concept_manager
+            .get_concept::<Vec<Vec<Vertex>>>(mesh.id, "vertices".to_string())
+            .unwrap()[0];
+        assert_eq!(vertices.len(), 4);
+        for vertex in vertices {
+            assert_eq!(vertex.normal, [0.0, 0.0, 1.0]);
+            assert_eq!(vertex.tex_coords, [0.0, 0.0]);
         }
     }
 }
@@ -6,18 +6,80 @@ use std::time::{Duration, Instant};
 // use ultraviolet::{Rotor3, Bivec3};
 use algoe::{bivector::Bivector, rotor::Rotor3};
 
-use crate::{ecs::scene::{Scene, TextParams}, new_component, ui_manager::UiManager};
+use crate::{
+    ecs::{
+        concepts::{ConceptHandle, ConceptManagerError},
+        scene::{Scene, TextParams},
+    },
+    new_component,
+    ui_manager::UiManager,
+};
+
+use super::transform_component::{TransformComponent, TransformHandles};
+
+/// Emit through [crate::events::EventBus] to push bodies without looking up their components
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ImpulseEvent {
+    /// Entity whose body is pushed, [None] pushes every body
+    pub target: Option<EntityId>,
+    pub force: Vector3<f32>,
+    pub duration: Duration,
+}
 
-use super::transform_component::TransformComponent;
+/// How a [PhysicsComponent] advances its position and velocity each frame
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Integrator {
+    /// Half a position step with the old velocity, then half with the new one
+    #[default]
+    SemiImplicitEuler,
+    /// Fourth order Runge-Kutta, more accurate for fast moving bodies
+    Rk4,
+}
 
 new_component!(
     PhysicsComponent {
         concept_ids: Vec<String>,
-        impulses: Vec<Impulse>
+        impulses: Vec<Impulse>,
+        integrator: Integrator,
+        previous_position: Option<Vector3<f32>>,
+        interpolation: Option<(Vector3<f32>, Vector3<f32>)>,
+        handles: Option<PhysicsHandles>,
+        transform_handles: Option<TransformHandles>
     }
 );
 
+/// Handles to the concepts of one body
+#[derive(Debug, Clone, Copy)]
+pub struct PhysicsHandles {
+    pub velocity: ConceptHandle<Vector3<f32>>,
+    pub net_force: ConceptHandle<Vector3<f32>>,
+    pub mass: ConceptHandle<f32>,
+    pub angular_velocity: ConceptHandle<Bivector>,
+    pub net_torque: ConceptHandle<Bivector>,
+    pub gravity: ConceptHandle<Vector3<f32>>,
+}
+
+impl PhysicsHandles {
+    pub fn new(
+        concept_manager: &ConceptManager,
+        id: ComponentId,
+    ) -> Result<Self, ConceptManagerError> {
+        Ok(Self {
+            velocity: concept_manager.concept_handle(id, "velocity")?,
+            net_force: concept_manager.concept_handle(id, "net_force")?,
+            mass: concept_manager.concept_handle(id, "mass")?,
+            angular_velocity: concept_manager.concept_handle(id, "angular_velocity")?,
+            net_torque: concept_manager.concept_handle(id, "net_torque")?,
+            gravity: concept_manager.concept_handle(id, "gravity")?,
+        })
+    }
+}
+
 impl PhysicsComponent {
+    /// Velocities are in units per second and forces in mass units times units per second
+    /// squared. Expects a [TransformComponent] on the entity, which the body moves and rotates.
+    /// The body is simulated in [ComponentSystem::fixed_update], in between its drawn position is
+    /// interpolated from the last two steps
     pub fn new(
         concept_manager: Rc<Mutex<ConceptManager>>,
         velocity: Vector3<f32>,
@@ -31,6 +93,11 @@ impl PhysicsComponent {
             concept_ids: Vec::new(),
             id: (EntityId::MAX, TypeId::of::<Self>(), 0),
             impulses: Vec::new(),
+            integrator: Integrator::default(),
+            previous_position: None,
+            interpolation: None,
+            handles: None,
+            transform_handles: None,
         };
 
         let mut concepts: HashMap<String, Box<dyn Any>> = HashMap::new();
@@ -40,22 +107,52 @@ impl PhysicsComponent {
         concepts.insert("mass".to_string(), Box::new(mass));
         concepts.insert("angular_velocity".to_string(), Box::new(angular_velocity));
         concepts.insert("net_torque".to_string(), Box::new(net_torque));
+        concepts.insert("gravity".to_string(), Box::new(Vector3::<f32>::zeros()));
 
         component.register_component(concept_manager, concepts);
 
         component
     }
 
+    pub fn with_integrator(mut self, integrator: Integrator) -> Self {
+        self.integrator = integrator;
+        self
+    }
+
+    /// Sets an acceleration applied to the body regardless of its mass, e.g.
+    /// `Vector3::new(0.0, -9.81, 0.0)`. Defaults to zero
+    pub fn set_gravity(&self, concept_manager: Rc<Mutex<ConceptManager>>, gravity: Vector3<f32>) {
+        let mut concept_manager = concept_manager.lock().unwrap();
+        let handles = self.handles(&concept_manager);
+        *concept_manager.get_mut(handles.gravity).unwrap() = gravity;
+    }
+
     pub fn add_constant_force(
         &self,
         concept_manager: Rc<Mutex<ConceptManager>>,
         force: Vector3<f32>,
     ) {
         let mut concept_manager = concept_manager.lock().unwrap();
-        let net_force = concept_manager
-            .get_concept_mut::<Vector3<f32>>(self.id, "net_force".to_string())
-            .unwrap();
-        *net_force += force;
+        let handles = self.handles(&concept_manager);
+        *concept_manager.get_mut(handles.net_force).unwrap() += force;
+    }
+
+    /// The handles cached in [ComponentSystem::initialize], or freshly looked up ones before
+    /// that, while the component's id may still change
+    fn handles(&self, concept_manager: &ConceptManager) -> PhysicsHandles {
+        match self.handles {
+            Some(handles) if handles.velocity.component() == self.id => handles,
+            _ => PhysicsHandles::new(concept_manager, self.id).unwrap(),
+        }
+    }
+
+    /// Same as [PhysicsComponent::handles] for the entity's transform, [None] without one
+    fn transform_handles(&self, concept_manager: &ConceptManager) -> Option<TransformHandles> {
+        let transform_id = (self.parent, TypeId::of::<TransformComponent>(), 0);
+        match self.transform_handles {
+            Some(handles) if handles.matrix.component() == transform_id => Some(handles),
+            _ => TransformHandles::new(concept_manager, transform_id).ok(),
+        }
     }
 
     pub fn add_impulse(&mut self, force: Vector3<f32>, duration: Duration) {
@@ -81,7 +178,63 @@ impl PhysicsComponent {
         impulses.into_iter().sum()
     }
 
-    fn remove_impulses(&mut self) {
+    /// Returns the position and velocity change over one step
+    fn integrate(
+        &self,
+        velocity: Vector3<f32>,
+        acceleration: Vector3<f32>,
+        delta_time: f32,
+    ) -> (Vector3<f32>, Vector3<f32>) {
+        match self.integrator {
+            Integrator::SemiImplicitEuler => {
+                let new_velocity = velocity + acceleration * delta_time;
+                (
+                    (velocity + new_velocity) * delta_time / 2.0,
+                    new_velocity - velocity,
+                )
+            }
+            Integrator::Rk4 => {
+                // Forces are constant over a step, so every stage sees the same acceleration
+                let k1 = velocity;
+                let k2 = velocity + acceleration * delta_time / 2.0;
+                let k3 = velocity + acceleration * delta_time / 2.0;
+                let k4 = velocity + acceleration * delta_time;
+                (
+                    (k1 + k2 * 2.0 + k3 * 2.0 + k4) * delta_time / 6.0,
+                    acceleration * delta_time,
+                )
+            }
+        }
+    }
+
+    /// Moves the transform back from the interpolated position drawn last frame to where the
+    /// simulation left it, unless something else moved it since. Returns the position
+    fn restore_simulated_position(
+        &mut self,
+        concept_manager: &Rc<Mutex<ConceptManager>>,
+    ) -> Option<Vector3<f32>> {
+        let mut concept_manager = concept_manager.lock().unwrap();
+        let transform_handles = self.transform_handles(&concept_manager)?;
+        let position = *concept_manager.get(transform_handles.position).ok()?;
+        let rotation = *concept_manager.get(transform_handles.rotation).ok()?;
+
+        match self.interpolation.take() {
+            Some((simulated, drawn)) if drawn == position => {
+                TransformComponent::set_position_and_rotation_with(
+                    &mut concept_manager,
+                    transform_handles,
+                    simulated,
+                    rotation,
+                )
+                .ok()?;
+                Some(simulated)
+            }
+            _ => Some(position),
+        }
+    }
+
+    /// Drops the impulses that expired by `now`, the rest keep pushing the body
+    fn remove_impulses(&mut self, now: Instant) {
         self.impulses.retain(
             |Impulse {
                  force: _,
@@ -89,7 +242,7 @@ impl PhysicsComponent {
                  duration,
              }| {
                 let expiration_time = *initialized_instant + *duration;
-                Instant::now() >= expiration_time
+                now < expiration_time
             },
         );
     }
@@ -114,7 +267,7 @@ impl ComponentSystem for PhysicsComponent {
         _device: Arc<wgpu::Device>,
         _queue: Arc<wgpu::Queue>,
         component_map: &AllComponents,
-        _concept_manager: Rc<Mutex<ConceptManager>>,
+        concept_manager: Rc<Mutex<ConceptManager>>,
         _engine_details: Option<Rc<Mutex<EngineDetails>>>,
         _engine_systems: Option<Rc<Mutex<EngineSystems>>>,
         _ui_manager: Rc<Mutex<UiManager>>,
@@ -123,13 +276,17 @@ impl ComponentSystem for PhysicsComponent {
         let _transform_component =
             Scene::get_component::<TransformComponent>(&component_map[&self.parent])
                 .expect("Physics component expects a transform component on this entity");
+
+        let concept_manager = concept_manager.lock().unwrap();
+        self.handles = Some(PhysicsHandles::new(&concept_manager, self.id).unwrap());
+        self.transform_handles = self.transform_handles(&concept_manager);
     }
 
-    fn update(
+    fn fixed_update(
         &mut self,
         _device: Arc<Device>,
         _queue: Arc<Queue>,
-        _component_map: &mut AllComponents,
+        component_map: &mut AllComponents,
         engine_details: Rc<Mutex<EngineDetails>>,
         _engine_systems: Rc<Mutex<EngineSystems>>,
         concept_manager: Rc<Mutex<ConceptManager>>,
@@ -139,78 +296,106 @@ impl ComponentSystem for PhysicsComponent {
         _compute_pipelines: &mut [ComputePipeline],
         _text_items: &mut Vec<TextParams>,
     ) {
-        let mut concept_manager = concept_manager.lock().unwrap();
-        let engine_details = engine_details.lock().unwrap();
-        let delta_time = (engine_details.last_frame_duration.as_micros() as f32) / 1000.0;
+        let delta_time = engine_details.lock().unwrap().fixed_timestep.as_secs_f32();
 
-        // First part of linear velocity
-        let velocity = concept_manager
-            .get_concept::<Vector3<f32>>(self.id, "velocity".to_string())
-            .unwrap()
-            .clone_owned();
-
-        let angular_velocity = *concept_manager
-            .get_concept::<Bivector>(self.id, "angular_velocity".to_string())
-            .unwrap();
-
-        let position = concept_manager
-            .get_concept_mut::<Vector3<f32>>(
-                (self.parent, TypeId::of::<TransformComponent>(), 0),
-                "position".to_string(),
-            )
-            .unwrap();
-
-        *position += velocity * delta_time / 2.0;
-
-        // First part of angular velocity
-        // let corrected_angular_velocity = match angular_velocity.to_normalized().mag().is_nan() {
-        //     true => (0.0, Bivector::zero()),
-        //     false => (angular_velocity.mag(), angular_velocity.to_normalized()),
-        // };
-        let rotor = Rotor3 {
-            scalar: angular_velocity.magnitude().cos(),
-            bivector: angular_velocity.to_normalized() * angular_velocity.magnitude().sin(),
-        };
-        // angular_velocity.scale_by(delta_time / 2.0);
-        let rotated_position_slice = rotor * *position;
-        *position = rotated_position_slice;
+        self.previous_position = self.restore_simulated_position(&concept_manager);
 
-        // Calculating new linear velocity
-        let mass = *concept_manager
-            .get_concept::<f32>(self.id, "mass".to_string())
-            .unwrap();
+        let impulses = self.sum_impulses();
+        let (translation, rotation) = {
+            let mut concept_manager = concept_manager.lock().unwrap();
+            let handles = self.handles(&concept_manager);
+            let angular_velocity = *concept_manager.get(handles.angular_velocity).unwrap();
+            let mass = *concept_manager.get(handles.mass).unwrap();
+            let net_force = *concept_manager.get(handles.net_force).unwrap() + impulses;
+            let gravity = *concept_manager.get(handles.gravity).unwrap();
+            let velocity = concept_manager.get_mut(handles.velocity).unwrap();
 
-        let net_force = concept_manager
-            .get_concept::<Vector3<f32>>(self.id, "net_force".to_string())
-            .unwrap()
-            .clone_owned()
-            + self.sum_impulses();
+            let acceleration = net_force / mass + gravity;
+            let (translation, velocity_change) =
+                self.integrate(*velocity, acceleration, delta_time);
 
-        let acceleration = net_force / mass;
+            *velocity += velocity_change;
 
-        let velocity = concept_manager
-            .get_concept_mut::<Vector3<f32>>(self.id, "velocity".to_string())
-            .unwrap();
+            // Advances the body's orientation, rather than rotating its position about the
+            // origin
+            let rotation = (angular_velocity.magnitude() > 0.0)
+                .then(|| (angular_velocity * delta_time).exponentiate());
 
-        let new_velocity = velocity.clone_owned() + acceleration * delta_time;
-        *velocity = new_velocity;
+            (translation, rotation)
+        };
 
-        // Calculating new angular velocity
-        /* let net_torque = *concept_manager
-        .get_concept::<Rotor3>(self.id, "net_torque".to_string())
-        .unwrap(); */
+        if let Some(transform) =
+            Scene::get_component::<TransformComponent>(&component_map[&self.parent])
+        {
+            transform.apply_translation(concept_manager.clone(), translation);
+            if let Some(rotation) = rotation {
+                transform.apply_rotation(concept_manager, rotation);
+            }
+        }
+
+        self.remove_impulses(Instant::now());
+    }
 
-        // Second part of linear velocity
-        let position = concept_manager
-            .get_concept_mut::<Vector3<f32>>(
-                (self.parent, TypeId::of::<TransformComponent>(), 0),
-                "position".to_string(),
-            )
-            .unwrap();
+    fn update(
+        &mut self,
+        _device: Arc<Device>,
+        _queue: Arc<Queue>,
+        _component_map: &mut AllComponents,
+        engine_details: Rc<Mutex<EngineDetails>>,
+        engine_systems: Rc<Mutex<EngineSystems>>,
+        concept_manager: Rc<Mutex<ConceptManager>>,
+        _active_camera_id: Option<EntityId>,
+        _entities: &mut Vec<Entity>,
+        _materials: Option<&mut (Vec<Material>, usize)>,
+        _compute_pipelines: &mut [ComputePipeline],
+        _text_items: &mut Vec<TextParams>,
+    ) {
+        // Events only last a frame, so they're read here rather than in the fixed update, which
+        // can run any number of times per frame
+        let impulse_events = engine_systems
+            .lock()
+            .unwrap()
+            .events
+            .events::<ImpulseEvent>()
+            .filter(|event| event.target.is_none() || event.target == Some(self.parent))
+            .copied()
+            .collect::<Vec<_>>();
+        for event in impulse_events {
+            self.add_impulse(event.force, event.duration);
+        }
 
-        *position += new_velocity * delta_time / 2.0;
+        let Some(previous_position) = self.previous_position else {
+            return;
+        };
+        let alpha = engine_details.lock().unwrap().fixed_step_alpha;
+        let mut concept_manager = concept_manager.lock().unwrap();
+        let Some(transform_handles) = self.transform_handles(&concept_manager) else {
+            return;
+        };
+        let Ok(&position) = concept_manager.get(transform_handles.position) else {
+            return;
+        };
+        let Ok(&rotation) = concept_manager.get(transform_handles.rotation) else {
+            return;
+        };
 
-        self.remove_impulses();
+        // Without a fixed update since the last frame the transform still holds the position
+        // drawn then
+        let simulated_position = match self.interpolation {
+            Some((simulated, drawn)) if drawn == position => simulated,
+            _ => position,
+        };
+        let drawn_position = previous_position.lerp(&simulated_position, alpha);
+        if TransformComponent::set_position_and_rotation_with(
+            &mut concept_manager,
+            transform_handles,
+            drawn_position,
+            rotation,
+        )
+        .is_ok()
+        {
+            self.interpolation = Some((simulated_position, drawn_position));
+        }
     }
 }
 
@@ -220,3 +405,31 @@ pub struct Impulse {
     initialized_instant: Instant,
     duration: Duration,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn impulses_push_until_they_expire() {
+        let concept_manager = Rc::new(Mutex::new(ConceptManager::default()));
+        let mut body = PhysicsComponent::new(
+            concept_manager,
+            Vector3::zeros(),
+            Vector3::zeros(),
+            1.0,
+            Bivector::new(0.0, 0.0, 0.0),
+            Bivector::new(0.0, 0.0, 0.0),
+        );
+        let force = Vector3::new(-0.00001, 0.0, 0.0);
+        body.add_impulse(force, Duration::from_secs(1));
+        let start = body.impulses[0].initialized_instant;
+
+        body.remove_impulses(start + Duration::from_millis(500));
+        assert_eq!(body.sum_impulses(), force);
+
+        body.remove_impulses(start + Duration::from_secs(1));
+        assert!(body.impulses.is_empty());
+        assert_eq!(body.sum_impulses(), Vector3::zeros());
+    }
+}
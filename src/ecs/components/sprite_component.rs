@@ -0,0 +1,232 @@
+use na::Vector2;
+use nalgebra as na;
+use wgpu::{
+    util::{BufferInitDescriptor, DeviceExt},
+    Buffer, BufferUsages, RenderPass,
+};
+
+use crate::{
+    ecs::scene::{Scene, TextParams},
+    model::Vertex,
+    new_component,
+    pipeline::PipelineConfig,
+    texture::Texture,
+    ui_manager::UiManager,
+};
+
+use super::{
+    camera_component::{CameraComponent, CameraProjection},
+    transform_component::TransformComponent,
+};
+
+/// How big a [SpriteComponent]'s quad is
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SpriteSize {
+    World(Vector2<f32>),
+    /// Kept the same size on screen through [CameraComponent::new_ortho] cameras, as the window
+    /// resizes. With other cameras a pixel is one world unit
+    Pixels(Vector2<f32>),
+}
+
+new_component!(SpriteComponent {
+    size: SpriteSize,
+    uv_rect: (Vector2<f32>, Vector2<f32>),
+    quad_size: Vector2<f32>,
+    needs_upload: bool,
+    vertex_buffer: Arc<Option<Buffer>>,
+    index_buffer: Arc<Option<Buffer>>
+}, render_order: usize::MAX);
+
+impl SpriteComponent {
+    const INDICES: [u32; 6] = [0, 1, 2, 1, 2, 3];
+
+    /// A textured quad centered on the entity's transform in its xy plane, facing `-z`. Draw it
+    /// with a material from [SpriteComponent::material]
+    pub fn new(size: SpriteSize) -> Self {
+        SpriteComponent {
+            parent: EntityId::MAX,
+            id: (EntityId::MAX, TypeId::of::<Self>(), 0),
+            size,
+            uv_rect: (Vector2::zeros(), Vector2::new(1.0, 1.0)),
+            quad_size: Vector2::zeros(),
+            needs_upload: true,
+            vertex_buffer: Arc::new(None),
+            index_buffer: Arc::new(None),
+        }
+    }
+
+    /// Draws only the `size` part of the texture starting at `min`, in texture coordinates, e.g.
+    /// one frame of a sprite sheet
+    pub fn with_uv_rect(mut self, min: Vector2<f32>, size: Vector2<f32>) -> Self {
+        self.set_uv_rect(min, size);
+        self
+    }
+
+    /// Uploaded on the next update, for animating through a sprite sheet
+    pub fn set_uv_rect(&mut self, min: Vector2<f32>, size: Vector2<f32>) {
+        self.uv_rect = (min, size);
+        self.needs_upload = true;
+    }
+
+    /// Uploaded on the next update
+    pub fn set_size(&mut self, size: SpriteSize) {
+        self.size = size;
+        self.needs_upload = true;
+    }
+
+    /// A material sampling `texture` at binding 0 with its sampler at 1, alpha blended so
+    /// transparent parts of the texture show what's behind them
+    pub fn material(
+        vertex_shader_path: &str,
+        fragment_shader_path: &str,
+        texture: Rc<Texture>,
+        device: Arc<Device>,
+    ) -> Material {
+        Material::new(
+            vertex_shader_path,
+            fragment_shader_path,
+            vec![texture],
+            None,
+            true,
+            device,
+        )
+        .with_pipeline_config(PipelineConfig::transparent())
+    }
+
+    fn vertices(&self) -> [Vertex; 4] {
+        let half_size = self.quad_size / 2.0;
+        let (uv_min, uv_size) = self.uv_rect;
+        let uv_max = uv_min + uv_size;
+        let normal = [0.0, 0.0, -1.0];
+        [
+            Vertex {
+                position: [-half_size.x, -half_size.y, 0.0],
+                tex_coords: [uv_min.x, uv_max.y],
+                normal,
+            },
+            Vertex {
+                position: [-half_size.x, half_size.y, 0.0],
+                tex_coords: [uv_min.x, uv_min.y],
+                normal,
+            },
+            Vertex {
+                position: [half_size.x, -half_size.y, 0.0],
+                tex_coords: [uv_max.x, uv_max.y],
+                normal,
+            },
+            Vertex {
+                position: [half_size.x, half_size.y, 0.0],
+                tex_coords: [uv_max.x, uv_min.y],
+                normal,
+            },
+        ]
+    }
+
+    /// The quad's size in world units under the active camera
+    fn world_size(
+        &self,
+        concept_manager: &ConceptManager,
+        active_camera_id: Option<EntityId>,
+        component_map: &AllComponents,
+        window_height: u32,
+    ) -> Vector2<f32> {
+        match self.size {
+            SpriteSize::World(size) => size,
+            SpriteSize::Pixels(size) => {
+                let ortho_height = active_camera_id
+                    .and_then(|camera_id| component_map.get(&camera_id))
+                    .and_then(|components| Scene::get_component::<CameraComponent>(components))
+                    .filter(|camera| camera.projection() == CameraProjection::Orthographic)
+                    .and_then(|camera| {
+                        concept_manager
+                            .get_concept::<f32>(camera.id, "ortho_height".to_string())
+                            .ok()
+                    });
+                match ortho_height {
+                    Some(ortho_height) => size * (*ortho_height / window_height.max(1) as f32),
+                    None => size,
+                }
+            }
+        }
+    }
+}
+
+impl ComponentSystem for SpriteComponent {
+    fn initialize(
+        &mut self,
+        device: Arc<Device>,
+        _queue: Arc<Queue>,
+        component_map: &AllComponents,
+        _concept_manager: Rc<Mutex<ConceptManager>>,
+        _engine_details: Option<Rc<Mutex<EngineDetails>>>,
+        _engine_systems: Option<Rc<Mutex<EngineSystems>>>,
+        _ui_manager: Rc<Mutex<UiManager>>,
+        _text_items: &mut Vec<TextParams>,
+    ) {
+        let _transform_component =
+            Scene::get_component::<TransformComponent>(&component_map[&self.parent])
+                .expect("Sprite component expects a transform component on this entity");
+
+        self.vertex_buffer = Arc::new(Some(device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("Sprite Vertex Buffer"),
+            contents: bytemuck::cast_slice(&self.vertices()),
+            usage: BufferUsages::VERTEX | BufferUsages::COPY_DST,
+        })));
+        self.index_buffer = Arc::new(Some(device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("Sprite Index Buffer"),
+            contents: bytemuck::cast_slice(&Self::INDICES),
+            usage: BufferUsages::INDEX,
+        })));
+    }
+
+    fn update(
+        &mut self,
+        _device: Arc<Device>,
+        queue: Arc<Queue>,
+        component_map: &mut AllComponents,
+        engine_details: Rc<Mutex<EngineDetails>>,
+        _engine_systems: Rc<Mutex<EngineSystems>>,
+        concept_manager: Rc<Mutex<ConceptManager>>,
+        active_camera_id: Option<EntityId>,
+        _entities: &mut Vec<Entity>,
+        _materials: Option<&mut (Vec<Material>, usize)>,
+        _compute_pipelines: &mut [ComputePipeline],
+        _text_items: &mut Vec<TextParams>,
+    ) {
+        let window_height = engine_details.lock().unwrap().window_size.1;
+        let quad_size = self.world_size(
+            &concept_manager.lock().unwrap(),
+            active_camera_id,
+            component_map,
+            window_height,
+        );
+        if quad_size == self.quad_size && !self.needs_upload {
+            return;
+        }
+        self.quad_size = quad_size;
+        self.needs_upload = false;
+
+        if let Some(vertex_buffer) = self.vertex_buffer.as_ref() {
+            queue.write_buffer(vertex_buffer, 0, bytemuck::cast_slice(&self.vertices()));
+        }
+    }
+
+    fn render<'a: 'b, 'b>(
+        &'a self,
+        _device: Arc<Device>,
+        _queue: Arc<Queue>,
+        render_pass: &mut RenderPass<'b>,
+        _component_map: &'a AllComponents,
+        _concept_manager: Rc<Mutex<ConceptManager>>,
+        _engine_details: &EngineDetails,
+        _engine_systems: &EngineSystems,
+    ) {
+        if let (Some(vertex_buffer), Some(index_buffer)) =
+            (self.vertex_buffer.as_ref(), self.index_buffer.as_ref())
+        {
+            render_pass.set_vertex_buffer(0, vertex_buffer.slice(..));
+            render_pass.set_index_buffer(index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+            render_pass.draw_indexed(0..Self::INDICES.len() as u32, 0, 0..1);
+        }
+    }
+}
@@ -2,7 +2,7 @@ use std::{fmt::Debug, sync::MutexGuard};
 
 use na::{Matrix3, Matrix4, Vector3, Vector4};
 // use ultraviolet::{Rotor3, Vec3};
-use algoe::rotor::Rotor3;
+use algoe::{rotor::Rotor3, vector::GeometricOperations};
 use wgpu::{
     util::{BufferInitDescriptor, DeviceExt},
     Buffer, BufferUsages, RenderPass,
@@ -10,15 +10,48 @@ use wgpu::{
 
 use nalgebra as na;
 
-use crate::{ecs::{component::Component, scene::TextParams}, model::VertexData, new_component, ui_manager::UiManager};
+use crate::{
+    ecs::{
+        component::Component,
+        concepts::{ConceptHandle, ConceptManagerError},
+        scene::TextParams,
+    },
+    model::VertexData,
+    new_component,
+    ui_manager::UiManager,
+};
 
 new_component!(
     TransformComponent {
         concept_ids: Vec<String>,
-        buf: Arc<Option<Buffer>>
+        buf: Arc<Option<Buffer>>,
+        handles: Option<TransformHandles>
     }
 );
 
+/// Handles to the concepts of one transform
+#[derive(Debug, Clone, Copy)]
+pub struct TransformHandles {
+    pub matrix: ConceptHandle<Matrix4<f32>>,
+    pub position: ConceptHandle<Vector3<f32>>,
+    pub rotation: ConceptHandle<Rotor3>,
+    pub scale: ConceptHandle<Vector3<f32>>,
+}
+
+impl TransformHandles {
+    pub fn new(
+        concept_manager: &ConceptManager,
+        id: ComponentId,
+    ) -> Result<Self, ConceptManagerError> {
+        Ok(Self {
+            matrix: concept_manager.concept_handle(id, "matrix")?,
+            position: concept_manager.concept_handle(id, "position")?,
+            rotation: concept_manager.concept_handle(id, "rotation")?,
+            scale: concept_manager.concept_handle(id, "scale")?,
+        })
+    }
+}
+
 impl TransformComponent {
     pub fn create_rotation_matrix(
         &self,
@@ -28,6 +61,10 @@ impl TransformComponent {
             .get_concept::<Rotor3>(self.id, "rotation".to_string())
             .unwrap();
 
+        Self::rotor_matrix(rotation)
+    }
+
+    pub fn rotor_matrix(rotation: Rotor3) -> na::Matrix4<f32> {
         let rotated_x = (rotation * Vector3::x_axis().xyz()).to_homogeneous();
         let rotated_y = (rotation * Vector3::y_axis().xyz()).to_homogeneous();
         let rotated_z = (rotation * Vector3::z_axis().xyz()).to_homogeneous();
@@ -39,6 +76,66 @@ impl TransformComponent {
             Vector4::new(0.0, 0.0, 0.0, 1.0),
         ])
     }
+
+    /// The rotor applying the rotation `matrix`, the inverse of [TransformComponent::rotor_matrix]
+    pub fn rotor_from_matrix(matrix: na::Matrix3<f32>) -> Rotor3 {
+        let Some((axis, angle)) = na::Rotation3::from_matrix_unchecked(matrix).axis_angle() else {
+            return Rotor3::default();
+        };
+
+        // Any two perpendicular vectors spanning the plane of rotation
+        let in_plane = axis
+            .cross(&Vector3::x())
+            .try_normalize(f32::EPSILON)
+            .unwrap_or_else(|| axis.cross(&Vector3::y()).normalize());
+        let plane = in_plane.wedge(&axis.cross(&in_plane));
+
+        // The plane's orientation decides the direction of rotation, flip it if the rotor turned
+        // the wrong way
+        let rotor = (plane * angle).exponentiate();
+        if (rotor * in_plane - matrix * in_plane).norm() <= 1e-3 {
+            rotor
+        } else {
+            (plane * -angle).exponentiate()
+        }
+    }
+
+    /// Sets the position and rotation concepts of the transform `id` and rebuilds its matrix from
+    /// them and its scale
+    pub fn set_position_and_rotation(
+        concept_manager: &mut ConceptManager,
+        id: ComponentId,
+        position: Vector3<f32>,
+        rotation: Rotor3,
+    ) -> Result<(), ConceptManagerError> {
+        let handles = TransformHandles::new(concept_manager, id)?;
+        Self::set_position_and_rotation_with(concept_manager, handles, position, rotation)
+    }
+
+    /// [TransformComponent::set_position_and_rotation] through already looked up handles
+    pub fn set_position_and_rotation_with(
+        concept_manager: &mut ConceptManager,
+        handles: TransformHandles,
+        position: Vector3<f32>,
+        rotation: Rotor3,
+    ) -> Result<(), ConceptManagerError> {
+        let scale = *concept_manager.get(handles.scale)?;
+        *concept_manager.get_mut(handles.position)? = position;
+        *concept_manager.get_mut(handles.rotation)? = rotation;
+        *concept_manager.get_mut(handles.matrix)? = Matrix4::new_translation(&position)
+            * Self::rotor_matrix(rotation)
+            * Matrix4::new_nonuniform_scaling(&scale);
+        Ok(())
+    }
+
+    /// The handles cached in [ComponentSystem::initialize], or freshly looked up ones before
+    /// that, while the component's id may still change
+    fn handles(&self, concept_manager: &ConceptManager) -> TransformHandles {
+        match self.handles {
+            Some(handles) if handles.matrix.component() == self.id => handles,
+            _ => TransformHandles::new(concept_manager, self.id).unwrap(),
+        }
+    }
 }
 
 impl VertexData for TransformComponent {
@@ -64,6 +161,7 @@ impl TransformComponent {
             concept_ids: Vec::new(),
             id: (EntityId::MAX, TypeId::of::<Self>(), 0),
             buf: Arc::new(None),
+            handles: None,
         };
 
         let rotated_x = (rotation * Vector3::x_axis().xyz()).to_homogeneous();
@@ -92,12 +190,45 @@ impl TransformComponent {
         component
     }
 
+    /// Creates a transform from a full model matrix. Position and scale are decomposed from it,
+    /// the rotation concept starts at identity since the matrix already carries the rotation
+    pub fn from_matrix(
+        concept_manager: Rc<Mutex<ConceptManager>>,
+        matrix: na::Matrix4<f32>,
+    ) -> TransformComponent {
+        let mut component = TransformComponent {
+            parent: EntityId::MAX,
+            concept_ids: Vec::new(),
+            id: (EntityId::MAX, TypeId::of::<Self>(), 0),
+            buf: Arc::new(None),
+            handles: None,
+        };
+
+        let position = matrix.column(3).xyz();
+        let scale = na::Vector3::new(
+            matrix.column(0).xyz().norm(),
+            matrix.column(1).xyz().norm(),
+            matrix.column(2).xyz().norm(),
+        );
+
+        let mut concepts: HashMap<String, Box<dyn Any>> = HashMap::new();
+
+        concepts.insert("matrix".to_string(), Box::new(matrix));
+        concepts.insert("position".to_string(), Box::new(position));
+        concepts.insert("rotation".to_string(), Box::<Rotor3>::default());
+        concepts.insert("scale".to_string(), Box::new(scale));
+
+        component.register_component(concept_manager, concepts);
+        component
+    }
+
     pub fn default(concept_manager: Rc<Mutex<ConceptManager>>) -> Self {
         let mut component = Self {
             parent: EntityId::MAX,
             concept_ids: Vec::new(),
             id: (EntityId::MAX, TypeId::of::<Self>(), 0),
             buf: Arc::new(None),
+            handles: None,
         };
 
         let mut concepts: HashMap<String, Box<dyn Any>> = HashMap::new();
@@ -128,25 +259,21 @@ impl TransformComponent {
         translation: Vector3<f32>,
     ) {
         let mut concept_manager = concept_manager.lock().unwrap();
-        let current_position = concept_manager
-            .get_concept_mut::<Vector3<f32>>(self.id, String::from("position"))
-            .unwrap();
+        let handles = self.handles(&concept_manager);
+        let current_position = concept_manager.get_mut(handles.position).unwrap();
         *current_position += translation;
 
         let new_matrix = na::Translation3::from(translation);
 
-        let transform = concept_manager
-            .get_concept_mut::<Matrix4<f32>>(self.id, String::from("matrix"))
-            .unwrap();
+        let transform = concept_manager.get_mut(handles.matrix).unwrap();
 
         *transform *= new_matrix.to_homogeneous();
     }
 
     pub fn apply_rotation(&self, concept_manager: Rc<Mutex<ConceptManager>>, rotation: Rotor3) {
         let mut concept_manager = concept_manager.lock().unwrap();
-        let current_rotation = concept_manager
-            .get_concept_mut::<Rotor3>(self.id, String::from("rotation"))
-            .unwrap();
+        let handles = self.handles(&concept_manager);
+        let current_rotation = concept_manager.get_mut(handles.rotation).unwrap();
         *current_rotation = *current_rotation * rotation;
 
         let new_matrix = Matrix3::from_columns(&[
@@ -155,24 +282,19 @@ impl TransformComponent {
             rotation * Vector3::z_axis().xyz(),
         ]);
 
-        let transform = concept_manager
-            .get_concept_mut::<Matrix4<f32>>(self.id, String::from("matrix"))
-            .unwrap();
+        let transform = concept_manager.get_mut(handles.matrix).unwrap();
 
         *transform *= new_matrix.to_homogeneous();
     }
 
     pub fn apply_scale(&self, concept_manager: Rc<Mutex<ConceptManager>>, dilation: Vector3<f32>) {
         let mut concept_manager = concept_manager.lock().unwrap();
-        let current_scale = concept_manager
-            .get_concept_mut::<Vector3<f32>>(self.id, String::from("scale"))
-            .unwrap();
+        let handles = self.handles(&concept_manager);
+        let current_scale = concept_manager.get_mut(handles.scale).unwrap();
         *current_scale += dilation;
 
         let new_matrix = Matrix4::new_nonuniform_scaling(&dilation);
-        let transform = concept_manager
-            .get_concept_mut::<Matrix4<f32>>(self.id, String::from("matrix"))
-            .unwrap();
+        let transform = concept_manager.get_mut(handles.matrix).unwrap();
 
         *transform *= new_matrix;
     }
@@ -197,7 +319,7 @@ impl TransformComponent {
             * rot_matrix
             * na::Matrix4::<f32>::new_nonuniform_scaling(scale); */
         let matrix = *concept_manager
-            .get_concept::<Matrix4<f32>>(self.id, "matrix".to_string())
+            .get(self.handles(&concept_manager).matrix)
             .unwrap();
         let matrix_as_arr: [[f32; 4]; 4] = matrix.into();
 
@@ -236,9 +358,9 @@ impl ComponentSystem for TransformComponent {
         _text_items: &mut Vec<TextParams>,
     ) {
         let concept_manager = concept_manager.lock().unwrap();
-        let matrix = concept_manager
-            .get_concept::<na::Matrix4<f32>>(self.id, "matrix".to_string())
-            .unwrap();
+        let handles = TransformHandles::new(&concept_manager, self.id).unwrap();
+        self.handles = Some(handles);
+        let matrix = concept_manager.get(handles.matrix).unwrap();
         let matrix_as_arr: [[f32; 4]; 4] = matrix.clone_owned().into();
         self.buf = Arc::new(Some(device.create_buffer_init(&BufferInitDescriptor {
             label: Some("Transform Component Buffer"),
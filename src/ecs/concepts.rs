@@ -1,4 +1,9 @@
-use std::{any::Any, collections::HashMap, fmt::Debug};
+use std::{
+    any::Any,
+    collections::{HashMap, HashSet},
+    fmt::Debug,
+    marker::PhantomData,
+};
 
 use super::component::ComponentId;
 
@@ -7,41 +12,192 @@ pub enum ConceptManagerError {
     ComponentNotFound(ComponentId),
     ConceptNotFound(String),
     DowncastFailed,
+    /// The concept was already taken out of the same [ConceptBundle]
+    ConceptAlreadyBorrowed(String),
 }
 
+/// Concept names are interned once, the per-component maps are keyed by these ids
+pub type ConceptKey = u32;
+
 #[derive(Debug, Default)]
 pub struct ConceptManager {
-    pub concepts: HashMap<ComponentId, HashMap<String, Box<dyn Any>>>,
+    pub concepts: HashMap<ComponentId, HashMap<ConceptKey, Box<dyn Any>>>,
+    concept_keys: HashMap<String, ConceptKey>,
+    concept_names: Vec<String>,
+}
+
+/// A typed reference to one concept of one component, see [ConceptManager::concept_handle].
+/// Looking a concept up through a handle skips hashing and allocating its name. A handle stores
+/// the component's id, so it goes stale when the component is moved to an entity; create it in
+/// [initialize](super::component::ComponentSystem::initialize) or later
+pub struct ConceptHandle<T> {
+    component: ComponentId,
+    key: ConceptKey,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T> ConceptHandle<T> {
+    pub fn component(&self) -> ComponentId {
+        self.component
+    }
+
+    pub fn key(&self) -> ConceptKey {
+        self.key
+    }
+}
+
+impl<T> Clone for ConceptHandle<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for ConceptHandle<T> {}
+
+impl<T> Debug for ConceptHandle<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ConceptHandle")
+            .field("component", &self.component)
+            .field("key", &self.key)
+            .finish()
+    }
+}
+
+/// Borrows several concepts of one component at the same time, see
+/// [ConceptManager::with_concepts]. Every concept can be taken out once, so two mutable
+/// references to the same concept can't exist
+#[derive(Debug)]
+pub struct ConceptBundle<'a> {
+    component: ComponentId,
+    concepts: HashMap<&'a str, &'a mut Box<dyn Any>>,
+    borrowed: HashSet<String>,
+}
+
+impl<'a> ConceptBundle<'a> {
+    pub fn component(&self) -> ComponentId {
+        self.component
+    }
+
+    pub fn get<T: Any + Debug>(
+        &mut self,
+        concept_name: &str,
+    ) -> Result<&'a T, ConceptManagerError> {
+        self.get_mut(concept_name).map(|concept| &*concept)
+    }
+
+    pub fn get_mut<T: Any + Debug>(
+        &mut self,
+        concept_name: &str,
+    ) -> Result<&'a mut T, ConceptManagerError> {
+        let Some((key, concept)) = self.concepts.remove_entry(concept_name) else {
+            return Err(if self.borrowed.contains(concept_name) {
+                ConceptManagerError::ConceptAlreadyBorrowed(concept_name.to_string())
+            } else {
+                ConceptManagerError::ConceptNotFound(concept_name.to_string())
+            });
+        };
+
+        if !(**concept).is::<T>() {
+            self.concepts.insert(key, concept);
+            return Err(ConceptManagerError::DowncastFailed);
+        }
+
+        self.borrowed.insert(concept_name.to_string());
+        Ok(concept.downcast_mut::<T>().unwrap())
+    }
 }
 
 impl ConceptManager {
+    fn intern(&mut self, name: &str) -> ConceptKey {
+        if let Some(key) = self.concept_keys.get(name) {
+            return *key;
+        }
+        let key = self.concept_names.len() as ConceptKey;
+        self.concept_names.push(name.to_string());
+        self.concept_keys.insert(name.to_string(), key);
+        key
+    }
+
+    fn key(&self, name: &str) -> Option<ConceptKey> {
+        self.concept_keys.get(name).copied()
+    }
+
+    pub fn concept_name(&self, key: ConceptKey) -> Option<&str> {
+        self.concept_names.get(key as usize).map(String::as_str)
+    }
+
     pub fn register_concept<T: Any>(
         &mut self,
         component: ComponentId,
         name: String,
         data: Box<T>,
     ) -> String {
-        match self.concepts.get_mut(&component) {
-            Some(concepts_map) => {
-                concepts_map.insert(name.clone(), data);
-            }
-            None => {
-                let mut map: HashMap<String, Box<dyn Any>> = HashMap::new();
-                map.insert(name.clone(), data);
-                self.concepts.insert(component, map);
-            }
-        };
+        let key = self.intern(&name);
+        self.concepts
+            .entry(component)
+            .or_default()
+            .insert(key, data);
         name
     }
 
+    /// Checks that the concept exists and holds a `T` once, so later lookups through the handle
+    /// only hash two integers
+    pub fn concept_handle<T: Any + Debug>(
+        &self,
+        component: ComponentId,
+        concept_name: &str,
+    ) -> Result<ConceptHandle<T>, ConceptManagerError> {
+        let concepts_map = self
+            .concepts
+            .get(&component)
+            .ok_or(ConceptManagerError::ComponentNotFound(component))?;
+        let key = self
+            .key(concept_name)
+            .filter(|key| concepts_map.contains_key(key))
+            .ok_or_else(|| ConceptManagerError::ConceptNotFound(concept_name.to_string()))?;
+        if !concepts_map[&key].is::<T>() {
+            return Err(ConceptManagerError::DowncastFailed);
+        }
+
+        Ok(ConceptHandle {
+            component,
+            key,
+            _marker: PhantomData,
+        })
+    }
+
+    pub fn get<T: Any + Debug>(&self, handle: ConceptHandle<T>) -> Result<&T, ConceptManagerError> {
+        self.concepts
+            .get(&handle.component)
+            .ok_or(ConceptManagerError::ComponentNotFound(handle.component))?
+            .get(&handle.key)
+            .ok_or_else(|| missing_concept(&self.concept_names, handle.key))?
+            .downcast_ref::<T>()
+            .ok_or(ConceptManagerError::DowncastFailed)
+    }
+
+    pub fn get_mut<T: Any + Debug>(
+        &mut self,
+        handle: ConceptHandle<T>,
+    ) -> Result<&mut T, ConceptManagerError> {
+        self.concepts
+            .get_mut(&handle.component)
+            .ok_or(ConceptManagerError::ComponentNotFound(handle.component))?
+            .get_mut(&handle.key)
+            .ok_or_else(|| missing_concept(&self.concept_names, handle.key))?
+            .downcast_mut::<T>()
+            .ok_or(ConceptManagerError::DowncastFailed)
+    }
+
     pub fn get_concept<T: Any + Debug>(
         &self,
         component: ComponentId,
         concept_name: String,
     ) -> Result<&T, ConceptManagerError> {
         let component_concepts = &self.concepts.get(&component);
+        let key = self.key(&concept_name);
         match component_concepts {
-            Some(concepts_map) => match concepts_map.get(&concept_name) {
+            Some(concepts_map) => match key.and_then(|key| concepts_map.get(&key)) {
                 Some(concept) => {
                     let concept_ref_option = concept.downcast_ref::<T>();
                     match concept_ref_option {
@@ -60,9 +216,10 @@ impl ConceptManager {
         component: ComponentId,
         concept_name: String,
     ) -> Result<&mut T, ConceptManagerError> {
+        let key = self.key(&concept_name);
         let component_concepts = self.concepts.get_mut(&component);
         match component_concepts {
-            Some(concepts_map) => match concepts_map.get_mut(&concept_name) {
+            Some(concepts_map) => match key.and_then(|key| concepts_map.get_mut(&key)) {
                 Some(concept) => {
                     let concept_mut_option = concept.downcast_mut::<T>();
                     match concept_mut_option {
@@ -82,9 +239,10 @@ impl ConceptManager {
         concept_name: String,
         data: T,
     ) -> Result<(), ConceptManagerError> {
+        let key = self.key(&concept_name);
         let component_concepts = self.concepts.get_mut(&component);
         match component_concepts {
-            Some(concepts_map) => match concepts_map.get_mut(&concept_name) {
+            Some(concepts_map) => match key.and_then(|key| concepts_map.get_mut(&key)) {
                 Some(concept) => {
                     let concept_mut_option = concept.downcast_mut::<T>();
                     match concept_mut_option {
@@ -101,19 +259,163 @@ impl ConceptManager {
         }
     }
 
+    /// Looks up the component's concepts once and hands them to `f`, which can then borrow
+    /// any number of different concepts at once
+    pub fn with_concepts<R>(
+        &mut self,
+        component: ComponentId,
+        f: impl FnOnce(&mut ConceptBundle) -> R,
+    ) -> Result<R, ConceptManagerError> {
+        let mut bundle = self.concept_bundle(component)?;
+        Ok(f(&mut bundle))
+    }
+
+    pub fn concept_bundle(
+        &mut self,
+        component: ComponentId,
+    ) -> Result<ConceptBundle, ConceptManagerError> {
+        let concepts_map = self
+            .concepts
+            .get_mut(&component)
+            .ok_or(ConceptManagerError::ComponentNotFound(component))?;
+        let concept_names = &self.concept_names;
+
+        Ok(ConceptBundle {
+            component,
+            concepts: concepts_map
+                .iter_mut()
+                .map(|(key, concept)| (concept_names[*key as usize].as_str(), concept))
+                .collect(),
+            borrowed: HashSet::new(),
+        })
+    }
+
     pub fn register_component_concepts(
         &mut self,
         component: ComponentId,
         concepts: HashMap<String, Box<dyn Any>>,
     ) -> Vec<String> {
         let names = concepts.keys().cloned().collect();
+        let concepts = concepts
+            .into_iter()
+            .map(|(name, concept)| (self.intern(&name), concept))
+            .collect();
         self.concepts.insert(component, concepts);
         names
     }
 
+    pub fn remove_component_concepts(&mut self, component: ComponentId) {
+        self.concepts.remove(&component);
+    }
+
     pub fn modify_key(&mut self, old_id: ComponentId, new_id: ComponentId) {
         if let Some(concepts) = self.concepts.remove(&old_id) {
             self.concepts.insert(new_id, concepts);
         }
     }
 }
+
+fn missing_concept(concept_names: &[String], key: ConceptKey) -> ConceptManagerError {
+    ConceptManagerError::ConceptNotFound(
+        concept_names.get(key as usize).cloned().unwrap_or_default(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::any::TypeId;
+
+    use super::*;
+
+    fn component(entity_id: u32) -> ComponentId {
+        (entity_id, TypeId::of::<ConceptManager>(), 0)
+    }
+
+    fn manager() -> ConceptManager {
+        let mut concept_manager = ConceptManager::default();
+        for entity_id in 0..2 {
+            let mut concepts: HashMap<String, Box<dyn Any>> = HashMap::new();
+            concepts.insert("mass".to_string(), Box::new(entity_id as f32));
+            concepts.insert("name".to_string(), Box::new(format!("body {entity_id}")));
+            concept_manager.register_component_concepts(component(entity_id), concepts);
+        }
+        concept_manager
+    }
+
+    #[test]
+    fn names_are_interned_once() {
+        let concept_manager = manager();
+        let first = concept_manager
+            .concept_handle::<f32>(component(0), "mass")
+            .unwrap();
+        let second = concept_manager
+            .concept_handle::<f32>(component(1), "mass")
+            .unwrap();
+
+        assert_eq!(first.key(), second.key());
+        assert_eq!(concept_manager.concept_name(first.key()), Some("mass"));
+        assert_eq!(concept_manager.concept_names.len(), 2);
+    }
+
+    #[test]
+    fn handles_and_names_reach_the_same_concept() {
+        let mut concept_manager = manager();
+        let mass = concept_manager
+            .concept_handle::<f32>(component(1), "mass")
+            .unwrap();
+
+        *concept_manager.get_mut(mass).unwrap() = 5.0;
+        assert_eq!(
+            *concept_manager
+                .get_concept::<f32>(component(1), "mass".to_string())
+                .unwrap(),
+            5.0
+        );
+        assert_eq!(*concept_manager.get(mass).unwrap(), 5.0);
+        assert_eq!(
+            concept_manager
+                .concept_bundle(component(1))
+                .unwrap()
+                .get::<String>("name")
+                .unwrap(),
+            "body 1"
+        );
+    }
+
+    #[test]
+    fn handles_are_checked_when_created() {
+        let concept_manager = manager();
+
+        assert!(matches!(
+            concept_manager.concept_handle::<String>(component(0), "mass"),
+            Err(ConceptManagerError::DowncastFailed)
+        ));
+        assert!(matches!(
+            concept_manager.concept_handle::<f32>(component(0), "velocity"),
+            Err(ConceptManagerError::ConceptNotFound(_))
+        ));
+        assert!(matches!(
+            concept_manager.concept_handle::<f32>(component(2), "mass"),
+            Err(ConceptManagerError::ComponentNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn handles_go_stale_when_the_component_moves() {
+        let mut concept_manager = manager();
+        let mass = concept_manager
+            .concept_handle::<f32>(component(0), "mass")
+            .unwrap();
+
+        concept_manager.modify_key(component(0), component(5));
+
+        assert!(matches!(
+            concept_manager.get(mass),
+            Err(ConceptManagerError::ComponentNotFound(_))
+        ));
+        let moved = concept_manager
+            .concept_handle::<f32>(component(5), "mass")
+            .unwrap();
+        assert_eq!(*concept_manager.get(moved).unwrap(), 0.0);
+    }
+}
@@ -17,12 +17,50 @@ use super::{
 
 pub type EntityId = u32;
 
+/// Render layers an entity is on, one per bit. A pass only draws entities on a layer it
+/// includes, e.g. a mirror on its own layer can leave itself out of its reflection
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct LayerMask(pub u32);
+
+impl LayerMask {
+    pub const ALL: LayerMask = LayerMask(u32::MAX);
+    pub const NONE: LayerMask = LayerMask(0);
+
+    /// Only layer `index`, which has to be below `32`
+    pub const fn layer(index: u32) -> Self {
+        LayerMask(1 << index)
+    }
+
+    pub const fn union(self, other: LayerMask) -> Self {
+        LayerMask(self.0 | other.0)
+    }
+
+    /// Every layer but the ones in `other`
+    pub const fn without(self, other: LayerMask) -> Self {
+        LayerMask(self.0 & !other.0)
+    }
+
+    pub const fn intersects(self, other: LayerMask) -> bool {
+        self.0 & other.0 != 0
+    }
+}
+
+impl Default for LayerMask {
+    fn default() -> Self {
+        LayerMask::ALL
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Entity {
     id: EntityId,
+    /// The entity's own flag, it also only updates and renders while its ancestors are enabled
     pub enabled: bool,
     parent: EntityId,
     children: Vec<EntityId>,
+    name: Option<String>,
+    tags: Vec<String>,
+    layers: LayerMask,
 }
 
 impl Entity {
@@ -32,10 +70,92 @@ impl Entity {
             enabled,
             parent,
             children,
+            name: None,
+            tags: Vec::new(),
+            layers: LayerMask::ALL,
         }
     }
 
     pub fn id(&self) -> &EntityId {
         &self.id
     }
+
+    pub fn parent(&self) -> EntityId {
+        self.parent
+    }
+
+    pub fn children(&self) -> &[EntityId] {
+        &self.children
+    }
+
+    /// Set through [crate::ecs::scene::Scene::set_name]
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    /// Added through [crate::ecs::scene::Scene::add_tag]
+    pub fn tags(&self) -> &[String] {
+        &self.tags
+    }
+
+    /// On every layer by default
+    pub fn layers(&self) -> LayerMask {
+        self.layers
+    }
+
+    pub fn set_layers(&mut self, layers: LayerMask) {
+        self.layers = layers;
+    }
+
+    pub(crate) fn set_name(&mut self, name: Option<String>) {
+        self.name = name;
+    }
+
+    pub(crate) fn tags_mut(&mut self) -> &mut Vec<String> {
+        &mut self.tags
+    }
+
+    pub(crate) fn add_child(&mut self, child: EntityId) {
+        self.children.push(child);
+    }
+
+    pub(crate) fn remove_child(&mut self, child: EntityId) {
+        self.children.retain(|id| *id != child);
+    }
+
+    pub(crate) fn set_parent(&mut self, parent: EntityId) {
+        self.parent = parent;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn layer_masks_combine() {
+        let ui = LayerMask::layer(0);
+        let world = LayerMask::layer(3);
+        assert_eq!(ui, LayerMask(0b1));
+        assert_eq!(world, LayerMask(0b1000));
+        assert_eq!(LayerMask::layer(31), LayerMask(1 << 31));
+
+        let both = ui.union(world);
+        assert_eq!(both, LayerMask(0b1001));
+        assert_eq!(both.without(ui), world);
+        assert_eq!(LayerMask::ALL.without(world), LayerMask(!0b1000));
+        assert_eq!(ui.without(ui), LayerMask::NONE);
+    }
+
+    #[test]
+    fn layer_masks_intersect_on_shared_layers() {
+        let ui = LayerMask::layer(0);
+        let world = LayerMask::layer(3);
+        assert!(ui.union(world).intersects(world));
+        assert!(!ui.intersects(world));
+        assert!(LayerMask::ALL.intersects(world));
+        assert!(!LayerMask::NONE.intersects(LayerMask::ALL));
+        assert!(!LayerMask::NONE.intersects(LayerMask::NONE));
+        assert_eq!(LayerMask::default(), LayerMask::ALL);
+    }
 }
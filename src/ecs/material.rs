@@ -1,14 +1,100 @@
 #![allow(unused)]
-use std::{num::NonZeroU32, rc::Rc, sync::Arc};
+use std::{
+    path::{Component as PathComponent, Path, PathBuf},
+    rc::Rc,
+    sync::Arc,
+};
 
+use bytemuck::Pod;
 use wgpu::{
     util::DeviceExt, BindGroup, BindGroupEntry, BindGroupLayoutEntry, BindingResource, BindingType,
-    Buffer, Device, SamplerBindingType, ShaderStages, TextureSampleType, TextureViewDimension,
+    Buffer, Device, Queue, SamplerBindingType, ShaderStages, TextureSampleType,
+    TextureViewDimension,
+};
+
+use crate::{
+    model::VertexType,
+    pipeline::PipelineConfig,
+    texture::Texture,
+    uniform_pool::{PooledUniform, UniformPool},
 };
 
-use crate::texture::Texture;
+/// Everything a material's pipeline depends on, materials with equal ids share a pipeline
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MaterialId {
+    /// Normalized with [MaterialId::normalize_path]
+    pub vertex_shader_path: String,
+    /// Normalized with [MaterialId::normalize_path]
+    pub fragment_shader_path: String,
+    pub texture_count: usize,
+    pub has_uniform: bool,
+    /// The uniform is a slot of a [UniformPool], bound with a dynamic offset
+    pub pooled_uniform: bool,
+    /// Sorted and deduplicated
+    pub shader_defines: Vec<String>,
+    pub pipeline_config: PipelineConfig,
+    pub vertex_type: VertexType,
+}
+
+impl MaterialId {
+    pub fn new(
+        vertex_shader_path: &str,
+        fragment_shader_path: &str,
+        texture_count: usize,
+        has_uniform: bool,
+    ) -> Self {
+        MaterialId {
+            vertex_shader_path: Self::normalize_path(vertex_shader_path),
+            fragment_shader_path: Self::normalize_path(fragment_shader_path),
+            texture_count,
+            has_uniform,
+            pooled_uniform: false,
+            shader_defines: Vec::new(),
+            pipeline_config: PipelineConfig::default(),
+            vertex_type: VertexType::default(),
+        }
+    }
+
+    /// Resolves relative paths against the working directory and removes `.` and `..`
+    /// components, so the same file referenced in different ways gets one pipeline.
+    /// Symlinks are not resolved
+    pub fn normalize_path(path: &str) -> String {
+        let path = Path::new(path);
+        let absolute_path = match std::env::current_dir() {
+            Ok(current_dir) => current_dir.join(path),
+            Err(_) => path.to_path_buf(),
+        };
+
+        let mut normalized_path = PathBuf::new();
+        for component in absolute_path.components() {
+            match component {
+                PathComponent::CurDir => {}
+                PathComponent::ParentDir => {
+                    normalized_path.pop();
+                }
+                component => normalized_path.push(component),
+            }
+        }
+        normalized_path.to_string_lossy().into_owned()
+    }
+}
 
-pub type MaterialId = (String, String, usize, bool);
+#[derive(Debug)]
+pub enum MaterialError {
+    NoUniformBuffer,
+    /// Sizes in bytes
+    UniformSizeMismatch {
+        expected: u64,
+        actual: u64,
+    },
+    /// Every slot of the [UniformPool] is taken
+    UniformPoolFull,
+    /// Sizes in bytes
+    UniformTooLarge {
+        slot_size: u64,
+        actual: u64,
+    },
+}
 
 #[derive(Debug)]
 pub struct Material {
@@ -19,6 +105,7 @@ pub struct Material {
     id: MaterialId,
     texture_bind_group: BindGroup,
     uniform_buffer_and_bind_group: Option<(BindGroup, Buffer)>,
+    pooled_uniform: Option<PooledUniform>,
 }
 
 impl Material {
@@ -30,9 +117,9 @@ impl Material {
         enabled: bool,
         device: Arc<Device>,
     ) -> Self {
-        let id = (
-            vertex_shader_path.to_string(),
-            fragment_shader_path.to_string(),
+        let id = MaterialId::new(
+            vertex_shader_path,
+            fragment_shader_path,
             textures.len(),
             uniform_buffer_data.is_some(),
         );
@@ -56,39 +143,122 @@ impl Material {
             id,
             texture_bind_group,
             uniform_buffer_and_bind_group,
+            pooled_uniform: None,
         }
     }
 
+    /// Same as [Material::new] with a uniform buffer sized for `T`, which should match the
+    /// shader's uniform struct. Use [Material::update_uniform] with the same type afterwards
+    pub fn new_with_uniform<T: Pod>(
+        vertex_shader_path: &str,
+        fragment_shader_path: &str,
+        textures: Vec<Rc<Texture>>,
+        uniform: T,
+        enabled: bool,
+        device: Arc<Device>,
+    ) -> Self {
+        Self::new(
+            vertex_shader_path,
+            fragment_shader_path,
+            textures,
+            Some(bytemuck::bytes_of(&uniform)),
+            enabled,
+            device,
+        )
+    }
+
+    /// Same as [Material::new_with_uniform], with the uniform in a slot of `pool` instead of a
+    /// buffer of its own. Fails if the pool is full or `T` is bigger than its slots
+    #[allow(clippy::too_many_arguments)]
+    pub fn new_with_pooled_uniform<T: Pod>(
+        vertex_shader_path: &str,
+        fragment_shader_path: &str,
+        textures: Vec<Rc<Texture>>,
+        uniform: T,
+        pool: &Rc<UniformPool>,
+        enabled: bool,
+        device: Arc<Device>,
+        queue: &Queue,
+    ) -> Result<Self, MaterialError> {
+        let pooled_uniform = pool.allocate(queue, bytemuck::bytes_of(&uniform))?;
+        let mut material = Self::new(
+            vertex_shader_path,
+            fragment_shader_path,
+            textures,
+            None,
+            enabled,
+            device,
+        );
+        material.id.has_uniform = true;
+        material.id.pooled_uniform = true;
+        material.pooled_uniform = Some(pooled_uniform);
+        Ok(material)
+    }
+
+    /// Writes `value` to the uniform buffer, fails if `T` isn't the size the buffer was created
+    /// with
+    pub fn update_uniform<T: Pod>(&self, queue: &Queue, value: T) -> Result<(), MaterialError> {
+        let actual = std::mem::size_of::<T>() as u64;
+        if let Some(pooled_uniform) = &self.pooled_uniform {
+            if pooled_uniform.size() != actual {
+                return Err(MaterialError::UniformSizeMismatch {
+                    expected: pooled_uniform.size(),
+                    actual,
+                });
+            }
+            pooled_uniform.write(queue, bytemuck::bytes_of(&value));
+            return Ok(());
+        }
+
+        let (_, buffer) = self
+            .uniform_buffer_and_bind_group
+            .as_ref()
+            .ok_or(MaterialError::NoUniformBuffer)?;
+
+        if buffer.size() != actual {
+            return Err(MaterialError::UniformSizeMismatch {
+                expected: buffer.size(),
+                actual,
+            });
+        }
+
+        queue.write_buffer(buffer, 0, bytemuck::bytes_of(&value));
+        Ok(())
+    }
+
+    /// Texture `i` is at binding `i * 2` and its sampler at `i * 2 + 1`. Both the bind groups
+    /// and the pipeline layouts are built from this so they can't disagree
+    pub fn texture_bind_group_layout_entries(texture_count: usize) -> Vec<BindGroupLayoutEntry> {
+        (0..texture_count as u32)
+            .flat_map(|i| {
+                [
+                    BindGroupLayoutEntry {
+                        binding: i * 2,
+                        visibility: ShaderStages::VERTEX_FRAGMENT,
+                        ty: BindingType::Texture {
+                            sample_type: TextureSampleType::Float { filterable: true },
+                            view_dimension: TextureViewDimension::D2,
+                            multisampled: false,
+                        },
+                        count: None,
+                    },
+                    BindGroupLayoutEntry {
+                        binding: i * 2 + 1,
+                        visibility: ShaderStages::VERTEX_FRAGMENT,
+                        ty: BindingType::Sampler(SamplerBindingType::Filtering),
+                        count: None,
+                    },
+                ]
+            })
+            .collect()
+    }
+
     pub fn create_texture_bind_group(
         views_and_samplers: &[(&wgpu::TextureView, &wgpu::Sampler)],
         device: Arc<Device>,
     ) -> BindGroup {
-        let bind_group_layout_entries = if views_and_samplers.is_empty() {
-            Vec::new()
-        } else {
-            (0..views_and_samplers.len())
-                .flat_map(|i| {
-                    [
-                        BindGroupLayoutEntry {
-                            binding: i as u32 * 2,
-                            visibility: ShaderStages::VERTEX_FRAGMENT,
-                            ty: wgpu::BindingType::Texture {
-                                sample_type: wgpu::TextureSampleType::Float { filterable: true },
-                                view_dimension: wgpu::TextureViewDimension::D2,
-                                multisampled: false,
-                            },
-                            count: None,
-                        },
-                        BindGroupLayoutEntry {
-                            binding: i as u32 * 2 + 1,
-                            visibility: ShaderStages::VERTEX_FRAGMENT,
-                            ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
-                            count: None,
-                        },
-                    ]
-                })
-                .collect::<Vec<_>>()
-        };
+        let bind_group_layout_entries =
+            Self::texture_bind_group_layout_entries(views_and_samplers.len());
         let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
             label: Some("Texture Bind Group Layout"),
             entries: &bind_group_layout_entries,
@@ -173,6 +343,43 @@ impl Material {
         )
     }
 
+    /// Specializes the material's shaders with `#define`-style feature flags. Materials sharing
+    /// shader files but using different flags get separate pipelines, see
+    /// [crate::pipeline::Pipeline::preprocess_shader] for the supported directives
+    pub fn with_shader_defines(mut self, defines: &[&str]) -> Self {
+        let mut defines = defines
+            .iter()
+            .map(|define| define.to_string())
+            .collect::<Vec<_>>();
+        defines.sort();
+        defines.dedup();
+        self.id.shader_defines = defines;
+        self
+    }
+
+    pub fn with_pipeline_config(mut self, config: PipelineConfig) -> Self {
+        self.id.pipeline_config = config;
+        self
+    }
+
+    /// See [PipelineConfig::flip_winding]
+    pub fn with_flipped_winding(mut self) -> Self {
+        self.id.pipeline_config = self.id.pipeline_config.flip_winding();
+        self
+    }
+
+    /// Builds the pipeline for meshes with this vertex type, normal mapped materials need
+    /// [VertexType::Tangent] and meshes created with [crate::ecs::components::mesh_component::MeshComponent::with_tangents].
+    /// Components drawing their own vertex buffers declare them with [VertexType::Custom]
+    pub fn with_vertex_type(mut self, vertex_type: VertexType) -> Self {
+        self.id.vertex_type = vertex_type;
+        self
+    }
+
+    pub fn pipeline_config(&self) -> PipelineConfig {
+        self.id.pipeline_config
+    }
+
     pub fn update_textures(&mut self, device: Arc<Device>, textures: &[(Rc<Texture>, usize)]) {
         for (tex, index) in textures {
             self.textures[*index] = tex.clone();
@@ -196,6 +403,11 @@ impl Material {
         self.enabled
     }
 
+    /// Disabled materials aren't drawn, the entity's other materials still are
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
+
     pub fn texture_bind_group(&self) -> &BindGroup {
         &self.texture_bind_group
     }
@@ -203,4 +415,10 @@ impl Material {
     pub fn uniform_buffer_bind_group(&self) -> Option<&(BindGroup, Buffer)> {
         self.uniform_buffer_and_bind_group.as_ref()
     }
+
+    /// Set instead of [Material::uniform_buffer_bind_group] for materials made with
+    /// [Material::new_with_pooled_uniform]
+    pub fn pooled_uniform(&self) -> Option<&PooledUniform> {
+        self.pooled_uniform.as_ref()
+    }
 }
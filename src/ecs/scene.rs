@@ -1,35 +1,121 @@
 use crate::{
-    compute::{ComputePipeline, ComputePipelineType},
+    compute::{ComputePackagedData, ComputePipeline, ComputePipelineType},
+    debug_draw::DebugDraw,
     ecs::{concepts::ConceptManager, entity::Entity},
-    model::{Vertex, VertexData},
-    pipeline::PipelineError,
+    indirect::IndirectDraws,
+    model::{VertexData, VertexType},
+    picking::PickingPipeline,
+    pipeline::{BlendMode, PipelineConfig, PipelineError},
+    post_process::PostProcessStack,
+    skybox::Skybox,
     texture::Texture,
     ui_manager::UiManager,
-    EngineDetails, EngineSystems,
+    ClearFlags, EngineDetails, EngineSystems,
 };
 use std::{
-    any::Any,
-    collections::HashMap,
+    any::{Any, TypeId},
+    collections::{HashMap, HashSet},
     fmt::Debug,
     rc::Rc,
     sync::{Arc, Mutex},
+    time::{Duration, Instant},
 };
 
 use glyphon::{FontSystem, SwashCache, TextAtlas, TextRenderer};
-use wgpu::{BindGroup, CommandEncoderDescriptor, Device, Queue, TextureFormat};
+use nalgebra as na;
+use wgpu::{util::DeviceExt, BindGroup, CommandEncoderDescriptor, Device, Queue, TextureFormat};
 
 use crate::pipeline::Pipeline;
 
 use super::{
-    component::{Component, ComponentSystem},
-    components::{camera_component::CameraComponent, transform_component::TransformComponent},
-    entity::EntityId,
+    component::{Component, ComponentId, ComponentSystem},
+    components::{
+        camera_component::{CameraComponent, RawCameraData, Viewport},
+        collider_component::{ColliderComponent, ColliderShape, CollisionEvent, RaycastHit},
+        mesh_component::{MeshComponent, MeshComponentError},
+        physics_component::PhysicsComponent,
+        transform_component::TransformComponent,
+    },
+    entity::{EntityId, LayerMask},
     material::{Material, MaterialId},
+    spatial_grid::SpatialGrid,
 };
 
 pub type AllComponents = HashMap<EntityId, Vec<Component>>;
 pub type Materials = HashMap<EntityId, (Vec<Material>, usize)>;
 
+#[derive(Debug)]
+pub enum SceneError {
+    /// The entity has no materials
+    NoMaterials(EntityId),
+    MaterialIndexOutOfRange {
+        index: usize,
+        material_count: usize,
+    },
+    ComputeAssetNotFound {
+        pipeline: usize,
+        asset: usize,
+    },
+    /// A buffer can only be linked to a buffer input and a texture to a texture input
+    ComputeAssetKindMismatch,
+    NotInitialized,
+    /// The entity has no camera component
+    NoCamera(EntityId),
+    EntityNotFound(EntityId),
+    /// Names are unique, this one already belongs to another entity
+    NameTaken {
+        name: String,
+        owner: EntityId,
+    },
+    /// The new parent is the entity itself or one of its descendants
+    HierarchyCycle {
+        child: EntityId,
+        new_parent: EntityId,
+    },
+    /// A material drawing the entity's mesh was built for another vertex type, see
+    /// [Material::with_vertex_type]. Such materials are skipped when drawing
+    VertexTypeMismatch {
+        entity: EntityId,
+        mesh: VertexType,
+        material: VertexType,
+    },
+    /// Only 8 bit RGBA and BGRA formats can be read back into an image
+    UnsupportedImageFormat(TextureFormat),
+    ReadbackFailed,
+}
+
+/// Snapshot of what a scene holds, see [Scene::stats]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SceneStats {
+    pub entity_count: usize,
+    pub enabled_entity_count: usize,
+    /// Number of components of each type, keyed by type name
+    pub component_counts: HashMap<&'static str, usize>,
+    pub material_count: usize,
+    pub pipeline_count: usize,
+    pub compute_pipeline_count: usize,
+    /// Mesh draws a frame issues across all layers, one per drawn submesh
+    pub draw_call_count: usize,
+    pub triangle_count: usize,
+}
+
+/// Snapshot of an entity for inspectors and debug UIs, see [Scene::entity_info]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EntityInfo {
+    pub id: EntityId,
+    pub name: Option<String>,
+    /// The entity's own flag
+    pub enabled: bool,
+    /// Whether it and all of its ancestors are enabled, see [Scene::is_entity_active]
+    pub active: bool,
+    pub parent: EntityId,
+    pub children: Vec<EntityId>,
+    pub tags: Vec<String>,
+    pub layers: LayerMask,
+    /// Type names of the entity's components, in the order they were added
+    pub component_types: Vec<&'static str>,
+}
+
 // #[derive(Debug)]
 pub struct TextState {
     pub font_system: glyphon::FontSystem,
@@ -40,6 +126,17 @@ pub struct TextState {
     pub text_items: Vec<TextParams>,
 }
 
+/// Where [TextParams::top_left_position] is measured from
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum TextAnchor {
+    /// Pixels from the top left of the window
+    #[default]
+    Screen,
+    /// Pixels from where this world position lands on screen, follows the active camera. Hidden
+    /// while the point is behind the camera
+    World(na::Vector3<f32>),
+}
+
 #[derive(Debug, Clone)]
 pub struct TextParams {
     pub metrics: glyphon::Metrics,
@@ -49,6 +146,7 @@ pub struct TextParams {
     pub weight: glyphon::Weight,
     pub fancy_render: bool,
     pub top_left_position: (f32, f32),
+    pub anchor: TextAnchor,
     pub text_scale: f32,
     pub bounds: glyphon::TextBounds,
     pub default_color: glyphon::Color,
@@ -71,18 +169,52 @@ impl Debug for TextState {
 pub struct Scene {
     entities: Arc<Mutex<Vec<Entity>>>,
     total_entities_created: u32,
+    /// Index for [Scene::find_by_name]
+    entity_names: HashMap<String, EntityId>,
+    /// Index for [Scene::find_all_with_tag], in tagging order
+    entity_tags: HashMap<String, Vec<EntityId>>,
     pipelines: HashMap<MaterialId, Pipeline>,
+    /// See [Scene::failed_pipelines]
+    failed_pipelines: HashSet<MaterialId>,
+    wireframe_pipelines: Option<HashMap<MaterialId, Pipeline>>,
     compute_pipelines: Vec<ComputePipeline>,
+    /// `(source, destination)` pipeline pairs from [Scene::link_compute_outputs]
+    compute_links: Vec<(usize, usize)>,
+    /// Order the compute pipelines run in, sources before the pipelines reading from them
+    compute_order: Vec<usize>,
+    /// How long the compute shaders took to dispatch in the last update
+    compute_duration: Duration,
     components: AllComponents,
     materials: Materials,
     active_camera_id: Option<EntityId>,
     concept_manager: Rc<Mutex<ConceptManager>>,
+    picking_pipeline: Option<PickingPipeline>,
+    skybox: Option<Skybox>,
+    debug_draw: DebugDraw,
+    initialized: bool,
+    panic_isolation: bool,
+    disabled_components: HashSet<ComponentId>,
+    collision_events: Vec<CollisionEvent>,
+    spatial_grid: SpatialGrid,
+    /// Color format, sample count and depth format the pipelines are built for, known once
+    /// initialized
+    pipeline_target: Option<(TextureFormat, u32, TextureFormat)>,
+    /// Replaces the engine's clear color, see [Scene::set_clear_color]
+    clear_color: Option<wgpu::Color>,
+    clear_depth: f32,
+    /// Loads the previous contents of the color target instead of clearing it
+    skip_color_clear: bool,
+    /// Set by [Scene::ui_draw] for the next render to draw the UI frame it started
+    ui_frame_pending: bool,
+    indirect_draws: Option<IndirectDraws>,
 
     pub text_state: TextState,
 }
 
 #[allow(clippy::too_many_arguments)]
 impl Scene {
+    /// The components are initialized by [Scene::initialize], so entities created after that
+    /// should get theirs through [Scene::add_component]
     pub fn create_entity(
         &mut self,
         parent: EntityId,
@@ -93,9 +225,12 @@ impl Scene {
         let new_entity_id = self.total_entities_created;
         let new_entity = Entity::new(new_entity_id, enabled, parent, Vec::new());
 
+        let mut same_component_counts: HashMap<TypeId, u32> = HashMap::new();
         for component in components.iter_mut() {
             let old_id = component.get_id();
-            component.update_metadata(new_entity_id, 0);
+            let same_component_count = same_component_counts.entry(old_id.1).or_default();
+            component.update_metadata(new_entity_id, *same_component_count);
+            *same_component_count += 1;
             self.concept_manager
                 .lock()
                 .unwrap()
@@ -108,16 +243,446 @@ impl Scene {
         }
         self.components.insert(new_entity_id, components);
         let entities = self.entities.clone();
-        entities.lock().unwrap().push(new_entity);
+        let mut entities = entities.lock().unwrap();
+        if let Some(parent_entity) = entities.iter_mut().find(|entity| *entity.id() == parent) {
+            parent_entity.add_child(new_entity_id);
+        }
+        entities.push(new_entity);
         self.total_entities_created += 1;
         new_entity_id
     }
 
+    /// Every entity in the scene, in creation order
+    pub fn entity_ids(&self) -> Vec<EntityId> {
+        self.entities
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|entity| *entity.id())
+            .collect()
+    }
+
+    pub fn entity_info(&self, entity_id: EntityId) -> Option<EntityInfo> {
+        let entities = self.entities.lock().unwrap();
+        let entity = entities.iter().find(|entity| *entity.id() == entity_id)?;
+        let component_types = self
+            .components
+            .get(&entity_id)
+            .map(|components| {
+                components
+                    .iter()
+                    .map(|component| component.type_name())
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        Some(EntityInfo {
+            id: entity_id,
+            name: entity.name().map(str::to_string),
+            enabled: entity.enabled,
+            active: Self::active_entities(&entities).contains(&entity_id),
+            parent: entity.parent(),
+            children: entity.children().to_vec(),
+            tags: entity.tags().to_vec(),
+            layers: entity.layers(),
+            component_types,
+        })
+    }
+
+    /// Direct children of the entity, empty if it doesn't exist
+    pub fn children_of(&self, entity_id: EntityId) -> Vec<EntityId> {
+        self.entities
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|entity| *entity.id() == entity_id)
+            .map(|entity| entity.children().to_vec())
+            .unwrap_or_default()
+    }
+
+    /// All children, grandchildren and so on of the entity, depth first and in child order
+    pub fn descendants(&self, entity_id: EntityId) -> Vec<EntityId> {
+        let entities = self.entities.lock().unwrap();
+        Self::collect_descendants(&entities, entity_id)
+    }
+
+    fn collect_descendants(entities: &[Entity], entity_id: EntityId) -> Vec<EntityId> {
+        let children_of = |id: EntityId| {
+            entities
+                .iter()
+                .find(|entity| *entity.id() == id)
+                .map(|entity| entity.children())
+                .unwrap_or_default()
+        };
+
+        let mut descendants = Vec::new();
+        let mut stack = children_of(entity_id)
+            .iter()
+            .rev()
+            .copied()
+            .collect::<Vec<_>>();
+        while let Some(id) = stack.pop() {
+            // An entity created as its own parent never lists itself as a child, but stay
+            // safe against malformed hierarchies
+            if id == entity_id || descendants.contains(&id) {
+                continue;
+            }
+            descendants.push(id);
+            stack.extend(children_of(id).iter().rev());
+        }
+        descendants
+    }
+
+    /// Entities whose parent is themselves or doesn't exist, in creation order
+    pub fn roots(&self) -> Vec<EntityId> {
+        let entities = self.entities.lock().unwrap();
+        entities
+            .iter()
+            .filter(|entity| {
+                entity.parent() == *entity.id()
+                    || !entities
+                        .iter()
+                        .any(|parent| *parent.id() == entity.parent())
+            })
+            .map(|entity| *entity.id())
+            .collect()
+    }
+
+    /// Moves `child` under `new_parent`, keeping both parents' children lists in sync. Fails
+    /// without changing anything if either entity doesn't exist or `new_parent` is `child`
+    /// itself or one of its descendants
+    pub fn reparent(&mut self, child: EntityId, new_parent: EntityId) -> Result<(), SceneError> {
+        let entities_arc = self.entities.clone();
+        let mut entities = entities_arc.lock().unwrap();
+        for id in [child, new_parent] {
+            if !entities.iter().any(|entity| *entity.id() == id) {
+                return Err(SceneError::EntityNotFound(id));
+            }
+        }
+        if child == new_parent || Self::collect_descendants(&entities, child).contains(&new_parent)
+        {
+            return Err(SceneError::HierarchyCycle { child, new_parent });
+        }
+
+        let old_parent = entities
+            .iter()
+            .find(|entity| *entity.id() == child)
+            .map(|entity| entity.parent());
+        for entity in entities.iter_mut() {
+            if Some(*entity.id()) == old_parent {
+                entity.remove_child(child);
+            }
+            if *entity.id() == new_parent {
+                entity.add_child(child);
+            }
+            if *entity.id() == child {
+                entity.set_parent(new_parent);
+            }
+        }
+        Ok(())
+    }
+
+    /// Names the entity, replacing its old name. `None` removes the name
+    pub fn set_name(&mut self, entity_id: EntityId, name: Option<&str>) -> Result<(), SceneError> {
+        if let Some(&owner) = name.and_then(|name| self.entity_names.get(name)) {
+            if owner != entity_id {
+                return Err(SceneError::NameTaken {
+                    name: name.unwrap().to_string(),
+                    owner,
+                });
+            }
+        }
+
+        let mut entities = self.entities.lock().unwrap();
+        let entity = entities
+            .iter_mut()
+            .find(|entity| *entity.id() == entity_id)
+            .ok_or(SceneError::EntityNotFound(entity_id))?;
+        if let Some(old_name) = entity.name() {
+            self.entity_names.remove(old_name);
+        }
+        if let Some(name) = name {
+            self.entity_names.insert(name.to_string(), entity_id);
+        }
+        entity.set_name(name.map(str::to_string));
+        Ok(())
+    }
+
+    /// Tags can be shared by any number of entities. Returns `false` if the entity doesn't exist
+    pub fn add_tag(&mut self, entity_id: EntityId, tag: &str) -> bool {
+        let mut entities = self.entities.lock().unwrap();
+        let Some(entity) = entities.iter_mut().find(|entity| *entity.id() == entity_id) else {
+            return false;
+        };
+        if !entity.tags().iter().any(|existing| existing == tag) {
+            entity.tags_mut().push(tag.to_string());
+            self.entity_tags
+                .entry(tag.to_string())
+                .or_default()
+                .push(entity_id);
+        }
+        true
+    }
+
+    /// Returns `false` if the entity didn't have the tag
+    pub fn remove_tag(&mut self, entity_id: EntityId, tag: &str) -> bool {
+        let mut entities = self.entities.lock().unwrap();
+        let Some(entity) = entities.iter_mut().find(|entity| *entity.id() == entity_id) else {
+            return false;
+        };
+        let Some(position) = entity.tags().iter().position(|existing| existing == tag) else {
+            return false;
+        };
+        entity.tags_mut().remove(position);
+        if let Some(tagged) = self.entity_tags.get_mut(tag) {
+            tagged.retain(|id| *id != entity_id);
+            if tagged.is_empty() {
+                self.entity_tags.remove(tag);
+            }
+        }
+        true
+    }
+
+    pub fn find_by_name(&self, name: &str) -> Option<EntityId> {
+        self.entity_names.get(name).copied()
+    }
+
+    pub fn find_all_with_tag(&self, tag: &str) -> Vec<EntityId> {
+        self.entity_tags.get(tag).cloned().unwrap_or_default()
+    }
+
+    /// Enables or disables an entity. A disabled entity's descendants are skipped during updates
+    /// and rendering too, but keep their own [Entity::enabled] flags, so enabling the entity
+    /// again brings back only the ones that were enabled before. Returns `false` if the entity
+    /// doesn't exist
+    pub fn set_entity_active(&mut self, entity_id: EntityId, active: bool) -> bool {
+        let mut entities = self.entities.lock().unwrap();
+        match entities.iter_mut().find(|entity| *entity.id() == entity_id) {
+            Some(entity) => {
+                entity.enabled = active;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Puts the entity on `layers`, passes rendering with a [LayerMask] only draw it if they share
+    /// a layer. Returns `false` if the entity doesn't exist
+    pub fn set_entity_layers(&mut self, entity_id: EntityId, layers: LayerMask) -> bool {
+        let mut entities = self.entities.lock().unwrap();
+        match entities.iter_mut().find(|entity| *entity.id() == entity_id) {
+            Some(entity) => {
+                entity.set_layers(layers);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Whether the entity and all of its ancestors are enabled, i.e. whether it updates and
+    /// renders
+    pub fn is_entity_active(&self, entity_id: EntityId) -> bool {
+        Self::active_entities(&self.entities.lock().unwrap()).contains(&entity_id)
+    }
+
+    /// Ids of the entities that are enabled and whose ancestors are all enabled, in creation order
+    fn active_entities(entities: &[Entity]) -> Vec<EntityId> {
+        let entities_by_id = entities
+            .iter()
+            .map(|entity| (*entity.id(), entity))
+            .collect::<HashMap<_, _>>();
+        entities
+            .iter()
+            .filter(|entity| {
+                let mut current: &Entity = entity;
+                // Bounded so a malformed hierarchy can't loop forever
+                for _ in 0..=entities.len() {
+                    if !current.enabled {
+                        return false;
+                    }
+                    match entities_by_id.get(&current.parent()) {
+                        Some(parent) if parent.id() != current.id() => current = *parent,
+                        _ => return true,
+                    }
+                }
+                true
+            })
+            .map(|entity| *entity.id())
+            .collect()
+    }
+
+    /// Adds a component to an existing entity. Once the scene is initialized the component is
+    /// initialized right away, so it updates from the next [Scene::update] on. The component's
+    /// concepts must not have been moved to another entity yet, i.e. it should be freshly created.
+    /// Returns `false` if the entity doesn't exist
+    pub fn add_component(
+        &mut self,
+        entity_id: EntityId,
+        mut component: Component,
+        device: Arc<Device>,
+        queue: Arc<Queue>,
+        engine_details: Rc<Mutex<EngineDetails>>,
+        engine_systems: Rc<Mutex<EngineSystems>>,
+        ui_manager: Rc<Mutex<UiManager>>,
+    ) -> bool {
+        let entity_exists = self
+            .entities
+            .lock()
+            .unwrap()
+            .iter()
+            .any(|entity| *entity.id() == entity_id);
+        if !entity_exists {
+            return false;
+        }
+
+        let old_id = component.get_id();
+        let same_component_count = self.components.get(&entity_id).map_or(0, |components| {
+            components
+                .iter()
+                .filter(|existing| existing.get_id().1 == old_id.1)
+                .count()
+        });
+        component.update_metadata(entity_id, same_component_count as u32);
+        self.concept_manager
+            .lock()
+            .unwrap()
+            .modify_key(old_id, component.get_id());
+
+        if self.initialized {
+            component.initialize(
+                device,
+                queue,
+                &self.components,
+                self.concept_manager.clone(),
+                Some(engine_details),
+                Some(engine_systems),
+                ui_manager,
+                &mut self.text_state.text_items,
+            );
+        }
+
+        self.components
+            .entry(entity_id)
+            .or_default()
+            .push(component);
+        true
+    }
+
+    /// Creates an entity for every node of a glTF/GLB file's default scene, keeping the node
+    /// hierarchy as parent/child entities under `parent`. Entity transforms are not hierarchical,
+    /// so each node's transform is baked into world space. Nodes with a mesh get a
+    /// [MeshComponent] with one material per primitive, holding the base color texture (if any)
+    /// and the base color factor as a `vec4<f32>` uniform. Returns the ids of the root entities
+    pub fn load_gltf_scene(
+        &mut self,
+        gltf_path: &str,
+        parent: EntityId,
+        vertex_shader_path: &str,
+        fragment_shader_path: &str,
+        device: Arc<Device>,
+        queue: Arc<Queue>,
+    ) -> Result<Vec<EntityId>, MeshComponentError> {
+        let path = std::path::Path::new(&std::env::current_dir().unwrap()).join(gltf_path);
+        let (document, buffers, images) =
+            gltf::import(path).map_err(|_| MeshComponentError::FailedToLoadGltf)?;
+
+        let textures = images
+            .iter()
+            .enumerate()
+            .map(|(i, image)| {
+                let label = format!("{gltf_path} image {i}");
+                Texture::from_gltf_image(&device, &queue, image, Some(&label))
+                    .map(Rc::new)
+                    .map_err(|_| MeshComponentError::FailedToLoadTexture(label))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let Some(gltf_scene) = document
+            .default_scene()
+            .or_else(|| document.scenes().next())
+        else {
+            return Ok(Vec::new());
+        };
+
+        let loader = GltfLoader {
+            document: &document,
+            buffers: &buffers,
+            textures: &textures,
+            vertex_shader_path,
+            fragment_shader_path,
+            device,
+        };
+
+        Ok(gltf_scene
+            .nodes()
+            .map(|node| {
+                self.create_gltf_node_entity(&loader, &node, parent, na::Matrix4::identity())
+            })
+            .collect())
+    }
+
+    fn create_gltf_node_entity(
+        &mut self,
+        loader: &GltfLoader,
+        node: &gltf::Node,
+        parent: EntityId,
+        parent_matrix: na::Matrix4<f32>,
+    ) -> EntityId {
+        let matrix = parent_matrix * na::Matrix4::from(node.transform().matrix());
+
+        let mut components: Vec<Component> = vec![Box::new(TransformComponent::from_matrix(
+            self.concept_manager.clone(),
+            matrix,
+        ))];
+        let mut materials = None;
+
+        if let Some(mesh) = node.mesh() {
+            let primitives = MeshComponent::read_gltf_primitives(&mesh, loader.buffers);
+
+            let mut gltf_material_indices: Vec<Option<usize>> = Vec::new();
+            let mut material_indices = Vec::new();
+            let mut submeshes = Vec::new();
+            for (vertices, indices, gltf_material_index) in primitives {
+                let material_index = gltf_material_indices
+                    .iter()
+                    .position(|index| *index == gltf_material_index)
+                    .unwrap_or_else(|| {
+                        gltf_material_indices.push(gltf_material_index);
+                        gltf_material_indices.len() - 1
+                    });
+                material_indices.push(material_index);
+                submeshes.push((vertices, indices));
+            }
+
+            let entity_materials = gltf_material_indices
+                .into_iter()
+                .map(|gltf_material_index| loader.create_material(gltf_material_index))
+                .collect::<Vec<_>>();
+
+            components.push(Box::new(MeshComponent::from_submeshes(
+                self.concept_manager.clone(),
+                submeshes,
+                material_indices,
+            )));
+            materials = Some((entity_materials, 0));
+        }
+
+        let entity_id = self.create_entity(parent, true, components, materials);
+
+        for child in node.children() {
+            self.create_gltf_node_entity(loader, &child, entity_id, matrix);
+        }
+
+        entity_id
+    }
+
     pub fn initialize(
         &mut self,
         device: Arc<Device>,
         queue: Arc<Queue>,
         color_format: TextureFormat,
+        sample_count: u32,
+        depth_format: TextureFormat,
         engine_details: Rc<Mutex<EngineDetails>>,
         engine_systems: Rc<Mutex<EngineSystems>>,
         ui_manager: Rc<Mutex<UiManager>>,
@@ -126,54 +691,61 @@ impl Scene {
         let entities = entities_arc.lock().unwrap();
         // let components_arc = self.components.clone();
 
-        let new_components = entities
+        self.pipeline_target = Some((color_format, sample_count, depth_format));
+        let material_ids = entities
             .iter()
-            .map(|entity| {
-                if let Some((materials, active_material_index)) = &self.materials.get(entity.id()) {
-                    let active_material = &materials[*active_material_index];
-                    let active_material_id = active_material.id().clone();
-                    self.pipelines
-                        .entry(active_material_id.clone())
-                        .or_insert_with(|| {
-                            Pipeline::new(
-                                device.clone(),
-                                color_format,
-                                &[Vertex::desc(), TransformComponent::desc()],
-                                &active_material_id,
-                            )
-                        });
-                }
-                (
-                    *entity.id(),
-                    self.components
-                        .get(entity.id())
-                        .unwrap_or(&Vec::<Component>::new())
-                        .iter()
-                        .map(|comp| {
-                            let mut comp_clone = dyn_clone::clone_box(&**comp);
-                            comp_clone.initialize(
-                                device.clone(),
-                                queue.clone(),
-                                &self.components,
-                                self.concept_manager.clone(),
-                                Some(engine_details.clone()),
-                                Some(engine_systems.clone()),
-                                ui_manager.clone(),
-                                &mut self.text_state.text_items,
-                            );
-                            comp_clone
-                        })
-                        .collect::<Vec<Component>>(),
-                )
+            .flat_map(|entity| {
+                Self::enabled_material_ids(&self.materials, &self.components, entity.id())
             })
-            .collect::<HashMap<EntityId, Vec<Component>>>();
+            .collect::<HashSet<_>>();
+        for material_id in material_ids {
+            self.ensure_pipeline(&device, &material_id);
+        }
+
+        // In place like in update, so a component isn't in `component_map` while it's initialized
+        for entity in entities.iter() {
+            let entity_id = *entity.id();
+            let entity_components_len = self
+                .components
+                .get(&entity_id)
+                .map_or(0, |components| components.len());
+            for comp_index in 0..entity_components_len {
+                let Some(mut comp) =
+                    Self::take_component(&mut self.components, entity_id, comp_index)
+                else {
+                    break;
+                };
+                comp.initialize(
+                    device.clone(),
+                    queue.clone(),
+                    &self.components,
+                    self.concept_manager.clone(),
+                    Some(engine_details.clone()),
+                    Some(engine_systems.clone()),
+                    ui_manager.clone(),
+                    &mut self.text_state.text_items,
+                );
+                Self::return_component(&mut self.components, entity_id, comp_index, comp);
+            }
+        }
 
-        self.components = new_components;
+        for entity in entities.iter() {
+            if let Err(err) =
+                Self::check_vertex_types(&self.materials, &self.components, entity.id())
+            {
+                log::error!(
+                    "Skipping mismatched materials of entity {}: {err:?}",
+                    entity.id()
+                );
+            }
+        }
 
         let cache = glyphon::Cache::new(&device);
         self.text_state.text_viewport = Some(glyphon::Viewport::new(&device, &cache));
-        let mut atlas =
-            TextAtlas::new(&device, &queue, &cache, wgpu::TextureFormat::Bgra8UnormSrgb);
+        // Text is drawn over the post-processed frame along with the UI, not into the scene's
+        // own target
+        let overlay_format = ui_manager.lock().unwrap().texture_format();
+        let mut atlas = TextAtlas::new(&device, &queue, &cache, overlay_format);
         self.text_state.text_renderer = Some(TextRenderer::new(
             &mut atlas,
             &device,
@@ -181,435 +753,1864 @@ impl Scene {
             None,
         ));
         self.text_state.atlas = Some(atlas);
+        self.initialized = true;
     }
 
-    pub fn update(
+    /// Switches which of the entity's materials is drawn, e.g. to highlight a selected object.
+    /// The new material's pipeline is built right away if no other material uses it yet
+    pub fn set_active_material(
         &mut self,
         device: Arc<Device>,
-        queue: Arc<Queue>,
-        engine_details: Rc<Mutex<EngineDetails>>,
-        engine_systems: Rc<Mutex<EngineSystems>>,
-    ) {
-        let entities_arc = self.entities.clone();
-        let entities = entities_arc.lock().unwrap();
+        entity_id: EntityId,
+        index: usize,
+    ) -> Result<(), SceneError> {
+        let (materials, active_material_index) = self
+            .materials
+            .get_mut(&entity_id)
+            .ok_or(SceneError::NoMaterials(entity_id))?;
+        if index >= materials.len() {
+            return Err(SceneError::MaterialIndexOutOfRange {
+                index,
+                material_count: materials.len(),
+            });
+        }
+        if let Some(mesh_vertex_type) = Self::mesh_vertex_type(&self.components, &entity_id) {
+            let material_vertex_type = materials[index].id().vertex_type;
+            if material_vertex_type != mesh_vertex_type {
+                return Err(SceneError::VertexTypeMismatch {
+                    entity: entity_id,
+                    mesh: mesh_vertex_type,
+                    material: material_vertex_type,
+                });
+            }
+        }
+        *active_material_index = index;
 
-        let mut entities_clone = entities.clone();
+        let material_id = materials[index].id().clone();
+        self.ensure_pipeline(&device, &material_id);
+        Ok(())
+    }
 
-        let mut cloned_components = self
-            .components
-            .iter()
-            .map(|(k, v)| {
-                (
-                    *k,
-                    v.iter()
-                        .map(|comp| dyn_clone::clone_box(&**comp))
-                        .collect::<Vec<_>>(),
-                )
-            })
-            .collect::<HashMap<EntityId, Vec<Component>>>();
+    /// Builds the pipeline for `material_id`, and its wireframe variant while wireframe is on,
+    /// if they don't exist yet. Does nothing before the scene is initialized, since the render
+    /// target isn't known until then. Pipelines that fail to build are logged once and their
+    /// materials aren't drawn
+    fn ensure_pipeline(&mut self, device: &Arc<Device>, material_id: &MaterialId) {
+        let _ = self.ensure_pipeline_with(device, material_id, || {
+            Ok((
+                Pipeline::load_shader_module_descriptor(
+                    &material_id.vertex_shader_path,
+                    &material_id.shader_defines,
+                )?,
+                Pipeline::load_shader_module_descriptor(
+                    &material_id.fragment_shader_path,
+                    &material_id.shader_defines,
+                )?,
+            ))
+        });
+    }
 
-        for entity in entities.iter() {
-            if entity.enabled {
-                let entity_components_len = cloned_components
-                    .get(entity.id())
-                    .unwrap_or(&Vec::<Component>::new())
-                    .len();
-                for comp_index in 0..entity_components_len {
-                    let mut comp =
-                        dyn_clone::clone_box(&*cloned_components[entity.id()][comp_index]);
-                    comp.update(
-                        device.clone(),
-                        queue.clone(),
-                        &mut cloned_components,
-                        engine_details.clone(),
-                        engine_systems.clone(),
-                        self.concept_manager.clone(),
-                        self.active_camera_id,
-                        &mut entities_clone,
-                        self.materials.get_mut(entity.id()),
-                        &mut self.compute_pipelines,
-                        &mut self.text_state.text_items,
-                    );
-                    let map_ref = cloned_components
-                        .get_mut(entity.id())
-                        .unwrap()
-                        .get_mut(comp_index)
-                        .unwrap();
-                    *map_ref = comp
+    /// [Scene::ensure_pipeline] with the vertex and fragment shaders from `load_shaders`, which
+    /// is only called when something has to be built. Returns why the pipeline failed to build
+    fn ensure_pipeline_with(
+        &mut self,
+        device: &Arc<Device>,
+        material_id: &MaterialId,
+        load_shaders: impl FnOnce() -> Result<
+            (
+                wgpu::ShaderModuleDescriptor<'static>,
+                wgpu::ShaderModuleDescriptor<'static>,
+            ),
+            PipelineError,
+        >,
+    ) -> Result<(), PipelineError> {
+        let Some((color_format, sample_count, depth_format)) = self.pipeline_target else {
+            return Ok(());
+        };
+        let missing_wireframe = self
+            .wireframe_pipelines
+            .as_ref()
+            .is_some_and(|wireframe_pipelines| !wireframe_pipelines.contains_key(material_id));
+        if self.failed_pipelines.contains(material_id)
+            || (self.pipelines.contains_key(material_id) && !missing_wireframe)
+        {
+            return Ok(());
+        }
+
+        let build = |id: &MaterialId,
+                     (vertex_descriptor, fragment_descriptor): (
+            wgpu::ShaderModuleDescriptor<'static>,
+            wgpu::ShaderModuleDescriptor<'static>,
+        )| {
+            Pipeline::from_descriptors(
+                device.clone(),
+                color_format,
+                sample_count,
+                depth_format,
+                &[id.vertex_type.desc(), TransformComponent::desc()],
+                id,
+                vertex_descriptor,
+                fragment_descriptor,
+            )
+        };
+        let has_pipeline = self.pipelines.contains_key(material_id);
+        let built = load_shaders().and_then(|shaders| {
+            let pipeline = if has_pipeline {
+                None
+            } else {
+                Some(build(material_id, shaders.clone())?)
+            };
+            Ok((shaders, pipeline))
+        });
+        let shaders = match built {
+            Ok((shaders, pipeline)) => {
+                if let Some(pipeline) = pipeline {
+                    self.pipelines.insert(material_id.clone(), pipeline);
                 }
+                shaders
             }
-        }
+            Err(err) if has_pipeline => {
+                log::error!("Failed to build a wireframe pipeline: {err:?}");
+                return Ok(());
+            }
+            Err(err) => {
+                log::error!("Failed to build a pipeline: {err:?}");
+                self.failed_pipelines.insert(material_id.clone());
+                return Err(err);
+            }
+        };
 
-        for compute_pipeline in &self.compute_pipelines {
-            compute_pipeline.run_compute_shader(&device, &queue);
+        if let Some(wireframe_pipelines) = &mut self.wireframe_pipelines {
+            if !wireframe_pipelines.contains_key(material_id) {
+                let mut wireframe_id = material_id.clone();
+                wireframe_id.pipeline_config.polygon_mode = wgpu::PolygonMode::Line;
+                match build(&wireframe_id, shaders) {
+                    Ok(pipeline) => {
+                        wireframe_pipelines.insert(material_id.clone(), pipeline);
+                    }
+                    Err(err) => log::error!("Failed to build a wireframe pipeline: {err:?}"),
+                }
+            }
         }
+        Ok(())
+    }
 
-        self.components = cloned_components;
-        self.entities = Arc::new(Mutex::new(entities_clone));
+    /// Materials whose pipelines failed to build, e.g. because a shader doesn't compile. They
+    /// aren't drawn, and building their pipelines is never retried for the lifetime of the
+    /// scene, so a fixed shader only gets picked up by a new scene
+    pub fn failed_pipelines(&self) -> &HashSet<MaterialId> {
+        &self.failed_pipelines
     }
 
-    pub fn ui_draw(
+    /// Same as [Scene::initialize], but first reads every shader file the scene's materials need
+    /// concurrently and builds their pipelines before initializing the components. Textures can
+    /// be loaded ahead of time in the same way with [Texture::load_textures]. Needs a tokio
+    /// runtime. Pipelines that fail to build are handled like in [Scene::initialize], the first
+    /// of their errors is returned once the scene is initialized
+    pub async fn initialize_async(
         &mut self,
         device: Arc<Device>,
         queue: Arc<Queue>,
+        color_format: TextureFormat,
+        sample_count: u32,
+        depth_format: TextureFormat,
         engine_details: Rc<Mutex<EngineDetails>>,
         engine_systems: Rc<Mutex<EngineSystems>>,
         ui_manager: Rc<Mutex<UiManager>>,
-    ) {
-        let entities_arc = self.entities.clone();
-        let entities = entities_arc.lock().unwrap();
+    ) -> Result<(), PipelineError> {
+        let missing_pipeline_ids = self
+            .materials
+            .keys()
+            .flat_map(|entity_id| {
+                Self::enabled_material_ids(&self.materials, &self.components, entity_id)
+            })
+            .filter(|id| !self.pipelines.contains_key(id))
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .collect::<Vec<_>>();
 
-        let mut manager = ui_manager.lock().unwrap();
-        let context_arc = manager.imgui_context.clone();
-        let mut context = context_arc.lock().unwrap();
-        let ui_frame = context.new_frame();
-        let mut cloned_components = self
-            .components
-            .iter()
-            .map(|(k, v)| {
-                (
-                    *k,
-                    v.iter()
-                        .map(|comp| dyn_clone::clone_box(&**comp))
-                        .collect::<Vec<_>>(),
+        let shader_loads = missing_pipeline_ids.iter().map(|id| async move {
+            futures::join!(
+                Pipeline::load_shader_module_descriptor_async(
+                    &id.vertex_shader_path,
+                    &id.shader_defines
+                ),
+                Pipeline::load_shader_module_descriptor_async(
+                    &id.fragment_shader_path,
+                    &id.shader_defines
                 )
-            })
-            .collect::<HashMap<EntityId, Vec<Component>>>();
+            )
+        });
+        let shaders = futures::future::join_all(shader_loads).await;
 
-        for entity in entities.iter() {
-            if entity.enabled {
-                let entity_components_len = cloned_components
-                    .get(entity.id())
-                    .unwrap_or(&Vec::<Component>::new())
-                    .len();
-                for comp_index in 0..entity_components_len {
-                    let mut comp =
-                        dyn_clone::clone_box(&*cloned_components[entity.id()][comp_index]);
-                    comp.ui_draw(
-                        device.clone(),
-                        queue.clone(),
-                        &mut manager,
-                        ui_frame,
-                        &mut cloned_components,
-                        self.concept_manager.clone(),
-                        engine_details.clone(),
-                        engine_systems.clone(),
-                    );
-                    let map_ref = cloned_components
-                        .get_mut(entity.id())
-                        .unwrap()
-                        .get_mut(comp_index)
-                        .unwrap();
-                    *map_ref = comp
-                }
+        self.pipeline_target = Some((color_format, sample_count, depth_format));
+        let mut first_error = None;
+        for (id, (vertex_descriptor, fragment_descriptor)) in
+            missing_pipeline_ids.iter().zip(shaders)
+        {
+            if let Err(err) = self.ensure_pipeline_with(&device, id, || {
+                Ok((vertex_descriptor?, fragment_descriptor?))
+            }) {
+                first_error.get_or_insert(err);
             }
         }
 
-        self.components = cloned_components;
+        self.initialize(
+            device,
+            queue,
+            color_format,
+            sample_count,
+            depth_format,
+            engine_details,
+            engine_systems,
+            ui_manager,
+        );
+
+        first_error.map_or(Ok(()), Err)
     }
 
-    pub fn render(
+    pub fn is_initialized(&self) -> bool {
+        self.initialized
+    }
+
+    /// Updates every component of every enabled entity in place. While a component is being
+    /// updated it is temporarily removed from `component_map`, so it will not find itself
+    /// there when looking up siblings on its own entity.
+    pub fn update(
         &mut self,
         device: Arc<Device>,
         queue: Arc<Queue>,
-        depth_texture: Arc<Texture>,
-        window_size: (u32, u32),
-        engine_details: &EngineDetails,
-        engine_systems: &EngineSystems,
-        smaa_frame: smaa::SmaaFrame,
-        output: wgpu::SurfaceTexture,
-        clear_color: wgpu::Color,
-        ui_manager: Rc<Mutex<UiManager>>,
+        engine_details: Rc<Mutex<EngineDetails>>,
+        engine_systems: Rc<Mutex<EngineSystems>>,
     ) {
-        let entities_arc = self.entities.clone();
-        let entities = entities_arc.lock().unwrap();
-
-        let camera_bind_group = self.create_camera_bind_group(
+        self.update_components(
             device.clone(),
             queue.clone(),
-            window_size,
-            ui_manager.clone(),
+            engine_details,
+            engine_systems,
+            false,
         );
 
-        let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor {
-            label: Some("Scene Encoder"),
-        });
+        let compute_start = Instant::now();
+        for compute_index in &self.compute_order {
+            self.compute_pipelines[*compute_index].run_compute_shader(&device, &queue);
+        }
+        self.compute_duration = compute_start.elapsed();
+    }
 
-        let mut default_transform = TransformComponent::default(self.concept_manager.clone());
-        default_transform.initialize(
-            device.clone(),
-            queue.clone(),
-            &self.components,
-            self.concept_manager.clone(),
-            None,
-            None,
-            ui_manager.clone(),
-            &mut self.text_state.text_items,
-        );
+    /// How long the compute shaders took to dispatch in the last [Scene::update], on the CPU
+    pub fn last_compute_duration(&self) -> Duration {
+        self.compute_duration
+    }
 
-        {
-            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                label: Some("Scene Render Pass"),
-                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: &smaa_frame,
-                    resolve_target: None,
-                    ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(clear_color),
-                        store: wgpu::StoreOp::Store,
-                    },
-                })],
-                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
-                    view: &depth_texture.view,
-                    depth_ops: Some(wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(1.0),
-                        store: wgpu::StoreOp::Store,
-                    }),
-                    stencil_ops: None,
-                }),
-                timestamp_writes: None,
-                occlusion_query_set: None,
-            });
-
-            if let Some(mask) = &engine_details.render_mask {
-                render_pass.set_viewport(mask.x, mask.y, mask.width, mask.height, 0.0, 1.0);
-            }
-
-            render_pass.set_bind_group(1, &camera_bind_group, &[]);
+    /// Runs [ComponentSystem::fixed_update] on every component of every enabled entity the same
+    /// way as [Scene::update], then resolves collisions. Called by the engine a fixed number of
+    /// times per second of game time, see [EngineDetails::fixed_timestep]
+    pub fn fixed_update(
+        &mut self,
+        device: Arc<Device>,
+        queue: Arc<Queue>,
+        engine_details: Rc<Mutex<EngineDetails>>,
+        engine_systems: Rc<Mutex<EngineSystems>>,
+    ) {
+        let enabled_entities =
+            self.update_components(device, queue, engine_details, engine_systems, true);
 
-            for (pipeline_id, pipeline) in &self.pipelines {
-                render_pass.set_pipeline(pipeline.pipeline());
+        self.resolve_collisions(&enabled_entities);
+    }
 
-                for entity in entities.iter() {
-                    if entity.enabled {
-                        let entity_materials = self.materials.get(entity.id());
-                        if let Some((materials, active_material_index)) = entity_materials {
-                            let active_material = &materials[*active_material_index];
-                            if active_material.id() == pipeline_id {
-                                render_pass.set_bind_group(
-                                    0,
-                                    active_material.texture_bind_group(),
-                                    &[],
-                                );
-                                if let Some(uniform_buffer_bind_group) =
-                                    active_material.uniform_buffer_bind_group()
-                                {
-                                    render_pass.set_bind_group(
-                                        2,
-                                        &uniform_buffer_bind_group.0,
-                                        &[],
-                                    );
-                                }
+    /// Calls [ComponentSystem::fixed_update] if `fixed`, [ComponentSystem::update] otherwise.
+    /// Returns the entities that were updated
+    fn update_components(
+        &mut self,
+        device: Arc<Device>,
+        queue: Arc<Queue>,
+        engine_details: Rc<Mutex<EngineDetails>>,
+        engine_systems: Rc<Mutex<EngineSystems>>,
+        fixed: bool,
+    ) -> Vec<EntityId> {
+        let entities_arc = self.entities.clone();
+        let mut entities = entities_arc.lock().unwrap();
 
-                                default_transform.render(
-                                    device.clone(),
-                                    queue.clone(),
-                                    &mut render_pass,
-                                    &self.components,
-                                    self.concept_manager.clone(),
-                                    engine_details,
-                                    engine_systems,
-                                );
+        let enabled_entities = Self::active_entities(&entities);
 
-                                // render_pass.set_vertex_buffer(1, default_transform_buffer.slice(..));
-                                let components_opt = self.components.get(entity.id());
-                                if let Some(components) = components_opt {
-                                    let ordered_components =
-                                        Self::get_component_render_order(components);
-                                    for component in ordered_components.iter() {
-                                        component.render(
-                                            device.clone(),
-                                            queue.clone(),
-                                            &mut render_pass,
-                                            &self.components,
-                                            self.concept_manager.clone(),
-                                            engine_details,
-                                            engine_systems,
-                                        );
-                                    }
-                                }
-                            }
-                        }
+        for entity_id in enabled_entities.iter().copied() {
+            let entity_components_len = self
+                .components
+                .get(&entity_id)
+                .map_or(0, |components| components.len());
+            for comp_index in 0..entity_components_len {
+                let Some(mut comp) =
+                    Self::take_component(&mut self.components, entity_id, comp_index)
+                else {
+                    break;
+                };
+                if self.disabled_components.contains(&comp.get_id()) {
+                    Self::return_component(&mut self.components, entity_id, comp_index, comp);
+                    continue;
+                }
+                let mut update_component = || {
+                    if fixed {
+                        comp.fixed_update(
+                            device.clone(),
+                            queue.clone(),
+                            &mut self.components,
+                            engine_details.clone(),
+                            engine_systems.clone(),
+                            self.concept_manager.clone(),
+                            self.active_camera_id,
+                            &mut entities,
+                            self.materials.get_mut(&entity_id),
+                            &mut self.compute_pipelines,
+                            &mut self.text_state.text_items,
+                        )
+                    } else {
+                        comp.update(
+                            device.clone(),
+                            queue.clone(),
+                            &mut self.components,
+                            engine_details.clone(),
+                            engine_systems.clone(),
+                            self.concept_manager.clone(),
+                            self.active_camera_id,
+                            &mut entities,
+                            self.materials.get_mut(&entity_id),
+                            &mut self.compute_pipelines,
+                            &mut self.text_state.text_items,
+                        )
                     }
+                };
+                if self.panic_isolation {
+                    let result =
+                        std::panic::catch_unwind(std::panic::AssertUnwindSafe(update_component));
+                    if result.is_err() {
+                        log::error!(
+                            "{} on entity {entity_id} panicked during {} and was disabled",
+                            comp.type_name(),
+                            if fixed { "fixed update" } else { "update" }
+                        );
+                        self.disabled_components.insert(comp.get_id());
+                        // A lock held by the component while panicking stays poisoned
+                        self.concept_manager.clear_poison();
+                        engine_details.clear_poison();
+                        engine_systems.clear_poison();
+                    }
+                } else {
+                    update_component();
                 }
+                Self::return_component(&mut self.components, entity_id, comp_index, comp);
             }
         }
-        smaa_frame.resolve();
 
-        if let Some(text_renderer) = &mut self.text_state.text_renderer {
-            self.text_state.text_viewport.as_mut().unwrap().update(
-                &queue,
-                glyphon::Resolution {
-                    width: window_size.0,
-                    height: window_size.1,
-                },
-            );
+        enabled_entities
+    }
 
-            let buffers = self
-                .text_state
-                .text_items
-                .iter()
-                .map(|params| {
-                    let mut buffer =
-                        glyphon::Buffer::new(&mut self.text_state.font_system, params.metrics);
+    pub fn ui_draw(
+        &mut self,
+        device: Arc<Device>,
+        queue: Arc<Queue>,
+        engine_details: Rc<Mutex<EngineDetails>>,
+        engine_systems: Rc<Mutex<EngineSystems>>,
+        ui_manager: Rc<Mutex<UiManager>>,
+    ) {
+        let entities_arc = self.entities.clone();
+        let entities = entities_arc.lock().unwrap();
 
-                    buffer.set_size(
-                        &mut self.text_state.font_system,
-                        Some(window_size.0 as f32),
-                        Some(window_size.1 as f32),
-                    );
+        let mut manager = ui_manager.lock().unwrap();
+        let context_arc = manager.imgui_context.clone();
+        let mut context = context_arc.lock().unwrap();
+        let ui_frame = context.new_frame();
+        self.ui_frame_pending = true;
 
-                    buffer.set_text(
-                        &mut self.text_state.font_system,
-                        &params.text,
-                        glyphon::Attrs::new()
-                            .color(params.color)
-                            .family(params.family)
-                            .weight(params.weight),
-                        if params.fancy_render {
-                            glyphon::Shaping::Advanced
-                        } else {
-                            glyphon::Shaping::Basic
-                        },
-                    );
+        let enabled_entities = Self::active_entities(&entities);
+        drop(entities);
 
-                    buffer.shape_until_scroll(&mut self.text_state.font_system, false);
-                    buffer
-                })
-                .collect::<Vec<_>>();
+        for entity_id in enabled_entities {
+            let entity_components_len = self
+                .components
+                .get(&entity_id)
+                .map_or(0, |components| components.len());
+            for comp_index in 0..entity_components_len {
+                let Some(mut comp) =
+                    Self::take_component(&mut self.components, entity_id, comp_index)
+                else {
+                    break;
+                };
+                comp.ui_draw(
+                    device.clone(),
+                    queue.clone(),
+                    &mut manager,
+                    ui_frame,
+                    &mut self.components,
+                    self.concept_manager.clone(),
+                    engine_details.clone(),
+                    engine_systems.clone(),
+                );
+                Self::return_component(&mut self.components, entity_id, comp_index, comp);
+            }
+        }
+    }
 
-            let text_areas = self
-                .text_state
-                .text_items
-                .iter()
-                .enumerate()
-                .map(|(i, params)| glyphon::TextArea {
-                    buffer: &buffers[i],
-                    left: params.top_left_position.0,
-                    top: params.top_left_position.1,
-                    scale: params.text_scale,
-                    bounds: params.bounds,
-                    default_color: params.default_color,
-                    custom_glyphs: &[],
-                });
+    /// Finds overlapping colliders through the spatial grid, pushes dynamic bodies apart and
+    /// reflects their velocities. Every pair is resolved once per update
+    fn resolve_collisions(&mut self, enabled_entities: &[EntityId]) {
+        struct Body {
+            entity_id: EntityId,
+            collider_id: ComponentId,
+            shape: ColliderShape,
+            restitution: f32,
+            position: na::Vector3<f32>,
+            velocity: na::Vector3<f32>,
+            inverse_mass: f32,
+        }
 
-            text_renderer
-                .prepare(
-                    &device,
-                    &queue,
-                    &mut self.text_state.font_system,
-                    self.text_state.atlas.as_mut().unwrap(),
-                    self.text_state.text_viewport.as_ref().unwrap(),
-                    text_areas,
-                    &mut self.text_state.swash_cache,
-                )
-                .unwrap();
+        let mut concept_manager = self.concept_manager.lock().unwrap();
+
+        let mut bodies = enabled_entities
+            .iter()
+            .filter_map(|entity_id| {
+                let components = self.components.get(entity_id)?;
+                let collider = Self::get_component::<ColliderComponent>(components)?;
+                let collider_id = collider.get_id();
+                let position = *concept_manager
+                    .get_concept::<na::Vector3<f32>>(
+                        (*entity_id, TypeId::of::<TransformComponent>(), 0),
+                        "position".to_string(),
+                    )
+                    .ok()?;
+                let physics_id = (*entity_id, TypeId::of::<PhysicsComponent>(), 0);
+                let (velocity, inverse_mass) =
+                    match Self::get_component::<PhysicsComponent>(components) {
+                        Some(_) => {
+                            let mass = *concept_manager
+                                .get_concept::<f32>(physics_id, "mass".to_string())
+                                .ok()?;
+                            let velocity = *concept_manager
+                                .get_concept::<na::Vector3<f32>>(physics_id, "velocity".to_string())
+                                .ok()?;
+                            let inverse_mass = if mass.is_finite() && mass > 0.0 {
+                                1.0 / mass
+                            } else {
+                                0.0
+                            };
+                            (velocity, inverse_mass)
+                        }
+                        None => (na::Vector3::zeros(), 0.0),
+                    };
+                Some(Body {
+                    entity_id: *entity_id,
+                    collider_id,
+                    shape: *concept_manager
+                        .get_concept::<ColliderShape>(collider_id, "shape".to_string())
+                        .ok()?,
+                    restitution: *concept_manager
+                        .get_concept::<f32>(collider_id, "restitution".to_string())
+                        .ok()?,
+                    position,
+                    velocity,
+                    inverse_mass,
+                })
+            })
+            .collect::<Vec<_>>();
+
+        for body in &bodies {
+            if let Ok(collisions) = concept_manager
+                .get_concept_mut::<Vec<CollisionEvent>>(body.collider_id, "collisions".to_string())
+            {
+                collisions.clear();
+            }
         }
+        self.collision_events.clear();
 
-        let ui_manager = ui_manager.lock().unwrap();
-        let mut renderer = ui_manager.imgui_renderer.lock().unwrap();
-        let mut context = ui_manager.imgui_context.lock().unwrap();
+        let fill_grid = |grid: &mut SpatialGrid, bodies: &[Body]| {
+            grid.clear();
+            for body in bodies {
+                let (min, max) = body.shape.bounds(body.position);
+                grid.insert(body.entity_id, min, max);
+            }
+        };
 
-        let view = output
-            .texture
-            .create_view(&wgpu::TextureViewDescriptor::default());
+        fill_grid(&mut self.spatial_grid, &bodies);
+        let body_indices = bodies
+            .iter()
+            .enumerate()
+            .map(|(i, body)| (body.entity_id, i))
+            .collect::<HashMap<_, _>>();
+        // Sorted so pairs are resolved in the same order every update
+        let mut pairs = self
+            .spatial_grid
+            .candidate_pairs()
+            .into_iter()
+            .collect::<Vec<_>>();
+        pairs.sort_unstable();
 
-        {
-            let mut ui_render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                label: Some("UI Render Pass"),
-                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: &view,
-                    resolve_target: None,
-                    ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Load,
-                        store: wgpu::StoreOp::Store,
-                    },
-                })],
-                depth_stencil_attachment: None,
-                timestamp_writes: None,
-                occlusion_query_set: None,
-            });
+        for (a_id, b_id) in pairs {
+            let (i, j) = (body_indices[&a_id], body_indices[&b_id]);
+            let (a_min, a_max) = bodies[i].shape.bounds(bodies[i].position);
+            let (b_min, b_max) = bodies[j].shape.bounds(bodies[j].position);
+            if (0..3).any(|axis| b_min[axis] > a_max[axis] || a_min[axis] > b_max[axis]) {
+                continue;
+            }
+            let Some((normal, penetration)) = ColliderComponent::contact(
+                (bodies[i].shape, bodies[i].position),
+                (bodies[j].shape, bodies[j].position),
+            ) else {
+                continue;
+            };
 
-            if let Some(text_renderer) = self.text_state.text_renderer.as_ref() {
-                text_renderer
-                    .render(
-                        self.text_state.atlas.as_ref().unwrap(),
-                        self.text_state.text_viewport.as_ref().unwrap(),
-                        &mut ui_render_pass,
-                    )
-                    .unwrap();
+            let event = CollisionEvent {
+                entities: (bodies[i].entity_id, bodies[j].entity_id),
+                normal,
+                penetration,
+            };
+            for collider_id in [bodies[i].collider_id, bodies[j].collider_id] {
+                if let Ok(collisions) = concept_manager
+                    .get_concept_mut::<Vec<CollisionEvent>>(collider_id, "collisions".to_string())
+                {
+                    collisions.push(event);
+                }
             }
+            self.collision_events.push(event);
 
-            self.render_ui(
-                device,
-                queue.clone(),
-                &mut renderer,
-                &mut context,
-                &mut ui_render_pass,
-            );
+            let total_inverse_mass = bodies[i].inverse_mass + bodies[j].inverse_mass;
+            // Two static bodies are only reported
+            if total_inverse_mass == 0.0 {
+                continue;
+            }
+
+            let correction = normal * penetration / total_inverse_mass;
+            bodies[i].position -= correction * bodies[i].inverse_mass;
+            bodies[j].position += correction * bodies[j].inverse_mass;
+
+            let approach_speed = (bodies[j].velocity - bodies[i].velocity).dot(&normal);
+            if approach_speed < 0.0 {
+                let restitution = bodies[i].restitution.min(bodies[j].restitution);
+                let impulse = normal * -(1.0 + restitution) * approach_speed / total_inverse_mass;
+                bodies[i].velocity -= impulse * bodies[i].inverse_mass;
+                bodies[j].velocity += impulse * bodies[j].inverse_mass;
+            }
         }
 
-        drop(renderer);
-        drop(context);
+        // Queries see the positions after resolution
+        fill_grid(&mut self.spatial_grid, &bodies);
 
-        queue.submit(std::iter::once(encoder.finish()));
-        output.present();
+        for body in bodies.iter().filter(|body| body.inverse_mass > 0.0) {
+            let physics_id = (body.entity_id, TypeId::of::<PhysicsComponent>(), 0);
+            let _ =
+                concept_manager.modify_concept(physics_id, "velocity".to_string(), body.velocity);
+        }
+        drop(concept_manager);
 
-        self.text_state.atlas.as_mut().unwrap().trim();
+        // Moved through the transform so its matrix stays in sync with the position
+        for body in bodies.iter().filter(|body| body.inverse_mass > 0.0) {
+            let Some(transform) = self
+                .components
+                .get(&body.entity_id)
+                .and_then(|components| Self::get_component::<TransformComponent>(components))
+            else {
+                continue;
+            };
+            let old_position = *self
+                .concept_manager
+                .lock()
+                .unwrap()
+                .get_concept::<na::Vector3<f32>>(transform.get_id(), "position".to_string())
+                .unwrap();
+            transform.apply_translation(self.concept_manager.clone(), body.position - old_position);
+        }
     }
 
-    fn get_component_render_order(components: &[Component]) -> Vec<&Component> {
-        let mut render_orders = components
-            .iter()
-            .enumerate()
-            .map(|(i, comp)| (i, comp.render_order()))
-            .collect::<Vec<_>>();
-        render_orders.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap());
+    /// Nearest collider hit by the ray, see [Scene::raycast_all]
+    pub fn raycast(
+        &self,
+        origin: na::Vector3<f32>,
+        direction: na::Vector3<f32>,
+    ) -> Option<RaycastHit> {
+        self.raycast_hits(origin, direction)
+            .into_iter()
+            .min_by(|a, b| a.distance.total_cmp(&b.distance))
+    }
 
-        render_orders
-            .iter()
-            .map(|(i, _)| &components[*i])
-            .collect::<Vec<_>>()
+    /// Every collider of an enabled entity the ray hits, nearest first. Candidates come from the
+    /// spatial grid built during the last [Scene::fixed_update], so colliders added or moved
+    /// since then may be missed until the next one
+    pub fn raycast_all(
+        &self,
+        origin: na::Vector3<f32>,
+        direction: na::Vector3<f32>,
+    ) -> Vec<RaycastHit> {
+        let mut hits = self.raycast_hits(origin, direction);
+        hits.sort_by(|a, b| a.distance.total_cmp(&b.distance));
+        hits
     }
 
-    fn render_ui<'c: 'd, 'd>(
+    fn raycast_hits(
         &self,
-        device: Arc<Device>,
-        queue: Arc<Queue>,
-        renderer: &'c mut imgui_wgpu::Renderer,
-        context: &'c mut imgui::Context,
-        rpass: &'d mut wgpu::RenderPass<'c>,
+        origin: na::Vector3<f32>,
+        direction: na::Vector3<f32>,
+    ) -> Vec<RaycastHit> {
+        let Some(direction) = direction.try_normalize(f32::EPSILON) else {
+            return Vec::new();
+        };
+        let concept_manager = self.concept_manager.lock().unwrap();
+
+        self.spatial_grid
+            .ray_candidates(origin, direction)
+            .into_iter()
+            .filter_map(|entity_id| {
+                let collider =
+                    Self::get_component::<ColliderComponent>(self.components.get(&entity_id)?)?;
+                let shape = *concept_manager
+                    .get_concept::<ColliderShape>(collider.get_id(), "shape".to_string())
+                    .ok()?;
+                let center = *concept_manager
+                    .get_concept::<na::Vector3<f32>>(
+                        (entity_id, TypeId::of::<TransformComponent>(), 0),
+                        "position".to_string(),
+                    )
+                    .ok()?;
+                let (distance, normal) = shape.ray_intersection(center, origin, direction)?;
+                Some(RaycastHit {
+                    entity: entity_id,
+                    point: origin + direction * distance,
+                    distance,
+                    normal,
+                })
+            })
+            .collect()
+    }
+
+    /// Cell size of the grid used to find collision and raycast candidates, see [SpatialGrid::new].
+    /// The grid is rebuilt with the new size on the next [Scene::fixed_update]
+    pub fn set_spatial_cell_size(&mut self, cell_size: f32) {
+        self.spatial_grid = SpatialGrid::new(cell_size);
+    }
+
+    /// Collisions found during the last [Scene::fixed_update], also stored per entity in each
+    /// [ColliderComponent]'s `collisions` concept
+    pub fn collision_events(&self) -> &[CollisionEvent] {
+        &self.collision_events
+    }
+
+    fn take_component(
+        components: &mut AllComponents,
+        entity_id: EntityId,
+        index: usize,
+    ) -> Option<Component> {
+        let entity_components = components.get_mut(&entity_id)?;
+        if index < entity_components.len() {
+            Some(entity_components.remove(index))
+        } else {
+            None
+        }
+    }
+
+    fn return_component(
+        components: &mut AllComponents,
+        entity_id: EntityId,
+        index: usize,
+        component: Component,
     ) {
-        renderer
-            .render(context.render(), &queue, &device, rpass)
-            .unwrap();
+        let entity_components = components.entry(entity_id).or_default();
+        let index = index.min(entity_components.len());
+        entity_components.insert(index, component);
     }
 
-    pub fn create_camera_bind_group(
+    /// Draws the scene's meshes and skybox into `color_view`, resolving into `resolve_target`
+    /// when multisampled. Text and UI are drawn separately. `camera_viewport` draws from that
+    /// camera into part of the target instead of from the active camera. Only entities on one of
+    /// `layers` are drawn
+    fn encode_scene_pass(
         &mut self,
         device: Arc<Device>,
         queue: Arc<Queue>,
+        depth_texture: &Texture,
         window_size: (u32, u32),
+        engine_details: &EngineDetails,
+        engine_systems: &EngineSystems,
+        color_view: &wgpu::TextureView,
+        resolve_target: Option<&wgpu::TextureView>,
+        clear_color: wgpu::Color,
+        clear_flags: ClearFlags,
+        camera_viewport: Option<(EntityId, Viewport)>,
+        layers: LayerMask,
         ui_manager: Rc<Mutex<UiManager>>,
-    ) -> BindGroup {
-        if let Some(active_camera_id) = self.active_camera_id {
-            let camera_component =
-                Scene::get_component::<CameraComponent>(&self.components[&active_camera_id]);
-            let bind_group = camera_component.unwrap().create_camera_bind_group(device);
-            return bind_group;
+    ) -> wgpu::CommandEncoder {
+        let entities_arc = self.entities.clone();
+        let entities = entities_arc.lock().unwrap();
+
+        let viewport_camera = camera_viewport.and_then(|(camera_id, viewport)| {
+            self.viewport_camera_data(camera_id, viewport, engine_details.window_aspect_ratio)
+        });
+        let (camera_bind_group, skybox_camera) = match viewport_camera {
+            Some(raw_data) => (
+                Self::create_raw_camera_bind_group(&device, &raw_data),
+                Some(raw_data),
+            ),
+            None => (
+                self.create_camera_bind_group(
+                    device.clone(),
+                    queue.clone(),
+                    window_size,
+                    ui_manager.clone(),
+                ),
+                self.active_camera_id
+                    .and_then(|camera_id| self.components.get(&camera_id))
+                    .and_then(|components| Scene::get_component::<CameraComponent>(components))
+                    .map(|camera| camera.raw_data),
+            ),
+        };
+
+        if let (Some(skybox), Some(raw_data)) = (&self.skybox, skybox_camera) {
+            skybox.update_camera(queue.clone(), &raw_data);
         }
 
-        let mut cam = CameraComponent::new_2d(self.concept_manager.clone(), window_size);
-        cam.initialize(
+        // Entities created after initialize only get their pipelines here, built once on the
+        // first frame they're drawn and cached after that
+        let active_entities = Self::active_entities(&entities)
+            .into_iter()
+            .collect::<HashSet<_>>();
+        let missing_pipeline_ids = active_entities
+            .iter()
+            .flat_map(|entity_id| {
+                Self::enabled_material_ids(&self.materials, &self.components, entity_id)
+            })
+            .filter(|id| !self.pipelines.contains_key(id) && !self.failed_pipelines.contains(id))
+            .collect::<HashSet<_>>();
+        for material_id in missing_pipeline_ids {
+            log::info!(
+                "Building pipeline for {} and {} on first use",
+                material_id.vertex_shader_path,
+                material_id.fragment_shader_path
+            );
+            self.ensure_pipeline(&device, &material_id);
+        }
+
+        if self.indirect_draws.is_some() {
+            let draws = Self::mesh_draws(
+                &entities,
+                &self.components,
+                &self.concept_manager.lock().unwrap(),
+            );
+            if let Some(indirect_draws) = &mut self.indirect_draws {
+                indirect_draws.update(&device, &queue, draws);
+            }
+        }
+        let indirect_draws = self.indirect_draws.as_ref();
+
+        let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor {
+            label: Some("Scene Encoder"),
+        });
+
+        let mut default_transform = TransformComponent::default(self.concept_manager.clone());
+        default_transform.initialize(
             device.clone(),
-            queue,
+            queue.clone(),
             &self.components,
             self.concept_manager.clone(),
             None,
             None,
-            ui_manager,
+            ui_manager.clone(),
             &mut self.text_state.text_items,
         );
-        cam.create_camera_bind_group(device)
+
+        {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Scene Render Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: color_view,
+                    resolve_target,
+                    ops: wgpu::Operations {
+                        load: if clear_flags.color {
+                            wgpu::LoadOp::Clear(clear_color)
+                        } else {
+                            wgpu::LoadOp::Load
+                        },
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: &depth_texture.view,
+                    depth_ops: Some(wgpu::Operations {
+                        load: if clear_flags.depth {
+                            wgpu::LoadOp::Clear(self.clear_depth)
+                        } else {
+                            wgpu::LoadOp::Load
+                        },
+                        store: wgpu::StoreOp::Store,
+                    }),
+                    stencil_ops: depth_texture
+                        .texture
+                        .format()
+                        .has_stencil_aspect()
+                        .then_some(wgpu::Operations {
+                            load: if clear_flags.depth {
+                                wgpu::LoadOp::Clear(0)
+                            } else {
+                                wgpu::LoadOp::Load
+                            },
+                            store: wgpu::StoreOp::Store,
+                        }),
+                }),
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+
+            if let Some((_, viewport)) = camera_viewport {
+                render_pass.set_viewport(
+                    viewport.x,
+                    viewport.y,
+                    viewport.width,
+                    viewport.height,
+                    0.0,
+                    1.0,
+                );
+            } else if let Some(mask) = &engine_details.render_mask {
+                render_pass.set_viewport(mask.x, mask.y, mask.width, mask.height, 0.0, 1.0);
+            }
+
+            render_pass.set_bind_group(1, &camera_bind_group, &[]);
+
+            // Stencil tests only see what materials drawn before them wrote
+            let mut pipelines = self.pipelines.iter().collect::<Vec<_>>();
+            pipelines.sort_by_key(
+                |(pipeline_id, _)| match pipeline_id.pipeline_config.stencil {
+                    None => 0,
+                    Some(stencil) if stencil.writes() => 1,
+                    Some(_) => 2,
+                },
+            );
+            for (pipeline_id, pipeline) in pipelines {
+                let pipeline = self
+                    .wireframe_pipelines
+                    .as_ref()
+                    .and_then(|wireframe_pipelines| wireframe_pipelines.get(pipeline_id))
+                    .unwrap_or(pipeline);
+                render_pass.set_pipeline(pipeline.pipeline());
+                if let Some(stencil) = pipeline_id.pipeline_config.stencil {
+                    render_pass.set_stencil_reference(stencil.reference);
+                }
+
+                for entity in entities.iter() {
+                    if active_entities.contains(entity.id()) && entity.layers().intersects(layers) {
+                        let mesh = self.components.get(entity.id()).and_then(|components| {
+                            Self::get_component::<MeshComponent>(components)
+                        });
+                        let submesh_mesh = mesh.filter(|mesh| !mesh.material_indices().is_empty());
+
+                        let draws =
+                            Self::entity_draws(&self.materials, &self.components, entity.id());
+                        for (material, submesh_index) in draws {
+                            if material.id() != pipeline_id {
+                                continue;
+                            }
+                            Self::set_material_bind_groups(&mut render_pass, material);
+
+                            default_transform.render(
+                                device.clone(),
+                                queue.clone(),
+                                &mut render_pass,
+                                &self.components,
+                                self.concept_manager.clone(),
+                                engine_details,
+                                engine_systems,
+                            );
+
+                            // render_pass.set_vertex_buffer(1, default_transform_buffer.slice(..));
+                            let components_opt = self.components.get(entity.id());
+                            if let Some(components) = components_opt {
+                                let ordered_components =
+                                    Self::get_component_render_order(components);
+                                for component in ordered_components.iter() {
+                                    // Submeshes and indirect draws are drawn one at a time below
+                                    if (submesh_index.is_some() || indirect_draws.is_some())
+                                        && component.as_any().is::<MeshComponent>()
+                                    {
+                                        continue;
+                                    }
+                                    component.render(
+                                        device.clone(),
+                                        queue.clone(),
+                                        &mut render_pass,
+                                        &self.components,
+                                        self.concept_manager.clone(),
+                                        engine_details,
+                                        engine_systems,
+                                    );
+                                }
+                            }
+
+                            if let (Some(indirect_draws), Some(mesh)) = (indirect_draws, mesh) {
+                                let submesh_indices = match submesh_index {
+                                    Some(submesh_index) => submesh_index..submesh_index + 1,
+                                    None => 0..mesh.mesh_count(),
+                                };
+                                for submesh_index in submesh_indices {
+                                    if let (Some(buffer), Some(offset)) = (
+                                        indirect_draws.buffer(),
+                                        indirect_draws.offset(*entity.id(), submesh_index),
+                                    ) {
+                                        mesh.render_submesh_indirect(
+                                            &mut render_pass,
+                                            submesh_index,
+                                            buffer,
+                                            offset,
+                                        );
+                                    }
+                                }
+                            } else if let (Some(mesh), Some(submesh_index)) =
+                                (submesh_mesh, submesh_index)
+                            {
+                                mesh.render_submesh(
+                                    &mut render_pass,
+                                    &self.concept_manager.lock().unwrap(),
+                                    submesh_index,
+                                );
+                            }
+                        }
+                    }
+                }
+            }
+
+            if let Some(skybox) = &self.skybox {
+                skybox.render(&mut render_pass);
+            }
+
+            self.debug_draw.render(&mut render_pass, &camera_bind_group);
+        }
+
+        encoder
+    }
+
+    /// Draws the scene, text and UI to `output` and submits them. Presenting is left to the
+    /// caller, so the frame can still be captured with [crate::renderer::Renderer::capture_frame].
+    /// `clear_color` is the engine's, [Scene::set_clear_color] replaces it. Only entities on one
+    /// of `layers` are drawn, see [Scene::set_entity_layers]
+    pub fn render(
+        &mut self,
+        device: Arc<Device>,
+        queue: Arc<Queue>,
+        depth_texture: Arc<Texture>,
+        window_size: (u32, u32),
+        engine_details: &EngineDetails,
+        engine_systems: &EngineSystems,
+        smaa_frame: smaa::SmaaFrame,
+        msaa_view: Option<&wgpu::TextureView>,
+        post_process: &PostProcessStack,
+        output: &wgpu::SurfaceTexture,
+        clear_color: wgpu::Color,
+        clear_flags: ClearFlags,
+        layers: LayerMask,
+        ui_manager: Rc<Mutex<UiManager>>,
+    ) {
+        self.render_with_cameras(
+            device,
+            queue,
+            depth_texture,
+            window_size,
+            engine_details,
+            engine_systems,
+            smaa_frame,
+            msaa_view,
+            post_process,
+            output,
+            clear_color,
+            clear_flags,
+            &[],
+            layers,
+            ui_manager,
+        );
+    }
+
+    /// Same as [Scene::render], but draws the scene once for every camera into its viewport,
+    /// for split screen and minimaps. The color target is only cleared before the first camera,
+    /// the depth buffer before each of them. Each camera's projection is stretched from the
+    /// window's aspect ratio to its viewport's. Draws from the active camera when `cameras` is
+    /// empty
+    pub fn render_with_cameras(
+        &mut self,
+        device: Arc<Device>,
+        queue: Arc<Queue>,
+        depth_texture: Arc<Texture>,
+        window_size: (u32, u32),
+        engine_details: &EngineDetails,
+        engine_systems: &EngineSystems,
+        smaa_frame: smaa::SmaaFrame,
+        msaa_view: Option<&wgpu::TextureView>,
+        post_process: &PostProcessStack,
+        output: &wgpu::SurfaceTexture,
+        clear_color: wgpu::Color,
+        clear_flags: ClearFlags,
+        cameras: &[(EntityId, Viewport)],
+        layers: LayerMask,
+        ui_manager: Rc<Mutex<UiManager>>,
+    ) {
+        if !self.render_scene(
+            device.clone(),
+            queue.clone(),
+            depth_texture,
+            window_size,
+            engine_details,
+            engine_systems,
+            smaa_frame,
+            msaa_view,
+            output.texture.size(),
+            clear_color,
+            clear_flags,
+            cameras,
+            layers,
+            ui_manager.clone(),
+        ) {
+            return;
+        }
+
+        let view = output
+            .texture
+            .create_view(&wgpu::TextureViewDescriptor::default());
+        let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor {
+            label: Some("Post Process Encoder"),
+        });
+        post_process.apply(&mut encoder, &view);
+        self.render_overlay(
+            device,
+            queue.clone(),
+            &mut encoder,
+            &view,
+            window_size,
+            ui_manager,
+        );
+        queue.submit(std::iter::once(encoder.finish()));
+    }
+
+    /// Draws the scene's meshes and skybox from `cameras` like [Scene::render_with_cameras] and
+    /// submits them, leaving out post-processing, text and UI. Several scenes can be drawn this
+    /// way before post-processing runs once over all of them, followed by
+    /// [Scene::render_overlay]. Returns `false` if the frame was skipped because the depth
+    /// texture doesn't match `target_size`
+    pub fn render_scene(
+        &mut self,
+        device: Arc<Device>,
+        queue: Arc<Queue>,
+        depth_texture: Arc<Texture>,
+        window_size: (u32, u32),
+        engine_details: &EngineDetails,
+        engine_systems: &EngineSystems,
+        smaa_frame: smaa::SmaaFrame,
+        msaa_view: Option<&wgpu::TextureView>,
+        target_size: wgpu::Extent3d,
+        clear_color: wgpu::Color,
+        clear_flags: ClearFlags,
+        cameras: &[(EntityId, Viewport)],
+        layers: LayerMask,
+        ui_manager: Rc<Mutex<UiManager>>,
+    ) -> bool {
+        let depth_size = depth_texture.texture.size();
+        if (depth_size.width, depth_size.height) != (target_size.width, target_size.height) {
+            log::error!(
+                "Depth texture is {}x{} but the render target is {}x{}, skipping the frame",
+                depth_size.width,
+                depth_size.height,
+                target_size.width,
+                target_size.height
+            );
+            self.discard_ui_frame(&ui_manager);
+            return false;
+        }
+
+        if let Some((color_format, sample_count, depth_format)) = self.pipeline_target {
+            self.debug_draw
+                .prepare(&device, color_format, sample_count, depth_format);
+        }
+
+        let clear_color = self.clear_color.unwrap_or(clear_color);
+        let clear_flags = ClearFlags {
+            color: clear_flags.color && !self.skip_color_clear,
+            ..clear_flags
+        };
+
+        let camera_viewports = if cameras.is_empty() {
+            vec![None]
+        } else {
+            cameras.iter().copied().map(Some).collect()
+        };
+
+        let mut scene_encoder: Option<wgpu::CommandEncoder> = None;
+        for (i, camera_viewport) in camera_viewports.into_iter().enumerate() {
+            // The skybox has a single camera uniform, so every view is submitted before the next
+            // one overwrites it
+            if let Some(encoder) = scene_encoder.take() {
+                queue.submit(std::iter::once(encoder.finish()));
+            }
+            scene_encoder = Some(self.encode_scene_pass(
+                device.clone(),
+                queue.clone(),
+                &depth_texture,
+                window_size,
+                engine_details,
+                engine_systems,
+                msaa_view.unwrap_or(&smaa_frame),
+                msaa_view.map(|_| &*smaa_frame),
+                clear_color,
+                ClearFlags {
+                    color: clear_flags.color && i == 0,
+                    ..clear_flags
+                },
+                camera_viewport,
+                layers,
+                ui_manager.clone(),
+            ));
+        }
+        // Submitted before the antialiasing pass reads what it drew
+        queue.submit(scene_encoder.map(|encoder| encoder.finish()));
+        smaa_frame.resolve();
+        true
+    }
+
+    /// Draws the scene's text, and the UI if this scene started the UI frame, over `view`. Call
+    /// after post-processing so neither is affected by it
+    pub fn render_overlay(
+        &mut self,
+        device: Arc<Device>,
+        queue: Arc<Queue>,
+        encoder: &mut wgpu::CommandEncoder,
+        view: &wgpu::TextureView,
+        window_size: (u32, u32),
+        ui_manager: Rc<Mutex<UiManager>>,
+    ) {
+        let view_projection = self
+            .camera_matrices()
+            .map(|(view, projection)| projection * view);
+
+        if let Some(text_renderer) = &mut self.text_state.text_renderer {
+            self.text_state.text_viewport.as_mut().unwrap().update(
+                &queue,
+                glyphon::Resolution {
+                    width: window_size.0,
+                    height: window_size.1,
+                },
+            );
+
+            let buffers = self
+                .text_state
+                .text_items
+                .iter()
+                .map(|params| {
+                    let mut buffer =
+                        glyphon::Buffer::new(&mut self.text_state.font_system, params.metrics);
+
+                    buffer.set_size(
+                        &mut self.text_state.font_system,
+                        Some(window_size.0 as f32),
+                        Some(window_size.1 as f32),
+                    );
+
+                    buffer.set_text(
+                        &mut self.text_state.font_system,
+                        &params.text,
+                        glyphon::Attrs::new()
+                            .color(params.color)
+                            .family(params.family)
+                            .weight(params.weight),
+                        if params.fancy_render {
+                            glyphon::Shaping::Advanced
+                        } else {
+                            glyphon::Shaping::Basic
+                        },
+                    );
+
+                    buffer.shape_until_scroll(&mut self.text_state.font_system, false);
+                    buffer
+                })
+                .collect::<Vec<_>>();
+
+            let text_areas =
+                self.text_state
+                    .text_items
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(i, params)| {
+                        let (anchor_x, anchor_y) = match params.anchor {
+                            TextAnchor::Screen => (0.0, 0.0),
+                            TextAnchor::World(position) => {
+                                Self::project_to_screen(view_projection?, position, window_size)?
+                            }
+                        };
+                        let bounds = glyphon::TextBounds {
+                            left: params.bounds.left.saturating_add(anchor_x as i32),
+                            top: params.bounds.top.saturating_add(anchor_y as i32),
+                            right: params.bounds.right.saturating_add(anchor_x as i32),
+                            bottom: params.bounds.bottom.saturating_add(anchor_y as i32),
+                        };
+                        Some(glyphon::TextArea {
+                            buffer: &buffers[i],
+                            left: anchor_x + params.top_left_position.0,
+                            top: anchor_y + params.top_left_position.1,
+                            scale: params.text_scale,
+                            bounds,
+                            default_color: params.default_color,
+                            custom_glyphs: &[],
+                        })
+                    });
+
+            text_renderer
+                .prepare(
+                    &device,
+                    &queue,
+                    &mut self.text_state.font_system,
+                    self.text_state.atlas.as_mut().unwrap(),
+                    self.text_state.text_viewport.as_ref().unwrap(),
+                    text_areas,
+                    &mut self.text_state.swash_cache,
+                )
+                .unwrap();
+        }
+
+        let ui_manager = ui_manager.lock().unwrap();
+        let mut renderer = ui_manager.imgui_renderer.lock().unwrap();
+        let mut context = ui_manager.imgui_context.lock().unwrap();
+
+        {
+            let mut ui_render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("UI Render Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+
+            if let Some(text_renderer) = self.text_state.text_renderer.as_ref() {
+                text_renderer
+                    .render(
+                        self.text_state.atlas.as_ref().unwrap(),
+                        self.text_state.text_viewport.as_ref().unwrap(),
+                        &mut ui_render_pass,
+                    )
+                    .unwrap();
+            }
+
+            // Only the scene that started the UI frame draws it, not the ones below it in a stack
+            if self.ui_frame_pending {
+                self.render_ui(
+                    device,
+                    queue.clone(),
+                    &mut renderer,
+                    &mut context,
+                    &mut ui_render_pass,
+                );
+            }
+        }
+        self.ui_frame_pending = false;
+
+        drop(renderer);
+        drop(context);
+
+        self.text_state.atlas.as_mut().unwrap().trim();
+    }
+
+    /// Renders the scene into an offscreen texture of `size` and reads it back, for screenshots
+    /// and image comparisons without a window (see [crate::renderer::Renderer::new_headless]).
+    /// Text and UI are left out. The image uses the color format and sample count the scene was
+    /// initialized with, and this stalls until the GPU has finished
+    pub fn render_to_image(
+        &mut self,
+        device: Arc<Device>,
+        queue: Arc<Queue>,
+        size: (u32, u32),
+        engine_details: &EngineDetails,
+        engine_systems: &EngineSystems,
+        clear_color: wgpu::Color,
+        ui_manager: Rc<Mutex<UiManager>>,
+    ) -> Result<image::RgbaImage, SceneError> {
+        let (color_format, sample_count, depth_format) =
+            self.pipeline_target.ok_or(SceneError::NotInitialized)?;
+        if !matches!(
+            color_format,
+            TextureFormat::Rgba8Unorm
+                | TextureFormat::Rgba8UnormSrgb
+                | TextureFormat::Bgra8Unorm
+                | TextureFormat::Bgra8UnormSrgb
+        ) {
+            return Err(SceneError::UnsupportedImageFormat(color_format));
+        }
+
+        let extent = wgpu::Extent3d {
+            width: size.0,
+            height: size.1,
+            depth_or_array_layers: 1,
+        };
+        let create_color_texture = |label, sample_count, usage| {
+            device.create_texture(&wgpu::TextureDescriptor {
+                label: Some(label),
+                size: extent,
+                mip_level_count: 1,
+                sample_count,
+                dimension: wgpu::TextureDimension::D2,
+                format: color_format,
+                usage,
+                view_formats: &[],
+            })
+        };
+        let color_texture = create_color_texture(
+            "Offscreen Color Texture",
+            1,
+            wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+        );
+        let color_view = color_texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let msaa_view = (sample_count > 1).then(|| {
+            create_color_texture(
+                "Offscreen Multisampled Color Texture",
+                sample_count,
+                wgpu::TextureUsages::RENDER_ATTACHMENT,
+            )
+            .create_view(&wgpu::TextureViewDescriptor::default())
+        });
+        let depth_texture = Texture::create_depth_texture_with_format(
+            &device,
+            size,
+            sample_count,
+            depth_format,
+            "Offscreen Depth Texture",
+        );
+
+        self.debug_draw
+            .prepare(&device, color_format, sample_count, depth_format);
+        let encoder = self.encode_scene_pass(
+            device.clone(),
+            queue.clone(),
+            &depth_texture,
+            size,
+            engine_details,
+            engine_systems,
+            msaa_view.as_ref().unwrap_or(&color_view),
+            msaa_view.as_ref().map(|_| &color_view),
+            clear_color,
+            ClearFlags::default(),
+            None,
+            LayerMask::ALL,
+            ui_manager,
+        );
+
+        Texture::read_to_image(&device, &queue, encoder, &color_texture)
+            .ok_or(SceneError::ReadbackFailed)
+    }
+
+    /// Renders the scene from `camera_id` into a new texture of `size`, which materials can sample
+    /// through [Material::update_textures] for monitors, mirrors and portals. This is a separate
+    /// pass costing a full draw of the scene, so prefer small textures and skip frames when
+    /// possible. Text and UI are left out and the texture has the scene's color format. Only
+    /// entities on one of `layers` are drawn, so a mirror can leave itself out
+    pub fn render_to_texture(
+        &mut self,
+        device: Arc<Device>,
+        queue: Arc<Queue>,
+        camera_id: EntityId,
+        size: (u32, u32),
+        engine_details: &EngineDetails,
+        engine_systems: &EngineSystems,
+        clear_color: wgpu::Color,
+        layers: LayerMask,
+        ui_manager: Rc<Mutex<UiManager>>,
+    ) -> Result<Rc<Texture>, SceneError> {
+        let (color_format, sample_count, depth_format) =
+            self.pipeline_target.ok_or(SceneError::NotInitialized)?;
+        self.components
+            .get(&camera_id)
+            .and_then(|components| Scene::get_component::<CameraComponent>(components))
+            .ok_or(SceneError::NoCamera(camera_id))?;
+
+        let create_color_texture = |label, sample_count, usage| {
+            device.create_texture(&wgpu::TextureDescriptor {
+                label: Some(label),
+                size: wgpu::Extent3d {
+                    width: size.0,
+                    height: size.1,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count,
+                dimension: wgpu::TextureDimension::D2,
+                format: color_format,
+                usage,
+                view_formats: &[],
+            })
+        };
+        let color_texture = create_color_texture(
+            "Camera Color Texture",
+            1,
+            wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+        );
+        let color_view = color_texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let msaa_view = (sample_count > 1).then(|| {
+            create_color_texture(
+                "Camera Multisampled Color Texture",
+                sample_count,
+                wgpu::TextureUsages::RENDER_ATTACHMENT,
+            )
+            .create_view(&wgpu::TextureViewDescriptor::default())
+        });
+        let depth_texture = Texture::create_depth_texture_with_format(
+            &device,
+            size,
+            sample_count,
+            depth_format,
+            "Camera Depth Texture",
+        );
+
+        let encoder = self.encode_scene_pass(
+            device.clone(),
+            queue.clone(),
+            &depth_texture,
+            size,
+            engine_details,
+            engine_systems,
+            msaa_view.as_ref().unwrap_or(&color_view),
+            msaa_view.as_ref().map(|_| &color_view),
+            clear_color,
+            ClearFlags::default(),
+            Some((camera_id, Viewport::from_window_size(size))),
+            layers,
+            ui_manager,
+        );
+        queue.submit(std::iter::once(encoder.finish()));
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Camera Texture Sampler"),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        Ok(Rc::new(Texture {
+            texture: color_texture,
+            view: color_view,
+            sampler,
+        }))
+    }
+
+    /// Renders the ids of all enabled entities with a material into an offscreen target and
+    /// reads back the one under `screen_pos`. This stalls until the GPU has finished the pass.
+    /// Only entities on one of `layers` can be picked, usually the layers the scene is drawn with
+    pub fn pick_entity(
+        &mut self,
+        device: Arc<Device>,
+        queue: Arc<Queue>,
+        window_size: (u32, u32),
+        engine_details: &EngineDetails,
+        engine_systems: &EngineSystems,
+        layers: LayerMask,
+        ui_manager: Rc<Mutex<UiManager>>,
+        screen_pos: (u32, u32),
+    ) -> Option<EntityId> {
+        if screen_pos.0 >= window_size.0 || screen_pos.1 >= window_size.1 {
+            return None;
+        }
+
+        let entities_arc = self.entities.clone();
+        let entities = entities_arc.lock().unwrap();
+
+        let camera_bind_group = self.create_camera_bind_group(
+            device.clone(),
+            queue.clone(),
+            window_size,
+            ui_manager.clone(),
+        );
+
+        let mut default_transform = TransformComponent::default(self.concept_manager.clone());
+        default_transform.initialize(
+            device.clone(),
+            queue.clone(),
+            &self.components,
+            self.concept_manager.clone(),
+            None,
+            None,
+            ui_manager,
+            &mut self.text_state.text_items,
+        );
+
+        let mut picking_pipeline = match self.picking_pipeline.take() {
+            Some(mut pipeline) => {
+                pipeline.resize(device.clone(), window_size);
+                pipeline
+            }
+            None => PickingPipeline::new(device.clone(), window_size),
+        };
+
+        let entities_on_layers = entities
+            .iter()
+            .filter(|entity| entity.layers().intersects(layers))
+            .map(|entity| *entity.id())
+            .collect::<HashSet<_>>();
+        let pickable_entities = Self::active_entities(&entities)
+            .into_iter()
+            .filter(|entity_id| {
+                self.materials.contains_key(entity_id) && entities_on_layers.contains(entity_id)
+            })
+            .collect::<Vec<_>>();
+
+        let (id_bind_group, id_stride) = picking_pipeline.create_id_bind_group(
+            device.clone(),
+            queue.clone(),
+            &pickable_entities,
+        );
+
+        let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor {
+            label: Some("Picking Encoder"),
+        });
+
+        {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Picking Render Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: picking_pipeline.target_view(),
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: &picking_pipeline.depth_texture().view,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(1.0),
+                        store: wgpu::StoreOp::Store,
+                    }),
+                    stencil_ops: None,
+                }),
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+
+            render_pass.set_bind_group(0, &camera_bind_group, &[]);
+
+            for (i, entity_id) in pickable_entities.iter().enumerate() {
+                let vertex_type = self
+                    .components
+                    .get(entity_id)
+                    .and_then(|components| Self::get_component::<MeshComponent>(components))
+                    .map(|mesh| mesh.vertex_type())
+                    .unwrap_or_default();
+                let Some(pipeline) = picking_pipeline.pipeline(vertex_type) else {
+                    continue;
+                };
+                render_pass.set_pipeline(pipeline);
+                render_pass.set_bind_group(1, &id_bind_group, &[i as u32 * id_stride]);
+
+                default_transform.render(
+                    device.clone(),
+                    queue.clone(),
+                    &mut render_pass,
+                    &self.components,
+                    self.concept_manager.clone(),
+                    engine_details,
+                    engine_systems,
+                );
+
+                if let Some(components) = self.components.get(entity_id) {
+                    for component in Self::get_component_render_order(components) {
+                        component.render(
+                            device.clone(),
+                            queue.clone(),
+                            &mut render_pass,
+                            &self.components,
+                            self.concept_manager.clone(),
+                            engine_details,
+                            engine_systems,
+                        );
+                    }
+                }
+            }
+        }
+
+        queue.submit(std::iter::once(encoder.finish()));
+
+        let picked_value = picking_pipeline.read_pixel(device, queue, screen_pos);
+        self.picking_pipeline = Some(picking_pipeline);
+
+        PickingPipeline::decode_id(picked_value)
+    }
+
+    fn set_material_bind_groups<'a>(
+        render_pass: &mut wgpu::RenderPass<'a>,
+        material: &'a Material,
+    ) {
+        render_pass.set_bind_group(0, material.texture_bind_group(), &[]);
+        if let Some(uniform_buffer_bind_group) = material.uniform_buffer_bind_group() {
+            render_pass.set_bind_group(2, &uniform_buffer_bind_group.0, &[]);
+        } else if let Some(pooled_uniform) = material.pooled_uniform() {
+            render_pass.set_bind_group(
+                2,
+                pooled_uniform.pool().bind_group(),
+                &[pooled_uniform.offset() as u32],
+            );
+        }
+    }
+
+    /// Every submesh of every mesh in the scene with its index count, in entity order
+    fn mesh_draws(
+        entities: &[Entity],
+        components: &AllComponents,
+        concept_manager: &ConceptManager,
+    ) -> Vec<((EntityId, usize), u32)> {
+        entities
+            .iter()
+            .filter_map(|entity| {
+                let mesh = Self::get_component::<MeshComponent>(components.get(entity.id())?)?;
+                let indices = concept_manager
+                    .get_concept::<Vec<Vec<u32>>>(mesh.id, "indices".to_string())
+                    .ok()?;
+                Some(
+                    indices
+                        .iter()
+                        .enumerate()
+                        .map(|(submesh_index, indices)| {
+                            ((*entity.id(), submesh_index), indices.len() as u32)
+                        })
+                        .collect::<Vec<_>>(),
+                )
+            })
+            .flatten()
+            .collect()
+    }
+
+    fn mesh_vertex_type(components: &AllComponents, entity_id: &EntityId) -> Option<VertexType> {
+        components
+            .get(entity_id)
+            .and_then(|components| Self::get_component::<MeshComponent>(components))
+            .map(|mesh| mesh.vertex_type())
+    }
+
+    /// Errors if a material drawing the entity's mesh is built for another vertex type than the
+    /// mesh's buffers hold, the render pass skips those materials. Entities without meshes draw
+    /// their own buffers and aren't checked
+    fn check_vertex_types(
+        materials: &HashMap<EntityId, (Vec<Material>, usize)>,
+        components: &AllComponents,
+        entity_id: &EntityId,
+    ) -> Result<(), SceneError> {
+        let Some(mesh_vertex_type) = Self::mesh_vertex_type(components, entity_id) else {
+            return Ok(());
+        };
+        for material_id in Self::entity_material_ids(materials, components, entity_id) {
+            if material_id.vertex_type != mesh_vertex_type {
+                return Err(SceneError::VertexTypeMismatch {
+                    entity: *entity_id,
+                    mesh: mesh_vertex_type,
+                    material: material_id.vertex_type,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks every entity's meshes against the vertex types of the materials drawing them,
+    /// which [Scene::initialize] also logs
+    pub fn check_vertex_layouts(&self) -> Result<(), SceneError> {
+        for entity_id in self.materials.keys() {
+            Self::check_vertex_types(&self.materials, &self.components, entity_id)?;
+        }
+        Ok(())
+    }
+
+    /// Ids of every material an entity draws with: its active material, or the material of each
+    /// submesh for meshes loaded with [MeshComponent::from_obj_with_materials]
+    fn entity_material_ids(
+        materials: &HashMap<EntityId, (Vec<Material>, usize)>,
+        components: &AllComponents,
+        entity_id: &EntityId,
+    ) -> Vec<MaterialId> {
+        Self::entity_materials(materials, components, entity_id)
+            .into_iter()
+            .map(|material| material.id().clone())
+            .collect()
+    }
+
+    /// Same as [Scene::entity_material_ids] without disabled materials, which aren't drawn and
+    /// don't need pipelines until they're enabled
+    fn enabled_material_ids(
+        materials: &HashMap<EntityId, (Vec<Material>, usize)>,
+        components: &AllComponents,
+        entity_id: &EntityId,
+    ) -> Vec<MaterialId> {
+        Self::entity_materials(materials, components, entity_id)
+            .into_iter()
+            .filter(|material| material.enabled())
+            .map(|material| material.id().clone())
+            .collect()
+    }
+
+    /// What the render pass draws for an entity: each enabled material with the submesh it draws,
+    /// or `None` when the active material draws the whole mesh. Materials built for another
+    /// vertex type than the mesh's would read its buffers with the wrong layout and are left out
+    fn entity_draws<'a>(
+        materials: &'a HashMap<EntityId, (Vec<Material>, usize)>,
+        components: &AllComponents,
+        entity_id: &EntityId,
+    ) -> Vec<(&'a Material, Option<usize>)> {
+        let Some((entity_materials, active_material_index)) = materials.get(entity_id) else {
+            return Vec::new();
+        };
+        let mesh = components
+            .get(entity_id)
+            .and_then(|components| Self::get_component::<MeshComponent>(components));
+
+        let draws = match mesh.filter(|mesh| !mesh.material_indices().is_empty()) {
+            Some(mesh) => mesh
+                .material_indices()
+                .iter()
+                .enumerate()
+                .map(|(submesh_index, material_index)| {
+                    (&entity_materials[*material_index], Some(submesh_index))
+                })
+                .collect::<Vec<_>>(),
+            None => vec![(&entity_materials[*active_material_index], None)],
+        };
+        draws
+            .into_iter()
+            .filter(|(material, _)| {
+                material.enabled()
+                    && !mesh.is_some_and(|mesh| mesh.vertex_type() != material.id().vertex_type)
+            })
+            .collect()
+    }
+
+    fn entity_materials<'a>(
+        materials: &'a HashMap<EntityId, (Vec<Material>, usize)>,
+        components: &AllComponents,
+        entity_id: &EntityId,
+    ) -> Vec<&'a Material> {
+        let Some((entity_materials, active_material_index)) = materials.get(entity_id) else {
+            return Vec::new();
+        };
+
+        let submesh_material_indices = components
+            .get(entity_id)
+            .and_then(|components| Self::get_component::<MeshComponent>(components))
+            .map(|mesh| mesh.material_indices())
+            .filter(|indices| !indices.is_empty());
+
+        match submesh_material_indices {
+            Some(indices) => indices
+                .iter()
+                .map(|index| &entity_materials[*index])
+                .collect(),
+            None => vec![&entity_materials[*active_material_index]],
+        }
+    }
+
+    fn get_component_render_order(components: &[Component]) -> Vec<&Component> {
+        let mut render_orders = components
+            .iter()
+            .enumerate()
+            .map(|(i, comp)| (i, comp.render_order()))
+            .collect::<Vec<_>>();
+        render_orders.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap());
+
+        render_orders
+            .iter()
+            .map(|(i, _)| &components[*i])
+            .collect::<Vec<_>>()
+    }
+
+    /// Ends the UI frame started by [Scene::ui_draw] without drawing it, for frames that are
+    /// skipped. imgui expects every frame to be ended before the next one starts
+    pub(crate) fn discard_ui_frame(&mut self, ui_manager: &Rc<Mutex<UiManager>>) {
+        if !self.ui_frame_pending {
+            return;
+        }
+        let ui_manager = ui_manager.lock().unwrap();
+        ui_manager.imgui_context.lock().unwrap().render();
+        self.ui_frame_pending = false;
+    }
+
+    fn render_ui<'c: 'd, 'd>(
+        &self,
+        device: Arc<Device>,
+        queue: Arc<Queue>,
+        renderer: &'c mut imgui_wgpu::Renderer,
+        context: &'c mut imgui::Context,
+        rpass: &'d mut wgpu::RenderPass<'c>,
+    ) {
+        renderer
+            .render(context.render(), &queue, &device, rpass)
+            .unwrap();
+    }
+
+    /// Camera data drawing from `camera_id` into `viewport`, `None` if the entity has no camera
+    fn viewport_camera_data(
+        &self,
+        camera_id: EntityId,
+        viewport: Viewport,
+        window_aspect_ratio: f32,
+    ) -> Option<RawCameraData> {
+        let camera = Scene::get_component::<CameraComponent>(self.components.get(&camera_id)?)?;
+        let concept_manager = self.concept_manager.lock().unwrap();
+
+        // Projections are built for the window's aspect ratio
+        let viewport_aspect_ratio = viewport.width / viewport.height;
+        let aspect_correction = na::Matrix4::new_nonuniform_scaling(&na::Vector3::new(
+            window_aspect_ratio / viewport_aspect_ratio,
+            1.0,
+            1.0,
+        ));
+
+        let cam_mat = aspect_correction
+            * camera.projection_matrix(&concept_manager)
+            * camera.view_matrix(&self.components, &concept_manager);
+        Some(RawCameraData {
+            cam_pos: camera.raw_data.cam_pos,
+            cam_mat: cam_mat.into(),
+        })
+    }
+
+    fn create_raw_camera_bind_group(device: &Arc<Device>, raw_data: &RawCameraData) -> BindGroup {
+        let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Viewport Camera Buffer"),
+            contents: bytemuck::cast_slice(&[*raw_data]),
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Viewport Camera Bind Group"),
+            layout: &CameraComponent::camera_bind_group_layout(device.clone()),
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: buffer.as_entire_binding(),
+            }],
+        })
+    }
+
+    pub fn create_camera_bind_group(
+        &mut self,
+        device: Arc<Device>,
+        queue: Arc<Queue>,
+        window_size: (u32, u32),
+        ui_manager: Rc<Mutex<UiManager>>,
+    ) -> BindGroup {
+        if let Some(active_camera_id) = self.active_camera_id {
+            let camera_component =
+                Scene::get_component::<CameraComponent>(&self.components[&active_camera_id]);
+            let bind_group = camera_component.unwrap().create_camera_bind_group(device);
+            return bind_group;
+        }
+
+        let mut cam = CameraComponent::new_2d(self.concept_manager.clone(), window_size);
+        cam.initialize(
+            device.clone(),
+            queue,
+            &self.components,
+            self.concept_manager.clone(),
+            None,
+            None,
+            ui_manager,
+            &mut self.text_state.text_items,
+        );
+        cam.create_camera_bind_group(device)
     }
 
     pub fn get_component<T: ComponentSystem + Any>(components: &[Component]) -> Option<&T> {
@@ -618,83 +2619,1905 @@ impl Scene {
                 return Some(comp);
             }
         }
-        None
-    }
+        None
+    }
+
+    /// Type ids of the entity's components, in the order they were added
+    pub fn component_types(&self, entity_id: EntityId) -> Vec<TypeId> {
+        self.components
+            .get(&entity_id)
+            .map(|components| {
+                components
+                    .iter()
+                    .map(|component| component.get_id().1)
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Removes the `index`th component of type `T` from the entity along with its concepts,
+    /// calling [ComponentSystem::on_remove] first. Later components of the same type move down
+    /// an index. Returns `false` if there's no such component
+    pub fn remove_component<T: ComponentSystem + Any>(
+        &mut self,
+        entity_id: EntityId,
+        index: usize,
+    ) -> bool {
+        let Some(components) = self.components.get_mut(&entity_id) else {
+            return false;
+        };
+        let Some(position) = components
+            .iter()
+            .enumerate()
+            .filter(|(_, component)| component.as_any().is::<T>())
+            .nth(index)
+            .map(|(position, _)| position)
+        else {
+            return false;
+        };
+
+        let mut component = components.remove(position);
+        let removed_id = component.get_id();
+        component.on_remove(&self.components, self.concept_manager.clone());
+
+        let mut concept_manager = self.concept_manager.lock().unwrap();
+        concept_manager.remove_component_concepts(removed_id);
+        self.disabled_components.remove(&removed_id);
+
+        if let Some(components) = self.components.get_mut(&entity_id) {
+            for component in components.iter_mut() {
+                let old_id = component.get_id();
+                if old_id.1 == removed_id.1 && old_id.2 > removed_id.2 {
+                    component.update_metadata(entity_id, old_id.2 - 1);
+                    concept_manager.modify_key(old_id, component.get_id());
+                }
+            }
+        }
+        true
+    }
+
+    pub fn get_component_mut<T: ComponentSystem + Any>(
+        components: &mut [Component],
+    ) -> Option<&mut T> {
+        for component in components.iter_mut() {
+            if let Some(comp) = component.as_any_mut().downcast_mut::<T>() {
+                return Some(comp);
+            }
+        }
+        None
+    }
+
+    /// First component of type `T` on any entity, in entity id order. Finds collaborators by
+    /// type instead of by entity id.
+    /// A component is taken out of `component_map` while it updates, so it can't find itself or
+    /// other components of its own type that are being updated at the same time
+    pub fn query_single<T: ComponentSystem + Any>(
+        component_map: &AllComponents,
+    ) -> Option<(EntityId, &T)> {
+        Self::query_all::<T>(component_map).into_iter().next()
+    }
+
+    /// Every component of type `T`, sorted by entity id. See [Scene::query_single] for what
+    /// can't be found during an update
+    pub fn query_all<T: ComponentSystem + Any>(
+        component_map: &AllComponents,
+    ) -> Vec<(EntityId, &T)> {
+        let mut found = component_map
+            .iter()
+            .flat_map(|(entity_id, components)| {
+                components
+                    .iter()
+                    .filter_map(|component| component.as_any().downcast_ref::<T>())
+                    .map(|component| (*entity_id, component))
+            })
+            .collect::<Vec<_>>();
+        found.sort_by_key(|(entity_id, _)| *entity_id);
+        found
+    }
+
+    /// Counts the scene's entities, components, materials and pipelines. Computed on every call
+    pub fn stats(&self) -> SceneStats {
+        let entities = self.entities.lock().unwrap();
+
+        let mut component_counts = HashMap::new();
+        for component in self.components.values().flatten() {
+            *component_counts.entry(component.type_name()).or_insert(0) += 1;
+        }
+
+        let active_entities = Self::active_entities(&entities);
+        let concept_manager = self.concept_manager.lock().unwrap();
+        let mut draw_call_count = 0;
+        let mut triangle_count = 0;
+        for entity_id in &active_entities {
+            let Some(mesh) = self
+                .components
+                .get(entity_id)
+                .and_then(|components| Self::get_component::<MeshComponent>(components))
+            else {
+                continue;
+            };
+            let Ok(indices) =
+                concept_manager.get_concept::<Vec<Vec<u32>>>(mesh.id, "indices".to_string())
+            else {
+                continue;
+            };
+            for (material, submesh_index) in
+                Self::entity_draws(&self.materials, &self.components, entity_id)
+            {
+                if !self.pipelines.contains_key(material.id()) {
+                    continue;
+                }
+                let submesh_indices = match submesh_index {
+                    Some(submesh_index) => submesh_index..submesh_index + 1,
+                    None => 0..mesh.mesh_count(),
+                };
+                for submesh_index in submesh_indices {
+                    draw_call_count += 1;
+                    triangle_count += indices[submesh_index].len() / 3;
+                }
+            }
+        }
+
+        SceneStats {
+            entity_count: entities.len(),
+            enabled_entity_count: active_entities.len(),
+            component_counts,
+            material_count: self
+                .materials
+                .values()
+                .map(|(materials, _)| materials.len())
+                .sum(),
+            pipeline_count: self.pipelines.len(),
+            compute_pipeline_count: self.compute_pipelines.len(),
+            draw_call_count,
+            triangle_count,
+        }
+    }
+
+    /// Draws `cubemap` behind the scene's geometry, `color_format`, `sample_count` and
+    /// `depth_format` have to match the renderer's. The skybox needs an active camera to be drawn
+    pub fn set_skybox(
+        &mut self,
+        device: Arc<Device>,
+        color_format: TextureFormat,
+        sample_count: u32,
+        depth_format: TextureFormat,
+        cubemap: Texture,
+    ) {
+        self.skybox = Some(Skybox::new(
+            device,
+            color_format,
+            sample_count,
+            depth_format,
+            cubemap,
+        ));
+    }
+
+    /// Draws every material as wireframe while enabled, regardless of its own polygon mode.
+    /// Wireframe variants of pipelines built later are created along with them
+    pub fn set_wireframe(
+        &mut self,
+        device: Arc<Device>,
+        color_format: TextureFormat,
+        sample_count: u32,
+        depth_format: TextureFormat,
+        enabled: bool,
+    ) {
+        if !enabled {
+            self.wireframe_pipelines = None;
+            return;
+        }
+        let wireframe_pipelines = self
+            .pipelines
+            .keys()
+            .filter_map(|id| {
+                let mut wireframe_id = id.clone();
+                wireframe_id.pipeline_config.polygon_mode = wgpu::PolygonMode::Line;
+                let pipeline = Pipeline::new(
+                    device.clone(),
+                    color_format,
+                    sample_count,
+                    depth_format,
+                    &[wireframe_id.vertex_type.desc(), TransformComponent::desc()],
+                    &wireframe_id,
+                )
+                .inspect_err(|err| log::error!("Failed to build a wireframe pipeline: {err:?}"))
+                .ok()?;
+                Some((id.clone(), pipeline))
+            })
+            .collect();
+        self.wireframe_pipelines = Some(wireframe_pipelines);
+    }
+
+    pub fn is_wireframe(&self) -> bool {
+        self.wireframe_pipelines.is_some()
+    }
+
+    /// Catches panics from component updates instead of letting them unwind through the game
+    /// loop. A component that panics is logged and its updates are skipped from then on.
+    /// Off by default, since state the component was halfway through changing is left as is
+    pub fn set_panic_isolation(&mut self, enabled: bool) {
+        self.panic_isolation = enabled;
+    }
+
+    /// Components whose updates are skipped after panicking, see [Scene::set_panic_isolation]
+    pub fn disabled_components(&self) -> &HashSet<ComponentId> {
+        &self.disabled_components
+    }
+
+    pub fn remove_skybox(&mut self) {
+        self.skybox = None;
+    }
+
+    /// Lines drawn on the next render, see [DebugDraw]
+    pub fn debug_draw(&mut self) -> &mut DebugDraw {
+        &mut self.debug_draw
+    }
+
+    pub fn get_components(&self) -> &AllComponents {
+        &self.components
+    }
+
+    pub fn get_components_mut(&mut self) -> &mut AllComponents {
+        &mut self.components
+    }
+
+    /// Draws meshes with indirect draws from one buffer of arguments, which a compute pass can
+    /// cull by writing to (see [IndirectDraws])
+    pub fn set_indirect_draws(&mut self, enabled: bool) {
+        if enabled != self.indirect_draws.is_some() {
+            self.indirect_draws = enabled.then(IndirectDraws::default);
+        }
+    }
+
+    /// `None` unless turned on with [Scene::set_indirect_draws]
+    pub fn indirect_draws(&self) -> Option<&IndirectDraws> {
+        self.indirect_draws.as_ref()
+    }
+
+    /// Clears the frame with `color` instead of the engine's clear color, from the next frame on
+    pub fn set_clear_color(&mut self, color: wgpu::Color) {
+        self.clear_color = Some(color);
+    }
+
+    /// Goes back to the engine's clear color
+    pub fn reset_clear_color(&mut self) {
+        self.clear_color = None;
+    }
+
+    /// `None` when the engine's clear color is used
+    pub fn clear_color(&self) -> Option<wgpu::Color> {
+        self.clear_color
+    }
+
+    /// Value the depth buffer is cleared to before drawing, `1.0` (the far plane) by default
+    pub fn set_clear_depth(&mut self, depth: f32) {
+        self.clear_depth = depth.clamp(0.0, 1.0);
+    }
+
+    pub fn clear_depth(&self) -> f32 {
+        self.clear_depth
+    }
+
+    /// Draws over whatever is already in the color target instead of clearing it, for overlay
+    /// scenes composited over another one. Like turning off [ClearFlags::color], which this
+    /// combines with
+    pub fn set_skip_color_clear(&mut self, skip: bool) {
+        self.skip_color_clear = skip;
+    }
+
+    pub fn skips_color_clear(&self) -> bool {
+        self.skip_color_clear
+    }
+
+    pub fn set_active_camera(&mut self, entity_id: EntityId) {
+        self.active_camera_id = Some(entity_id);
+    }
+
+    pub fn get_active_camera(&self) -> Option<EntityId> {
+        self.active_camera_id
+    }
+
+    /// The active camera's view and projection matrices, `None` without an active camera.
+    /// Computed from the camera's current concepts, the same way the camera uniform is
+    pub fn camera_matrices(&self) -> Option<(na::Matrix4<f32>, na::Matrix4<f32>)> {
+        let camera_id = self.active_camera_id?;
+        let camera = Scene::get_component::<CameraComponent>(self.components.get(&camera_id)?)?;
+        let concept_manager = self.concept_manager.lock().unwrap();
+        Some((
+            camera.view_matrix(&self.components, &concept_manager),
+            camera.projection_matrix(&concept_manager),
+        ))
+    }
+
+    /// Window pixel coordinates of a world position, [None] if it's behind the camera
+    fn project_to_screen(
+        view_projection: na::Matrix4<f32>,
+        position: na::Vector3<f32>,
+        window_size: (u32, u32),
+    ) -> Option<(f32, f32)> {
+        let clip = view_projection * position.push(1.0);
+        if clip.w <= 0.0 {
+            return None;
+        }
+        let ndc = clip.xy() / clip.w;
+        Some((
+            (ndc.x + 1.0) * 0.5 * window_size.0 as f32,
+            (1.0 - ndc.y) * 0.5 * window_size.1 as f32,
+        ))
+    }
+
+    pub fn get_concept_manager(&self) -> Rc<Mutex<ConceptManager>> {
+        self.concept_manager.clone()
+    }
+
+    pub fn create_compute_pipeline(
+        &mut self,
+        device: Arc<Device>,
+        queue: Arc<Queue>,
+        shader_path: &str,
+        workgroup_size: (u32, u32, u32),
+        pipeline_type: ComputePipelineType,
+    ) -> Result<usize, PipelineError> {
+        let this_compute_index = self.compute_pipelines.len();
+        let defines = match pipeline_type.params {
+            Some(params) if ComputePipeline::uses_push_constants(&device, params.len()) => {
+                vec![ComputePipeline::PUSH_CONSTANT_PARAMS_DEFINE.to_string()]
+            }
+            _ => Vec::new(),
+        };
+        let shader_module_descriptor =
+            Pipeline::load_shader_module_descriptor(shader_path, &defines)?;
+        let pipeline = ComputePipeline::new(
+            device,
+            queue,
+            shader_module_descriptor,
+            pipeline_type,
+            this_compute_index,
+            workgroup_size,
+        );
+        self.compute_pipelines.push(pipeline);
+        self.compute_order = self.sorted_compute_order();
+        Ok(this_compute_index)
+    }
+
+    /// Makes `destination_input` of one compute pipeline use the same buffer or texture as
+    /// `source_asset` of another, for multi-pass work like blurs or simulation steps. The
+    /// source pipeline then runs before the destination in [Scene::update].
+    /// Linking two pipelines both ways (ping-pong) is allowed, they keep their creation order
+    pub fn link_compute_outputs(
+        &mut self,
+        device: Arc<Device>,
+        source_pipeline: usize,
+        source_asset: usize,
+        destination_pipeline: usize,
+        destination_input: usize,
+    ) -> Result<(), SceneError> {
+        let source = self
+            .compute_pipelines
+            .get(source_pipeline)
+            .and_then(|pipeline| pipeline.pipeline_assets.get(source_asset))
+            .ok_or(SceneError::ComputeAssetNotFound {
+                pipeline: source_pipeline,
+                asset: source_asset,
+            })?;
+        let destination = self
+            .compute_pipelines
+            .get(destination_pipeline)
+            .and_then(|pipeline| pipeline.pipeline_assets.get(destination_input))
+            .ok_or(SceneError::ComputeAssetNotFound {
+                pipeline: destination_pipeline,
+                asset: destination_input,
+            })?;
+
+        let shared_asset = match (source, destination) {
+            (ComputePackagedData::Buffer(buffer), ComputePackagedData::Buffer(_)) => {
+                ComputePackagedData::Buffer(buffer.clone())
+            }
+            (ComputePackagedData::Texture(texture), ComputePackagedData::Texture(_)) => {
+                ComputePackagedData::Texture(texture.clone())
+            }
+            _ => return Err(SceneError::ComputeAssetKindMismatch),
+        };
+
+        self.compute_pipelines[destination_pipeline]
+            .update_pipeline_assets(device, vec![(shared_asset, destination_input)]);
+
+        if !self
+            .compute_links
+            .contains(&(source_pipeline, destination_pipeline))
+        {
+            self.compute_links
+                .push((source_pipeline, destination_pipeline));
+        }
+        self.compute_order = self.sorted_compute_order();
+        Ok(())
+    }
+
+    /// Topological order of the compute links. Pipelines in a cycle run in creation order
+    fn sorted_compute_order(&self) -> Vec<usize> {
+        let mut remaining = (0..self.compute_pipelines.len()).collect::<Vec<_>>();
+        let mut order = Vec::with_capacity(remaining.len());
+
+        while !remaining.is_empty() {
+            let has_pending_source = |index: &usize| {
+                self.compute_links.iter().any(|(source, destination)| {
+                    destination == index && source != index && remaining.contains(source)
+                })
+            };
+            let next_position = remaining
+                .iter()
+                .position(|index| !has_pending_source(index))
+                .unwrap_or(0);
+            order.push(remaining.remove(next_position));
+        }
+
+        order
+    }
+}
+
+impl Default for Scene {
+    fn default() -> Self {
+        Self {
+            entities: Arc::new(Mutex::new(Vec::new())),
+            total_entities_created: 0,
+            entity_names: HashMap::new(),
+            entity_tags: HashMap::new(),
+            pipelines: HashMap::new(),
+            failed_pipelines: HashSet::new(),
+            wireframe_pipelines: None,
+            compute_pipelines: Vec::new(),
+            compute_links: Vec::new(),
+            compute_order: Vec::new(),
+            compute_duration: Duration::ZERO,
+            components: HashMap::new(),
+            materials: HashMap::new(),
+            active_camera_id: None,
+            concept_manager: Rc::new(Mutex::new(ConceptManager::default())),
+            picking_pipeline: None,
+            skybox: None,
+            debug_draw: DebugDraw::default(),
+            initialized: false,
+            panic_isolation: false,
+            disabled_components: HashSet::new(),
+            collision_events: Vec::new(),
+            spatial_grid: SpatialGrid::default(),
+            pipeline_target: None,
+            clear_color: None,
+            clear_depth: 1.0,
+            skip_color_clear: false,
+            ui_frame_pending: false,
+            indirect_draws: None,
+
+            text_state: TextState {
+                font_system: FontSystem::new(),
+                swash_cache: SwashCache::new(),
+                text_viewport: None,
+                atlas: None,
+                text_renderer: None,
+                text_items: Vec::new(),
+            },
+        }
+    }
+}
+
+struct GltfLoader<'a> {
+    document: &'a gltf::Document,
+    buffers: &'a [gltf::buffer::Data],
+    textures: &'a [Rc<Texture>],
+    vertex_shader_path: &'a str,
+    fragment_shader_path: &'a str,
+    device: Arc<Device>,
+}
+
+impl GltfLoader<'_> {
+    fn create_material(&self, gltf_material_index: Option<usize>) -> Material {
+        let gltf_material =
+            gltf_material_index.and_then(|index| self.document.materials().nth(index));
+
+        // glTF materials are single sided and opaque unless marked otherwise
+        let mut pipeline_config = PipelineConfig::imported_mesh();
+        if let Some(gltf_material) = gltf_material {
+            if gltf_material.double_sided() {
+                pipeline_config.cull_mode = None;
+            }
+            if gltf_material.alpha_mode() == gltf::material::AlphaMode::Blend {
+                pipeline_config.blend_mode = BlendMode::AlphaBlend;
+                pipeline_config.depth_write = false;
+            }
+        }
+
+        let (base_color, textures) = match gltf_material {
+            Some(gltf_material) => {
+                let pbr = gltf_material.pbr_metallic_roughness();
+                let base_color_texture = pbr
+                    .base_color_texture()
+                    .map(|info| self.textures[info.texture().source().index()].clone());
+                (
+                    pbr.base_color_factor(),
+                    base_color_texture.into_iter().collect(),
+                )
+            }
+            None => ([1.0; 4], Vec::new()),
+        };
+
+        Material::new(
+            self.vertex_shader_path,
+            self.fragment_shader_path,
+            textures,
+            Some(bytemuck::cast_slice(&base_color)),
+            true,
+            self.device.clone(),
+        )
+        .with_pipeline_config(pipeline_config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use algoe::{bivector::Bivector, rotor::Rotor3};
+
+    use super::*;
+    use crate::{
+        ecs::components::physics_component::Integrator,
+        renderer::Renderer,
+        test_utils::{
+            fragment_shader, headless_device, shader_dir, with_engine_systems, VERTEX_SHADER,
+        },
+    };
+
+    /// What [Scene::initialize] and the update and render calls need, without a window
+    struct Headless {
+        device: Arc<Device>,
+        queue: Arc<Queue>,
+        engine_details: Rc<Mutex<EngineDetails>>,
+        engine_systems: Rc<Mutex<EngineSystems>>,
+        ui_manager: Rc<Mutex<UiManager>>,
+    }
+
+    impl Headless {
+        fn new(engine_systems: Rc<Mutex<EngineSystems>>) -> Option<Self> {
+            let (device, queue) = headless_device(wgpu::Features::empty())?;
+            Some(Headless {
+                ui_manager: Rc::new(Mutex::new(UiManager::without_window(
+                    Renderer::HEADLESS_FORMAT,
+                    device.clone(),
+                    queue.clone(),
+                ))),
+                device,
+                queue,
+                engine_details: Rc::new(Mutex::new(EngineDetails::new((64, 64)))),
+                engine_systems,
+            })
+        }
+
+        fn initialize(&self, scene: &mut Scene, sample_count: u32) {
+            scene.initialize(
+                self.device.clone(),
+                self.queue.clone(),
+                Renderer::HEADLESS_FORMAT,
+                sample_count,
+                Texture::DEPTH_FORMAT,
+                self.engine_details.clone(),
+                self.engine_systems.clone(),
+                self.ui_manager.clone(),
+            );
+        }
+
+        /// The scene drawn over black at the engine's 64x64 window size
+        fn render_to_image(&self, scene: &mut Scene) -> image::RgbaImage {
+            scene
+                .render_to_image(
+                    self.device.clone(),
+                    self.queue.clone(),
+                    (64, 64),
+                    &self.engine_details.lock().unwrap(),
+                    &self.engine_systems.lock().unwrap(),
+                    wgpu::Color::BLACK,
+                    self.ui_manager.clone(),
+                )
+                .unwrap()
+        }
+
+        /// A 64x64 target that's kept across frames like the window's
+        fn render_target(&self) -> RenderTarget {
+            let texture = self.device.create_texture(&wgpu::TextureDescriptor {
+                label: Some("Test Render Target"),
+                size: wgpu::Extent3d {
+                    width: 64,
+                    height: 64,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: Renderer::HEADLESS_FORMAT,
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+                view_formats: &[],
+            });
+            RenderTarget {
+                view: texture.create_view(&wgpu::TextureViewDescriptor::default()),
+                texture,
+                smaa_target: smaa::SmaaTarget::new(
+                    &self.device,
+                    &self.queue,
+                    64,
+                    64,
+                    Renderer::HEADLESS_FORMAT,
+                    smaa::SmaaMode::Disabled,
+                ),
+            }
+        }
+
+        /// Draws the scene into `target` like a frame of the engine, leaving out post-processing
+        fn render_scene(
+            &self,
+            scene: &mut Scene,
+            target: &mut RenderTarget,
+            depth_texture: Arc<Texture>,
+        ) -> bool {
+            scene.render_scene(
+                self.device.clone(),
+                self.queue.clone(),
+                depth_texture,
+                (64, 64),
+                &self.engine_details.lock().unwrap(),
+                &self.engine_systems.lock().unwrap(),
+                target
+                    .smaa_target
+                    .start_frame(&self.device, &self.queue, &target.view),
+                None,
+                target.texture.size(),
+                wgpu::Color::BLACK,
+                ClearFlags::default(),
+                &[],
+                LayerMask::ALL,
+                self.ui_manager.clone(),
+            )
+        }
+
+        fn add_component(&self, scene: &mut Scene, entity_id: EntityId, component: Component) {
+            assert!(scene.add_component(
+                entity_id,
+                component,
+                self.device.clone(),
+                self.queue.clone(),
+                self.engine_details.clone(),
+                self.engine_systems.clone(),
+                self.ui_manager.clone(),
+            ));
+        }
+
+        fn fixed_update(&self, scene: &mut Scene) {
+            scene.fixed_update(
+                self.device.clone(),
+                self.queue.clone(),
+                self.engine_details.clone(),
+                self.engine_systems.clone(),
+            );
+        }
+    }
+
+    mod panicking {
+        use std::cell::Cell;
+
+        use crate::{ecs::scene::TextParams, new_component};
+
+        new_component!(Panicking { calls: Rc<Cell<u32>> });
+
+        impl Panicking {
+            pub fn new(calls: Rc<Cell<u32>>) -> Self {
+                Panicking {
+                    calls,
+                    parent: EntityId::MAX,
+                    id: (EntityId::MAX, TypeId::of::<Self>(), 0),
+                }
+            }
+        }
 
-    pub fn get_component_mut<T: ComponentSystem + Any>(
-        components: &mut [Component],
-    ) -> Option<&mut T> {
-        for component in components.iter_mut() {
-            if let Some(comp) = component.as_any_mut().downcast_mut::<T>() {
-                return Some(comp);
+        impl ComponentSystem for Panicking {
+            fn fixed_update(
+                &mut self,
+                _device: Arc<Device>,
+                _queue: Arc<Queue>,
+                _component_map: &mut AllComponents,
+                _engine_details: Rc<Mutex<EngineDetails>>,
+                _engine_systems: Rc<Mutex<EngineSystems>>,
+                concept_manager: Rc<Mutex<ConceptManager>>,
+                _active_camera_id: Option<EntityId>,
+                _entities: &mut Vec<Entity>,
+                _materials: Option<&mut (Vec<Material>, usize)>,
+                _compute_pipelines: &mut [ComputePipeline],
+                _text_items: &mut Vec<TextParams>,
+            ) {
+                self.calls.set(self.calls.get() + 1);
+                let _concept_manager = concept_manager.lock().unwrap();
+                panic!("panicking on purpose");
             }
         }
-        None
     }
 
-    pub fn get_components(&self) -> &AllComponents {
-        &self.components
+    struct RenderTarget {
+        texture: wgpu::Texture,
+        view: wgpu::TextureView,
+        smaa_target: smaa::SmaaTarget,
     }
 
-    pub fn get_components_mut(&mut self) -> &mut AllComponents {
-        &mut self.components
+    /// A unit box at `x` on the X axis, with a physics component of mass `1.0` when `velocity`
+    /// is given
+    fn add_box(scene: &mut Scene, x: f32, velocity: Option<f32>) -> EntityId {
+        add_box_at(scene, na::Vector3::new(x, 0.0, 0.0), velocity)
     }
 
-    pub fn set_active_camera(&mut self, entity_id: EntityId) {
-        self.active_camera_id = Some(entity_id);
+    fn add_box_at(
+        scene: &mut Scene,
+        position: na::Vector3<f32>,
+        velocity: Option<f32>,
+    ) -> EntityId {
+        let concept_manager = scene.get_concept_manager();
+        let mut components: Vec<Component> = vec![
+            Box::new(TransformComponent::new(
+                concept_manager.clone(),
+                position,
+                Rotor3::default(),
+                na::Vector3::repeat(1.0),
+            )),
+            Box::new(ColliderComponent::new(
+                concept_manager.clone(),
+                ColliderShape::Aabb {
+                    half_extents: na::Vector3::repeat(0.5),
+                },
+                1.0,
+            )),
+        ];
+        if let Some(velocity) = velocity {
+            components.push(Box::new(PhysicsComponent::new(
+                concept_manager,
+                na::Vector3::new(velocity, 0.0, 0.0),
+                na::Vector3::zeros(),
+                1.0,
+                Bivector::new(0.0, 0.0, 0.0),
+                Bivector::new(0.0, 0.0, 0.0),
+            )));
+        }
+        scene.create_entity(EntityId::MAX, true, components, None)
     }
 
-    pub fn get_active_camera(&self) -> Option<EntityId> {
-        self.active_camera_id
+    /// A quad drawn with the first of `materials`
+    fn add_quad(scene: &mut Scene, materials: Vec<Material>) -> EntityId {
+        let components: Vec<Component> =
+            vec![Box::new(MeshComponent::quad(scene.get_concept_manager()))];
+        scene.create_entity(EntityId::MAX, true, components, Some((materials, 0)))
     }
 
-    pub fn get_concept_manager(&self) -> Rc<Mutex<ConceptManager>> {
-        self.concept_manager.clone()
+    fn position(scene: &Scene, entity_id: EntityId) -> na::Vector3<f32> {
+        *scene
+            .concept_manager
+            .lock()
+            .unwrap()
+            .get_concept::<na::Vector3<f32>>(
+                (entity_id, TypeId::of::<TransformComponent>(), 0),
+                "position".to_string(),
+            )
+            .unwrap()
     }
 
-    pub fn create_compute_pipeline(
-        &mut self,
-        device: Arc<Device>,
-        queue: Arc<Queue>,
-        shader_path: &str,
-        workgroup_size: (u32, u32, u32),
-        pipeline_type: ComputePipelineType,
-    ) -> Result<usize, PipelineError> {
-        let this_compute_index = self.compute_pipelines.len();
-        let shader_module_descriptor = Pipeline::load_shader_module_descriptor(shader_path)?;
-        let pipeline = ComputePipeline::new(
-            device,
-            queue,
-            shader_module_descriptor,
-            pipeline_type,
-            this_compute_index,
-            workgroup_size,
+    fn velocity(scene: &Scene, entity_id: EntityId) -> na::Vector3<f32> {
+        *scene
+            .concept_manager
+            .lock()
+            .unwrap()
+            .get_concept::<na::Vector3<f32>>(
+                (entity_id, TypeId::of::<PhysicsComponent>(), 0),
+                "velocity".to_string(),
+            )
+            .unwrap()
+    }
+
+    fn collision_count(scene: &Scene, entity_id: EntityId) -> usize {
+        scene
+            .concept_manager
+            .lock()
+            .unwrap()
+            .get_concept::<Vec<CollisionEvent>>(
+                (entity_id, TypeId::of::<ColliderComponent>(), 0),
+                "collisions".to_string(),
+            )
+            .unwrap()
+            .len()
+    }
+
+    #[test]
+    fn disabled_materials_are_neither_drawn_nor_built() {
+        let Some((device, _)) = headless_device(wgpu::Features::empty()) else {
+            return;
+        };
+        let material = |fragment_shader_path: &str, enabled: bool| {
+            Material::new(
+                "shaders/vert.wgsl",
+                fragment_shader_path,
+                Vec::new(),
+                None,
+                enabled,
+                device.clone(),
+            )
+        };
+
+        let mut scene = Scene::default();
+        let concept_manager = scene.get_concept_manager();
+        let quad =
+            || -> Vec<Component> { vec![Box::new(MeshComponent::quad(concept_manager.clone()))] };
+        let disabled_entity = scene.create_entity(
+            EntityId::MAX,
+            true,
+            quad(),
+            Some((vec![material("shaders/disabled.wgsl", false)], 0)),
         );
-        self.compute_pipelines.push(pipeline);
-        Ok(this_compute_index)
+        let mixed_entity = scene.create_entity(
+            EntityId::MAX,
+            true,
+            quad(),
+            Some((
+                vec![
+                    material("shaders/enabled.wgsl", true),
+                    material("shaders/disabled.wgsl", false),
+                ],
+                0,
+            )),
+        );
+
+        let material_ids = |scene: &Scene, entity_id| {
+            Scene::enabled_material_ids(&scene.materials, &scene.components, &entity_id)
+        };
+        let draw_count = |scene: &Scene, entity_id| {
+            Scene::entity_draws(&scene.materials, &scene.components, &entity_id).len()
+        };
+        assert!(material_ids(&scene, disabled_entity).is_empty());
+        assert_eq!(draw_count(&scene, disabled_entity), 0);
+        assert_eq!(
+            material_ids(&scene, mixed_entity),
+            [scene.materials[&mixed_entity].0[0].id().clone()]
+        );
+        assert_eq!(draw_count(&scene, mixed_entity), 1);
+
+        scene.materials.get_mut(&mixed_entity).unwrap().1 = 1;
+        assert!(material_ids(&scene, mixed_entity).is_empty());
+        assert_eq!(draw_count(&scene, mixed_entity), 0);
+
+        scene.materials.get_mut(&disabled_entity).unwrap().0[0].set_enabled(true);
+        assert_eq!(material_ids(&scene, disabled_entity).len(), 1);
+        assert_eq!(draw_count(&scene, disabled_entity), 1);
     }
-}
 
-impl Default for Scene {
-    fn default() -> Self {
-        Self {
-            entities: Arc::new(Mutex::new(Vec::new())),
-            total_entities_created: 0,
-            pipelines: HashMap::new(),
-            compute_pipelines: Vec::new(),
-            components: HashMap::new(),
-            materials: HashMap::new(),
-            active_camera_id: None,
-            concept_manager: Rc::new(Mutex::new(ConceptManager::default())),
+    #[test]
+    fn camera_matrices_project_to_ndc() {
+        let mut scene = Scene::default();
+        let concept_manager = scene.get_concept_manager();
+        let components: Vec<Component> = vec![
+            Box::new(TransformComponent::new(
+                concept_manager.clone(),
+                na::Vector3::new(0.0, 0.0, -5.0),
+                Rotor3::default(),
+                na::Vector3::repeat(1.0),
+            )),
+            Box::new(CameraComponent::new_ortho(
+                concept_manager,
+                4.0,
+                2.0,
+                1.0,
+                11.0,
+            )),
+        ];
+        let camera = scene.create_entity(EntityId::MAX, true, components, None);
+        assert!(scene.camera_matrices().is_none());
 
-            text_state: TextState {
-                font_system: FontSystem::new(),
-                swash_cache: SwashCache::new(),
-                text_viewport: None,
-                atlas: None,
-                text_renderer: None,
-                text_items: Vec::new(),
-            },
+        scene.set_active_camera(camera);
+        let (view, projection) = scene.camera_matrices().unwrap();
+        let clip = projection * view * na::Vector4::new(1.0, 0.5, 0.0, 1.0);
+        let ndc = clip.xyz() / clip.w;
+        assert!(
+            (ndc - na::Vector3::new(0.5, 0.5, 0.4)).norm() < 1e-5,
+            "{ndc:?}"
+        );
+    }
+
+    #[test]
+    fn head_on_boxes_bounce_apart() {
+        let mut scene = Scene::default();
+        let left = add_box(&mut scene, -0.45, Some(1.0));
+        let right = add_box(&mut scene, 0.45, Some(-1.0));
+
+        scene.resolve_collisions(&[left, right]);
+
+        assert_eq!(scene.collision_events().len(), 1);
+        assert_eq!(collision_count(&scene, left), 1);
+        assert_eq!(collision_count(&scene, right), 1);
+        assert!((position(&scene, left).x + 0.5).abs() < 1e-5);
+        assert!((position(&scene, right).x - 0.5).abs() < 1e-5);
+        assert!((velocity(&scene, left).x + 1.0).abs() < 1e-5);
+        assert!((velocity(&scene, right).x - 1.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn static_box_only_moves_the_dynamic_one() {
+        let mut scene = Scene::default();
+        let wall = add_box(&mut scene, 0.0, None);
+        let ball = add_box(&mut scene, 0.9, Some(-1.0));
+
+        scene.resolve_collisions(&[wall, ball]);
+
+        assert_eq!(scene.collision_events().len(), 1);
+        assert_eq!(collision_count(&scene, wall), 1);
+        assert_eq!(collision_count(&scene, ball), 1);
+        assert_eq!(position(&scene, wall), na::Vector3::zeros());
+        assert!((position(&scene, ball).x - 1.0).abs() < 1e-5);
+        assert!((velocity(&scene, ball).x - 1.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn gravity_matches_the_closed_form_over_fixed_steps() {
+        with_engine_systems(|engine_systems| {
+            let Some(headless) = Headless::new(engine_systems) else {
+                return;
+            };
+            let start = na::Vector3::new(1.0, 2.0, 0.0);
+            let start_velocity = na::Vector3::new(1.0, 3.0, 0.0);
+            let gravity = na::Vector3::new(0.0, -9.81, 0.0);
+            let steps = 60;
+
+            for integrator in [Integrator::SemiImplicitEuler, Integrator::Rk4] {
+                let mut scene = Scene::default();
+                let concept_manager = scene.get_concept_manager();
+                let body = PhysicsComponent::new(
+                    concept_manager.clone(),
+                    start_velocity,
+                    na::Vector3::zeros(),
+                    1.0,
+                    Bivector::new(0.0, 0.0, 0.0),
+                    Bivector::new(0.0, 0.0, 0.0),
+                )
+                .with_integrator(integrator);
+                body.set_gravity(concept_manager.clone(), gravity);
+                let components: Vec<Component> = vec![
+                    Box::new(TransformComponent::new(
+                        concept_manager,
+                        start,
+                        Rotor3::default(),
+                        na::Vector3::repeat(1.0),
+                    )),
+                    Box::new(body),
+                ];
+                let entity = scene.create_entity(EntityId::MAX, true, components, None);
+
+                for _ in 0..steps {
+                    headless.fixed_update(&mut scene);
+                }
+
+                let time = headless
+                    .engine_details
+                    .lock()
+                    .unwrap()
+                    .fixed_timestep
+                    .as_secs_f32()
+                    * steps as f32;
+                let expected = start + start_velocity * time + gravity * time * time / 2.0;
+                let error = (position(&scene, entity) - expected).magnitude();
+                assert!(error < 1e-3, "{integrator:?} is off by {error}");
+                let velocity_error =
+                    (velocity(&scene, entity) - (start_velocity + gravity * time)).magnitude();
+                assert!(
+                    velocity_error < 1e-3,
+                    "{integrator:?} is off by {velocity_error}"
+                );
+            }
+        });
+    }
+
+    #[test]
+    fn panicking_components_are_disabled_and_the_frame_goes_on() {
+        with_engine_systems(|engine_systems| {
+            let Some(headless) = Headless::new(engine_systems) else {
+                return;
+            };
+            let mut scene = Scene::default();
+            scene.set_panic_isolation(true);
+            let calls = Rc::new(std::cell::Cell::new(0));
+            let components: Vec<Component> =
+                vec![Box::new(panicking::Panicking::new(calls.clone()))];
+            let panicking_entity = scene.create_entity(EntityId::MAX, true, components, None);
+            let moving_box = add_box(&mut scene, 0.0, Some(1.0));
+
+            headless.fixed_update(&mut scene);
+            let moved_to = position(&scene, moving_box);
+            assert!(moved_to.x > 0.0);
+            assert_eq!(calls.get(), 1);
+            assert_eq!(
+                scene.disabled_components().iter().collect::<Vec<_>>(),
+                [&(panicking_entity, TypeId::of::<panicking::Panicking>(), 0)]
+            );
+
+            // The lock the component held while panicking isn't left poisoned
+            headless.fixed_update(&mut scene);
+            assert!(position(&scene, moving_box).x > moved_to.x);
+            assert_eq!(calls.get(), 1);
+        });
+    }
+
+    #[test]
+    fn components_added_and_removed_at_runtime_take_their_concepts() {
+        with_engine_systems(|engine_systems| {
+            let Some(headless) = Headless::new(engine_systems) else {
+                return;
+            };
+            let mut scene = Scene::default();
+            let concept_manager = scene.get_concept_manager();
+            let components: Vec<Component> = vec![Box::new(TransformComponent::new(
+                concept_manager.clone(),
+                na::Vector3::zeros(),
+                Rotor3::default(),
+                na::Vector3::repeat(1.0),
+            ))];
+            let entity = scene.create_entity(EntityId::MAX, true, components, None);
+            headless.initialize(&mut scene, 1);
+
+            let body = |mass: f32| -> Component {
+                Box::new(PhysicsComponent::new(
+                    concept_manager.clone(),
+                    na::Vector3::zeros(),
+                    na::Vector3::zeros(),
+                    mass,
+                    Bivector::new(0.0, 0.0, 0.0),
+                    Bivector::new(0.0, 0.0, 0.0),
+                ))
+            };
+
+            let physics_id = |index| (entity, TypeId::of::<PhysicsComponent>(), index);
+            let mass = |index| {
+                concept_manager
+                    .lock()
+                    .unwrap()
+                    .get_concept::<f32>(physics_id(index), "mass".to_string())
+                    .ok()
+                    .copied()
+            };
+            let registered = || concept_manager.lock().unwrap().concepts.len();
+            let before = registered();
+            headless.add_component(&mut scene, entity, body(1.0));
+            headless.add_component(&mut scene, entity, body(2.0));
+            assert_eq!(mass(0), Some(1.0));
+            assert_eq!(mass(1), Some(2.0));
+            assert_eq!(registered(), before + 2);
+
+            assert!(scene.remove_component::<PhysicsComponent>(entity, 0));
+            assert_eq!(mass(0), Some(2.0));
+            assert_eq!(mass(1), None);
+            assert_eq!(registered(), before + 1);
+
+            assert!(scene.remove_component::<PhysicsComponent>(entity, 0));
+            assert_eq!(mass(0), None);
+            assert_eq!(registered(), before);
+            assert!(!scene.remove_component::<PhysicsComponent>(entity, 0));
+        });
+    }
+
+    #[test]
+    fn physics_added_after_initialize_moves_the_body() {
+        with_engine_systems(|engine_systems| {
+            let Some(headless) = Headless::new(engine_systems) else {
+                return;
+            };
+            let mut scene = Scene::default();
+            let concept_manager = scene.get_concept_manager();
+            let components: Vec<Component> = vec![Box::new(TransformComponent::new(
+                concept_manager.clone(),
+                na::Vector3::zeros(),
+                Rotor3::default(),
+                na::Vector3::repeat(1.0),
+            ))];
+            let entity = scene.create_entity(EntityId::MAX, true, components, None);
+            headless.initialize(&mut scene, 1);
+
+            headless.add_component(
+                &mut scene,
+                entity,
+                Box::new(PhysicsComponent::new(
+                    concept_manager,
+                    na::Vector3::new(0.0, 6.0, 0.0),
+                    na::Vector3::zeros(),
+                    1.0,
+                    Bivector::new(0.0, 0.0, 0.0),
+                    Bivector::new(0.0, 0.0, 0.0),
+                )),
+            );
+            headless.fixed_update(&mut scene);
+
+            let step = headless
+                .engine_details
+                .lock()
+                .unwrap()
+                .fixed_timestep
+                .as_secs_f32();
+            assert!((position(&scene, entity).y - 6.0 * step).abs() < 1e-5);
+        });
+    }
+
+    #[test]
+    fn rays_hit_stacked_boxes_in_order() {
+        let mut scene = Scene::default();
+        let stack =
+            [0.0, 1.0, 2.0].map(|y| add_box_at(&mut scene, na::Vector3::new(0.0, y, 0.0), None));
+        scene.resolve_collisions(&stack);
+        let down = -na::Vector3::y();
+
+        let hits = scene.raycast_all(na::Vector3::new(0.0, 5.0, 0.0), down);
+        assert_eq!(
+            hits.iter().map(|hit| hit.entity).collect::<Vec<_>>(),
+            [stack[2], stack[1], stack[0]]
+        );
+        for (hit, distance) in hits.iter().zip([2.5, 3.5, 4.5]) {
+            assert!((hit.distance - distance).abs() < 1e-5);
+            assert_eq!(hit.normal, na::Vector3::y());
         }
+
+        let nearest = scene
+            .raycast(na::Vector3::new(-3.0, 1.0, 0.0), na::Vector3::x())
+            .unwrap();
+        assert_eq!(nearest.entity, stack[1]);
+        assert!((nearest.point - na::Vector3::new(-0.5, 1.0, 0.0)).magnitude() < 1e-5);
+        assert_eq!(nearest.normal, -na::Vector3::x());
+    }
+
+    #[test]
+    fn rays_along_a_face_graze_it() {
+        let mut scene = Scene::default();
+        let stack = [0.0, 1.0].map(|y| add_box_at(&mut scene, na::Vector3::new(0.0, y, 0.0), None));
+        scene.resolve_collisions(&stack);
+
+        let hits = scene.raycast_all(na::Vector3::new(0.5, 5.0, 0.0), -na::Vector3::y());
+        assert_eq!(
+            hits.iter().map(|hit| hit.entity).collect::<Vec<_>>(),
+            [stack[1], stack[0]]
+        );
+        assert!((hits[0].point - na::Vector3::new(0.5, 1.5, 0.0)).magnitude() < 1e-5);
+    }
+
+    #[test]
+    fn rays_beside_or_away_from_boxes_miss() {
+        let mut scene = Scene::default();
+        let stack = [0.0, 1.0].map(|y| add_box_at(&mut scene, na::Vector3::new(0.0, y, 0.0), None));
+        scene.resolve_collisions(&stack);
+
+        assert!(scene
+            .raycast(na::Vector3::new(0.51, 5.0, 0.0), -na::Vector3::y())
+            .is_none());
+        assert!(scene
+            .raycast(na::Vector3::new(0.0, 5.0, 0.0), na::Vector3::y())
+            .is_none());
+        assert!(scene
+            .raycast(na::Vector3::new(-3.0, 0.0, 2.0), na::Vector3::x())
+            .is_none());
+        assert!(scene
+            .raycast_all(na::Vector3::new(0.0, 5.0, 0.0), na::Vector3::zeros())
+            .is_empty());
+    }
+
+    #[test]
+    fn blend_modes_get_their_own_pipelines() {
+        with_engine_systems(|engine_systems| {
+            let Some(headless) = Headless::new(engine_systems) else {
+                return;
+            };
+            let dir = shader_dir(
+                "blend_modes",
+                &[
+                    ("vert.wgsl", VERTEX_SHADER),
+                    ("frag.wgsl", &fragment_shader([1.0; 4])),
+                ],
+            );
+            let path = |name: &str| dir.join(name).to_str().unwrap().to_string();
+
+            let mut scene = Scene::default();
+            let entities = [BlendMode::AlphaBlend, BlendMode::Additive].map(|blend_mode| {
+                let material = Material::new(
+                    &path("vert.wgsl"),
+                    &path("frag.wgsl"),
+                    Vec::new(),
+                    None,
+                    true,
+                    headless.device.clone(),
+                )
+                .with_pipeline_config(PipelineConfig {
+                    blend_mode,
+                    ..PipelineConfig::default()
+                });
+                add_quad(&mut scene, vec![material])
+            });
+            headless.initialize(&mut scene, 1);
+
+            assert!(scene.failed_pipelines.is_empty());
+            assert_eq!(scene.pipelines.len(), 2);
+            for entity in entities {
+                let material_id = scene.materials[&entity].0[0].id();
+                assert!(scene.pipelines.contains_key(material_id));
+            }
+        });
+    }
+
+    #[test]
+    fn stats_count_the_draws_of_a_known_scene() {
+        with_engine_systems(|engine_systems| {
+            let Some(headless) = Headless::new(engine_systems) else {
+                return;
+            };
+            let dir = shader_dir(
+                "stats",
+                &[
+                    ("vert.wgsl", VERTEX_SHADER),
+                    ("frag.wgsl", &fragment_shader([1.0; 4])),
+                ],
+            );
+            let path = |name: &str| dir.join(name).to_str().unwrap().to_string();
+            let material = || {
+                Material::new(
+                    &path("vert.wgsl"),
+                    &path("frag.wgsl"),
+                    Vec::new(),
+                    None,
+                    true,
+                    headless.device.clone(),
+                )
+            };
+
+            let mut scene = Scene::default();
+            add_quad(&mut scene, vec![material()]);
+            add_quad(&mut scene, vec![material()]);
+            let hidden = add_quad(&mut scene, vec![material()]);
+            let components: Vec<Component> =
+                vec![Box::new(MeshComponent::cube(scene.get_concept_manager()))];
+            scene.create_entity(EntityId::MAX, true, components, Some((vec![material()], 0)));
+            add_box(&mut scene, 0.0, None);
+            headless.initialize(&mut scene, 1);
+            assert!(scene.set_entity_active(hidden, false));
+
+            let stats = scene.stats();
+            assert_eq!(stats.entity_count, 5);
+            assert_eq!(stats.enabled_entity_count, 4);
+            assert_eq!(
+                stats.component_counts[std::any::type_name::<MeshComponent>()],
+                4
+            );
+            assert_eq!(
+                stats.component_counts[std::any::type_name::<ColliderComponent>()],
+                1
+            );
+            assert_eq!(stats.material_count, 4);
+            assert_eq!(stats.pipeline_count, 1);
+            assert_eq!(stats.compute_pipeline_count, 0);
+            // Two quads and the cube, the hidden quad and the box without a mesh aren't drawn
+            assert_eq!(stats.draw_call_count, 3);
+            assert_eq!(stats.triangle_count, 2 + 2 + 12);
+        });
+    }
+
+    #[test]
+    fn shader_defines_get_their_own_pipelines() {
+        with_engine_systems(|engine_systems| {
+            let Some(headless) = Headless::new(engine_systems) else {
+                return;
+            };
+            let fragment_source = format!(
+                "#ifdef RED\n{}\n#else\n{}\n#endif\n",
+                fragment_shader([1.0, 0.0, 0.0, 1.0]),
+                fragment_shader([0.0, 0.0, 1.0, 1.0])
+            );
+            let dir = shader_dir(
+                "shader_defines",
+                &[
+                    ("vert.wgsl", VERTEX_SHADER),
+                    ("frag.wgsl", &fragment_source),
+                ],
+            );
+            let path = |name: &str| dir.join(name).to_str().unwrap().to_string();
+
+            let mut scene = Scene::default();
+            let entities = [&["RED"][..], &["BLUE"], &["RED", "RED"]].map(|defines| {
+                let material = Material::new(
+                    &path("vert.wgsl"),
+                    &path("frag.wgsl"),
+                    Vec::new(),
+                    None,
+                    true,
+                    headless.device.clone(),
+                )
+                .with_shader_defines(defines);
+                add_quad(&mut scene, vec![material])
+            });
+            headless.initialize(&mut scene, 1);
+
+            let material_id = |entity| scene.materials[&entity].0[0].id().clone();
+            assert!(scene.failed_pipelines.is_empty());
+            assert_eq!(scene.pipelines.len(), 2);
+            assert_ne!(material_id(entities[0]), material_id(entities[1]));
+            assert_eq!(material_id(entities[0]), material_id(entities[2]));
+        });
+    }
+
+    #[test]
+    fn switching_materials_draws_the_active_one() {
+        with_engine_systems(|engine_systems| {
+            let Some(headless) = Headless::new(engine_systems) else {
+                return;
+            };
+            let dir = shader_dir(
+                "switching_materials",
+                &[
+                    ("vert.wgsl", VERTEX_SHADER),
+                    ("red.wgsl", &fragment_shader([1.0, 0.0, 0.0, 1.0])),
+                    ("blue.wgsl", &fragment_shader([0.0, 0.0, 1.0, 1.0])),
+                ],
+            );
+            let path = |name: &str| dir.join(name).to_str().unwrap().to_string();
+            let material = |fragment_shader_path| {
+                Material::new(
+                    &path("vert.wgsl"),
+                    &path(fragment_shader_path),
+                    Vec::new(),
+                    None,
+                    true,
+                    headless.device.clone(),
+                )
+            };
+
+            let mut scene = Scene::default();
+            let entity = add_quad(
+                &mut scene,
+                vec![material("red.wgsl"), material("blue.wgsl")],
+            );
+            headless.initialize(&mut scene, 1);
+            // The quad covers the middle of the default camera's view
+            let center = |scene: &mut Scene| headless.render_to_image(scene).get_pixel(32, 32).0;
+            let (red, blue) = ([255, 0, 0, 255], [0, 0, 255, 255]);
+            assert_eq!(scene.pipelines.len(), 1);
+            assert_eq!(center(&mut scene), red);
+
+            scene
+                .set_active_material(headless.device.clone(), entity, 1)
+                .unwrap();
+            assert_eq!(scene.pipelines.len(), 2);
+            assert_eq!(center(&mut scene), blue);
+
+            scene
+                .set_active_material(headless.device.clone(), entity, 0)
+                .unwrap();
+            assert_eq!(center(&mut scene), red);
+
+            assert!(matches!(
+                scene.set_active_material(headless.device.clone(), entity, 2),
+                Err(SceneError::MaterialIndexOutOfRange {
+                    index: 2,
+                    material_count: 2
+                })
+            ));
+            assert_eq!(center(&mut scene), red);
+        });
+    }
+
+    #[test]
+    fn entities_added_after_initialize_are_drawn() {
+        with_engine_systems(|engine_systems| {
+            let Some(headless) = Headless::new(engine_systems) else {
+                return;
+            };
+            let dir = shader_dir(
+                "entities_added_after_initialize",
+                &[
+                    ("vert.wgsl", VERTEX_SHADER),
+                    ("frag.wgsl", &fragment_shader([0.0, 1.0, 0.0, 1.0])),
+                ],
+            );
+            let path = |name: &str| dir.join(name).to_str().unwrap().to_string();
+
+            let mut scene = Scene::default();
+            headless.initialize(&mut scene, 1);
+            assert_eq!(
+                headless.render_to_image(&mut scene).get_pixel(32, 32).0,
+                [0, 0, 0, 255]
+            );
+
+            let material = Material::new(
+                &path("vert.wgsl"),
+                &path("frag.wgsl"),
+                Vec::new(),
+                None,
+                true,
+                headless.device.clone(),
+            );
+            let entity =
+                scene.create_entity(EntityId::MAX, true, Vec::new(), Some((vec![material], 0)));
+            let quad = MeshComponent::quad(scene.get_concept_manager());
+            headless.add_component(&mut scene, entity, Box::new(quad));
+            assert!(scene.pipelines.is_empty());
+
+            assert_eq!(
+                headless.render_to_image(&mut scene).get_pixel(32, 32).0,
+                [0, 255, 0, 255]
+            );
+            assert!(scene
+                .pipelines
+                .contains_key(scene.materials[&entity].0[0].id()));
+        });
+    }
+
+    #[test]
+    fn two_texture_materials_draw_both_textures() {
+        with_engine_systems(|engine_systems| {
+            let Some(headless) = Headless::new(engine_systems) else {
+                return;
+            };
+            let fragment_source = "
+                @group(0) @binding(0) var red: texture_2d<f32>;
+                @group(0) @binding(1) var red_sampler: sampler;
+                @group(0) @binding(2) var blue: texture_2d<f32>;
+                @group(0) @binding(3) var blue_sampler: sampler;
+
+                @fragment
+                fn main() -> @location(0) vec4<f32> {
+                    let uv = vec2<f32>(0.5, 0.5);
+                    let color = textureSample(red, red_sampler, uv) + textureSample(blue, blue_sampler, uv);
+                    return vec4<f32>(color.rgb, 1.0);
+                }
+            ";
+            let dir = shader_dir(
+                "two_texture_materials",
+                &[("vert.wgsl", VERTEX_SHADER), ("frag.wgsl", fragment_source)],
+            );
+            let path = |name: &str| dir.join(name).to_str().unwrap().to_string();
+            let texture = |color: [u8; 4]| {
+                let image = image::RgbaImage::from_pixel(1, 1, image::Rgba(color));
+                Rc::new(
+                    Texture::from_rgba(
+                        &headless.device,
+                        &headless.queue,
+                        &image,
+                        None,
+                        false,
+                        false,
+                    )
+                    .unwrap(),
+                )
+            };
+
+            headless
+                .device
+                .push_error_scope(wgpu::ErrorFilter::Validation);
+            let mut scene = Scene::default();
+            let material = Material::new(
+                &path("vert.wgsl"),
+                &path("frag.wgsl"),
+                vec![texture([255, 0, 0, 255]), texture([0, 0, 255, 255])],
+                None,
+                true,
+                headless.device.clone(),
+            );
+            add_quad(&mut scene, vec![material]);
+            headless.initialize(&mut scene, 1);
+            let image = headless.render_to_image(&mut scene);
+            let error = pollster::block_on(headless.device.pop_error_scope());
+
+            assert!(error.is_none(), "{error:?}");
+            assert!(scene.failed_pipelines.is_empty());
+            assert_eq!(image.get_pixel(32, 32).0, [255, 0, 255, 255]);
+        });
+    }
+
+    #[test]
+    fn disabled_parents_stop_their_children_updating() {
+        with_engine_systems(|engine_systems| {
+            let Some(headless) = Headless::new(engine_systems) else {
+                return;
+            };
+            let mut scene = Scene::default();
+            let grandparent = scene.create_entity(EntityId::MAX, true, Vec::new(), None);
+            let parent = scene.create_entity(grandparent, true, Vec::new(), None);
+            let concept_manager = scene.get_concept_manager();
+            let components: Vec<Component> = vec![
+                Box::new(TransformComponent::new(
+                    concept_manager.clone(),
+                    na::Vector3::zeros(),
+                    Rotor3::default(),
+                    na::Vector3::repeat(1.0),
+                )),
+                Box::new(PhysicsComponent::new(
+                    concept_manager,
+                    na::Vector3::new(1.0, 0.0, 0.0),
+                    na::Vector3::zeros(),
+                    1.0,
+                    Bivector::new(0.0, 0.0, 0.0),
+                    Bivector::new(0.0, 0.0, 0.0),
+                )),
+            ];
+            let child = scene.create_entity(parent, true, components, None);
+
+            assert!(scene.set_entity_active(grandparent, false));
+            headless.fixed_update(&mut scene);
+            assert!(!scene.is_entity_active(child));
+            assert_eq!(position(&scene, child), na::Vector3::zeros());
+
+            assert!(scene.set_entity_active(grandparent, true));
+            headless.fixed_update(&mut scene);
+            let moved_to = position(&scene, child);
+            assert!(moved_to.x > 0.0);
+
+            assert!(scene.set_entity_active(parent, false));
+            headless.fixed_update(&mut scene);
+            assert_eq!(position(&scene, child), moved_to);
+        });
+    }
+
+    #[test]
+    fn fixed_steps_simulate_the_same_at_any_frame_rate() {
+        with_engine_systems(|engine_systems| {
+            let Some(headless) = Headless::new(engine_systems) else {
+                return;
+            };
+            // Each a second of frames, in milliseconds
+            let frame_rates = [
+                vec![5; 200],
+                vec![8; 125],
+                vec![25; 40],
+                vec![40; 25],
+                [3, 37].repeat(25),
+            ];
+
+            let results = frame_rates.map(|frame_durations| {
+                let mut engine_details = EngineDetails::new((64, 64));
+                engine_details.fixed_timestep = Duration::from_millis(10);
+                *headless.engine_details.lock().unwrap() = engine_details;
+
+                let mut scene = Scene::default();
+                let concept_manager = scene.get_concept_manager();
+                let body = PhysicsComponent::new(
+                    concept_manager.clone(),
+                    na::Vector3::new(1.0, 2.0, 0.0),
+                    na::Vector3::zeros(),
+                    1.0,
+                    Bivector::new(0.0, 0.0, 0.0),
+                    Bivector::new(0.0, 0.0, 0.0),
+                );
+                body.set_gravity(concept_manager.clone(), na::Vector3::new(0.0, -9.81, 0.0));
+                let components: Vec<Component> = vec![
+                    Box::new(TransformComponent::new(
+                        concept_manager,
+                        na::Vector3::zeros(),
+                        Rotor3::default(),
+                        na::Vector3::repeat(1.0),
+                    )),
+                    Box::new(body),
+                ];
+                let entity = scene.create_entity(EntityId::MAX, true, components, None);
+
+                let mut total_steps = 0;
+                for frame_duration in frame_durations {
+                    let steps = {
+                        let mut engine_details = headless.engine_details.lock().unwrap();
+                        engine_details.last_frame_duration = Duration::from_millis(frame_duration);
+                        engine_details.take_fixed_steps()
+                    };
+                    for _ in 0..steps {
+                        headless.fixed_update(&mut scene);
+                    }
+                    total_steps += steps;
+                }
+                let alpha = headless.engine_details.lock().unwrap().fixed_step_alpha;
+                (total_steps, alpha, position(&scene, entity))
+            });
+
+            for (total_steps, alpha, position) in results {
+                assert_eq!(total_steps, 100);
+                assert_eq!(alpha, 0.0);
+                assert_eq!(position, results[0].2);
+            }
+        });
+    }
+
+    #[test]
+    fn skipped_frames_end_the_ui_frame() {
+        with_engine_systems(|engine_systems| {
+            let Some(headless) = Headless::new(engine_systems) else {
+                return;
+            };
+            let mut scene = Scene::default();
+            headless.initialize(&mut scene, 1);
+
+            let mut target = headless.render_target();
+
+            // Right after a resize the depth texture is still the old size, the frame is skipped
+            // and the next one drawn once it has caught up
+            for depth_size in [(32, 32), (64, 64)] {
+                scene.ui_draw(
+                    headless.device.clone(),
+                    headless.queue.clone(),
+                    headless.engine_details.clone(),
+                    headless.engine_systems.clone(),
+                    headless.ui_manager.clone(),
+                );
+                let depth_texture = Arc::new(Texture::create_depth_texture_with_size(
+                    &headless.device,
+                    depth_size,
+                    "Test Depth Texture",
+                ));
+                let rendered = headless.render_scene(&mut scene, &mut target, depth_texture);
+                assert_eq!(rendered, depth_size == (64, 64));
+
+                if rendered {
+                    let mut encoder = headless
+                        .device
+                        .create_command_encoder(&CommandEncoderDescriptor::default());
+                    scene.render_overlay(
+                        headless.device.clone(),
+                        headless.queue.clone(),
+                        &mut encoder,
+                        &target.view,
+                        (64, 64),
+                        headless.ui_manager.clone(),
+                    );
+                    headless.queue.submit(std::iter::once(encoder.finish()));
+                }
+                assert!(!scene.ui_frame_pending);
+            }
+        });
+    }
+
+    #[test]
+    fn skipping_the_color_clear_accumulates_frames() {
+        with_engine_systems(|engine_systems| {
+            let Some(headless) = Headless::new(engine_systems) else {
+                return;
+            };
+            let dir = shader_dir(
+                "skipping_the_color_clear",
+                &[
+                    ("vert.wgsl", VERTEX_SHADER),
+                    ("frag.wgsl", &fragment_shader([0.25, 0.0, 0.0, 1.0])),
+                ],
+            );
+            let path = |name: &str| dir.join(name).to_str().unwrap().to_string();
+
+            let mut scene = Scene::default();
+            let material = Material::new(
+                &path("vert.wgsl"),
+                &path("frag.wgsl"),
+                Vec::new(),
+                None,
+                true,
+                headless.device.clone(),
+            )
+            .with_pipeline_config(PipelineConfig {
+                blend_mode: BlendMode::Additive,
+                ..PipelineConfig::default()
+            });
+            add_quad(&mut scene, vec![material]);
+            headless.initialize(&mut scene, 1);
+
+            let mut target = headless.render_target();
+            let depth_texture = Arc::new(Texture::create_depth_texture_with_size(
+                &headless.device,
+                (64, 64),
+                "Test Depth Texture",
+            ));
+            let mut red_after_frame = |scene: &mut Scene| {
+                assert!(headless.render_scene(scene, &mut target, depth_texture.clone()));
+                let encoder = headless
+                    .device
+                    .create_command_encoder(&CommandEncoderDescriptor::default());
+                Texture::read_to_image(&headless.device, &headless.queue, encoder, &target.texture)
+                    .unwrap()
+                    .get_pixel(32, 32)
+                    .0[0]
+            };
+
+            let first = red_after_frame(&mut scene);
+            scene.set_skip_color_clear(true);
+            let second = red_after_frame(&mut scene);
+            let third = red_after_frame(&mut scene);
+            scene.set_skip_color_clear(false);
+            let cleared = red_after_frame(&mut scene);
+
+            assert!(first > 0);
+            assert!(second > first, "{second} should be brighter than {first}");
+            assert!(third > second, "{third} should be brighter than {second}");
+            assert_eq!(cleared, first);
+        });
+    }
+
+    #[test]
+    fn alpha_to_coverage_covers_part_of_each_pixel() {
+        with_engine_systems(|engine_systems| {
+            let Some(headless) = Headless::new(engine_systems) else {
+                return;
+            };
+            let dir = shader_dir(
+                "alpha_to_coverage",
+                &[
+                    ("vert.wgsl", VERTEX_SHADER),
+                    ("frag.wgsl", &fragment_shader([1.0, 1.0, 1.0, 0.5])),
+                ],
+            );
+            let path = |name: &str| dir.join(name).to_str().unwrap().to_string();
+
+            let center_with = |alpha_to_coverage| {
+                let mut scene = Scene::default();
+                let material = Material::new(
+                    &path("vert.wgsl"),
+                    &path("frag.wgsl"),
+                    Vec::new(),
+                    None,
+                    true,
+                    headless.device.clone(),
+                )
+                .with_pipeline_config(PipelineConfig {
+                    alpha_to_coverage,
+                    blend_mode: BlendMode::Opaque,
+                    ..PipelineConfig::default()
+                });
+                add_quad(&mut scene, vec![material]);
+                headless.initialize(&mut scene, 4);
+                assert!(scene.failed_pipelines.is_empty());
+                headless.render_to_image(&mut scene).get_pixel(32, 32).0
+            };
+
+            // Every sample is written without it, about half of them with it
+            assert_eq!(center_with(false)[..3], [255, 255, 255]);
+            let [red, green, blue, _] = center_with(true);
+            assert!(0 < red && red < 255, "{red} should be partly covered");
+            assert_eq!((red, red), (green, blue));
+        });
+    }
+
+    #[test]
+    fn initialize_keeps_the_scenes_components() {
+        with_engine_systems(|engine_systems| {
+            let Some(headless) = Headless::new(engine_systems) else {
+                return;
+            };
+            let mut scene = Scene::default();
+            let entity = add_box(&mut scene, 0.0, Some(1.0));
+
+            headless.initialize(&mut scene, 1);
+
+            let components = &scene.components[&entity];
+            assert_eq!(components.len(), 3);
+            let transform = Scene::get_component::<TransformComponent>(components).unwrap();
+            assert!(transform.buf.is_some());
+            assert!(transform.handles.is_some());
+            assert!(Scene::get_component::<PhysicsComponent>(components)
+                .unwrap()
+                .handles
+                .is_some());
+
+            headless.fixed_update(&mut scene);
+            let timestep = headless.engine_details.lock().unwrap().fixed_timestep;
+            assert!((position(&scene, entity).x - timestep.as_secs_f32()).abs() < 1e-5);
+        });
+    }
+
+    #[test]
+    fn initialize_async_records_failed_pipelines() {
+        with_engine_systems(|engine_systems| {
+            let Some(headless) = Headless::new(engine_systems) else {
+                return;
+            };
+            let dir = shader_dir(
+                "initialize_async",
+                &[
+                    ("vert.wgsl", VERTEX_SHADER),
+                    ("frag.wgsl", &fragment_shader([1.0; 4])),
+                ],
+            );
+            let path = |name: &str| dir.join(name).to_str().unwrap().to_string();
+
+            let mut scene = Scene::default();
+            let concept_manager = scene.get_concept_manager();
+            for fragment_shader_path in ["frag.wgsl", "missing.wgsl"] {
+                let components: Vec<Component> =
+                    vec![Box::new(MeshComponent::quad(concept_manager.clone()))];
+                let material = Material::new(
+                    &path("vert.wgsl"),
+                    &path(fragment_shader_path),
+                    Vec::new(),
+                    None,
+                    true,
+                    headless.device.clone(),
+                );
+                scene.create_entity(EntityId::MAX, true, components, Some((vec![material], 0)));
+            }
+
+            let result = tokio::runtime::Runtime::new()
+                .unwrap()
+                .block_on(scene.initialize_async(
+                    headless.device.clone(),
+                    headless.queue.clone(),
+                    Renderer::HEADLESS_FORMAT,
+                    1,
+                    Texture::DEPTH_FORMAT,
+                    headless.engine_details.clone(),
+                    headless.engine_systems.clone(),
+                    headless.ui_manager.clone(),
+                ));
+
+            assert!(matches!(result, Err(PipelineError::PathNotFound(_))));
+            assert!(scene.initialized);
+            assert_eq!(scene.pipelines.len(), 1);
+            assert_eq!(scene.failed_pipelines.len(), 1);
+            assert!(scene
+                .failed_pipelines
+                .iter()
+                .all(|id| id.fragment_shader_path.ends_with("missing.wgsl")));
+        });
+    }
+
+    #[test]
+    fn text_is_drawn_in_the_ui_format() {
+        with_engine_systems(|engine_systems| {
+            let Some(headless) = Headless::new(engine_systems) else {
+                return;
+            };
+            let mut scene = Scene::default();
+            headless.initialize(&mut scene, 1);
+            if scene.text_state.font_system.db().faces().next().is_none() {
+                return;
+            }
+            let white = glyphon::Color::rgb(255, 255, 255);
+            scene.text_state.text_items.push(TextParams {
+                metrics: glyphon::Metrics::new(32.0, 32.0),
+                text: "WW".to_string(),
+                color: white,
+                family: glyphon::Family::SansSerif,
+                weight: glyphon::Weight::BOLD,
+                fancy_render: false,
+                top_left_position: (0.0, 0.0),
+                anchor: TextAnchor::Screen,
+                text_scale: 1.0,
+                bounds: glyphon::TextBounds {
+                    left: 0,
+                    top: 0,
+                    right: 64,
+                    bottom: 64,
+                },
+                default_color: white,
+            });
+
+            let texture = headless.device.create_texture(&wgpu::TextureDescriptor {
+                label: Some("Test Overlay Target"),
+                size: wgpu::Extent3d {
+                    width: 64,
+                    height: 64,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: headless.ui_manager.lock().unwrap().texture_format(),
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+                view_formats: &[],
+            });
+            let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+            let mut encoder = headless
+                .device
+                .create_command_encoder(&CommandEncoderDescriptor::default());
+            scene.render_overlay(
+                headless.device.clone(),
+                headless.queue.clone(),
+                &mut encoder,
+                &view,
+                (64, 64),
+                headless.ui_manager.clone(),
+            );
+
+            let image =
+                Texture::read_to_image(&headless.device, &headless.queue, encoder, &texture)
+                    .unwrap();
+            assert!(image.pixels().any(|pixel| pixel.0[3] > 0));
+        });
     }
 }
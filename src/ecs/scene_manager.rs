@@ -0,0 +1,113 @@
+use super::scene::Scene;
+
+/// Which scenes of a [SceneManager]'s stack take part in updating or rendering
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum StackMode {
+    /// Only the scene on top
+    #[default]
+    Top,
+    /// Every scene, from the bottom of the stack up
+    All,
+}
+
+/// Changes the [SceneManager]'s stack from inside components, emit it through
+/// [crate::EngineSystems::events]. It's applied at the start of the next frame
+pub enum SceneCommand {
+    Push(Scene),
+    Pop,
+    Replace(Scene),
+}
+
+/// A stack of scenes, e.g. a pause menu pushed over the game. The engine updates and renders
+/// the scenes chosen by [SceneManager::update_mode] and [SceneManager::render_mode], with the
+/// scenes above the bottom one drawn over it without clearing the color.
+///
+/// A scene's components are initialized the first frame the scene is updated or rendered, so
+/// a scene pushed mid-game is initialized the frame after. Scenes below the top don't get
+/// any hooks while they're covered, and continue where they left off once uncovered. Popped
+/// and replaced scenes are handed back as they are, no hooks run on their components either
+#[derive(Default)]
+pub struct SceneManager {
+    scenes: Vec<Scene>,
+    /// Which scenes run their updates, fixed updates and input events. Only the top scene by
+    /// default, so covered scenes are paused
+    pub update_mode: StackMode,
+    /// Which scenes are drawn, only the top scene by default. The UI is only drawn by the top
+    /// scene, text by every drawn scene
+    pub render_mode: StackMode,
+}
+
+impl SceneManager {
+    /// Puts `scene` on top of the stack
+    pub fn push(&mut self, scene: Scene) {
+        self.scenes.push(scene);
+    }
+
+    /// Takes the top scene off the stack, uncovering the one below
+    pub fn pop(&mut self) -> Option<Scene> {
+        self.scenes.pop()
+    }
+
+    /// Swaps the top scene for `scene`, e.g. from a menu into the game. Pushes it onto an empty
+    /// stack
+    pub fn replace(&mut self, scene: Scene) -> Option<Scene> {
+        let previous = self.scenes.pop();
+        self.scenes.push(scene);
+        previous
+    }
+
+    pub fn apply(&mut self, command: SceneCommand) {
+        match command {
+            SceneCommand::Push(scene) => self.push(scene),
+            SceneCommand::Pop => {
+                self.pop();
+            }
+            SceneCommand::Replace(scene) => {
+                self.replace(scene);
+            }
+        }
+    }
+
+    pub fn top(&self) -> Option<&Scene> {
+        self.scenes.last()
+    }
+
+    pub fn top_mut(&mut self) -> Option<&mut Scene> {
+        self.scenes.last_mut()
+    }
+
+    /// Bottom of the stack first
+    pub fn scenes(&self) -> &[Scene] {
+        &self.scenes
+    }
+
+    pub fn scenes_mut(&mut self) -> &mut [Scene] {
+        &mut self.scenes
+    }
+
+    pub fn len(&self) -> usize {
+        self.scenes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.scenes.is_empty()
+    }
+
+    /// Index of the first scene `mode` includes
+    fn first_index(&self, mode: StackMode) -> usize {
+        match mode {
+            StackMode::Top => self.scenes.len().saturating_sub(1),
+            StackMode::All => 0,
+        }
+    }
+
+    /// Whether the scene at `index` of the stack is updated this frame
+    pub(crate) fn is_updating(&self, index: usize) -> bool {
+        index >= self.first_index(self.update_mode)
+    }
+
+    /// Whether the scene at `index` of the stack is rendered this frame
+    pub(crate) fn is_rendering(&self, index: usize) -> bool {
+        index >= self.first_index(self.render_mode)
+    }
+}
@@ -0,0 +1,237 @@
+use std::collections::{HashMap, HashSet};
+
+use nalgebra as na;
+
+use super::entity::EntityId;
+
+pub type CellCoord = na::Vector3<i32>;
+
+/// Uniform spatial hash of entity bounding boxes, used as the broad phase for collisions and
+/// raycasts. Entities are stored in every cell their box overlaps
+#[derive(Debug, Clone)]
+pub struct SpatialGrid {
+    cell_size: f32,
+    cells: HashMap<CellCoord, Vec<EntityId>>,
+    /// Smallest and largest occupied cell, bounds ray traversal
+    occupied_bounds: Option<(CellCoord, CellCoord)>,
+}
+
+impl Default for SpatialGrid {
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_CELL_SIZE)
+    }
+}
+
+impl SpatialGrid {
+    pub const DEFAULT_CELL_SIZE: f32 = 4.0;
+
+    /// `cell_size` should be around the size of a typical collider, much smaller cells make
+    /// large colliders span many cells
+    pub fn new(cell_size: f32) -> Self {
+        SpatialGrid {
+            cell_size: cell_size.max(f32::EPSILON),
+            cells: HashMap::new(),
+            occupied_bounds: None,
+        }
+    }
+
+    pub fn cell_size(&self) -> f32 {
+        self.cell_size
+    }
+
+    pub fn clear(&mut self) {
+        self.cells.clear();
+        self.occupied_bounds = None;
+    }
+
+    pub fn cell_of(&self, point: na::Vector3<f32>) -> CellCoord {
+        (point / self.cell_size).map(|coord| coord.floor() as i32)
+    }
+
+    /// Returns `false` without inserting if the box isn't finite, a NaN or infinite corner
+    /// would otherwise span billions of cells
+    pub fn insert(
+        &mut self,
+        entity_id: EntityId,
+        min: na::Vector3<f32>,
+        max: na::Vector3<f32>,
+    ) -> bool {
+        if !min.iter().chain(max.iter()).all(|coord| coord.is_finite()) {
+            log::warn!("Entity {entity_id} has non-finite bounds and was left out of the grid");
+            return false;
+        }
+        let (min_cell, max_cell) = (self.cell_of(min), self.cell_of(max));
+        for x in min_cell.x..=max_cell.x {
+            for y in min_cell.y..=max_cell.y {
+                for z in min_cell.z..=max_cell.z {
+                    self.cells
+                        .entry(CellCoord::new(x, y, z))
+                        .or_default()
+                        .push(entity_id);
+                }
+            }
+        }
+        self.occupied_bounds = Some(match self.occupied_bounds {
+            Some((bounds_min, bounds_max)) => {
+                (bounds_min.inf(&min_cell), bounds_max.sup(&max_cell))
+            }
+            None => (min_cell, max_cell),
+        });
+        true
+    }
+
+    /// Every pair of entities sharing at least one cell, each pair once with the smaller id first
+    pub fn candidate_pairs(&self) -> HashSet<(EntityId, EntityId)> {
+        let mut pairs = HashSet::new();
+        for entities in self.cells.values() {
+            for (i, a) in entities.iter().enumerate() {
+                for b in &entities[i + 1..] {
+                    if a != b {
+                        pairs.insert((*a.min(b), *a.max(b)));
+                    }
+                }
+            }
+        }
+        pairs
+    }
+
+    /// Entities in the cells the ray passes through, in the order they're reached. The walk stops
+    /// once the ray leaves the occupied part of the grid. `direction` must be normalized
+    pub fn ray_candidates(
+        &self,
+        origin: na::Vector3<f32>,
+        direction: na::Vector3<f32>,
+    ) -> Vec<EntityId> {
+        let Some((min_cell, max_cell)) = self.occupied_bounds else {
+            return Vec::new();
+        };
+        let region_min = min_cell.cast::<f32>() * self.cell_size;
+        let region_max = (max_cell + CellCoord::repeat(1)).cast::<f32>() * self.cell_size;
+
+        // Start where the ray enters the occupied region
+        let is_inside =
+            (0..3).all(|axis| origin[axis] >= region_min[axis] && origin[axis] <= region_max[axis]);
+        let start = if is_inside {
+            origin
+        } else {
+            let mut t_enter = 0.0_f32;
+            let mut t_exit = f32::INFINITY;
+            for axis in 0..3 {
+                if direction[axis].abs() < f32::EPSILON {
+                    if origin[axis] < region_min[axis] || origin[axis] > region_max[axis] {
+                        return Vec::new();
+                    }
+                    continue;
+                }
+                let t_0 = (region_min[axis] - origin[axis]) / direction[axis];
+                let t_1 = (region_max[axis] - origin[axis]) / direction[axis];
+                t_enter = t_enter.max(t_0.min(t_1));
+                t_exit = t_exit.min(t_0.max(t_1));
+            }
+            if t_enter > t_exit {
+                return Vec::new();
+            }
+            origin + direction * t_enter
+        };
+
+        // Cell by cell walk along the ray (Amanatides and Woo)
+        let mut cell = self.cell_of(start).sup(&min_cell).inf(&max_cell);
+        let mut step = CellCoord::zeros();
+        let mut t_max = na::Vector3::repeat(f32::INFINITY);
+        let mut t_delta = na::Vector3::repeat(f32::INFINITY);
+        for axis in 0..3 {
+            if direction[axis].abs() < f32::EPSILON {
+                continue;
+            }
+            step[axis] = direction[axis].signum() as i32;
+            let next_boundary = if step[axis] > 0 {
+                (cell[axis] + 1) as f32 * self.cell_size
+            } else {
+                cell[axis] as f32 * self.cell_size
+            };
+            t_max[axis] = (next_boundary - start[axis]) / direction[axis];
+            t_delta[axis] = self.cell_size / direction[axis].abs();
+        }
+
+        let mut seen = HashSet::new();
+        let mut candidates = Vec::new();
+        loop {
+            if let Some(entities) = self.cells.get(&cell) {
+                candidates.extend(entities.iter().filter(|entity| seen.insert(**entity)));
+            }
+            let axis = t_max.imin();
+            if t_max[axis].is_infinite() {
+                break;
+            }
+            cell[axis] += step[axis];
+            if cell[axis] < min_cell[axis] || cell[axis] > max_cell[axis] {
+                break;
+            }
+            t_max[axis] += t_delta[axis];
+        }
+        candidates
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn insert_cube(grid: &mut SpatialGrid, entity_id: EntityId, center: [f32; 3], half: f32) {
+        let center = na::Vector3::from(center);
+        assert!(grid.insert(
+            entity_id,
+            center - na::Vector3::repeat(half),
+            center + na::Vector3::repeat(half),
+        ));
+    }
+
+    #[test]
+    fn candidate_pairs_share_a_cell() {
+        let mut grid = SpatialGrid::new(1.0);
+        insert_cube(&mut grid, 0, [0.5, 0.5, 0.5], 0.25);
+        insert_cube(&mut grid, 1, [0.6, 0.6, 0.6], 0.25);
+        // Spans the cells of both of the others, but is only paired with each once
+        insert_cube(&mut grid, 2, [1.0, 1.0, 1.0], 0.6);
+        insert_cube(&mut grid, 3, [10.5, 0.5, 0.5], 0.25);
+
+        let pairs = grid.candidate_pairs();
+        assert_eq!(pairs, HashSet::from([(0, 1), (0, 2), (1, 2)]));
+    }
+
+    #[test]
+    fn ray_candidates_follow_the_ray_in_order() {
+        let mut grid = SpatialGrid::new(1.0);
+        insert_cube(&mut grid, 0, [5.5, 0.5, 0.5], 0.25);
+        insert_cube(&mut grid, 1, [2.5, 0.5, 0.5], 0.25);
+        insert_cube(&mut grid, 2, [2.5, 5.5, 0.5], 0.25);
+
+        let candidates = grid.ray_candidates(na::Vector3::new(-3.0, 0.5, 0.5), na::Vector3::x());
+        assert_eq!(candidates, vec![1, 0]);
+
+        let backwards = grid.ray_candidates(na::Vector3::new(9.0, 0.5, 0.5), -na::Vector3::x());
+        assert_eq!(backwards, vec![0, 1]);
+
+        let missing = grid.ray_candidates(na::Vector3::new(-3.0, 20.5, 0.5), na::Vector3::x());
+        assert!(missing.is_empty());
+    }
+
+    #[test]
+    fn non_finite_bounds_are_rejected() {
+        let mut grid = SpatialGrid::new(1.0);
+        assert!(!grid.insert(
+            0,
+            na::Vector3::new(f32::NEG_INFINITY, 0.0, 0.0),
+            na::Vector3::repeat(1.0),
+        ));
+        assert!(!grid.insert(
+            0,
+            na::Vector3::zeros(),
+            na::Vector3::new(0.0, f32::NAN, 0.0)
+        ));
+        assert!(grid.candidate_pairs().is_empty());
+        assert!(grid
+            .ray_candidates(na::Vector3::zeros(), na::Vector3::x())
+            .is_empty());
+    }
+}
@@ -0,0 +1,48 @@
+use std::{
+    any::{Any, TypeId},
+    collections::HashMap,
+};
+
+/// Typed messages between components. Events emitted during a frame can be read during the
+/// next one, then they're dropped
+#[derive(Debug, Default)]
+pub struct EventBus {
+    /// Emitted this frame
+    pending: HashMap<TypeId, Vec<Box<dyn Any>>>,
+    /// Emitted last frame, what [EventBus::events] and [EventBus::drain] read
+    ready: HashMap<TypeId, Vec<Box<dyn Any>>>,
+}
+
+impl EventBus {
+    pub fn emit<E: Any>(&mut self, event: E) {
+        self.pending
+            .entry(TypeId::of::<E>())
+            .or_default()
+            .push(Box::new(event));
+    }
+
+    /// Reads last frame's events without consuming them, so every listener sees them
+    pub fn events<E: Any>(&self) -> impl Iterator<Item = &E> {
+        self.ready
+            .get(&TypeId::of::<E>())
+            .into_iter()
+            .flatten()
+            .filter_map(|event| event.downcast_ref::<E>())
+    }
+
+    /// Takes last frame's events, later listeners won't see them
+    pub fn drain<E: Any>(&mut self) -> Vec<E> {
+        self.ready
+            .remove(&TypeId::of::<E>())
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|event| event.downcast::<E>().ok())
+            .map(|event| *event)
+            .collect()
+    }
+
+    /// Called by the engine at the end of every frame
+    pub fn next_frame(&mut self) {
+        self.ready = std::mem::take(&mut self.pending);
+    }
+}
@@ -0,0 +1,134 @@
+use std::collections::HashMap;
+
+use nalgebra as na;
+use sdl2::{
+    controller::{Axis, Button, GameController},
+    GameControllerSubsystem, Sdl,
+};
+
+/// Buttons of the standard (Xbox style) layout. SDL maps every supported controller onto it, so
+/// e.g. [Button::A] is always the bottom face button
+pub const STANDARD_BUTTONS: [Button; 15] = [
+    Button::A,
+    Button::B,
+    Button::X,
+    Button::Y,
+    Button::Back,
+    Button::Guide,
+    Button::Start,
+    Button::LeftStick,
+    Button::RightStick,
+    Button::LeftShoulder,
+    Button::RightShoulder,
+    Button::DPadUp,
+    Button::DPadDown,
+    Button::DPadLeft,
+    Button::DPadRight,
+];
+
+/// Snapshot of one controller, taken once per frame into [crate::EngineDetails::gamepads]
+#[derive(Debug, Clone, PartialEq)]
+pub struct GamepadState {
+    /// Stays the same while the controller is connected
+    pub id: u32,
+    pub name: String,
+    /// Each axis goes from `-1.0` to `1.0`, `y` points down as SDL reports it
+    pub left_stick: na::Vector2<f32>,
+    pub right_stick: na::Vector2<f32>,
+    /// `0.0` released to `1.0` fully pressed
+    pub left_trigger: f32,
+    pub right_trigger: f32,
+    pub pressed_buttons: Vec<Button>,
+}
+
+impl GamepadState {
+    pub fn is_button_down(&self, button: Button) -> bool {
+        self.pressed_buttons.contains(&button)
+    }
+}
+
+/// Opens controllers as they're connected and closes them when they're removed
+pub struct GamepadManager {
+    subsystem: GameControllerSubsystem,
+    controllers: HashMap<u32, GameController>,
+    /// Stick deflection below this (from `0.0` to `1.0`) reads as zero, hides stick drift
+    pub deadzone: f32,
+}
+
+impl GamepadManager {
+    pub const DEFAULT_DEADZONE: f32 = 0.15;
+
+    pub fn new(sdl_context: &Sdl) -> Self {
+        GamepadManager {
+            subsystem: sdl_context.game_controller().unwrap(),
+            controllers: HashMap::new(),
+            deadzone: Self::DEFAULT_DEADZONE,
+        }
+    }
+
+    /// Handles [sdl2::event::Event::ControllerDeviceAdded], which SDL also sends for controllers
+    /// that were already plugged in at startup
+    pub fn connect(&mut self, joystick_index: u32) {
+        match self.subsystem.open(joystick_index) {
+            Ok(controller) => {
+                log::info!("Gamepad connected: {}", controller.name());
+                self.controllers
+                    .insert(controller.instance_id(), controller);
+            }
+            Err(err) => log::warn!("Failed to open gamepad {joystick_index}: {err}"),
+        }
+    }
+
+    /// Handles [sdl2::event::Event::ControllerDeviceRemoved]
+    pub fn disconnect(&mut self, instance_id: u32) {
+        if let Some(controller) = self.controllers.remove(&instance_id) {
+            log::info!("Gamepad disconnected: {}", controller.name());
+        }
+    }
+
+    pub fn states(&self) -> Vec<GamepadState> {
+        let mut states = self
+            .controllers
+            .iter()
+            .map(|(id, controller)| GamepadState {
+                id: *id,
+                name: controller.name(),
+                left_stick: self.read_stick(controller, Axis::LeftX, Axis::LeftY),
+                right_stick: self.read_stick(controller, Axis::RightX, Axis::RightY),
+                left_trigger: Self::read_axis(controller, Axis::TriggerLeft),
+                right_trigger: Self::read_axis(controller, Axis::TriggerRight),
+                pressed_buttons: STANDARD_BUTTONS
+                    .into_iter()
+                    .filter(|button| controller.button(*button))
+                    .collect(),
+            })
+            .collect::<Vec<_>>();
+        // Connection order, so the first pad stays first
+        states.sort_by_key(|state| state.id);
+        states
+    }
+
+    fn read_axis(controller: &GameController, axis: Axis) -> f32 {
+        (controller.axis(axis) as f32 / i16::MAX as f32).clamp(-1.0, 1.0)
+    }
+
+    /// Applies a radial deadzone and rescales the rest of the range back to `0.0..1.0`, so
+    /// small deflections past the deadzone still give small values
+    fn read_stick(
+        &self,
+        controller: &GameController,
+        x_axis: Axis,
+        y_axis: Axis,
+    ) -> na::Vector2<f32> {
+        let stick = na::Vector2::new(
+            Self::read_axis(controller, x_axis),
+            Self::read_axis(controller, y_axis),
+        );
+        let magnitude = stick.norm();
+        if magnitude <= self.deadzone {
+            return na::Vector2::zeros();
+        }
+        let scaled_magnitude = ((magnitude - self.deadzone) / (1.0 - self.deadzone)).min(1.0);
+        stick / magnitude * scaled_magnitude
+    }
+}
@@ -0,0 +1,121 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use wgpu::{Buffer, BufferDescriptor, BufferUsages, Device, Features, QuerySet, Queue};
+
+/// Measures how long the GPU takes to render a frame with timestamp queries. Results are read
+/// back without blocking, so they arrive a frame or two late
+pub(crate) struct GpuTimer {
+    query_set: QuerySet,
+    resolve_buffer: Buffer,
+    readback_buffer: Buffer,
+    /// Nanoseconds per timestamp tick
+    period: f32,
+    /// Set by the readback buffer's map callback, `Some(true)` once it can be read
+    mapped: Arc<Mutex<Option<bool>>>,
+    /// Whether the readback buffer is being mapped, new timings aren't copied into it until then
+    pending: bool,
+}
+
+impl GpuTimer {
+    pub(crate) const FEATURES: Features =
+        Features::TIMESTAMP_QUERY.union(Features::TIMESTAMP_QUERY_INSIDE_ENCODERS);
+    const QUERY_SIZE: u64 = std::mem::size_of::<u64>() as u64;
+
+    /// `None` if the device doesn't have [GpuTimer::FEATURES]
+    pub(crate) fn new(device: &Device, queue: &Queue) -> Option<Self> {
+        if !device.features().contains(Self::FEATURES) {
+            return None;
+        }
+
+        let query_set = device.create_query_set(&wgpu::QuerySetDescriptor {
+            label: Some("GPU timer query set"),
+            ty: wgpu::QueryType::Timestamp,
+            count: 2,
+        });
+        let resolve_buffer = device.create_buffer(&BufferDescriptor {
+            label: Some("GPU timer resolve buffer"),
+            size: Self::QUERY_SIZE * 2,
+            usage: BufferUsages::QUERY_RESOLVE | BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        let readback_buffer = device.create_buffer(&BufferDescriptor {
+            label: Some("GPU timer readback buffer"),
+            size: Self::QUERY_SIZE * 2,
+            usage: BufferUsages::MAP_READ | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        Some(GpuTimer {
+            query_set,
+            resolve_buffer,
+            readback_buffer,
+            period: queue.get_timestamp_period(),
+            mapped: Arc::new(Mutex::new(None)),
+            pending: false,
+        })
+    }
+
+    /// Submits the start timestamp, call right before submitting the frame's work
+    pub(crate) fn begin(&self, device: &Device, queue: &Queue) {
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("GPU timer begin encoder"),
+        });
+        encoder.write_timestamp(&self.query_set, 0);
+        queue.submit(Some(encoder.finish()));
+    }
+
+    /// Submits the end timestamp and starts reading both back if the last reading is done
+    pub(crate) fn end(&mut self, device: &Device, queue: &Queue) {
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("GPU timer end encoder"),
+        });
+        encoder.write_timestamp(&self.query_set, 1);
+        encoder.resolve_query_set(&self.query_set, 0..2, &self.resolve_buffer, 0);
+        if !self.pending {
+            encoder.copy_buffer_to_buffer(
+                &self.resolve_buffer,
+                0,
+                &self.readback_buffer,
+                0,
+                Self::QUERY_SIZE * 2,
+            );
+        }
+        queue.submit(Some(encoder.finish()));
+
+        if !self.pending {
+            self.pending = true;
+            let mapped = self.mapped.clone();
+            self.readback_buffer
+                .slice(..)
+                .map_async(wgpu::MapMode::Read, move |result| {
+                    *mapped.lock().unwrap() = Some(result.is_ok());
+                });
+        }
+    }
+
+    /// The latest GPU frame time, if a new one has been read back since the last call
+    pub(crate) fn read(&mut self, device: &Device) -> Option<Duration> {
+        device.poll(wgpu::Maintain::Poll);
+
+        let mapped = self.mapped.lock().unwrap().take()?;
+        self.pending = false;
+        if !mapped {
+            return None;
+        }
+
+        let timestamps: [u64; 2] = {
+            let data = self.readback_buffer.slice(..).get_mapped_range();
+            let timestamps: &[u64] = bytemuck::cast_slice(&data);
+            [timestamps[0], timestamps[1]]
+        };
+        self.readback_buffer.unmap();
+
+        let ticks = timestamps[1].saturating_sub(timestamps[0]);
+        Some(Duration::from_nanos(
+            (ticks as f64 * self.period as f64) as u64,
+        ))
+    }
+}
@@ -0,0 +1,98 @@
+use std::collections::HashMap;
+
+use wgpu::{util::DrawIndexedIndirectArgs, Buffer, BufferUsages, Device, Queue};
+
+use crate::ecs::entity::EntityId;
+
+/// The draw arguments of every mesh in a scene, kept in one buffer the render pass issues
+/// indirect draws from. See [crate::ecs::scene::Scene::set_indirect_draws]
+///
+/// The buffer can also be bound as a storage buffer, so a compute pass can cull draws on the
+/// GPU by setting their `instance_count` to `0`. The arguments are only rewritten from the CPU
+/// when meshes are added or removed, which resets the culling. They always start at instance
+/// `0`, a pass writing another `first_instance` needs [wgpu::Features::INDIRECT_FIRST_INSTANCE]
+#[derive(Default)]
+pub struct IndirectDraws {
+    buffer: Option<Buffer>,
+    /// Number of draws the buffer has room for
+    capacity: usize,
+    /// `(entity, submesh)` of each draw and its index count, in buffer order
+    draws: Vec<((EntityId, usize), u32)>,
+    slots: HashMap<(EntityId, usize), u32>,
+}
+
+impl IndirectDraws {
+    /// Size of one draw's arguments in the buffer, laid out as [DrawIndexedIndirectArgs]
+    pub const STRIDE: u64 = std::mem::size_of::<DrawIndexedIndirectArgs>() as u64;
+
+    /// Only there once the scene has been drawn with indirect draws
+    pub fn buffer(&self) -> Option<&Buffer> {
+        self.buffer.as_ref()
+    }
+
+    /// Index of the submesh's draw in the buffer
+    pub fn slot(&self, entity_id: EntityId, submesh_index: usize) -> Option<u32> {
+        self.slots.get(&(entity_id, submesh_index)).copied()
+    }
+
+    /// Byte offset of the submesh's draw in the buffer
+    pub fn offset(&self, entity_id: EntityId, submesh_index: usize) -> Option<u64> {
+        self.slot(entity_id, submesh_index)
+            .map(|slot| slot as u64 * Self::STRIDE)
+    }
+
+    pub fn len(&self) -> usize {
+        self.draws.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.draws.is_empty()
+    }
+
+    /// Rewrites the buffer if the set of draws changed, growing it when they don't fit
+    pub(crate) fn update(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        draws: Vec<((EntityId, usize), u32)>,
+    ) {
+        if draws == self.draws && self.buffer.is_some() {
+            return;
+        }
+
+        if self.buffer.is_none() || draws.len() > self.capacity {
+            self.capacity = draws.len().max(1).next_power_of_two();
+            self.buffer = Some(device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("Indirect Draw Buffer"),
+                size: self.capacity as u64 * Self::STRIDE,
+                usage: BufferUsages::INDIRECT | BufferUsages::STORAGE | BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            }));
+        }
+
+        let contents = draws
+            .iter()
+            .flat_map(|(_, index_count)| {
+                DrawIndexedIndirectArgs {
+                    index_count: *index_count,
+                    instance_count: 1,
+                    first_index: 0,
+                    base_vertex: 0,
+                    first_instance: 0,
+                }
+                .as_bytes()
+                .to_vec()
+            })
+            .collect::<Vec<u8>>();
+        if !contents.is_empty() {
+            queue.write_buffer(self.buffer.as_ref().unwrap(), 0, &contents);
+        }
+
+        self.slots = draws
+            .iter()
+            .enumerate()
+            .map(|(slot, (draw, _))| (*draw, slot as u32))
+            .collect();
+        self.draws = draws;
+    }
+}
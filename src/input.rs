@@ -0,0 +1,181 @@
+use std::collections::HashSet;
+
+use sdl2::{event::Event, keyboard::Scancode, mouse::MouseButton};
+
+/// Held keyboard keys and mouse buttons, along with the ones that went down or up during the
+/// current frame, so components can tell a key that was just pressed from one that's being held
+#[derive(Debug, Clone, Default)]
+pub struct InputState {
+    keys: HashSet<Scancode>,
+    just_pressed_keys: HashSet<Scancode>,
+    just_released_keys: HashSet<Scancode>,
+    mouse_buttons: HashSet<MouseButton>,
+    just_pressed_mouse_buttons: HashSet<MouseButton>,
+    just_released_mouse_buttons: HashSet<MouseButton>,
+}
+
+impl InputState {
+    /// Forgets which keys and buttons went down or up during the last frame, called before the
+    /// frame's events are handled
+    pub fn begin_frame(&mut self) {
+        self.just_pressed_keys.clear();
+        self.just_released_keys.clear();
+        self.just_pressed_mouse_buttons.clear();
+        self.just_released_mouse_buttons.clear();
+    }
+
+    /// Applies a key or mouse button event, other events are ignored. A key pressed and released
+    /// within one frame counts as both pressed and released during it. Key repeats don't count
+    /// as presses
+    pub fn handle_event(&mut self, event: &Event) {
+        match *event {
+            Event::KeyDown {
+                scancode: Some(scancode),
+                repeat: false,
+                ..
+            } => {
+                self.keys.insert(scancode);
+                self.just_pressed_keys.insert(scancode);
+            }
+            Event::KeyUp {
+                scancode: Some(scancode),
+                ..
+            } => {
+                self.keys.remove(&scancode);
+                self.just_released_keys.insert(scancode);
+            }
+            Event::MouseButtonDown { mouse_btn, .. } => {
+                self.mouse_buttons.insert(mouse_btn);
+                self.just_pressed_mouse_buttons.insert(mouse_btn);
+            }
+            Event::MouseButtonUp { mouse_btn, .. } => {
+                self.mouse_buttons.remove(&mouse_btn);
+                self.just_released_mouse_buttons.insert(mouse_btn);
+            }
+            _ => {}
+        }
+    }
+
+    pub fn is_key_down(&self, key: Scancode) -> bool {
+        self.keys.contains(&key)
+    }
+
+    /// Only true on the frame the key went down
+    pub fn was_key_pressed(&self, key: Scancode) -> bool {
+        self.just_pressed_keys.contains(&key)
+    }
+
+    /// Only true on the frame the key went up
+    pub fn was_key_released(&self, key: Scancode) -> bool {
+        self.just_released_keys.contains(&key)
+    }
+
+    pub fn pressed_keys(&self) -> impl Iterator<Item = &Scancode> {
+        self.keys.iter()
+    }
+
+    pub fn is_mouse_button_down(&self, button: MouseButton) -> bool {
+        self.mouse_buttons.contains(&button)
+    }
+
+    pub fn was_mouse_button_pressed(&self, button: MouseButton) -> bool {
+        self.just_pressed_mouse_buttons.contains(&button)
+    }
+
+    pub fn was_mouse_button_released(&self, button: MouseButton) -> bool {
+        self.just_released_mouse_buttons.contains(&button)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sdl2::keyboard::Mod;
+
+    use super::*;
+
+    fn key_event(scancode: Scancode, down: bool, repeat: bool) -> Event {
+        if down {
+            Event::KeyDown {
+                timestamp: 0,
+                window_id: 0,
+                keycode: None,
+                scancode: Some(scancode),
+                keymod: Mod::NOMOD,
+                repeat,
+            }
+        } else {
+            Event::KeyUp {
+                timestamp: 0,
+                window_id: 0,
+                keycode: None,
+                scancode: Some(scancode),
+                keymod: Mod::NOMOD,
+                repeat,
+            }
+        }
+    }
+
+    fn mouse_event(button: MouseButton, down: bool) -> Event {
+        if down {
+            Event::MouseButtonDown {
+                timestamp: 0,
+                window_id: 0,
+                which: 0,
+                mouse_btn: button,
+                clicks: 1,
+                x: 0,
+                y: 0,
+            }
+        } else {
+            Event::MouseButtonUp {
+                timestamp: 0,
+                window_id: 0,
+                which: 0,
+                mouse_btn: button,
+                clicks: 1,
+                x: 0,
+                y: 0,
+            }
+        }
+    }
+
+    #[test]
+    fn press_and_release_within_one_frame_is_seen() {
+        let mut input = InputState::default();
+        input.begin_frame();
+        input.handle_event(&key_event(Scancode::Space, true, false));
+        input.handle_event(&key_event(Scancode::Space, false, false));
+        input.handle_event(&mouse_event(MouseButton::Left, true));
+        input.handle_event(&mouse_event(MouseButton::Left, false));
+
+        assert!(input.was_key_pressed(Scancode::Space));
+        assert!(input.was_key_released(Scancode::Space));
+        assert!(!input.is_key_down(Scancode::Space));
+        assert!(input.was_mouse_button_pressed(MouseButton::Left));
+        assert!(input.was_mouse_button_released(MouseButton::Left));
+        assert!(!input.is_mouse_button_down(MouseButton::Left));
+
+        input.begin_frame();
+        assert!(!input.was_key_pressed(Scancode::Space));
+        assert!(!input.was_key_released(Scancode::Space));
+        assert!(!input.was_mouse_button_pressed(MouseButton::Left));
+    }
+
+    #[test]
+    fn held_keys_only_press_once() {
+        let mut input = InputState::default();
+        input.begin_frame();
+        input.handle_event(&key_event(Scancode::W, true, false));
+        assert!(input.was_key_pressed(Scancode::W));
+
+        input.begin_frame();
+        input.handle_event(&key_event(Scancode::W, true, true));
+        assert!(input.is_key_down(Scancode::W));
+        assert!(!input.was_key_pressed(Scancode::W));
+
+        input.begin_frame();
+        input.handle_event(&key_event(Scancode::W, false, false));
+        assert!(!input.is_key_down(Scancode::W));
+        assert!(input.was_key_released(Scancode::W));
+    }
+}
@@ -1,39 +1,72 @@
 use std::{
-    cell::{Ref, RefCell},
+    cell::{Cell, Ref, RefCell},
     rc::Rc,
     sync::Mutex,
     time::{Duration, Instant},
 };
 
-use ecs::scene::Scene;
+use asset_cache::AssetCache;
+use ecs::{
+    entity::LayerMask,
+    scene::Scene,
+    scene_manager::{SceneCommand, SceneManager},
+};
+use events::EventBus;
+use gamepad::{GamepadManager, GamepadState};
+use input::InputState;
 use sdl2::{
     event::{Event, WindowEvent},
     keyboard::Scancode,
-    mouse::RelativeMouseState,
+    mouse::{MouseWheelDirection, RelativeMouseState},
     video::Window,
     EventPump, Sdl, VideoSubsystem,
 };
 use ui_manager::UiManager;
 
-use crate::renderer::Renderer;
+use crate::{
+    gpu_timer::GpuTimer,
+    renderer::{AntialiasMode, FrameCaptured, RenderError, Renderer, RendererSettings},
+};
 
 // pub mod compute;
+pub mod asset_cache;
+pub mod atlas;
+pub mod bloom;
 pub mod compute;
+pub mod debug_draw;
+pub mod events;
+pub mod gamepad;
+mod gpu_timer;
+pub mod indirect;
+pub mod input;
 pub mod model;
+pub mod picking;
 pub mod pipeline;
+pub mod post_process;
+pub mod primitives;
 pub mod renderer;
+pub mod skybox;
+#[cfg(test)]
+mod test_utils;
 pub mod texture;
 pub mod ui_manager;
+pub mod uniform_pool;
 pub mod ecs {
     pub mod component;
     pub mod concepts;
     pub mod entity;
     pub mod material;
     pub mod scene;
+    pub mod scene_manager;
+    pub mod spatial_grid;
     pub mod components {
+        pub mod animation_component;
+        pub mod billboard_component;
         pub mod camera_component;
+        pub mod collider_component;
         pub mod mesh_component;
         pub mod physics_component;
+        pub mod sprite_component;
         pub mod transform_component;
         pub mod text_component;
     }
@@ -48,31 +81,213 @@ pub struct GameZap {
     pub details: Rc<Mutex<EngineDetails>>,
     pub ui_manager: Rc<Mutex<UiManager>>,
 
-    scenes: Vec<Scene>,
-    active_scene_index: usize,
+    /// The scenes the main loop updates and renders
+    pub scene_manager: SceneManager,
+    on_frame_stats: Option<Box<dyn FnMut(&FrameStats)>>,
+    gpu_timer: Option<GpuTimer>,
 }
 
 pub struct EngineDetails {
     pub frame_number: u128,
     pub initialized_instant: Instant,
+    /// Real time since the engine started, unaffected by [EngineDetails::time_scale]
     pub time_elapsed: Duration,
+    /// Game time the last frame took, the real time scaled by [EngineDetails::time_scale]. Used
+    /// by physics, through the fixed updates, and animations, so it's what pausing stops
     pub last_frame_duration: Duration,
+    /// Real time the last frame took, for what should stay responsive while the game is paused
+    /// or slowed down, like the UI and camera controls
+    pub real_frame_duration: Duration,
+    /// Speed of game time, `1.0` by default. `0.0` pauses the game and values in between slow it
+    /// down. Negative values are treated as `0.0`
+    pub time_scale: f32,
     pub time_of_last_frame: Instant,
     time_of_last_fps_calc: Instant,
     frame_count_at_last_fps_calc: u128,
     pub fps: u32,
 
     pub mouse_state: (Option<RelativeMouseState>, bool),
+    /// In pixels from the window's top left corner
+    pub cursor_pos: (f64, f64),
+    /// Wheel movement during the last frame, positive `y` scrolls away from the user
+    pub scroll_delta: (f64, f64),
+    /// Text typed during the last frame, already composed into UTF-8 by the platform, for text
+    /// fields. imgui gets it on its own
+    pub text_input: String,
     pub pressed_scancodes: Vec<Scancode>,
+    /// Held, just pressed and just released keys and mouse buttons
+    pub input: InputState,
+    /// Connected controllers, see [gamepad::GamepadManager]
+    pub gamepads: Vec<GamepadState>,
     pub window_aspect_ratio: f32,
+    /// In pixels
+    pub window_size: (u32, u32),
     pub render_mask: Option<RenderMask>,
     pub is_cursor_showing: bool,
+    pub clear_flags: ClearFlags,
+    /// Set to capture the next rendered frame, it arrives as a [renderer::FrameCaptured] event
+    /// the frame after. Reset once the frame has been captured
+    pub capture_frame: bool,
+    /// Time simulated by every [ecs::component::ComponentSystem::fixed_update], a 60th of a
+    /// second by default
+    pub fixed_timestep: Duration,
+    /// How far the frame is between the last fixed update and the next, from `0` to `1`. Things
+    /// moved in fixed updates are drawn this far from their last position towards their current
+    /// one, so their motion is smooth at any frame rate
+    pub fixed_step_alpha: f32,
+    /// Frame time that hasn't been simulated by fixed updates yet
+    fixed_step_accumulator: Duration,
+    /// Where the frame time goes, averaged over the last frames
+    pub frame_stats: FrameStats,
+}
+
+/// What can go wrong while starting the engine, see [GameZapBuilder::build], or changing its
+/// window
+#[derive(Debug)]
+pub enum GameZapError {
+    /// SDL, its video subsystem or its event pump failed to initialize, or SDL rejected a window
+    /// change
+    Sdl(String),
+    /// [GameZapBuilder::window_and_renderer] wasn't called
+    NoWindow,
+    Renderer(RenderError),
+    /// The window title contains a nul byte
+    InvalidTitle(std::ffi::NulError),
+}
+
+impl From<RenderError> for GameZapError {
+    fn from(err: RenderError) -> Self {
+        GameZapError::Renderer(err)
+    }
 }
 
 pub struct EngineSystems {
     pub sdl_context: Sdl,
     pub video_subsystem: VideoSubsystem,
     pub event_pump: RefCell<EventPump>,
+    pub asset_cache: AssetCache,
+    pub gamepads: RefCell<GamepadManager>,
+    pub events: EventBus,
+    /// Set by [EngineSystems::set_cursor_mode], the event pump may be borrowed by the main loop
+    /// at that point so the motion is dropped by [GameZap::update_details] instead
+    discard_mouse_motion: Cell<bool>,
+}
+
+/// How the mouse cursor behaves over the window, see [EngineSystems::set_cursor_mode]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CursorMode {
+    #[default]
+    Normal,
+    Hidden,
+    /// Hidden and kept in the window, only the motion in [EngineDetails::mouse_state] is
+    /// reported. For mouse look
+    Disabled,
+}
+
+impl EngineSystems {
+    /// Starts SDL's video and controller subsystems and its event pump. [GameZap] creates its
+    /// own, this is for driving a scene without one. Without a display SDL's `dummy` video
+    /// driver (`SDL_VIDEODRIVER=dummy`) is enough
+    pub fn new(sdl_context: Sdl) -> Result<Self, String> {
+        let video_subsystem = sdl_context.video()?;
+        let event_pump = RefCell::new(sdl_context.event_pump()?);
+        let gamepads = RefCell::new(GamepadManager::new(&sdl_context));
+        Ok(EngineSystems {
+            sdl_context,
+            video_subsystem,
+            event_pump,
+            asset_cache: AssetCache::default(),
+            gamepads,
+            events: EventBus::default(),
+            discard_mouse_motion: Cell::new(false),
+        })
+    }
+
+    /// Switching to [CursorMode::Disabled] drops the motion gathered this frame, so the warp to
+    /// relative mode doesn't show up as a large delta on the next frame. Safe to call from
+    /// components while the main loop is handling events
+    pub fn set_cursor_mode(&self, mode: CursorMode) {
+        let mouse = self.sdl_context.mouse();
+        mouse.show_cursor(mode == CursorMode::Normal);
+        mouse.set_relative_mouse_mode(mode == CursorMode::Disabled);
+        if mode == CursorMode::Disabled {
+            self.discard_mouse_motion.set(true);
+        }
+    }
+
+    /// The system clipboard's text, `None` if it's empty or doesn't hold text
+    pub fn get_clipboard(&self) -> Option<String> {
+        let clipboard = self.video_subsystem.clipboard();
+        if !clipboard.has_clipboard_text() {
+            return None;
+        }
+        clipboard.clipboard_text().ok()
+    }
+
+    pub fn set_clipboard(&self, text: &str) -> Result<(), String> {
+        self.video_subsystem.clipboard().set_clipboard_text(text)
+    }
+}
+
+/// Which attachments the scene render pass clears at the start of a frame. Turning color
+/// clearing off keeps the previous frame's image to draw over, e.g. for motion trails. The
+/// previous image only persists when antialiasing is on, since the swapchain texture itself is
+/// not preserved between frames
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClearFlags {
+    pub color: bool,
+    pub depth: bool,
+}
+
+impl Default for ClearFlags {
+    fn default() -> Self {
+        Self {
+            color: true,
+            depth: true,
+        }
+    }
+}
+
+/// How long each phase of a frame takes, see [EngineDetails::frame_stats] and
+/// [GameZapBuilder::on_frame_stats]
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct FrameStats {
+    /// All the fixed updates run in the frame
+    pub fixed_update: Duration,
+    /// Component updates, without the compute shaders
+    pub update: Duration,
+    /// Dispatching the scene's compute shaders
+    pub compute: Duration,
+    /// Recording and submitting the UI, scene and post processing on the CPU
+    pub render: Duration,
+    /// Time the GPU spends rendering the frame. Only measured with a
+    /// [GameZapBuilder::on_frame_stats] callback on adapters supporting timestamp queries, and a
+    /// frame or two behind the rest
+    pub gpu_render: Option<Duration>,
+}
+
+impl FrameStats {
+    /// Weight of the newest frame in the averages
+    const SMOOTHING: f32 = 0.1;
+
+    /// Blends a frame's timings into the averages
+    fn record(&mut self, frame: &FrameStats) {
+        let blend = |average: Duration, sample: Duration| {
+            if average.is_zero() {
+                sample
+            } else {
+                average.mul_f32(1.0 - Self::SMOOTHING) + sample.mul_f32(Self::SMOOTHING)
+            }
+        };
+        self.fixed_update = blend(self.fixed_update, frame.fixed_update);
+        self.update = blend(self.update, frame.update);
+        self.compute = blend(self.compute, frame.compute);
+        self.render = blend(self.render, frame.render);
+        self.gpu_render = match (self.gpu_render, frame.gpu_render) {
+            (Some(average), Some(sample)) => Some(blend(average, sample)),
+            (average, sample) => sample.or(average),
+        };
+    }
 }
 
 pub struct RenderMask {
@@ -94,11 +309,80 @@ impl EngineSettings for Sdl {
 }
 
 impl EngineDetails {
-    pub fn update_details(&mut self, event_pump: Ref<EventPump>, sdl_context: &Sdl) {
+    /// More fixed updates than this in one frame are dropped, so a long frame doesn't cause an
+    /// even longer one
+    pub const MAX_FIXED_STEPS: u32 = 8;
+
+    /// Details at frame zero with the same defaults as [GameZapBuilder]. [GameZap] creates its
+    /// own, this is for driving a scene without one, e.g. with
+    /// [ecs::scene::Scene::render_to_image]
+    pub fn new(window_size: (u32, u32)) -> Self {
+        let now = Instant::now();
+        EngineDetails {
+            frame_number: 0,
+            initialized_instant: now,
+            time_elapsed: Duration::ZERO,
+            last_frame_duration: Duration::ZERO,
+            real_frame_duration: Duration::ZERO,
+            time_scale: 1.0,
+            time_of_last_frame: now,
+            time_of_last_fps_calc: now,
+            frame_count_at_last_fps_calc: 0,
+            fps: 0,
+
+            mouse_state: (None, true),
+            cursor_pos: (0.0, 0.0),
+            scroll_delta: (0.0, 0.0),
+            text_input: String::new(),
+            pressed_scancodes: vec![],
+            input: InputState::default(),
+            gamepads: Vec::new(),
+            window_aspect_ratio: window_size.0 as f32 / window_size.1 as f32,
+            window_size,
+            render_mask: None,
+            is_cursor_showing: true,
+            clear_flags: ClearFlags::default(),
+            capture_frame: false,
+            fixed_timestep: Duration::from_secs_f64(1.0 / 60.0),
+            fixed_step_alpha: 0.0,
+            fixed_step_accumulator: Duration::ZERO,
+            frame_stats: FrameStats::default(),
+        }
+    }
+
+    /// Adds the last frame's duration to the accumulator and takes the number of fixed updates
+    /// to run this frame out of it
+    pub(crate) fn take_fixed_steps(&mut self) -> u32 {
+        if self.fixed_timestep.is_zero() {
+            return 0;
+        }
+        self.fixed_step_accumulator += self.last_frame_duration;
+        // In whole nanoseconds, dividing floats can come up a step short on exact multiples
+        let steps =
+            u32::try_from(self.fixed_step_accumulator.as_nanos() / self.fixed_timestep.as_nanos())
+                .unwrap_or(u32::MAX);
+        if steps > Self::MAX_FIXED_STEPS {
+            self.fixed_step_accumulator = Duration::ZERO;
+        } else {
+            self.fixed_step_accumulator -= self.fixed_timestep * steps;
+        }
+        self.fixed_step_alpha = (self.fixed_step_accumulator.as_secs_f32()
+            / self.fixed_timestep.as_secs_f32())
+        .clamp(0.0, 1.0);
+        steps.min(Self::MAX_FIXED_STEPS)
+    }
+
+    pub fn update_details(
+        &mut self,
+        event_pump: Ref<EventPump>,
+        sdl_context: &Sdl,
+        gamepads: &GamepadManager,
+    ) {
         let now = Instant::now();
         self.frame_number += 1;
         self.time_elapsed = now - self.initialized_instant;
-        self.last_frame_duration = now - self.time_of_last_frame;
+        self.real_frame_duration = now - self.time_of_last_frame;
+        self.last_frame_duration = self.real_frame_duration.mul_f32(self.time_scale.max(0.0));
         self.time_of_last_frame = now;
 
         if (now - self.time_of_last_fps_calc).as_secs_f32() > 1.0 {
@@ -111,7 +395,10 @@ impl EngineDetails {
             Some(event_pump.relative_mouse_state()),
             sdl_context.mouse().is_cursor_showing(),
         );
+        let mouse = event_pump.mouse_state();
+        self.cursor_pos = (mouse.x() as f64, mouse.y() as f64);
         self.pressed_scancodes = event_pump.keyboard_state().pressed_scancodes().collect();
+        self.gamepads = gamepads.states();
     }
 }
 
@@ -124,15 +411,29 @@ impl GameZap {
     pub fn update_details(&mut self) {
         let mut details = self.details.lock().unwrap();
         let systems = self.systems.lock().unwrap();
-        details.update_details(systems.event_pump.borrow(), &systems.sdl_context);
+        if systems.discard_mouse_motion.take() {
+            systems.event_pump.borrow().relative_mouse_state();
+        }
+        details.update_details(
+            systems.event_pump.borrow(),
+            &systems.sdl_context,
+            &systems.gamepads.borrow(),
+        );
     }
 
     pub fn main_loop(&mut self) {
         env_logger::init();
 
         'running: loop {
-            let active_scene_opt = self.scenes.get_mut(self.active_scene_index);
+            // Stack changes requested during the last frame
+            let scene_commands = self.systems.lock().unwrap().events.drain::<SceneCommand>();
+            for command in scene_commands {
+                self.scene_manager.apply(command);
+            }
+
             let mut window_size = (u32::MAX, u32::MAX);
+            let mut scroll_delta = (0.0, 0.0);
+            let mut text_input = String::new();
             {
                 let systems = self.systems.lock().unwrap();
 
@@ -142,17 +443,26 @@ impl GameZap {
                 let mut imgui_context = ui_manager.imgui_context.lock().unwrap();
                 let mut imgui_platform = ui_manager.imgui_platform.lock().unwrap();
 
+                // Presses and releases come from the events, so ones shorter than a frame count
+                self.details.lock().unwrap().input.begin_frame();
                 for event in event_pump.poll_iter() {
                     imgui_platform.handle_event(&mut imgui_context, &event);
+                    self.details.lock().unwrap().input.handle_event(&event);
 
-                    if let Some(active_scene) = &active_scene_opt {
-                        let component_map = active_scene.get_components();
+                    let updating_scenes = self
+                        .scene_manager
+                        .scenes()
+                        .iter()
+                        .enumerate()
+                        .filter(|(index, _)| self.scene_manager.is_updating(*index));
+                    for (_, scene) in updating_scenes {
+                        let component_map = scene.get_components();
                         for component in component_map.values().flatten() {
                             component.on_event(
                                 &event,
                                 component_map,
-                                active_scene.get_concept_manager(),
-                                active_scene.get_active_camera(),
+                                scene.get_concept_manager(),
+                                scene.get_active_camera(),
                                 &self.details.lock().unwrap(),
                                 &systems,
                             );
@@ -167,9 +477,32 @@ impl GameZap {
                         } => {
                             window_size = (width as u32, height as u32);
                         }
+                        Event::ControllerDeviceAdded { which, .. } => {
+                            systems.gamepads.borrow_mut().connect(which);
+                        }
+                        Event::ControllerDeviceRemoved { which, .. } => {
+                            systems.gamepads.borrow_mut().disconnect(which);
+                        }
+                        Event::MouseWheel {
+                            precise_x,
+                            precise_y,
+                            direction,
+                            ..
+                        } => {
+                            let sign = match direction {
+                                MouseWheelDirection::Flipped => -1.0,
+                                _ => 1.0,
+                            };
+                            scroll_delta.0 += precise_x as f64 * sign;
+                            scroll_delta.1 += precise_y as f64 * sign;
+                        }
+                        Event::TextInput { ref text, .. } => text_input.push_str(text),
                         _ => {}
                     }
                 }
+                let mut details = self.details.lock().unwrap();
+                details.scroll_delta = scroll_delta;
+                details.text_input = text_input;
 
                 imgui_platform.prepare_frame(&mut imgui_context, &self.window, &event_pump);
             }
@@ -177,81 +510,253 @@ impl GameZap {
             {
                 let renderer = &mut self.renderer;
 
-                let output = renderer.surface.get_current_texture().unwrap();
+                // Resize before acquiring so an outdated surface is reconfigured at the new size
+                if window_size != (u32::MAX, u32::MAX) {
+                    renderer.resize(
+                        window_size,
+                        self.scene_manager
+                            .top_mut()
+                            .and_then(|scene| scene.text_state.text_viewport.as_mut()),
+                    );
+                    // A minimized window isn't resized to zero, so the size the scenes render
+                    // at has to stay the renderer's, which its depth texture was made with
+                    let window_size = renderer.size;
+                    self.window_size = window_size;
+                    let mut details = self.details.lock().unwrap();
+                    details.window_aspect_ratio = window_size.0 as f32 / window_size.1 as f32;
+                    details.window_size = window_size;
+                }
+
+                let output = match renderer.acquire_frame() {
+                    Ok(Some(output)) => output,
+                    // Skipped frames still end like drawn ones, so the details and the
+                    // events emitted during them move on to the next frame
+                    Ok(None) => {
+                        self.update_details();
+                        self.systems.lock().unwrap().events.next_frame();
+                        continue;
+                    }
+                    Err(err) => {
+                        log::error!("Failed to acquire a frame: {err:?}");
+                        break 'running;
+                    }
+                };
                 let view = output
                     .texture
                     .create_view(&wgpu::TextureViewDescriptor::default());
 
 
-                if let Some(active_scene) = active_scene_opt {
-                    {
-                        if window_size != (u32::MAX, u32::MAX) {
-                            self.window_size = window_size;
+                if !self.scene_manager.is_empty() {
+                    let updating = (0..self.scene_manager.len())
+                        .map(|index| self.scene_manager.is_updating(index))
+                        .collect::<Vec<_>>();
+                    let rendering = (0..self.scene_manager.len())
+                        .map(|index| self.scene_manager.is_rendering(index))
+                        .collect::<Vec<_>>();
+                    let scenes = self.scene_manager.scenes_mut();
 
-                            renderer.resize(
-                                window_size,
-                                active_scene.text_state.text_viewport.as_mut(),
+                    // Scenes pushed since the last frame are initialized before their first use
+                    let mut initialized_scene = false;
+                    for (index, scene) in scenes.iter_mut().enumerate() {
+                        if (updating[index] || rendering[index]) && !scene.is_initialized() {
+                            scene.initialize(
+                                renderer.device.clone(),
+                                renderer.queue.clone(),
+                                renderer.scene_format,
+                                renderer.sample_count,
+                                renderer.depth_format,
+                                self.details.clone(),
+                                self.systems.clone(),
+                                self.ui_manager.clone(),
                             );
-                            self.details.lock().unwrap().window_aspect_ratio =
-                                window_size.0 as f32 / window_size.1 as f32;
+                            initialized_scene = true;
                         }
                     }
+                    if initialized_scene || self.details.lock().unwrap().frame_number == 0 {
+                        self.ui_manager.lock().unwrap().rebuild_font_atlas();
+                    }
 
-                let mut smaa_binding = renderer.smaa_target.lock().unwrap();
-                let smaa_frame = smaa_binding.start_frame(&renderer.device, &renderer.queue, &view);
-
-                    if self.details.lock().unwrap().frame_number == 0 {
-                        active_scene.initialize(
+                    let fixed_update_start = Instant::now();
+                    let fixed_steps = self.details.lock().unwrap().take_fixed_steps();
+                    for _ in 0..fixed_steps {
+                        for (index, scene) in scenes.iter_mut().enumerate() {
+                            if updating[index] {
+                                scene.fixed_update(
+                                    renderer.device.clone(),
+                                    renderer.queue.clone(),
+                                    self.details.clone(),
+                                    self.systems.clone(),
+                                );
+                            }
+                        }
+                    }
+                    let update_start = Instant::now();
+                    let mut compute = Duration::ZERO;
+                    for (index, scene) in scenes.iter_mut().enumerate() {
+                        if updating[index] {
+                            scene.update(
+                                renderer.device.clone(),
+                                renderer.queue.clone(),
+                                self.details.clone(),
+                                self.systems.clone(),
+                            );
+                            compute += scene.last_compute_duration();
+                        }
+                    }
+                    let render_start = Instant::now();
+                    if let Some(gpu_timer) = &self.gpu_timer {
+                        gpu_timer.begin(&renderer.device, &renderer.queue);
+                    }
+                    // There's a single UI frame, owned by the top scene
+                    if let Some(top_scene) = scenes.last_mut() {
+                        top_scene.ui_draw(
                             renderer.device.clone(),
                             renderer.queue.clone(),
-                            renderer.config.format,
                             self.details.clone(),
                             self.systems.clone(),
                             self.ui_manager.clone(),
                         );
-                        let ui_manager = self.ui_manager.lock().unwrap();
-                        let mut imgui_renderer = ui_manager.imgui_renderer.lock().unwrap();
-                        let mut context = ui_manager.imgui_context.lock().unwrap();
-                        imgui_renderer.reload_font_texture(
-                            &mut context,
-                            &renderer.device.clone(),
-                            &renderer.queue.clone(),
+                    }
+                    let clear_flags = self.details.lock().unwrap().clear_flags;
+                    let mut smaa_binding = renderer.smaa_target.lock().unwrap();
+                    let mut rendered_scenes = scenes
+                        .iter_mut()
+                        .enumerate()
+                        .filter(|(index, _)| rendering[*index])
+                        .map(|(_, scene)| scene)
+                        .collect::<Vec<_>>();
+                    let mut rendered_any = false;
+                    for (i, scene) in rendered_scenes.iter_mut().enumerate() {
+                        // With post effects the scene is drawn to their input first
+                        let scene_view = renderer.post_process.scene_target().unwrap_or(&view);
+                        let smaa_frame =
+                            smaa_binding.start_frame(&renderer.device, &renderer.queue, scene_view);
+                        // Scenes higher up the stack are drawn over the ones below
+                        rendered_any |= scene.render_scene(
+                            renderer.device.clone(),
+                            renderer.queue.clone(),
+                            renderer.depth_texture.clone(),
+                            self.window_size,
+                            &self.details.lock().unwrap(),
+                            &self.systems.lock().unwrap(),
+                            smaa_frame,
+                            renderer.msaa_view.as_ref(),
+                            output.texture.size(),
+                            self.clear_color,
+                            ClearFlags {
+                                color: clear_flags.color && i == 0,
+                                ..clear_flags
+                            },
+                            &[],
+                            LayerMask::ALL,
+                            self.ui_manager.clone(),
                         );
                     }
-                    active_scene.update(
-                        renderer.device.clone(),
-                        renderer.queue.clone(),
-                        self.details.clone(),
-                        self.systems.clone(),
-                    );
-                    active_scene.ui_draw(
-                        renderer.device.clone(),
-                        renderer.queue.clone(),
-                        self.details.clone(),
-                        self.systems.clone(),
-                        self.ui_manager.clone(),
-                    );
-                    active_scene.render(
-                        renderer.device.clone(),
-                        renderer.queue.clone(),
-                        renderer.depth_texture.clone(),
-                        self.window_size,
-                        &self.details.lock().unwrap(),
-                        &self.systems.lock().unwrap(),
-                        smaa_frame,
-                        output,
-                        self.clear_color,
-                        self.ui_manager.clone(),
-                    );
+                    drop(smaa_binding);
+                    // Post-processing runs once over every scene, text and UI go on top of it
+                    if rendered_any {
+                        let mut encoder = renderer.device.create_command_encoder(
+                            &wgpu::CommandEncoderDescriptor {
+                                label: Some("Post Process Encoder"),
+                            },
+                        );
+                        renderer.post_process.apply(&mut encoder, &view);
+                        for scene in rendered_scenes {
+                            scene.render_overlay(
+                                renderer.device.clone(),
+                                renderer.queue.clone(),
+                                &mut encoder,
+                                &view,
+                                self.window_size,
+                                self.ui_manager.clone(),
+                            );
+                        }
+                        renderer.queue.submit(std::iter::once(encoder.finish()));
+                    }
+                    // The top scene's UI frame is left open if it isn't rendering
+                    if let Some(top_scene) = scenes.last_mut() {
+                        top_scene.discard_ui_frame(&self.ui_manager);
+                    }
+                    if let Some(gpu_timer) = &mut self.gpu_timer {
+                        gpu_timer.end(&renderer.device, &renderer.queue);
+                    }
+                    let render_end = Instant::now();
+
+                    let frame_stats = FrameStats {
+                        fixed_update: update_start - fixed_update_start,
+                        update: (render_start - update_start).saturating_sub(compute),
+                        compute,
+                        render: render_end - render_start,
+                        gpu_render: self
+                            .gpu_timer
+                            .as_mut()
+                            .and_then(|gpu_timer| gpu_timer.read(&renderer.device)),
+                    };
+
+                    let mut details = self.details.lock().unwrap();
+                    details.frame_stats.record(&frame_stats);
+                    if let Some(on_frame_stats) = &mut self.on_frame_stats {
+                        on_frame_stats(&details.frame_stats);
+                    }
+                    if details.capture_frame {
+                        details.capture_frame = false;
+                        match renderer.capture_frame(&output) {
+                            Ok(image) => self
+                                .systems
+                                .lock()
+                                .unwrap()
+                                .events
+                                .emit(FrameCaptured(image)),
+                            Err(err) => log::error!("Failed to capture the frame: {err:?}"),
+                        }
+                    }
+                    drop(details);
+
+                    output.present();
                 }
             }
 
             self.update_details();
+            self.systems.lock().unwrap().events.next_frame();
         }
     }
 
+    /// Pushes `scene` onto the [SceneManager], making it the top scene
     pub fn create_scene(&mut self, scene: Scene) {
-        self.scenes.push(scene);
+        self.scene_manager.push(scene);
+    }
+
+    /// See [EngineSystems::set_cursor_mode], which components can reach
+    pub fn set_cursor_mode(&mut self, mode: CursorMode) {
+        self.systems.lock().unwrap().set_cursor_mode(mode);
+    }
+
+    /// Changes the window's title, e.g. to show the current level
+    pub fn set_title(&mut self, title: &str) -> Result<(), GameZapError> {
+        self.window
+            .set_title(title)
+            .map_err(GameZapError::InvalidTitle)
+    }
+
+    /// Sets the window's icon from the same image in one or more sizes. SDL only takes a single
+    /// icon, so the largest one is used and scaled down by the window system where needed. Does
+    /// nothing without any icons
+    pub fn set_icon(&mut self, icons: &[image::RgbaImage]) -> Result<(), GameZapError> {
+        let Some(icon) = icons.iter().max_by_key(|icon| icon.width() * icon.height()) else {
+            return Ok(());
+        };
+        let (width, height) = icon.dimensions();
+        let mut pixels = icon.as_raw().clone();
+        let surface = sdl2::surface::Surface::from_data(
+            &mut pixels,
+            width,
+            height,
+            width * 4,
+            sdl2::pixels::PixelFormatEnum::RGBA32,
+        )
+        .map_err(GameZapError::Sdl)?;
+        self.window.set_icon(surface);
+        Ok(())
     }
 }
 
@@ -270,14 +775,26 @@ pub struct GameZapBuilder {
     last_frame_duration: Duration,
     time_of_last_frame: Instant,
 
-    antialiasing: bool,
-
-    active_scene_index: usize,
+    antialias_mode: AntialiasMode,
 
     render_mask: Option<RenderMask>,
+    clear_flags: ClearFlags,
+    fixed_timestep: Duration,
 
     limits: wgpu::Limits,
+    features: wgpu::Features,
     is_cursor_showing: bool,
+
+    alpha_mode: Option<wgpu::CompositeAlphaMode>,
+    max_frame_latency: u32,
+    backends: wgpu::Backends,
+    power_preference: wgpu::PowerPreference,
+    present_mode: wgpu::PresentMode,
+    msaa_samples: u32,
+    hdr: bool,
+    depth_format: Option<wgpu::TextureFormat>,
+    stencil: bool,
+    on_frame_stats: Option<Box<dyn FnMut(&FrameStats)>>,
 }
 
 impl<'a: 'b, 'b> GameZapBuilder {
@@ -300,15 +817,27 @@ impl<'a: 'b, 'b> GameZapBuilder {
             last_frame_duration: Duration::ZERO,
             time_of_last_frame: Instant::now(),
 
-            antialiasing: false,
-
-            active_scene_index: 0,
+            antialias_mode: AntialiasMode::None,
 
             render_mask: None,
+            clear_flags: ClearFlags::default(),
+            fixed_timestep: Duration::from_secs_f64(1.0 / 60.0),
 
             limits: wgpu::Limits::default(),
+            features: Renderer::DEFAULT_FEATURES,
 
             is_cursor_showing: true,
+
+            alpha_mode: None,
+            max_frame_latency: Renderer::DEFAULT_FRAME_LATENCY,
+            backends: wgpu::Backends::all(),
+            power_preference: wgpu::PowerPreference::HighPerformance,
+            present_mode: wgpu::PresentMode::AutoNoVsync,
+            msaa_samples: 1,
+            hdr: false,
+            depth_format: None,
+            stencil: false,
+            on_frame_stats: None,
         }
     }
     /// Pass in a [sdl2::video::Window] object, generates a [Renderer] with a [wgpu::Surface] corresponding to the window
@@ -330,8 +859,14 @@ impl<'a: 'b, 'b> GameZapBuilder {
         self
     }
 
+    /// Enables SMAA, same as [GameZapBuilder::antialias_mode] with [AntialiasMode::Smaa]
     pub fn antialiasing(mut self) -> GameZapBuilder {
-        self.antialiasing = true;
+        self.antialias_mode = AntialiasMode::Smaa;
+        self
+    }
+
+    pub fn antialias_mode(mut self, mode: AntialiasMode) -> GameZapBuilder {
+        self.antialias_mode = mode;
         self
     }
 
@@ -349,33 +884,155 @@ impl<'a: 'b, 'b> GameZapBuilder {
         self
     }
 
-    pub fn device_limits(mut self, limits: wgpu::Limits) -> GameZapBuilder {
+    /// Set which attachments are cleared each frame, see [ClearFlags]. Can also be changed at
+    /// runtime through [EngineDetails::clear_flags]
+    pub fn clear_flags(mut self, clear_flags: ClearFlags) -> GameZapBuilder {
+        self.clear_flags = clear_flags;
+        self
+    }
+
+    /// Called every frame with the averaged [FrameStats], which also turns on GPU timing when
+    /// the adapter supports timestamp queries
+    pub fn on_frame_stats(mut self, callback: impl FnMut(&FrameStats) + 'static) -> GameZapBuilder {
+        self.on_frame_stats = Some(Box::new(callback));
+        self
+    }
+
+    /// Time simulated by every fixed update, see [EngineDetails::fixed_timestep]
+    pub fn fixed_timestep(mut self, fixed_timestep: Duration) -> GameZapBuilder {
+        self.fixed_timestep = fixed_timestep;
+        self
+    }
+
+    /// Defaults to [wgpu::Limits::default]. Building fails with
+    /// [GameZapError::Renderer]\([RenderError::UnsupportedLimits]) if the adapter doesn't meet them
+    pub fn required_limits(mut self, limits: wgpu::Limits) -> GameZapBuilder {
         self.limits = limits;
         self
     }
 
+    #[deprecated = "renamed to `required_limits`"]
+    pub fn device_limits(self, limits: wgpu::Limits) -> GameZapBuilder {
+        self.required_limits(limits)
+    }
+
+    /// Features the device has to have, defaults to [Renderer::DEFAULT_FEATURES]. Building fails
+    /// with [GameZapError::Renderer]\([RenderError::MissingFeatures]) listing the ones the
+    /// adapter lacks
+    pub fn required_features(mut self, features: wgpu::Features) -> GameZapBuilder {
+        self.features = features;
+        self
+    }
+
+    /// Request a specific compositing mode for the window surface, e.g.
+    /// [wgpu::CompositeAlphaMode::PreMultiplied] for transparent overlay windows.
+    /// Falls back to the first mode the surface supports if the request isn't supported
+    pub fn alpha_mode(mut self, alpha_mode: wgpu::CompositeAlphaMode) -> GameZapBuilder {
+        self.alpha_mode = Some(alpha_mode);
+        self
+    }
+
+    /// Set how many frames may be queued ahead of presentation (1 to 3, defaults to 2).
+    /// `1` gives the lowest input latency, higher values trade latency for smoother pacing
+    pub fn max_frame_latency(mut self, latency: u32) -> GameZapBuilder {
+        self.max_frame_latency = latency;
+        self
+    }
+
+    /// Restrict which graphics APIs the adapter is picked from, e.g. [wgpu::Backends::VULKAN]
+    /// to work around a driver bug. Building fails with
+    /// [GameZapError::Renderer]\([RenderError::NoAdapter]) if no adapter supports them
+    pub fn backends(mut self, backends: wgpu::Backends) -> GameZapBuilder {
+        self.backends = backends;
+        self
+    }
+
+    /// Which kind of GPU to prefer, defaults to [wgpu::PowerPreference::HighPerformance]. If
+    /// none matches, low power and then software adapters are tried, the chosen one is in
+    /// [Renderer::adapter_info]
+    pub fn power_preference(mut self, power_preference: wgpu::PowerPreference) -> GameZapBuilder {
+        self.power_preference = power_preference;
+        self
+    }
+
+    /// Choose how frames are presented, e.g. [wgpu::PresentMode::Fifo] for vsync. Defaults to
+    /// [wgpu::PresentMode::AutoNoVsync], unsupported modes fall back to Fifo with a warning
+    pub fn present_mode(mut self, present_mode: wgpu::PresentMode) -> GameZapBuilder {
+        self.present_mode = present_mode;
+        self
+    }
+
+    /// Enable MSAA with the given samples per pixel (usually 4). Takes the place of SMAA, and
+    /// falls back to no MSAA with a warning if the adapter doesn't support the count
+    pub fn msaa_samples(mut self, samples: u32) -> GameZapBuilder {
+        self.msaa_samples = samples;
+        self
+    }
+
+    /// Draw the scene into a float target so colors can go above `1.0`, e.g. for
+    /// [bloom::Bloom]. Materials and skyboxes have to use [Renderer::scene_format]
+    pub fn hdr(mut self) -> GameZapBuilder {
+        self.hdr = true;
+        self
+    }
+
+    /// Prefer `depth_format` for the depth buffer, e.g. [wgpu::TextureFormat::Depth24Plus] on
+    /// targets where it's faster. Falls back through [Renderer::DEPTH_FORMATS] if the adapter
+    /// can't render to it, the chosen one is [Renderer::depth_format]
+    pub fn depth_format(mut self, depth_format: wgpu::TextureFormat) -> GameZapBuilder {
+        self.depth_format = Some(depth_format);
+        self
+    }
+
+    /// Give the depth buffer a stencil aspect, for materials with a [pipeline::StencilConfig]
+    pub fn stencil(mut self) -> GameZapBuilder {
+        self.stencil = true;
+        self
+    }
+
     /// Build the [GameZapBuilder] builder struct into the original [GameZap] struct
-    pub async fn build(self) -> GameZap {
+    /// Creates the renderer for the window and sets up the engine. Fails if no adapter or
+    /// device meets the requested settings, or the window's surface can't be created
+    pub async fn build(self) -> Result<GameZap, GameZapError> {
         let sdl_context = if let Some(context) = self.sdl_context {
             context
         } else {
-            sdl2::init().unwrap()
+            sdl2::init().map_err(GameZapError::Sdl)?
         };
         let video_subsystem = if let Some(video) = self.video_subsystem {
             video
         } else {
-            sdl_context.video().unwrap()
+            sdl_context.video().map_err(GameZapError::Sdl)?
         };
         let event_pump = RefCell::new(if let Some(pump) = self.event_pump {
             pump
         } else {
-            sdl_context.event_pump().unwrap()
+            sdl_context.event_pump().map_err(GameZapError::Sdl)?
         });
+        let gamepads = RefCell::new(GamepadManager::new(&sdl_context));
 
-        let window = self.window.unwrap();
+        let window = self.window.ok_or(GameZapError::NoWindow)?;
+        let window_size = self.window_size.unwrap_or(window.size());
 
-        let renderer =
-            Renderer::new(&window, self.clear_color, self.antialiasing, self.limits).await;
+        let renderer = Renderer::new(
+            &window,
+            self.clear_color,
+            RendererSettings {
+                antialias_mode: self.antialias_mode,
+                required_features: self.features,
+                required_limits: self.limits,
+                alpha_mode: self.alpha_mode,
+                max_frame_latency: self.max_frame_latency,
+                backends: self.backends,
+                power_preference: self.power_preference,
+                present_mode: self.present_mode,
+                msaa_samples: self.msaa_samples,
+                hdr: self.hdr,
+                depth_format: self.depth_format,
+                stencil: self.stencil,
+            },
+        )
+        .await?;
 
         let ui_manager = Rc::new(Mutex::new(UiManager::new(
             renderer.surface_format,
@@ -384,36 +1041,45 @@ impl<'a: 'b, 'b> GameZapBuilder {
             &window,
         )));
 
-        GameZap {
+        // Only timed on the GPU when someone is listening
+        let gpu_timer = self
+            .on_frame_stats
+            .as_ref()
+            .and_then(|_| GpuTimer::new(&renderer.device, &renderer.queue));
+
+        Ok(GameZap {
             systems: Rc::new(Mutex::new(EngineSystems {
                 sdl_context,
                 video_subsystem,
                 event_pump,
+                asset_cache: AssetCache::default(),
+                gamepads,
+                events: EventBus::default(),
+                discard_mouse_motion: Cell::new(false),
             })),
             renderer,
             clear_color: self.clear_color,
             window,
-            window_size: self.window_size.unwrap(),
+            window_size,
             details: Rc::new(Mutex::new(EngineDetails {
                 frame_number: self.frame_number,
                 initialized_instant: self.initialized_instant,
                 time_elapsed: self.time_elapsed,
                 last_frame_duration: self.last_frame_duration,
+                real_frame_duration: self.last_frame_duration,
                 time_of_last_frame: self.time_of_last_frame,
                 time_of_last_fps_calc: self.initialized_instant,
                 frame_count_at_last_fps_calc: self.frame_number,
-                fps: 0,
-
-                mouse_state: (None, true),
-                pressed_scancodes: vec![],
-                window_aspect_ratio: self.window_size.unwrap().0 as f32
-                    / self.window_size.unwrap().1 as f32,
                 render_mask: self.render_mask,
                 is_cursor_showing: self.is_cursor_showing,
+                clear_flags: self.clear_flags,
+                fixed_timestep: self.fixed_timestep,
+                ..EngineDetails::new(window_size)
             })),
             ui_manager,
-            scenes: Vec::new(),
-            active_scene_index: self.active_scene_index,
-        }
+            scene_manager: SceneManager::default(),
+            gpu_timer,
+            on_frame_stats: self.on_frame_stats,
+        })
     }
 }
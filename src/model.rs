@@ -45,3 +45,154 @@ impl VertexData for Vertex {
         }
     }
 }
+
+/// [Vertex] with a tangent frame, for normal mapping. Build it from a mesh with
+/// [TangentVertex::from_vertices]
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable, PartialEq)]
+pub struct TangentVertex {
+    pub position: [f32; 3],
+    pub tex_coords: [f32; 2],
+    pub normal: [f32; 3],
+    pub tangent: [f32; 3],
+    pub bitangent: [f32; 3],
+}
+
+impl TangentVertex {
+    /// Derives per-vertex tangents from the texture coordinates of the triangles in `indices`.
+    /// Triangles with degenerate UVs don't contribute, vertices left without a usable tangent
+    /// get an arbitrary one perpendicular to their normal
+    pub fn from_vertices(vertices: &[Vertex], indices: &[u32]) -> Vec<TangentVertex> {
+        let mut tangents = vec![na::Vector3::<f32>::zeros(); vertices.len()];
+        let mut bitangents = vec![na::Vector3::<f32>::zeros(); vertices.len()];
+
+        for face in indices.chunks_exact(3) {
+            let (Some(a), Some(b), Some(c)) = (
+                vertices.get(face[0] as usize),
+                vertices.get(face[1] as usize),
+                vertices.get(face[2] as usize),
+            ) else {
+                continue;
+            };
+
+            let edge_a = na::Vector3::from(b.position) - na::Vector3::from(a.position);
+            let edge_b = na::Vector3::from(c.position) - na::Vector3::from(a.position);
+            let uv_a = na::Vector2::from(b.tex_coords) - na::Vector2::from(a.tex_coords);
+            let uv_b = na::Vector2::from(c.tex_coords) - na::Vector2::from(a.tex_coords);
+
+            let determinant = uv_a.x * uv_b.y - uv_b.x * uv_a.y;
+            if determinant.abs() <= f32::EPSILON {
+                continue;
+            }
+            let tangent = (edge_a * uv_b.y - edge_b * uv_a.y) / determinant;
+            let bitangent = (edge_b * uv_a.x - edge_a * uv_b.x) / determinant;
+            if !tangent
+                .iter()
+                .chain(bitangent.iter())
+                .all(|v| v.is_finite())
+            {
+                continue;
+            }
+
+            for index in face {
+                tangents[*index as usize] += tangent;
+                bitangents[*index as usize] += bitangent;
+            }
+        }
+
+        vertices
+            .iter()
+            .zip(tangents)
+            .zip(bitangents)
+            .map(|((vertex, tangent), bitangent)| {
+                let normal = na::Vector3::from(vertex.normal)
+                    .try_normalize(f32::EPSILON)
+                    .unwrap_or(na::Vector3::y());
+                // Keeps the tangent perpendicular to the normal after the faces were averaged
+                let tangent = (tangent - normal * normal.dot(&tangent))
+                    .try_normalize(f32::EPSILON)
+                    .unwrap_or_else(|| Self::perpendicular(&normal));
+                // Mirrored UVs flip the bitangent
+                let handedness = if normal.cross(&tangent).dot(&bitangent) < 0.0 {
+                    -1.0
+                } else {
+                    1.0
+                };
+
+                TangentVertex {
+                    position: vertex.position,
+                    tex_coords: vertex.tex_coords,
+                    normal: vertex.normal,
+                    tangent: tangent.into(),
+                    bitangent: (normal.cross(&tangent) * handedness).into(),
+                }
+            })
+            .collect()
+    }
+
+    fn perpendicular(normal: &na::Vector3<f32>) -> na::Vector3<f32> {
+        let axis = if normal.x.abs() < 0.9 {
+            na::Vector3::x()
+        } else {
+            na::Vector3::y()
+        };
+        normal.cross(&axis).normalize()
+    }
+}
+
+impl VertexData for TangentVertex {
+    fn desc() -> wgpu::VertexBufferLayout<'static> {
+        const ATTRIBUTES: [wgpu::VertexAttribute; 5] = wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x2, 2 => Float32x3, 3 => Float32x3, 4 => Float32x3];
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<TangentVertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &ATTRIBUTES,
+        }
+    }
+}
+
+/// Which vertex struct a mesh's vertex buffers hold. A material's pipeline has to be built for
+/// the same one as the meshes it draws
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum VertexType {
+    /// [Vertex]
+    #[default]
+    Standard,
+    /// [TangentVertex]
+    Tangent,
+    /// Any other vertex struct, for components that upload and draw their own vertex buffers
+    Custom(VertexLayout),
+}
+
+impl VertexType {
+    pub fn desc(self) -> wgpu::VertexBufferLayout<'static> {
+        match self {
+            VertexType::Standard => Vertex::desc(),
+            VertexType::Tangent => TangentVertex::desc(),
+            VertexType::Custom(layout) => wgpu::VertexBufferLayout {
+                array_stride: layout.array_stride,
+                step_mode: wgpu::VertexStepMode::Vertex,
+                attributes: layout.attributes,
+            },
+        }
+    }
+}
+
+/// Layout of a [VertexType::Custom] vertex. Shader locations 5 to 8 are taken by the instance's
+/// transform matrix, see [crate::ecs::components::transform_component::TransformComponent]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct VertexLayout {
+    pub array_stride: wgpu::BufferAddress,
+    pub attributes: &'static [wgpu::VertexAttribute],
+}
+
+impl VertexLayout {
+    /// The layout of a vertex struct implementing [VertexData]
+    pub fn of<V: VertexData>() -> Self {
+        let desc = V::desc();
+        VertexLayout {
+            array_stride: desc.array_stride,
+            attributes: desc.attributes,
+        }
+    }
+}
@@ -0,0 +1,399 @@
+use std::{num::NonZeroU64, sync::Arc};
+
+use wgpu::{BindGroup, BindGroupLayout, Device, Queue, RenderPipeline, ShaderStages};
+
+use crate::{
+    ecs::{
+        components::{camera_component::CameraComponent, transform_component::TransformComponent},
+        entity::EntityId,
+    },
+    model::{VertexData, VertexType},
+    texture::Texture,
+};
+
+/// Offscreen pipeline that renders entity ids into an integer target so a single pixel can be
+/// read back to find the entity under the cursor. Ids are written offset by one so that `0`
+/// means "no entity", see [PickingPipeline::encode_id].
+#[derive(Debug)]
+pub struct PickingPipeline {
+    pipeline: RenderPipeline,
+    /// For meshes with [VertexType::Tangent]
+    tangent_pipeline: RenderPipeline,
+    id_bind_group_layout: BindGroupLayout,
+    target: wgpu::Texture,
+    target_view: wgpu::TextureView,
+    depth_texture: Texture,
+    size: (u32, u32),
+}
+
+impl PickingPipeline {
+    pub const ID_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::R32Uint;
+    const ID_BINDING_SIZE: u64 = std::mem::size_of::<[u32; 4]>() as u64;
+
+    pub fn new(device: Arc<Device>, size: (u32, u32)) -> Self {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Picking Shader"),
+            source: wgpu::ShaderSource::Wgsl(std::borrow::Cow::Borrowed(include_str!(
+                "default-shaders/picking.wgsl"
+            ))),
+        });
+
+        let id_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Picking Id Bind Group Layout"),
+                entries: &[wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: true,
+                        min_binding_size: NonZeroU64::new(Self::ID_BINDING_SIZE),
+                    },
+                    count: None,
+                }],
+            });
+
+        let camera_bind_group_layout = CameraComponent::camera_bind_group_layout(device.clone());
+
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Picking Pipeline Layout"),
+            bind_group_layouts: &[&camera_bind_group_layout, &id_bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let create_pipeline = |vertex_type: VertexType| {
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("Picking Pipeline"),
+                layout: Some(&layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: "vs_main",
+                    buffers: &[vertex_type.desc(), TransformComponent::desc()],
+                    compilation_options: wgpu::PipelineCompilationOptions::default(),
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: "fs_main",
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format: Self::ID_FORMAT,
+                        blend: None,
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                    compilation_options: wgpu::PipelineCompilationOptions::default(),
+                }),
+                primitive: wgpu::PrimitiveState {
+                    topology: wgpu::PrimitiveTopology::TriangleList,
+                    strip_index_format: None,
+                    front_face: wgpu::FrontFace::Ccw,
+                    cull_mode: None,
+                    unclipped_depth: false,
+                    polygon_mode: wgpu::PolygonMode::Fill,
+                    conservative: false,
+                },
+                depth_stencil: Some(wgpu::DepthStencilState {
+                    format: Texture::DEPTH_FORMAT,
+                    depth_write_enabled: true,
+                    depth_compare: wgpu::CompareFunction::Less,
+                    stencil: wgpu::StencilState::default(),
+                    bias: wgpu::DepthBiasState::default(),
+                }),
+                multisample: wgpu::MultisampleState::default(),
+                multiview: None,
+                cache: None,
+            })
+        };
+        let pipeline = create_pipeline(VertexType::Standard);
+        let tangent_pipeline = create_pipeline(VertexType::Tangent);
+
+        let (target, target_view) = Self::create_target(&device, size);
+        let depth_texture =
+            Texture::create_depth_texture_with_size(&device, size, "Picking Depth Texture");
+
+        PickingPipeline {
+            pipeline,
+            tangent_pipeline,
+            id_bind_group_layout,
+            target,
+            target_view,
+            depth_texture,
+            size,
+        }
+    }
+
+    /// The value written for an entity, its id plus one. `None` for [EntityId::MAX], which has
+    /// no value left and can't be picked
+    pub fn encode_id(entity_id: EntityId) -> Option<u32> {
+        entity_id.checked_add(1)
+    }
+
+    /// The entity a read back value belongs to, `None` for the cleared background
+    pub fn decode_id(value: u32) -> Option<EntityId> {
+        value.checked_sub(1)
+    }
+
+    fn create_target(device: &Device, size: (u32, u32)) -> (wgpu::Texture, wgpu::TextureView) {
+        let target = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Picking Target"),
+            size: wgpu::Extent3d {
+                width: size.0,
+                height: size.1,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: Self::ID_FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        let target_view = target.create_view(&wgpu::TextureViewDescriptor::default());
+        (target, target_view)
+    }
+
+    pub fn resize(&mut self, device: Arc<Device>, size: (u32, u32)) {
+        if size == self.size {
+            return;
+        }
+        let (target, target_view) = Self::create_target(&device, size);
+        self.target = target;
+        self.target_view = target_view;
+        self.depth_texture =
+            Texture::create_depth_texture_with_size(&device, size, "Picking Depth Texture");
+        self.size = size;
+    }
+
+    /// Creates a bind group holding one id slot per entity, returns it along with the
+    /// dynamic offset stride between slots. Entities whose id can't be encoded are written as
+    /// the background
+    pub fn create_id_bind_group(
+        &self,
+        device: Arc<Device>,
+        queue: Arc<Queue>,
+        entity_ids: &[EntityId],
+    ) -> (BindGroup, u32) {
+        let stride =
+            (Self::ID_BINDING_SIZE as u32).max(device.limits().min_uniform_buffer_offset_alignment);
+
+        let mut contents = vec![0_u8; (stride as usize) * entity_ids.len().max(1)];
+        for (i, id) in entity_ids.iter().enumerate() {
+            let offset = i * stride as usize;
+            let value = Self::encode_id(*id).unwrap_or(0);
+            contents[offset..offset + 4].copy_from_slice(&value.to_ne_bytes());
+        }
+
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Picking Id Buffer"),
+            size: contents.len() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        queue.write_buffer(&buffer, 0, &contents);
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Picking Id Bind Group"),
+            layout: &self.id_bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                    buffer: &buffer,
+                    offset: 0,
+                    size: NonZeroU64::new(Self::ID_BINDING_SIZE),
+                }),
+            }],
+        });
+
+        (bind_group, stride)
+    }
+
+    /// Copies back a single texel of the id target. The copy still has to respect
+    /// [wgpu::COPY_BYTES_PER_ROW_ALIGNMENT], so a full aligned row is allocated for it.
+    pub fn read_pixel(&self, device: Arc<Device>, queue: Arc<Queue>, position: (u32, u32)) -> u32 {
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Picking Readback Buffer"),
+            size: wgpu::COPY_BYTES_PER_ROW_ALIGNMENT as u64,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Picking Readback Encoder"),
+        });
+
+        encoder.copy_texture_to_buffer(
+            wgpu::ImageCopyTexture {
+                texture: &self.target,
+                mip_level: 0,
+                origin: wgpu::Origin3d {
+                    x: position.0,
+                    y: position.1,
+                    z: 0,
+                },
+                aspect: wgpu::TextureAspect::All,
+            },
+            wgpu::ImageCopyBuffer {
+                buffer: &buffer,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT),
+                    rows_per_image: Some(1),
+                },
+            },
+            wgpu::Extent3d {
+                width: 1,
+                height: 1,
+                depth_or_array_layers: 1,
+            },
+        );
+
+        queue.submit(Some(encoder.finish()));
+
+        let buffer_slice = buffer.slice(..);
+        let (sender, receiver) = flume::bounded(1);
+        buffer_slice.map_async(wgpu::MapMode::Read, move |v| sender.send(v).unwrap());
+
+        device.poll(wgpu::Maintain::Wait);
+
+        if let Ok(Ok(())) = receiver.recv() {
+            let data = buffer_slice.get_mapped_range();
+            let value = u32::from_ne_bytes([data[0], data[1], data[2], data[3]]);
+            drop(data);
+            buffer.unmap();
+            value
+        } else {
+            0
+        }
+    }
+
+    /// `None` for [VertexType::Custom], meshes never hold those
+    pub fn pipeline(&self, vertex_type: VertexType) -> Option<&RenderPipeline> {
+        match vertex_type {
+            VertexType::Standard => Some(&self.pipeline),
+            VertexType::Tangent => Some(&self.tangent_pipeline),
+            VertexType::Custom(_) => None,
+        }
+    }
+
+    pub fn target_view(&self) -> &wgpu::TextureView {
+        &self.target_view
+    }
+
+    pub fn depth_texture(&self) -> &Texture {
+        &self.depth_texture
+    }
+
+    pub fn size(&self) -> (u32, u32) {
+        self.size
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use wgpu::util::DeviceExt;
+
+    use super::*;
+    use crate::{model::Vertex, test_utils::headless_device};
+
+    #[test]
+    fn id_encoding_reserves_zero_and_rejects_max() {
+        assert_eq!(PickingPipeline::encode_id(0), Some(1));
+        assert_eq!(PickingPipeline::encode_id(EntityId::MAX), None);
+        assert_eq!(PickingPipeline::decode_id(0), None);
+        assert_eq!(
+            PickingPipeline::decode_id(PickingPipeline::encode_id(41).unwrap()),
+            Some(41)
+        );
+    }
+
+    #[test]
+    fn picks_entity_at_pixel() {
+        let Some((device, queue)) = headless_device(wgpu::Features::empty()) else {
+            return;
+        };
+        let size = (16, 16);
+        let picking_pipeline = PickingPipeline::new(device.clone(), size);
+
+        // A triangle over the left half of the target, drawn with an identity camera and model
+        let vertex = |x: f32, y: f32| Vertex {
+            position: [x, y, 0.5],
+            tex_coords: [0.0, 0.0],
+            normal: [0.0, 0.0, 1.0],
+        };
+        let vertices = [vertex(-1.0, -1.0), vertex(0.0, -1.0), vertex(-1.0, 3.0)];
+        let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: None,
+            contents: bytemuck::cast_slice(&vertices),
+            usage: wgpu::BufferUsages::VERTEX,
+        });
+        let identity: [[f32; 4]; 4] = nalgebra::Matrix4::<f32>::identity().into();
+        let transform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: None,
+            contents: bytemuck::cast_slice(&identity),
+            usage: wgpu::BufferUsages::VERTEX,
+        });
+
+        let camera_data = crate::ecs::components::camera_component::RawCameraData {
+            cam_pos: [0.0; 4],
+            cam_mat: identity,
+        };
+        let camera_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: None,
+            contents: bytemuck::cast_slice(&[camera_data]),
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+        let camera_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: None,
+            layout: &CameraComponent::camera_bind_group_layout(device.clone()),
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: camera_buffer.as_entire_binding(),
+            }],
+        });
+
+        let entity_ids = [3, 7];
+        let (id_bind_group, id_stride) =
+            picking_pipeline.create_id_bind_group(device.clone(), queue.clone(), &entity_ids);
+
+        let mut encoder =
+            device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: None,
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: picking_pipeline.target_view(),
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: &picking_pipeline.depth_texture().view,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(1.0),
+                        store: wgpu::StoreOp::Store,
+                    }),
+                    stencil_ops: None,
+                }),
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+            render_pass.set_pipeline(picking_pipeline.pipeline(VertexType::Standard).unwrap());
+            render_pass.set_bind_group(0, &camera_bind_group, &[]);
+            render_pass.set_bind_group(1, &id_bind_group, &[id_stride]);
+            render_pass.set_vertex_buffer(0, vertex_buffer.slice(..));
+            render_pass.set_vertex_buffer(1, transform_buffer.slice(..));
+            render_pass.draw(0..3, 0..1);
+        }
+        queue.submit(Some(encoder.finish()));
+
+        let picked = |position| {
+            PickingPipeline::decode_id(picking_pipeline.read_pixel(
+                device.clone(),
+                queue.clone(),
+                position,
+            ))
+        };
+        assert_eq!(picked((2, 8)), Some(7));
+        assert_eq!(picked((12, 8)), None);
+    }
+}
@@ -1,15 +1,229 @@
 #![allow(clippy::too_many_arguments)]
-use std::{fmt::Debug, sync::Arc};
+use std::{
+    collections::HashSet,
+    fmt::Debug,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
 use wgpu::{Device, PipelineLayout, RenderPipeline, ShaderStages};
 
 use crate::{
-    ecs::{components::camera_component::CameraComponent, material::MaterialId},
-    texture::Texture,
+    ecs::{
+        components::camera_component::CameraComponent,
+        material::{Material, MaterialId},
+    },
+    uniform_pool::UniformPool,
 };
 
 #[derive(Debug)]
 pub enum PipelineError {
     PathNotFound(String),
+    /// An `#include` points at a file that can't be read
+    IncludeNotFound {
+        path: String,
+        included_from: String,
+    },
+    /// The chain of files including each other, ending with the file that closes the loop
+    IncludeCycle(Vec<String>),
+    /// An `#include` line without a quoted path
+    MalformedInclude {
+        line: String,
+        file: String,
+    },
+    /// The shader's path followed by wgpu's validation message
+    ShaderCompile(String),
+}
+
+/// How a material's output is combined with what's already drawn.
+/// [BlendMode::AlphaBlend] and [BlendMode::PremultipliedAlpha] only look right when drawn back
+/// to front after the opaque geometry, so give those materials' meshes a later render order and
+/// usually turn off their depth writes. [BlendMode::Opaque] and [BlendMode::Additive] don't
+/// depend on draw order
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum BlendMode {
+    /// Replaces the destination, skipping blending entirely
+    Opaque,
+    #[default]
+    AlphaBlend,
+    /// Adds the source on top of the destination, for glows and particles
+    Additive,
+    /// Like [BlendMode::AlphaBlend] for colors that are already multiplied by their alpha
+    PremultipliedAlpha,
+}
+
+impl BlendMode {
+    pub fn blend_state(self) -> Option<wgpu::BlendState> {
+        match self {
+            BlendMode::Opaque => None,
+            BlendMode::AlphaBlend => Some(wgpu::BlendState::ALPHA_BLENDING),
+            BlendMode::Additive => Some(wgpu::BlendState {
+                color: wgpu::BlendComponent {
+                    src_factor: wgpu::BlendFactor::SrcAlpha,
+                    dst_factor: wgpu::BlendFactor::One,
+                    operation: wgpu::BlendOperation::Add,
+                },
+                alpha: wgpu::BlendComponent {
+                    src_factor: wgpu::BlendFactor::One,
+                    dst_factor: wgpu::BlendFactor::One,
+                    operation: wgpu::BlendOperation::Add,
+                },
+            }),
+            BlendMode::PremultipliedAlpha => Some(wgpu::BlendState::PREMULTIPLIED_ALPHA_BLENDING),
+        }
+    }
+}
+
+/// Fixed function state a material's pipeline is created with. Part of the [MaterialId], so
+/// materials with different configs get separate pipelines
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PipelineConfig {
+    /// Derive multisample coverage from the fragment's alpha, giving cutout materials like
+    /// foliage anti-aliased edges. Only valid with multisampling, ignored otherwise
+    pub alpha_to_coverage: bool,
+    /// [wgpu::PolygonMode::Line] draws the material as wireframe, for debugging. Falls back to
+    /// fill if the device doesn't support the mode
+    pub polygon_mode: wgpu::PolygonMode,
+    /// Faces that are skipped, `None` draws both sides
+    pub cull_mode: Option<wgpu::Face>,
+    /// Winding order of front facing triangles
+    pub front_face: wgpu::FrontFace,
+    pub blend_mode: BlendMode,
+    /// Whether the material's fragments are written to the depth buffer. Transparent materials
+    /// usually test against depth without writing it, so they need to be drawn after the opaque
+    /// geometry they can be seen through
+    pub depth_write: bool,
+    pub depth_compare: wgpu::CompareFunction,
+    /// Stencil test and writes, e.g. for outlines and portals. Materials writing the stencil are
+    /// drawn before the ones only testing it
+    pub stencil: Option<StencilConfig>,
+}
+
+impl Default for PipelineConfig {
+    fn default() -> Self {
+        PipelineConfig {
+            alpha_to_coverage: false,
+            polygon_mode: wgpu::PolygonMode::default(),
+            cull_mode: None,
+            front_face: wgpu::FrontFace::default(),
+            blend_mode: BlendMode::default(),
+            depth_write: true,
+            depth_compare: wgpu::CompareFunction::Less,
+            stencil: None,
+        }
+    }
+}
+
+impl PipelineConfig {
+    /// Alpha blended without depth writes, see [BlendMode] for the draw order this needs
+    pub fn transparent() -> Self {
+        PipelineConfig {
+            blend_mode: BlendMode::AlphaBlend,
+            depth_write: false,
+            ..Default::default()
+        }
+    }
+
+    /// Opaque with back face culling and counter clockwise front faces, used for imported
+    /// meshes
+    pub fn imported_mesh() -> Self {
+        PipelineConfig {
+            cull_mode: Some(wgpu::Face::Back),
+            blend_mode: BlendMode::Opaque,
+            ..Default::default()
+        }
+    }
+
+    /// Swaps the front face winding, for meshes exported with clockwise triangles
+    pub fn flip_winding(mut self) -> Self {
+        self.front_face = match self.front_face {
+            wgpu::FrontFace::Ccw => wgpu::FrontFace::Cw,
+            wgpu::FrontFace::Cw => wgpu::FrontFace::Ccw,
+        };
+        self
+    }
+
+    pub fn with_stencil(mut self, stencil: StencilConfig) -> Self {
+        self.stencil = Some(stencil);
+        self
+    }
+}
+
+/// How a material tests and writes the stencil buffer, the same for front and back faces. Needs
+/// a depth format with a stencil aspect, see [crate::renderer::RendererSettings::stencil]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct StencilConfig {
+    /// Compared against and written, set on the render pass before the material is drawn
+    pub reference: u32,
+    pub compare: wgpu::CompareFunction,
+    /// Applied when the stencil test fails
+    pub fail_op: wgpu::StencilOperation,
+    /// Applied when the stencil test passes but the depth test fails
+    pub depth_fail_op: wgpu::StencilOperation,
+    /// Applied when both tests pass
+    pub pass_op: wgpu::StencilOperation,
+    pub read_mask: u32,
+    pub write_mask: u32,
+}
+
+impl StencilConfig {
+    /// Writes `reference` wherever the material is drawn, e.g. for the object an outline goes
+    /// around
+    pub fn write(reference: u32) -> Self {
+        StencilConfig {
+            reference,
+            compare: wgpu::CompareFunction::Always,
+            fail_op: wgpu::StencilOperation::Keep,
+            depth_fail_op: wgpu::StencilOperation::Keep,
+            pass_op: wgpu::StencilOperation::Replace,
+            read_mask: !0,
+            write_mask: !0,
+        }
+    }
+
+    /// Only draws where the stencil is `reference`, e.g. the view through a portal
+    pub fn equal(reference: u32) -> Self {
+        StencilConfig {
+            reference,
+            compare: wgpu::CompareFunction::Equal,
+            fail_op: wgpu::StencilOperation::Keep,
+            depth_fail_op: wgpu::StencilOperation::Keep,
+            pass_op: wgpu::StencilOperation::Keep,
+            read_mask: !0,
+            write_mask: 0,
+        }
+    }
+
+    /// Only draws where the stencil isn't `reference`, e.g. a scaled up copy of an object that
+    /// [StencilConfig::write]s it, leaving only its outline
+    pub fn not_equal(reference: u32) -> Self {
+        StencilConfig {
+            compare: wgpu::CompareFunction::NotEqual,
+            ..Self::equal(reference)
+        }
+    }
+
+    /// Whether drawing with this config can change the stencil buffer
+    pub fn writes(&self) -> bool {
+        self.write_mask != 0
+            && [self.fail_op, self.depth_fail_op, self.pass_op]
+                .iter()
+                .any(|op| *op != wgpu::StencilOperation::Keep)
+    }
+
+    fn state(&self) -> wgpu::StencilState {
+        let face = wgpu::StencilFaceState {
+            compare: self.compare,
+            fail_op: self.fail_op,
+            depth_fail_op: self.depth_fail_op,
+            pass_op: self.pass_op,
+        };
+        wgpu::StencilState {
+            front: face,
+            back: face,
+            read_mask: self.read_mask,
+            write_mask: self.write_mask,
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -29,13 +243,43 @@ impl Pipeline {
     pub fn new(
         device: Arc<Device>,
         color_format: wgpu::TextureFormat,
+        sample_count: u32,
+        depth_format: wgpu::TextureFormat,
         vertex_layouts: &[wgpu::VertexBufferLayout],
         id: &MaterialId,
-    ) -> Self {
-        let vertex_descriptor = Pipeline::load_shader_module_descriptor(&id.0).unwrap();
-        let fragment_descriptor = Pipeline::load_shader_module_descriptor(&id.1).unwrap();
-        let vertex_shader = device.create_shader_module(vertex_descriptor);
-        let fragment_shader = device.create_shader_module(fragment_descriptor);
+    ) -> Result<Self, PipelineError> {
+        let vertex_descriptor =
+            Pipeline::load_shader_module_descriptor(&id.vertex_shader_path, &id.shader_defines)?;
+        let fragment_descriptor =
+            Pipeline::load_shader_module_descriptor(&id.fragment_shader_path, &id.shader_defines)?;
+        Pipeline::from_descriptors(
+            device,
+            color_format,
+            sample_count,
+            depth_format,
+            vertex_layouts,
+            id,
+            vertex_descriptor,
+            fragment_descriptor,
+        )
+    }
+
+    /// Creates the pipeline from already loaded shader modules, used when the shader sources
+    /// were read ahead of time (see [crate::ecs::scene::Scene::initialize_async])
+    pub fn from_descriptors(
+        device: Arc<Device>,
+        color_format: wgpu::TextureFormat,
+        sample_count: u32,
+        depth_format: wgpu::TextureFormat,
+        vertex_layouts: &[wgpu::VertexBufferLayout],
+        id: &MaterialId,
+        vertex_descriptor: wgpu::ShaderModuleDescriptor,
+        fragment_descriptor: wgpu::ShaderModuleDescriptor,
+    ) -> Result<Self, PipelineError> {
+        let vertex_shader =
+            Self::create_shader_module(&device, vertex_descriptor, &id.vertex_shader_path)?;
+        let fragment_shader =
+            Self::create_shader_module(&device, fragment_descriptor, &id.fragment_shader_path)?;
 
         let layout = Pipeline::create_pipeline_layout(id, device.clone());
 
@@ -53,7 +297,7 @@ impl Pipeline {
                 entry_point: "main",
                 targets: &[Some(wgpu::ColorTargetState {
                     format: color_format,
-                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    blend: id.pipeline_config.blend_mode.blend_state(),
                     write_mask: wgpu::ColorWrites::ALL,
                 })],
                 compilation_options: wgpu::PipelineCompilationOptions::default(),
@@ -61,63 +305,97 @@ impl Pipeline {
             primitive: wgpu::PrimitiveState {
                 topology: wgpu::PrimitiveTopology::TriangleList,
                 strip_index_format: None,
-                front_face: wgpu::FrontFace::Ccw,
-                cull_mode: None,
+                front_face: id.pipeline_config.front_face,
+                cull_mode: id.pipeline_config.cull_mode,
                 unclipped_depth: false,
-                polygon_mode: wgpu::PolygonMode::Fill,
+                polygon_mode: Self::validate_polygon_mode(&device, id),
                 conservative: false,
             },
             depth_stencil: Some(wgpu::DepthStencilState {
-                format: Texture::DEPTH_FORMAT,
-                depth_write_enabled: true,
-                depth_compare: wgpu::CompareFunction::Less,
-                stencil: wgpu::StencilState::default(),
+                format: depth_format,
+                depth_write_enabled: id.pipeline_config.depth_write,
+                depth_compare: id.pipeline_config.depth_compare,
+                stencil: Self::validate_stencil(id, depth_format),
                 bias: wgpu::DepthBiasState::default(),
             }),
             multisample: wgpu::MultisampleState {
-                count: 1,
+                count: sample_count,
                 mask: !0,
-                alpha_to_coverage_enabled: false,
+                alpha_to_coverage_enabled: Self::validate_alpha_to_coverage(id, sample_count),
             },
             multiview: None,
             cache: None,
         });
 
-        Pipeline {
+        Ok(Pipeline {
             pipeline: render_pipeline,
             id: id.clone(),
+        })
+    }
+
+    /// Compiles the shader inside an error scope, so invalid WGSL comes back as
+    /// [PipelineError::ShaderCompile] instead of going to wgpu's uncaptured error handler
+    pub fn create_shader_module(
+        device: &Device,
+        descriptor: wgpu::ShaderModuleDescriptor,
+        shader_path: &str,
+    ) -> Result<wgpu::ShaderModule, PipelineError> {
+        device.push_error_scope(wgpu::ErrorFilter::Validation);
+        let shader = device.create_shader_module(descriptor);
+        match pollster::block_on(device.pop_error_scope()) {
+            Some(err) => Err(PipelineError::ShaderCompile(format!(
+                "{shader_path}: {err}"
+            ))),
+            None => Ok(shader),
         }
     }
 
-    pub fn create_pipeline_layout(material_id: &MaterialId, device: Arc<Device>) -> PipelineLayout {
-        let texture_bind_group_layout_entries: Vec<wgpu::BindGroupLayoutEntry> = if material_id.2
-            == 0
-        {
-            Vec::new()
-        } else {
-            (0..material_id.2)
-                .flat_map(|i| {
-                    [
-                        wgpu::BindGroupLayoutEntry {
-                            binding: i as u32 * 2,
-                            visibility: ShaderStages::VERTEX_FRAGMENT,
-                            ty: wgpu::BindingType::Texture {
-                                sample_type: wgpu::TextureSampleType::Float { filterable: true },
-                                view_dimension: wgpu::TextureViewDimension::D2,
-                                multisampled: false,
-                            },
-                            count: None,
-                        },
-                        wgpu::BindGroupLayoutEntry {
-                            binding: i as u32 * 2 + 1,
-                            visibility: ShaderStages::VERTEX_FRAGMENT,
-                            ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
-                            count: None,
-                        },
-                    ]
-                })
-                .collect::<Vec<wgpu::BindGroupLayoutEntry>>()
+    fn validate_alpha_to_coverage(id: &MaterialId, sample_count: u32) -> bool {
+        if id.pipeline_config.alpha_to_coverage && sample_count == 1 {
+            log::warn!(
+                "Alpha to coverage requires multisampling, disabling it for {:?}",
+                (&id.vertex_shader_path, &id.fragment_shader_path)
+            );
+            return false;
+        }
+        id.pipeline_config.alpha_to_coverage
+    }
+
+    fn validate_polygon_mode(device: &Device, id: &MaterialId) -> wgpu::PolygonMode {
+        let required_feature = match id.pipeline_config.polygon_mode {
+            wgpu::PolygonMode::Fill => return wgpu::PolygonMode::Fill,
+            wgpu::PolygonMode::Line => wgpu::Features::POLYGON_MODE_LINE,
+            wgpu::PolygonMode::Point => wgpu::Features::POLYGON_MODE_POINT,
         };
+        if device.features().contains(required_feature) {
+            id.pipeline_config.polygon_mode
+        } else {
+            log::warn!(
+                "Polygon mode {:?} is not supported by this device, falling back to fill for {:?}",
+                id.pipeline_config.polygon_mode,
+                (&id.vertex_shader_path, &id.fragment_shader_path)
+            );
+            wgpu::PolygonMode::Fill
+        }
+    }
+
+    fn validate_stencil(id: &MaterialId, depth_format: wgpu::TextureFormat) -> wgpu::StencilState {
+        match id.pipeline_config.stencil {
+            Some(stencil) if depth_format.has_stencil_aspect() => stencil.state(),
+            Some(_) => {
+                log::warn!(
+                    "The {depth_format:?} depth format has no stencil, ignoring the stencil of {:?}",
+                    (&id.vertex_shader_path, &id.fragment_shader_path)
+                );
+                wgpu::StencilState::default()
+            }
+            None => wgpu::StencilState::default(),
+        }
+    }
+
+    pub fn create_pipeline_layout(material_id: &MaterialId, device: Arc<Device>) -> PipelineLayout {
+        let texture_bind_group_layout_entries =
+            Material::texture_bind_group_layout_entries(material_id.texture_count);
         let texture_bind_group_layout =
             device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
                 label: Some(&format!("{material_id:?} Texture Bind Group Layout")),
@@ -127,20 +405,24 @@ impl Pipeline {
         let uniform_buffer_bind_group_layout =
             device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
                 label: Some(&format!("{material_id:?} Uniform Buffer Bind Group Layout")),
-                entries: &[wgpu::BindGroupLayoutEntry {
-                    binding: 0,
-                    visibility: ShaderStages::VERTEX_FRAGMENT,
-                    ty: wgpu::BindingType::Buffer {
-                        ty: wgpu::BufferBindingType::Uniform,
-                        has_dynamic_offset: false,
-                        min_binding_size: None,
-                    },
-                    count: None,
+                entries: &[if material_id.pooled_uniform {
+                    UniformPool::bind_group_layout_entry()
+                } else {
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: ShaderStages::VERTEX_FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    }
                 }],
             });
         let camera_bind_group_layout = CameraComponent::camera_bind_group_layout(device.clone());
 
-        let all_layouts = if material_id.3 {
+        let all_layouts = if material_id.has_uniform {
             vec![
                 &texture_bind_group_layout,
                 &camera_bind_group_layout,
@@ -159,12 +441,16 @@ impl Pipeline {
 
     pub fn load_shader_module_descriptor(
         shader_path: &str,
-    ) -> Result<wgpu::ShaderModuleDescriptor, PipelineError> {
+        defines: &[String],
+    ) -> Result<wgpu::ShaderModuleDescriptor<'static>, PipelineError> {
         let shader_string = std::fs::read_to_string(shader_path);
         match shader_string {
             Ok(shader) => Ok(wgpu::ShaderModuleDescriptor {
                 label: None,
-                source: wgpu::ShaderSource::Wgsl(std::borrow::Cow::Owned(shader)),
+                source: wgpu::ShaderSource::Wgsl(std::borrow::Cow::Owned(Self::preprocess_shader(
+                    &Self::resolve_includes(shader_path, &shader)?,
+                    defines,
+                ))),
             }),
             Err(_) => Err(PipelineError::PathNotFound(format!(
                 "Failed to read shader file at path: {shader_path}"
@@ -172,6 +458,136 @@ impl Pipeline {
         }
     }
 
+    /// Same as [Pipeline::load_shader_module_descriptor], but the shader and its includes are
+    /// read on tokio's blocking thread pool so the executor isn't stalled. Needs a tokio runtime
+    pub async fn load_shader_module_descriptor_async(
+        shader_path: &str,
+        defines: &[String],
+    ) -> Result<wgpu::ShaderModuleDescriptor<'static>, PipelineError> {
+        let shader_path = shader_path.to_string();
+        let defines = defines.to_vec();
+        tokio::task::spawn_blocking(move || {
+            Self::load_shader_module_descriptor(&shader_path, &defines)
+        })
+        .await
+        .unwrap_or_else(|err| std::panic::resume_unwind(err.into_panic()))
+    }
+
+    /// Replaces `#include "path"` lines with the contents of that file, resolved relative to the
+    /// including file. Includes nest, and a file included more than once is only inlined the
+    /// first time so shared functions aren't defined twice. Files including each other in a
+    /// loop are an error. Compilation errors in shaders with includes point at lines of the
+    /// combined source
+    pub fn resolve_includes(shader_path: &str, source: &str) -> Result<String, PipelineError> {
+        let mut included = HashSet::new();
+        let mut include_stack = vec![Self::canonical_shader_path(Path::new(shader_path))];
+        Self::inline_includes(source, &mut include_stack, &mut included)
+    }
+
+    fn canonical_shader_path(path: &Path) -> PathBuf {
+        path.canonicalize().unwrap_or_else(|_| path.to_path_buf())
+    }
+
+    fn inline_includes(
+        source: &str,
+        include_stack: &mut Vec<PathBuf>,
+        included: &mut HashSet<PathBuf>,
+    ) -> Result<String, PipelineError> {
+        let including_path = include_stack.last().unwrap().clone();
+        let mut lines = Vec::new();
+        for line in source.lines() {
+            let Some(include) = line.trim().strip_prefix("#include") else {
+                lines.push(line.to_string());
+                continue;
+            };
+
+            let malformed = || PipelineError::MalformedInclude {
+                line: line.to_string(),
+                file: including_path.display().to_string(),
+            };
+            let relative_path = include
+                .trim()
+                .strip_prefix('"')
+                .and_then(|path| path.strip_suffix('"'))
+                .ok_or_else(malformed)?;
+            let path = Self::canonical_shader_path(
+                &including_path
+                    .parent()
+                    .unwrap_or(Path::new(""))
+                    .join(relative_path),
+            );
+
+            if include_stack.contains(&path) {
+                let mut cycle = include_stack
+                    .iter()
+                    .map(|path| path.display().to_string())
+                    .collect::<Vec<_>>();
+                cycle.push(path.display().to_string());
+                return Err(PipelineError::IncludeCycle(cycle));
+            }
+            if !included.insert(path.clone()) {
+                continue;
+            }
+
+            let included_source =
+                std::fs::read_to_string(&path).map_err(|_| PipelineError::IncludeNotFound {
+                    path: path.display().to_string(),
+                    included_from: including_path.display().to_string(),
+                })?;
+            include_stack.push(path);
+            lines.push(Self::inline_includes(
+                &included_source,
+                include_stack,
+                included,
+            )?);
+            include_stack.pop();
+        }
+        Ok(lines.join("\n"))
+    }
+
+    /// Resolves `#define NAME`, `#ifdef NAME`, `#ifndef NAME`, `#else` and `#endif` lines
+    /// against the given defines. Directive and disabled lines are blanked rather than removed
+    /// so that shader compilation errors still point at the right line of the file
+    pub fn preprocess_shader(source: &str, defines: &[String]) -> String {
+        let mut defines = defines.to_vec();
+        // Each entry is whether the enclosing branch and the current branch are active
+        let mut branches: Vec<(bool, bool)> = Vec::new();
+
+        source
+            .lines()
+            .map(|line| {
+                let active = !matches!(branches.last(), Some((_, false)));
+                let mut directive = line.split_whitespace();
+
+                match (directive.next(), directive.next()) {
+                    (Some("#define"), Some(name)) => {
+                        if active {
+                            defines.push(name.to_string());
+                        }
+                    }
+                    (Some("#ifdef"), Some(name)) => {
+                        branches.push((active, active && defines.iter().any(|d| d == name)));
+                    }
+                    (Some("#ifndef"), Some(name)) => {
+                        branches.push((active, active && !defines.iter().any(|d| d == name)));
+                    }
+                    (Some("#else"), _) => {
+                        if let Some((parent_active, branch_active)) = branches.last_mut() {
+                            *branch_active = *parent_active && !*branch_active;
+                        }
+                    }
+                    (Some("#endif"), _) => {
+                        branches.pop();
+                    }
+                    _ if active => return line,
+                    _ => {}
+                }
+                ""
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
     pub fn id(&self) -> &MaterialId {
         &self.id
     }
@@ -180,3 +596,131 @@ impl Pipeline {
         &self.pipeline
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{headless_device, shader_dir};
+
+    fn resolve(dir: &Path, name: &str) -> Result<String, PipelineError> {
+        let path = dir.join(name);
+        let source = std::fs::read_to_string(&path).unwrap();
+        Pipeline::resolve_includes(path.to_str().unwrap(), &source)
+    }
+
+    fn file_name(path: &str) -> &str {
+        Path::new(path).file_name().unwrap().to_str().unwrap()
+    }
+
+    #[test]
+    fn nested_includes_resolve_relative_to_their_file() {
+        let dir = shader_dir(
+            "nested_includes",
+            &[
+                ("main.wgsl", "#include \"lib/a.wgsl\"\nfn main() {}"),
+                ("lib/a.wgsl", "#include \"b.wgsl\"\nfn a() {}"),
+                ("lib/b.wgsl", "fn b() {}"),
+            ],
+        );
+        assert_eq!(
+            resolve(&dir, "main.wgsl").unwrap(),
+            "fn b() {}\nfn a() {}\nfn main() {}"
+        );
+    }
+
+    #[test]
+    fn shared_includes_are_inlined_once() {
+        let dir = shader_dir(
+            "diamond_includes",
+            &[
+                (
+                    "main.wgsl",
+                    "#include \"left.wgsl\"\n#include \"right.wgsl\"\nfn main() {}",
+                ),
+                ("left.wgsl", "#include \"common.wgsl\"\nfn left() {}"),
+                ("right.wgsl", "  #include \"common.wgsl\"\nfn right() {}"),
+                ("common.wgsl", "fn common() {}"),
+            ],
+        );
+        assert_eq!(
+            resolve(&dir, "main.wgsl").unwrap(),
+            "fn common() {}\nfn left() {}\nfn right() {}\nfn main() {}"
+        );
+    }
+
+    #[test]
+    fn include_cycles_list_the_chain() {
+        let dir = shader_dir(
+            "include_cycle",
+            &[
+                ("a.wgsl", "#include \"b.wgsl\""),
+                ("b.wgsl", "#include \"c.wgsl\""),
+                ("c.wgsl", "#include \"a.wgsl\""),
+            ],
+        );
+        let Err(PipelineError::IncludeCycle(cycle)) = resolve(&dir, "a.wgsl") else {
+            panic!("Expected an include cycle");
+        };
+        let names = cycle.iter().map(|path| file_name(path)).collect::<Vec<_>>();
+        assert_eq!(names, ["a.wgsl", "b.wgsl", "c.wgsl", "a.wgsl"]);
+    }
+
+    #[test]
+    fn missing_includes_name_both_files() {
+        let dir = shader_dir(
+            "missing_include",
+            &[("main.wgsl", "#include \"missing.wgsl\"")],
+        );
+        let Err(PipelineError::IncludeNotFound {
+            path,
+            included_from,
+        }) = resolve(&dir, "main.wgsl")
+        else {
+            panic!("Expected a missing include");
+        };
+        assert_eq!(file_name(&path), "missing.wgsl");
+        assert_eq!(file_name(&included_from), "main.wgsl");
+    }
+
+    #[test]
+    fn includes_need_a_quoted_path() {
+        let dir = shader_dir(
+            "malformed_include",
+            &[("main.wgsl", "fn main() {}\n#include common.wgsl")],
+        );
+        let Err(PipelineError::MalformedInclude { line, file }) = resolve(&dir, "main.wgsl") else {
+            panic!("Expected a malformed include");
+        };
+        assert_eq!(line, "#include common.wgsl");
+        assert_eq!(file_name(&file), "main.wgsl");
+    }
+
+    #[test]
+    fn invalid_wgsl_is_a_compile_error() {
+        let Some((device, _)) = headless_device(wgpu::Features::empty()) else {
+            return;
+        };
+        let descriptor = |source: &'static str| wgpu::ShaderModuleDescriptor {
+            label: None,
+            source: wgpu::ShaderSource::Wgsl(source.into()),
+        };
+
+        let result = Pipeline::create_shader_module(
+            &device,
+            descriptor("@fragment fn main() -> @location(0) vec4<f32> { return undefined_color; }"),
+            "shaders/broken.wgsl",
+        );
+        let Err(PipelineError::ShaderCompile(message)) = result else {
+            panic!("Expected a compile error");
+        };
+        assert!(message.starts_with("shaders/broken.wgsl: "), "{message}");
+        assert!(message.contains("undefined_color"), "{message}");
+
+        assert!(Pipeline::create_shader_module(
+            &device,
+            descriptor("@fragment fn main() -> @location(0) vec4<f32> { return vec4<f32>(1.0); }"),
+            "shaders/valid.wgsl",
+        )
+        .is_ok());
+    }
+}
@@ -0,0 +1,497 @@
+use std::sync::Arc;
+
+use bytemuck::Pod;
+use wgpu::{
+    util::{BufferInitDescriptor, DeviceExt},
+    BindGroupLayout, Buffer, Device, Queue, RenderPipeline, Sampler, TextureView,
+};
+
+const POST_PROCESS_HEADER: &str = include_str!("default-shaders/post_process.wgsl");
+
+/// Prepends the shared header (input bindings, `VertexOutput` and `vs_main`) to a fragment shader
+pub(crate) fn create_post_process_shader(
+    device: &Device,
+    label: &str,
+    fragment_source: &str,
+) -> wgpu::ShaderModule {
+    device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some(&format!("{label} Shader")),
+        source: wgpu::ShaderSource::Wgsl(
+            format!("{POST_PROCESS_HEADER}\n{fragment_source}").into(),
+        ),
+    })
+}
+
+/// Pipeline drawing a fullscreen triangle with a shader from [create_post_process_shader]
+pub(crate) fn create_fullscreen_pipeline(
+    device: &Device,
+    label: &str,
+    shader: &wgpu::ShaderModule,
+    fragment_entry_point: &str,
+    bind_group_layout: &BindGroupLayout,
+    color_format: wgpu::TextureFormat,
+    blend: Option<wgpu::BlendState>,
+) -> RenderPipeline {
+    let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: Some(&format!("{label} Pipeline Layout")),
+        bind_group_layouts: &[bind_group_layout],
+        push_constant_ranges: &[],
+    });
+
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some(&format!("{label} Pipeline")),
+        layout: Some(&layout),
+        vertex: wgpu::VertexState {
+            module: shader,
+            entry_point: "vs_main",
+            buffers: &[],
+            compilation_options: wgpu::PipelineCompilationOptions::default(),
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: shader,
+            entry_point: fragment_entry_point,
+            targets: &[Some(wgpu::ColorTargetState {
+                format: color_format,
+                blend,
+                write_mask: wgpu::ColorWrites::ALL,
+            })],
+            compilation_options: wgpu::PipelineCompilationOptions::default(),
+        }),
+        primitive: wgpu::PrimitiveState::default(),
+        depth_stencil: None,
+        multisample: wgpu::MultisampleState::default(),
+        multiview: None,
+        cache: None,
+    })
+}
+
+pub(crate) fn texture_layout_entry(binding: u32) -> wgpu::BindGroupLayoutEntry {
+    wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility: wgpu::ShaderStages::FRAGMENT,
+        ty: wgpu::BindingType::Texture {
+            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+            view_dimension: wgpu::TextureViewDimension::D2,
+            multisampled: false,
+        },
+        count: None,
+    }
+}
+
+/// The header's input texture and sampler, and the params uniform at binding 2 if `params`
+pub(crate) fn input_layout_entries(params: bool) -> Vec<wgpu::BindGroupLayoutEntry> {
+    let mut entries = vec![
+        texture_layout_entry(0),
+        wgpu::BindGroupLayoutEntry {
+            binding: 1,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+            count: None,
+        },
+    ];
+    if params {
+        entries.push(wgpu::BindGroupLayoutEntry {
+            binding: 2,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        });
+    }
+    entries
+}
+
+pub(crate) fn create_post_process_sampler(device: &Device, label: &str) -> Sampler {
+    device.create_sampler(&wgpu::SamplerDescriptor {
+        label: Some(&format!("{label} Sampler")),
+        address_mode_u: wgpu::AddressMode::ClampToEdge,
+        address_mode_v: wgpu::AddressMode::ClampToEdge,
+        mag_filter: wgpu::FilterMode::Linear,
+        min_filter: wgpu::FilterMode::Linear,
+        ..Default::default()
+    })
+}
+
+pub(crate) fn draw_fullscreen(
+    encoder: &mut wgpu::CommandEncoder,
+    label: &str,
+    pipeline: &RenderPipeline,
+    bind_group: &wgpu::BindGroup,
+    output_view: &TextureView,
+    load: wgpu::LoadOp<wgpu::Color>,
+) {
+    let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+        label: Some(label),
+        color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+            view: output_view,
+            resolve_target: None,
+            ops: wgpu::Operations {
+                load,
+                store: wgpu::StoreOp::Store,
+            },
+        })],
+        depth_stencil_attachment: None,
+        timestamp_writes: None,
+        occlusion_query_set: None,
+    });
+    render_pass.set_pipeline(pipeline);
+    render_pass.set_bind_group(0, bind_group, &[]);
+    render_pass.draw(0..3, 0..1);
+}
+
+#[derive(Debug)]
+pub enum PostProcessError {
+    NoParams,
+    ParamsSizeMismatch { expected: u64, actual: u64 },
+}
+
+/// A fullscreen pass run on the finished scene, before text and UI are drawn.
+/// Every effect reads from and writes to textures in [PostProcessStack::color_format] at the
+/// window's size, so effects that keep their own textures have to match both
+pub trait PostEffect: std::fmt::Debug {
+    /// Draws the processed `input_view` over all of `output_view`
+    fn apply(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        input_view: &TextureView,
+        output_view: &TextureView,
+    );
+
+    /// Called when the window is resized
+    fn resize(&mut self, _device: &Device, _size: (u32, u32)) {}
+}
+
+/// A [PostEffect] made from a WGSL fragment shader. The shader is appended to a header that
+/// declares the input at `@group(0) @binding(0)` (`input_texture`) and `@binding(1)`
+/// (`input_sampler`), a `VertexOutput` with `uv` and the vertex stage, so it only needs an
+/// `fs_main(in: VertexOutput) -> @location(0) vec4<f32>`. Params are bound as a uniform at
+/// `@binding(2)`
+#[derive(Debug)]
+pub struct ShaderEffect {
+    device: Arc<Device>,
+    pipeline: RenderPipeline,
+    bind_group_layout: BindGroupLayout,
+    sampler: Sampler,
+    params: Option<(Buffer, u64)>,
+}
+
+impl ShaderEffect {
+    pub fn new(
+        device: Arc<Device>,
+        color_format: wgpu::TextureFormat,
+        label: &str,
+        fragment_source: &str,
+    ) -> Self {
+        Self::create(device, color_format, label, fragment_source, None)
+    }
+
+    pub fn with_params<T: Pod>(
+        device: Arc<Device>,
+        color_format: wgpu::TextureFormat,
+        label: &str,
+        fragment_source: &str,
+        params: T,
+    ) -> Self {
+        Self::create(
+            device,
+            color_format,
+            label,
+            fragment_source,
+            Some(bytemuck::bytes_of(&params)),
+        )
+    }
+
+    pub fn grayscale(device: Arc<Device>, color_format: wgpu::TextureFormat) -> Self {
+        Self::new(
+            device,
+            color_format,
+            "Grayscale",
+            include_str!("default-shaders/grayscale.wgsl"),
+        )
+    }
+
+    /// Darkens the corners, `strength` from `0.0` (off) to `1.0` (black corners)
+    pub fn vignette(device: Arc<Device>, color_format: wgpu::TextureFormat, strength: f32) -> Self {
+        Self::with_params(
+            device,
+            color_format,
+            "Vignette",
+            include_str!("default-shaders/vignette.wgsl"),
+            [strength, 0.0, 0.0, 0.0],
+        )
+    }
+
+    /// Values above `1.0` brighten the image
+    pub fn gamma(device: Arc<Device>, color_format: wgpu::TextureFormat, gamma: f32) -> Self {
+        Self::with_params(
+            device,
+            color_format,
+            "Gamma",
+            include_str!("default-shaders/gamma.wgsl"),
+            [gamma, 0.0, 0.0, 0.0],
+        )
+    }
+
+    /// Fast approximate antialiasing, cheaper than SMAA but blurs textures slightly more
+    pub fn fxaa(device: Arc<Device>, color_format: wgpu::TextureFormat) -> Self {
+        Self::new(
+            device,
+            color_format,
+            "FXAA",
+            include_str!("default-shaders/fxaa.wgsl"),
+        )
+    }
+
+    /// `value` has to be the same type the effect was created with
+    pub fn set_params<T: Pod>(&self, queue: &Queue, value: T) -> Result<(), PostProcessError> {
+        let (buffer, size) = self.params.as_ref().ok_or(PostProcessError::NoParams)?;
+        let bytes = bytemuck::bytes_of(&value);
+        if bytes.len() as u64 != *size {
+            return Err(PostProcessError::ParamsSizeMismatch {
+                expected: *size,
+                actual: bytes.len() as u64,
+            });
+        }
+        queue.write_buffer(buffer, 0, bytes);
+        Ok(())
+    }
+
+    fn create(
+        device: Arc<Device>,
+        color_format: wgpu::TextureFormat,
+        label: &str,
+        fragment_source: &str,
+        params: Option<&[u8]>,
+    ) -> Self {
+        let shader = create_post_process_shader(&device, label, fragment_source);
+
+        let layout_entries = input_layout_entries(params.is_some());
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some(&format!("{label} Bind Group Layout")),
+            entries: &layout_entries,
+        });
+
+        // Uniform buffers have to be a multiple of 16 bytes
+        let params = params.map(|bytes| {
+            let mut contents = bytes.to_vec();
+            contents.resize(bytes.len().div_ceil(16).max(1) * 16, 0);
+            let buffer = device.create_buffer_init(&BufferInitDescriptor {
+                label: Some(&format!("{label} Params Buffer")),
+                contents: &contents,
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            });
+            (buffer, bytes.len() as u64)
+        });
+
+        let sampler = create_post_process_sampler(&device, label);
+
+        let pipeline = create_fullscreen_pipeline(
+            &device,
+            label,
+            &shader,
+            "fs_main",
+            &bind_group_layout,
+            color_format,
+            None,
+        );
+
+        ShaderEffect {
+            device,
+            pipeline,
+            bind_group_layout,
+            sampler,
+            params,
+        }
+    }
+}
+
+impl PostEffect for ShaderEffect {
+    fn apply(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        input_view: &TextureView,
+        output_view: &TextureView,
+    ) {
+        let mut entries = vec![
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(input_view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::Sampler(&self.sampler),
+            },
+        ];
+        if let Some((buffer, _)) = &self.params {
+            entries.push(wgpu::BindGroupEntry {
+                binding: 2,
+                resource: buffer.as_entire_binding(),
+            });
+        }
+        // The input alternates between the stack's two targets, so the bind group is made per
+        // pass
+        let bind_group = self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Post Effect Bind Group"),
+            layout: &self.bind_group_layout,
+            entries: &entries,
+        });
+
+        draw_fullscreen(
+            encoder,
+            "Post Effect Render Pass",
+            &self.pipeline,
+            &bind_group,
+            output_view,
+            wgpu::LoadOp::Load,
+        );
+    }
+}
+
+/// Effects run in order on the scene. While the stack isn't empty the scene is drawn into the
+/// first of two ping-pong textures, each effect reads one and writes the other and the last one
+/// writes to the surface.
+/// With an HDR scene format the scene is always drawn to the targets, and a tonemapping pass
+/// after the last effect converts the result to the surface's format
+#[derive(Debug)]
+pub struct PostProcessStack {
+    effects: Vec<Box<dyn PostEffect>>,
+    targets: Option<[TextureView; 2]>,
+    /// Maps the scene format to the surface format when they differ
+    tonemap: Option<ShaderEffect>,
+    color_format: wgpu::TextureFormat,
+    size: (u32, u32),
+}
+
+impl PostProcessStack {
+    /// `color_format` is what the scene and effects are drawn in, `output_format` the surface's
+    pub fn new(
+        device: Arc<Device>,
+        color_format: wgpu::TextureFormat,
+        output_format: wgpu::TextureFormat,
+        size: (u32, u32),
+    ) -> Self {
+        let tonemap = (color_format != output_format).then(|| {
+            ShaderEffect::new(
+                device.clone(),
+                output_format,
+                "Tonemap",
+                include_str!("default-shaders/tonemap.wgsl"),
+            )
+        });
+        let targets = tonemap
+            .is_some()
+            .then(|| Self::create_targets(&device, color_format, size));
+
+        PostProcessStack {
+            effects: Vec::new(),
+            targets,
+            tonemap,
+            color_format,
+            size,
+        }
+    }
+
+    /// `effect` has to draw in [PostProcessStack::color_format]
+    pub fn push(&mut self, device: &Device, effect: Box<dyn PostEffect>) {
+        if self.targets.is_none() {
+            self.targets = Some(Self::create_targets(device, self.color_format, self.size));
+        }
+        self.effects.push(effect);
+    }
+
+    pub fn effects(&self) -> &[Box<dyn PostEffect>] {
+        &self.effects
+    }
+
+    /// Effects can be reordered or removed, but new ones have to go through
+    /// [PostProcessStack::push] so the targets exist
+    pub fn effects_mut(&mut self) -> &mut Vec<Box<dyn PostEffect>> {
+        &mut self.effects
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.effects.is_empty()
+    }
+
+    /// Where the scene has to be drawn (or resolved) to, `None` if it can be drawn straight to
+    /// the surface
+    pub fn scene_target(&self) -> Option<&TextureView> {
+        if self.is_empty() && self.tonemap.is_none() {
+            return None;
+        }
+        self.targets.as_ref().map(|targets| &targets[0])
+    }
+
+    /// Format of the scene and of every effect's output except the tonemapping pass
+    pub fn color_format(&self) -> wgpu::TextureFormat {
+        self.color_format
+    }
+
+    pub fn is_hdr(&self) -> bool {
+        self.tonemap.is_some()
+    }
+
+    pub fn resize(&mut self, device: &Device, size: (u32, u32)) {
+        self.size = size;
+        if self.targets.is_some() {
+            self.targets = Some(Self::create_targets(device, self.color_format, size));
+        }
+        for effect in &mut self.effects {
+            effect.resize(device, size);
+        }
+    }
+
+    /// Runs every effect on [PostProcessStack::scene_target], the last one drawing to
+    /// `output_view`. Does nothing if the scene was drawn straight to the surface
+    pub fn apply(&self, encoder: &mut wgpu::CommandEncoder, output_view: &TextureView) {
+        let Some(targets) = &self.targets else {
+            return;
+        };
+        let pass_count = self.effects.len() + self.tonemap.is_some() as usize;
+        let passes = self.effects.iter().map(|effect| effect.as_ref()).chain(
+            self.tonemap
+                .iter()
+                .map(|tonemap| tonemap as &dyn PostEffect),
+        );
+
+        let mut input = &targets[0];
+        for (i, effect) in passes.enumerate() {
+            let output = if i + 1 == pass_count {
+                output_view
+            } else {
+                &targets[(i + 1) % 2]
+            };
+            effect.apply(encoder, input, output);
+            input = output;
+        }
+    }
+
+    fn create_targets(
+        device: &Device,
+        color_format: wgpu::TextureFormat,
+        size: (u32, u32),
+    ) -> [TextureView; 2] {
+        ["Post Process Target A", "Post Process Target B"].map(|label| {
+            device
+                .create_texture(&wgpu::TextureDescriptor {
+                    label: Some(label),
+                    size: wgpu::Extent3d {
+                        width: size.0,
+                        height: size.1,
+                        depth_or_array_layers: 1,
+                    },
+                    mip_level_count: 1,
+                    sample_count: 1,
+                    dimension: wgpu::TextureDimension::D2,
+                    format: color_format,
+                    usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                        | wgpu::TextureUsages::TEXTURE_BINDING,
+                    view_formats: &[],
+                })
+                .create_view(&wgpu::TextureViewDescriptor::default())
+        })
+    }
+}
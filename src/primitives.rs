@@ -0,0 +1,276 @@
+use std::f32::consts::{PI, TAU};
+
+use nalgebra::Vector3;
+
+use crate::model::Vertex;
+
+/// Vertices and indices of a mesh, as taken by
+/// [crate::ecs::components::mesh_component::MeshComponent::new]
+pub type MeshData = (Vec<Vertex>, Vec<u32>);
+
+/// A square one unit wide in the XY plane, facing +Z
+pub fn quad() -> MeshData {
+    let mut mesh = (Vec::new(), Vec::new());
+    push_face(&mut mesh, Vector3::z(), Vector3::x(), Vector3::y(), 0.0);
+    mesh
+}
+
+/// A cube one unit wide, centered on the origin. Every face has its own vertices so the normals
+/// stay flat, and the whole texture mapped onto it
+pub fn cube() -> MeshData {
+    let faces = [
+        (Vector3::x(), -Vector3::z(), Vector3::y()),
+        (-Vector3::x(), Vector3::z(), Vector3::y()),
+        (Vector3::y(), Vector3::x(), -Vector3::z()),
+        (-Vector3::y(), Vector3::x(), Vector3::z()),
+        (Vector3::z(), Vector3::x(), Vector3::y()),
+        (-Vector3::z(), -Vector3::x(), Vector3::y()),
+    ];
+
+    let mut mesh = (Vec::with_capacity(24), Vec::with_capacity(36));
+    for (normal, right, up) in faces {
+        push_face(&mut mesh, normal, right, up, 0.5);
+    }
+    mesh
+}
+
+/// A square one unit wide in the XZ plane, facing +Y. `subdivisions` splits each side into that
+/// many more rows of quads, for meshes that get deformed later
+pub fn plane(subdivisions: u32) -> MeshData {
+    let cells = subdivisions + 1;
+    let mut vertices = Vec::with_capacity(((cells + 1) * (cells + 1)) as usize);
+    for row in 0..=cells {
+        for column in 0..=cells {
+            let (u, v) = (column as f32 / cells as f32, row as f32 / cells as f32);
+            vertices.push(Vertex {
+                position: [u - 0.5, 0.0, v - 0.5],
+                tex_coords: [u, v],
+                normal: [0.0, 1.0, 0.0],
+            });
+        }
+    }
+
+    let mut indices = Vec::with_capacity((cells * cells * 6) as usize);
+    for row in 0..cells {
+        for column in 0..cells {
+            let current = row * (cells + 1) + column;
+            let below = current + cells + 1;
+            indices.extend([current, below, below + 1, current, below + 1, current + 1]);
+        }
+    }
+
+    (vertices, indices)
+}
+
+/// A sphere one unit wide, with `segments` slices around it and half as many rings. At least `3`
+/// segments are used. The texture wraps around once, with a seam at +X
+pub fn sphere(segments: u32) -> MeshData {
+    let segments = segments.max(3);
+    let rings = (segments / 2).max(2);
+
+    let mut vertices = Vec::with_capacity(((rings + 1) * (segments + 1)) as usize);
+    for ring in 0..=rings {
+        let polar = PI * ring as f32 / rings as f32;
+        for segment in 0..=segments {
+            let azimuth = TAU * segment as f32 / segments as f32;
+            let normal = [
+                polar.sin() * azimuth.cos(),
+                polar.cos(),
+                -polar.sin() * azimuth.sin(),
+            ];
+            vertices.push(Vertex {
+                position: normal.map(|coordinate| coordinate * 0.5),
+                tex_coords: [segment as f32 / segments as f32, ring as f32 / rings as f32],
+                normal,
+            });
+        }
+    }
+
+    // The triangles touching a pole would be degenerate, so only one of each quad's is kept there
+    let mut indices = Vec::with_capacity((segments * (rings - 1) * 6) as usize);
+    for ring in 0..rings {
+        for segment in 0..segments {
+            let current = ring * (segments + 1) + segment;
+            let below = current + segments + 1;
+            if ring != 0 {
+                indices.extend([current, below, current + 1]);
+            }
+            if ring != rings - 1 {
+                indices.extend([current + 1, below, below + 1]);
+            }
+        }
+    }
+
+    (vertices, indices)
+}
+
+/// A capped cylinder one unit wide and tall along the Y axis, with `segments` sides. At least
+/// `3` segments are used
+pub fn cylinder(segments: u32) -> MeshData {
+    let segments = segments.max(3);
+    let directions = (0..=segments)
+        .map(|segment| {
+            let azimuth = TAU * segment as f32 / segments as f32;
+            (azimuth.cos(), -azimuth.sin())
+        })
+        .collect::<Vec<_>>();
+
+    let mut vertices =
+        Vec::with_capacity((segments as usize + 1) * 2 + (segments as usize + 2) * 2);
+    let mut indices = Vec::with_capacity(segments as usize * 12);
+
+    for (segment, (x, z)) in directions.iter().enumerate() {
+        let u = segment as f32 / segments as f32;
+        for (y, v) in [(0.5, 0.0), (-0.5, 1.0)] {
+            vertices.push(Vertex {
+                position: [x * 0.5, y, z * 0.5],
+                tex_coords: [u, v],
+                normal: [*x, 0.0, *z],
+            });
+        }
+    }
+    for segment in 0..segments {
+        let top = segment * 2;
+        let bottom = top + 1;
+        indices.extend([top, bottom, top + 2, top + 2, bottom, bottom + 2]);
+    }
+
+    for (y, normal_y) in [(0.5, 1.0), (-0.5, -1.0)] {
+        let center = vertices.len() as u32;
+        vertices.push(Vertex {
+            position: [0.0, y, 0.0],
+            tex_coords: [0.5, 0.5],
+            normal: [0.0, normal_y, 0.0],
+        });
+        for (x, z) in &directions {
+            vertices.push(Vertex {
+                position: [x * 0.5, y, z * 0.5],
+                tex_coords: [0.5 + x * 0.5, 0.5 + z * 0.5],
+                normal: [0.0, normal_y, 0.0],
+            });
+        }
+        for segment in 0..segments {
+            let edge = center + 1 + segment;
+            if normal_y > 0.0 {
+                indices.extend([center, edge, edge + 1]);
+            } else {
+                indices.extend([center, edge + 1, edge]);
+            }
+        }
+    }
+
+    (vertices, indices)
+}
+
+/// Adds a counter-clockwise square facing `normal`, `offset` away from the origin. `right` and
+/// `up` are the directions the texture's U and V axes run along
+fn push_face(
+    (vertices, indices): &mut MeshData,
+    normal: Vector3<f32>,
+    right: Vector3<f32>,
+    up: Vector3<f32>,
+    offset: f32,
+) {
+    let first = vertices.len() as u32;
+    let corners = [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)];
+    for (x, y) in corners {
+        let position = normal * offset + right * (x * 0.5) + up * (y * 0.5);
+        vertices.push(Vertex {
+            position: position.into(),
+            tex_coords: [(x + 1.0) * 0.5, (1.0 - y) * 0.5],
+            normal: normal.into(),
+        });
+    }
+    indices.extend([first, first + 1, first + 2, first, first + 2, first + 3]);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn position(vertices: &[Vertex], index: u32) -> Vector3<f32> {
+        vertices[index as usize].position.into()
+    }
+
+    /// Counter-clockwise facing normal of every triangle, scaled by twice its area
+    fn face_normals((vertices, indices): &MeshData) -> Vec<(Vector3<f32>, [u32; 3])> {
+        indices
+            .chunks_exact(3)
+            .map(|triangle| {
+                let [a, b, c] = [triangle[0], triangle[1], triangle[2]];
+                let normal = (position(vertices, b) - position(vertices, a))
+                    .cross(&(position(vertices, c) - position(vertices, a)));
+                (normal, [a, b, c])
+            })
+            .collect()
+    }
+
+    fn assert_counts(mesh: &MeshData, vertex_count: usize, index_count: usize) {
+        assert_eq!(mesh.0.len(), vertex_count);
+        assert_eq!(mesh.1.len(), index_count);
+        assert!(mesh.1.iter().all(|index| (*index as usize) < vertex_count));
+    }
+
+    /// Every triangle winds counter-clockwise when seen from the side its vertex normals face
+    fn assert_faces_outward(mesh: &MeshData) {
+        for (face_normal, triangle) in face_normals(mesh) {
+            if face_normal.norm() < 1e-6 {
+                continue;
+            }
+            let vertex_normal = triangle
+                .iter()
+                .map(|index| Vector3::from(mesh.0[*index as usize].normal))
+                .sum::<Vector3<f32>>();
+            assert!(
+                face_normal.dot(&vertex_normal) > 0.0,
+                "{triangle:?} winds away from its normals"
+            );
+        }
+    }
+
+    #[test]
+    fn vertex_and_index_counts() {
+        assert_counts(&quad(), 4, 6);
+        assert_counts(&cube(), 24, 36);
+        assert_counts(&plane(0), 4, 6);
+        assert_counts(&plane(3), 25, 96);
+        assert_counts(&sphere(8), 45, 144);
+        assert_counts(&sphere(1), 12, 18);
+        assert_counts(&cylinder(8), 38, 96);
+        assert_counts(&cylinder(0), 18, 36);
+    }
+
+    #[test]
+    fn sphere_normals_are_unit_length() {
+        for segments in [3, 8, 31] {
+            let (vertices, _) = sphere(segments);
+            for vertex in vertices {
+                let normal = Vector3::from(vertex.normal);
+                assert!((normal.norm() - 1.0).abs() < 1e-5);
+                let position = Vector3::from(vertex.position);
+                assert!((position - normal * 0.5).norm() < 1e-5);
+            }
+        }
+    }
+
+    #[test]
+    fn plane_winds_counter_clockwise_seen_from_above() {
+        for subdivisions in [0, 2] {
+            for (face_normal, triangle) in face_normals(&plane(subdivisions)) {
+                assert!(
+                    face_normal.dot(&Vector3::y()) > 0.0,
+                    "{triangle:?} faces down"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn every_shape_faces_outward() {
+        assert_faces_outward(&quad());
+        assert_faces_outward(&cube());
+        assert_faces_outward(&plane(2));
+        assert_faces_outward(&sphere(12));
+        assert_faces_outward(&cylinder(12));
+    }
+}
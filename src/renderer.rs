@@ -4,7 +4,100 @@ use sdl2::video::Window;
 use smaa::SmaaTarget;
 use wgpu::rwh::{HasDisplayHandle, HasWindowHandle};
 
-use crate::texture::Texture;
+use crate::{
+    gpu_timer::GpuTimer,
+    post_process::{PostProcessStack, ShaderEffect},
+    texture::Texture,
+};
+
+#[derive(Debug)]
+pub enum RenderError {
+    OutOfMemory,
+    /// Required features the adapter doesn't have
+    MissingFeatures(wgpu::Features),
+    /// Each entry names a limit with the requested and the adapter's value
+    UnsupportedLimits(Vec<String>),
+    RequestDevice(wgpu::RequestDeviceError),
+    /// Not even a fallback adapter is available for the requested backends
+    NoAdapter(wgpu::Backends),
+    /// The window's native handles aren't available
+    WindowHandle(wgpu::rwh::HandleError),
+    CreateSurface(wgpu::CreateSurfaceError),
+    /// The surface can't be copied from on this platform, or its format isn't 8 bit RGBA/BGRA
+    CaptureUnsupported,
+    CaptureFailed,
+}
+
+/// Emitted on [crate::EngineSystems::events] with the frame captured after
+/// [crate::EngineDetails::capture_frame] was set
+#[derive(Debug, Clone)]
+pub struct FrameCaptured(pub image::RgbaImage);
+
+/// Post-process antialiasing of the final image
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AntialiasMode {
+    #[default]
+    None,
+    /// Cheapest, a single fullscreen pass, see [ShaderEffect::fxaa]. It is the first effect in
+    /// [Renderer::post_process], effects pushed later run on the antialiased image
+    Fxaa,
+    /// Sharper than FXAA but needs three passes
+    Smaa,
+}
+
+#[derive(Debug, Clone)]
+pub struct RendererSettings {
+    pub antialias_mode: AntialiasMode,
+    /// Device creation fails with [RenderError::MissingFeatures] if the adapter lacks any of
+    /// these. Compute pipelines with array outputs need
+    /// [wgpu::Features::MAPPABLE_PRIMARY_BUFFERS]
+    pub required_features: wgpu::Features,
+    pub required_limits: wgpu::Limits,
+    /// Requested compositing mode for the surface, falls back to the first supported mode
+    pub alpha_mode: Option<wgpu::CompositeAlphaMode>,
+    /// Number of frames the GPU is allowed to queue ahead of the CPU. Lower values reduce input
+    /// latency at the cost of throughput, higher values give smoother pacing under load
+    pub max_frame_latency: u32,
+    /// Graphics APIs the adapter may be picked from
+    pub backends: wgpu::Backends,
+    /// Tried first, then [wgpu::PowerPreference::LowPower] and then a software fallback adapter
+    pub power_preference: wgpu::PowerPreference,
+    /// Falls back to [wgpu::PresentMode::Fifo] if the surface doesn't support it
+    pub present_mode: wgpu::PresentMode,
+    /// Samples per pixel for MSAA, `1` disables it. MSAA replaces SMAA, so
+    /// [AntialiasMode::Smaa] is ignored when this is above `1`. FXAA still runs on the resolved
+    /// image
+    pub msaa_samples: u32,
+    /// Draws the scene in [Renderer::HDR_FORMAT] so colors can go above `1.0`, then tonemaps it
+    /// onto the surface. Falls back to the surface format if the adapter can't render to and
+    /// filter float textures
+    pub hdr: bool,
+    /// Depth format to try before [Renderer::DEPTH_FORMATS], `None` to go through them in order.
+    /// Falls back to them if the adapter can't render to it
+    pub depth_format: Option<wgpu::TextureFormat>,
+    /// Only picks depth formats with a stencil aspect, needed by materials with a
+    /// [crate::pipeline::StencilConfig]
+    pub stencil: bool,
+}
+
+impl Default for RendererSettings {
+    fn default() -> Self {
+        RendererSettings {
+            antialias_mode: AntialiasMode::None,
+            required_features: Renderer::DEFAULT_FEATURES,
+            required_limits: wgpu::Limits::default(),
+            alpha_mode: None,
+            max_frame_latency: Renderer::DEFAULT_FRAME_LATENCY,
+            backends: wgpu::Backends::all(),
+            power_preference: wgpu::PowerPreference::HighPerformance,
+            present_mode: wgpu::PresentMode::AutoNoVsync,
+            msaa_samples: 1,
+            hdr: false,
+            depth_format: None,
+            stencil: false,
+        }
+    }
+}
 
 pub struct Renderer {
     pub surface: Arc<wgpu::Surface<'static>>,
@@ -16,59 +109,82 @@ pub struct Renderer {
     pub depth_texture: Arc<Texture>,
     pub clear_color: wgpu::Color,
     pub smaa_target: Arc<Mutex<SmaaTarget>>,
+    /// Samples per pixel of the scene's color and depth targets
+    pub sample_count: u32,
+    /// What the scene's pipelines draw in, the surface format unless HDR is enabled
+    pub scene_format: wgpu::TextureFormat,
+    /// Depth format of [Renderer::depth_texture], what the scene's pipelines are built with. See
+    /// [RendererSettings::depth_format]
+    pub depth_format: wgpu::TextureFormat,
+    /// Multisampled color target the scene is drawn to and resolved from, `None` without MSAA
+    pub msaa_view: Option<wgpu::TextureView>,
+    pub post_process: PostProcessStack,
+    /// The adapter that was picked, see [RendererSettings::power_preference]
+    pub adapter_info: wgpu::AdapterInfo,
+    supported_present_modes: Vec<wgpu::PresentMode>,
 }
 
 impl Renderer {
+    pub const DEFAULT_FRAME_LATENCY: u32 = 2;
+    pub const MAX_FRAME_LATENCY: u32 = 3;
+    /// What the engine itself needs, see [RendererSettings::required_features]
+    pub const DEFAULT_FEATURES: wgpu::Features = wgpu::Features::MAPPABLE_PRIMARY_BUFFERS;
+    /// Enabled whenever the adapter supports them, only needed for adapter specific texture
+    /// formats, compressed KTX2 textures, wireframe materials, compute params, GPU frame timings,
+    /// indirect draws culled to another first instance and binding arrays of textures in custom
+    /// shaders respectively
+    pub const OPTIONAL_FEATURES: wgpu::Features =
+        wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES
+            .union(wgpu::Features::TEXTURE_COMPRESSION_BC)
+            .union(wgpu::Features::POLYGON_MODE_LINE)
+            .union(wgpu::Features::PUSH_CONSTANTS)
+            .union(GpuTimer::FEATURES)
+            .union(wgpu::Features::INDIRECT_FIRST_INSTANCE)
+            .union(wgpu::Features::TEXTURE_BINDING_ARRAY);
+    pub const HDR_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+    /// Depth formats in order of preference, the first one the adapter can render to is used
+    pub const DEPTH_FORMATS: [wgpu::TextureFormat; 3] = [
+        wgpu::TextureFormat::Depth32Float,
+        wgpu::TextureFormat::Depth24PlusStencil8,
+        wgpu::TextureFormat::Depth24Plus,
+    ];
+    /// Color format used for offscreen rendering, reads back straight into an RGBA image
+    pub const HEADLESS_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+
     pub async fn new(
         window: &Window,
         clear_color: wgpu::Color,
-        antialiasing: bool,
-        required_limits: wgpu::Limits,
-    ) -> Renderer {
+        settings: RendererSettings,
+    ) -> Result<Renderer, RenderError> {
         let size = window.size();
 
         let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
-            backends: wgpu::Backends::all(),
+            backends: settings.backends,
             dx12_shader_compiler: Default::default(),
             ..Default::default()
         });
 
+        let raw_display_handle = window
+            .display_handle()
+            .map_err(RenderError::WindowHandle)?
+            .into();
+        let raw_window_handle = window
+            .window_handle()
+            .map_err(RenderError::WindowHandle)?
+            .into();
         let surface = Arc::new(
             unsafe {
                 instance.create_surface_unsafe(wgpu::SurfaceTargetUnsafe::RawHandle {
-                    raw_display_handle: window.display_handle().unwrap().into(),
-                    raw_window_handle: window.window_handle().unwrap().into(),
+                    raw_display_handle,
+                    raw_window_handle,
                 })
             }
-            .unwrap(),
+            .map_err(RenderError::CreateSurface)?,
         );
 
-        let adapter = instance
-            .request_adapter(&wgpu::RequestAdapterOptionsBase {
-                power_preference: wgpu::PowerPreference::HighPerformance,
-                force_fallback_adapter: false,
-                compatible_surface: Some(&surface),
-            })
-            .await
-            .unwrap();
+        let adapter = Self::request_adapter(&instance, &settings, Some(&surface)).await?;
 
-        let (device, queue) = adapter
-            .request_device(
-                &wgpu::DeviceDescriptor {
-                    label: Some("Renderer device descriptor"),
-                    required_features: wgpu::Features::MAPPABLE_PRIMARY_BUFFERS
-                        | wgpu::Features::TEXTURE_BINDING_ARRAY
-                        | wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES,
-                    required_limits,
-                    memory_hints: wgpu::MemoryHints::Performance,
-                },
-                None,
-            )
-            .await
-            .unwrap();
-
-        let device = Arc::new(device);
-        let queue = Arc::new(queue);
+        let (device, queue) = Self::request_device(&adapter, &settings).await?;
 
         let surface_caps = surface.get_capabilities(&adapter);
 
@@ -78,40 +194,73 @@ impl Renderer {
             .copied()
             .find(|f| f.is_srgb())
             .unwrap_or(surface_caps.formats[0]);
+
+        let alpha_mode = Self::select_alpha_mode(&surface_caps.alpha_modes, settings.alpha_mode);
+
         let config = wgpu::SurfaceConfiguration {
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            // Copying from the surface is what makes frame captures possible
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                | (surface_caps.usages & wgpu::TextureUsages::COPY_SRC),
             format: surface_format,
             width: size.0,
             height: size.1,
-            // present_mode: surface_caps.present_modes[0],
-            present_mode: wgpu::PresentMode::AutoNoVsync,
-            alpha_mode: surface_caps.alpha_modes[0],
+            present_mode: Self::select_present_mode(
+                &surface_caps.present_modes,
+                settings.present_mode,
+            ),
+            alpha_mode,
             view_formats: vec![],
-            desired_maximum_frame_latency: 2,
+            desired_maximum_frame_latency: Self::validate_frame_latency(settings.max_frame_latency),
         };
         surface.configure(&device, &config);
 
-        let depth_texture = Arc::new(Texture::create_depth_texture(
+        let scene_format = Self::select_scene_format(&adapter, config.format, settings.hdr);
+        let depth_format =
+            Self::select_depth_format(&adapter, settings.depth_format, settings.stencil);
+        let sample_count = Self::validate_sample_count(
+            &adapter,
+            scene_format,
+            depth_format,
+            settings.msaa_samples,
+        );
+
+        let depth_texture = Arc::new(Texture::create_depth_texture_with_format(
             &device,
-            &config,
+            size,
+            sample_count,
+            depth_format,
             "depth_texture",
         ));
+        let msaa_view = Self::create_msaa_view(&device, scene_format, size, sample_count);
+
+        let smaa_enabled = settings.antialias_mode == AntialiasMode::Smaa;
+        if sample_count > 1 && smaa_enabled {
+            log::warn!("SMAA is disabled while MSAA is enabled");
+        }
 
         let smaa_target = Arc::new(Mutex::new(SmaaTarget::new(
             &device,
             &queue,
             size.0,
             size.1,
-            config.format,
-            if antialiasing {
+            scene_format,
+            if smaa_enabled && sample_count == 1 {
                 smaa::SmaaMode::Smaa1X
             } else {
                 smaa::SmaaMode::Disabled
             },
         )));
 
+        let mut post_process =
+            PostProcessStack::new(device.clone(), scene_format, config.format, size);
+        if settings.antialias_mode == AntialiasMode::Fxaa {
+            post_process.push(
+                &device,
+                Box::new(ShaderEffect::fxaa(device.clone(), scene_format)),
+            );
+        }
 
-        Renderer {
+        Ok(Renderer {
             surface,
             surface_format,
             device,
@@ -121,6 +270,414 @@ impl Renderer {
             depth_texture,
             clear_color,
             smaa_target,
+            sample_count,
+            scene_format,
+            depth_format,
+            msaa_view,
+            post_process,
+            adapter_info: adapter.get_info(),
+            supported_present_modes: surface_caps.present_modes,
+        })
+    }
+
+    /// Creates a device and queue without a window or surface, for rendering offscreen with
+    /// [crate::ecs::scene::Scene::render_to_image]. Scenes are initialized with
+    /// [Renderer::HEADLESS_FORMAT] and [Texture::DEPTH_FORMAT], and the engine state comes from
+    /// [crate::EngineSystems::new]
+    pub async fn new_headless(
+        settings: RendererSettings,
+    ) -> Result<(Arc<wgpu::Device>, Arc<wgpu::Queue>), RenderError> {
+        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
+            backends: settings.backends,
+            dx12_shader_compiler: Default::default(),
+            ..Default::default()
+        });
+
+        let adapter = Self::request_adapter(&instance, &settings, None).await?;
+
+        Self::request_device(&adapter, &settings).await
+    }
+
+    /// Tries the preferred power preference, then low power, then a fallback adapter, so
+    /// machines without a discrete GPU (or any GPU, like most CI runners) still get one
+    async fn request_adapter(
+        instance: &wgpu::Instance,
+        settings: &RendererSettings,
+        compatible_surface: Option<&wgpu::Surface<'_>>,
+    ) -> Result<wgpu::Adapter, RenderError> {
+        let attempts = [
+            (settings.power_preference, false),
+            (wgpu::PowerPreference::LowPower, false),
+            (wgpu::PowerPreference::None, true),
+        ];
+        for (power_preference, force_fallback_adapter) in attempts {
+            let adapter = instance
+                .request_adapter(&wgpu::RequestAdapterOptions {
+                    power_preference,
+                    force_fallback_adapter,
+                    compatible_surface,
+                })
+                .await;
+            if let Some(adapter) = adapter {
+                let adapter_info = adapter.get_info();
+                log::info!(
+                    "Using {} ({:?}) with the {:?} backend",
+                    adapter_info.name,
+                    adapter_info.device_type,
+                    adapter_info.backend
+                );
+                return Ok(adapter);
+            }
+            log::warn!(
+                "No adapter found with {power_preference:?} power preference{}",
+                if force_fallback_adapter {
+                    " and a fallback adapter"
+                } else {
+                    ""
+                }
+            );
+        }
+        Err(RenderError::NoAdapter(settings.backends))
+    }
+
+    async fn request_device(
+        adapter: &wgpu::Adapter,
+        settings: &RendererSettings,
+    ) -> Result<(Arc<wgpu::Device>, Arc<wgpu::Queue>), RenderError> {
+        let missing_features = settings.required_features.difference(adapter.features());
+        if !missing_features.is_empty() {
+            return Err(RenderError::MissingFeatures(missing_features));
+        }
+
+        let mut unsupported_limits = Vec::new();
+        settings.required_limits.check_limits_with_fail_fn(
+            &adapter.limits(),
+            false,
+            |name, requested, allowed| {
+                unsupported_limits.push(format!("{name}: requested {requested}, allowed {allowed}"))
+            },
+        );
+        if !unsupported_limits.is_empty() {
+            return Err(RenderError::UnsupportedLimits(unsupported_limits));
+        }
+
+        let mut required_limits = settings.required_limits.clone();
+        if adapter.features().contains(wgpu::Features::PUSH_CONSTANTS) {
+            // Lets compute pipelines pass their params as push constants
+            required_limits.max_push_constant_size = required_limits
+                .max_push_constant_size
+                .max(adapter.limits().max_push_constant_size);
+        }
+
+        let (device, queue) = adapter
+            .request_device(
+                &wgpu::DeviceDescriptor {
+                    label: Some("Renderer device descriptor"),
+                    required_features: settings.required_features
+                        | (adapter.features() & Self::OPTIONAL_FEATURES),
+                    required_limits,
+                    memory_hints: wgpu::MemoryHints::Performance,
+                },
+                None,
+            )
+            .await
+            .map_err(RenderError::RequestDevice)?;
+
+        Ok((Arc::new(device), Arc::new(queue)))
+    }
+
+    /// Changes the present mode at runtime and reconfigures the surface, falling back to
+    /// [wgpu::PresentMode::Fifo] if the mode isn't supported
+    pub fn set_present_mode(&mut self, present_mode: wgpu::PresentMode) {
+        self.config.present_mode =
+            Self::select_present_mode(&self.supported_present_modes, present_mode);
+        self.surface.configure(&self.device, &self.config);
+    }
+
+    fn select_present_mode(
+        supported_modes: &[wgpu::PresentMode],
+        requested_mode: wgpu::PresentMode,
+    ) -> wgpu::PresentMode {
+        // The automatic modes pick a supported mode themselves
+        let is_automatic = matches!(
+            requested_mode,
+            wgpu::PresentMode::AutoVsync | wgpu::PresentMode::AutoNoVsync
+        );
+        if is_automatic || supported_modes.contains(&requested_mode) {
+            requested_mode
+        } else {
+            log::warn!(
+                "Present mode {requested_mode:?} is not supported by this surface, falling back to Fifo"
+            );
+            wgpu::PresentMode::Fifo
+        }
+    }
+
+    /// The requested mode if the surface supports it, otherwise the surface's preferred mode,
+    /// which wgpu lists first
+    fn select_alpha_mode(
+        supported_modes: &[wgpu::CompositeAlphaMode],
+        requested_mode: Option<wgpu::CompositeAlphaMode>,
+    ) -> wgpu::CompositeAlphaMode {
+        match requested_mode {
+            Some(mode) if supported_modes.contains(&mode) => mode,
+            Some(mode) => {
+                log::warn!(
+                    "Alpha mode {mode:?} is not supported by this surface, falling back to {:?}",
+                    supported_modes[0]
+                );
+                supported_modes[0]
+            }
+            None => supported_modes[0],
+        }
+    }
+
+    /// Falls back to `1` if either the surface or the depth format can't be multisampled
+    /// `requested_count` times on this adapter
+    fn validate_sample_count(
+        adapter: &wgpu::Adapter,
+        color_format: wgpu::TextureFormat,
+        depth_format: wgpu::TextureFormat,
+        requested_count: u32,
+    ) -> u32 {
+        if requested_count <= 1 {
+            return 1;
+        }
+        let is_supported = [color_format, depth_format].iter().all(|format| {
+            adapter
+                .get_texture_format_features(*format)
+                .flags
+                .sample_count_supported(requested_count)
+        });
+        if is_supported {
+            requested_count
+        } else {
+            log::warn!("{requested_count}x MSAA is not supported by this adapter, disabling MSAA");
+            1
+        }
+    }
+
+    /// The requested format if the adapter can render to and sample it, otherwise the first of
+    /// [Renderer::DEPTH_FORMATS] it can. Formats without a stencil aspect are skipped when
+    /// `stencil` is set
+    fn select_depth_format(
+        adapter: &wgpu::Adapter,
+        requested: Option<wgpu::TextureFormat>,
+        stencil: bool,
+    ) -> wgpu::TextureFormat {
+        let is_supported = |format: wgpu::TextureFormat| {
+            format.is_depth_stencil_format()
+                && (!stencil || format.has_stencil_aspect())
+                && adapter
+                    .get_texture_format_features(format)
+                    .allowed_usages
+                    .contains(
+                        wgpu::TextureUsages::RENDER_ATTACHMENT
+                            | wgpu::TextureUsages::TEXTURE_BINDING,
+                    )
+        };
+        if let Some(format) = requested.filter(|format| !is_supported(*format)) {
+            log::warn!("{format:?} depth textures are not supported by this adapter");
+        }
+        let depth_format = requested
+            .into_iter()
+            .chain(Self::DEPTH_FORMATS)
+            .find(|format| is_supported(*format))
+            // Both can always be rendered to
+            .unwrap_or(if stencil {
+                wgpu::TextureFormat::Depth24PlusStencil8
+            } else {
+                wgpu::TextureFormat::Depth24Plus
+            });
+        log::info!("Using the {depth_format:?} depth format");
+        depth_format
+    }
+
+    fn select_scene_format(
+        adapter: &wgpu::Adapter,
+        surface_format: wgpu::TextureFormat,
+        hdr: bool,
+    ) -> wgpu::TextureFormat {
+        if !hdr {
+            return surface_format;
+        }
+        let features = adapter.get_texture_format_features(Self::HDR_FORMAT);
+        let is_supported = features.allowed_usages.contains(
+            wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+        ) && features
+            .flags
+            .contains(wgpu::TextureFormatFeatureFlags::FILTERABLE);
+        if is_supported {
+            Self::HDR_FORMAT
+        } else {
+            log::warn!(
+                "{:?} render targets are not supported by this adapter, disabling HDR",
+                Self::HDR_FORMAT
+            );
+            surface_format
+        }
+    }
+
+    fn create_msaa_view(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        size: (u32, u32),
+        sample_count: u32,
+    ) -> Option<wgpu::TextureView> {
+        if sample_count == 1 {
+            return None;
+        }
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("MSAA Color Texture"),
+            size: wgpu::Extent3d {
+                width: size.0,
+                height: size.1,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        });
+        Some(texture.create_view(&wgpu::TextureViewDescriptor::default()))
+    }
+
+    fn validate_frame_latency(requested_latency: u32) -> u32 {
+        let latency = requested_latency.clamp(1, Self::MAX_FRAME_LATENCY);
+        if latency != requested_latency {
+            log::warn!(
+                "Maximum frame latency of {requested_latency} is outside the supported range of 1 to {}, using {latency}",
+                Self::MAX_FRAME_LATENCY
+            );
+        }
+        latency
+    }
+
+    /// Gets the next surface texture to draw to. A lost or outdated surface is reconfigured and
+    /// the frame skipped (`Ok(None)`), as is a timed out acquire. Running out of memory can't be
+    /// recovered from here, so it's left to the caller
+    pub fn acquire_frame(&mut self) -> Result<Option<wgpu::SurfaceTexture>, RenderError> {
+        match self.surface.get_current_texture() {
+            Ok(output) => Ok(Some(output)),
+            Err(wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated) => {
+                self.resize(self.size, None);
+                Ok(None)
+            }
+            Err(wgpu::SurfaceError::Timeout) => {
+                log::warn!("Timed out acquiring the next surface texture, skipping the frame");
+                Ok(None)
+            }
+            Err(wgpu::SurfaceError::OutOfMemory) => Err(RenderError::OutOfMemory),
+        }
+    }
+
+    /// Reads back a rendered frame that hasn't been presented yet, as it appears on screen.
+    /// This waits for the GPU to finish the frame
+    pub fn capture_frame(
+        &self,
+        output: &wgpu::SurfaceTexture,
+    ) -> Result<image::RgbaImage, RenderError> {
+        if !self.config.usage.contains(wgpu::TextureUsages::COPY_SRC) {
+            return Err(RenderError::CaptureUnsupported);
+        }
+        if !matches!(
+            self.config.format,
+            wgpu::TextureFormat::Rgba8Unorm
+                | wgpu::TextureFormat::Rgba8UnormSrgb
+                | wgpu::TextureFormat::Bgra8Unorm
+                | wgpu::TextureFormat::Bgra8UnormSrgb
+        ) {
+            return Err(RenderError::CaptureUnsupported);
+        }
+
+        let encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Frame Capture Encoder"),
+            });
+        Texture::read_to_image(&self.device, &self.queue, encoder, &output.texture)
+            .ok_or(RenderError::CaptureFailed)
+    }
+
+    /// The depth buffer the scene is drawn with, see [Texture::depth_bind_group_layout] for
+    /// reading it from shaders. It is recreated on resize, so bind groups using it have to be
+    /// recreated as well. Always the size of the surface, the scene skips frames where it isn't
+    pub fn depth_texture(&self) -> Arc<Texture> {
+        self.depth_texture.clone()
+    }
+
+    /// Reads the depth buffer value under a pixel, `None` if the position is outside the window,
+    /// MSAA is enabled, since multisampled textures can't be copied, or the depth format isn't
+    /// [wgpu::TextureFormat::Depth32Float].
+    /// The read waits for the GPU to finish, so it returns the depth of the last submitted
+    /// frame rather than the one currently being recorded
+    pub fn read_depth_at(&self, position: (u32, u32)) -> Option<f32> {
+        if self.sample_count > 1
+            || self.depth_format != wgpu::TextureFormat::Depth32Float
+            || position.0 >= self.size.0
+            || position.1 >= self.size.1
+        {
+            return None;
+        }
+
+        // Only one texel is needed, but the copy still has to respect the row alignment
+        let buffer = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Depth Readback Buffer"),
+            size: wgpu::COPY_BYTES_PER_ROW_ALIGNMENT as u64,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Depth Readback Encoder"),
+            });
+
+        encoder.copy_texture_to_buffer(
+            wgpu::ImageCopyTexture {
+                texture: &self.depth_texture.texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d {
+                    x: position.0,
+                    y: position.1,
+                    z: 0,
+                },
+                aspect: wgpu::TextureAspect::DepthOnly,
+            },
+            wgpu::ImageCopyBuffer {
+                buffer: &buffer,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT),
+                    rows_per_image: Some(1),
+                },
+            },
+            wgpu::Extent3d {
+                width: 1,
+                height: 1,
+                depth_or_array_layers: 1,
+            },
+        );
+
+        self.queue.submit(Some(encoder.finish()));
+
+        let buffer_slice = buffer.slice(..);
+        let (sender, receiver) = flume::bounded(1);
+        buffer_slice.map_async(wgpu::MapMode::Read, move |v| sender.send(v).unwrap());
+
+        self.device.poll(wgpu::Maintain::Wait);
+
+        if let Ok(Ok(())) = receiver.recv() {
+            let data = buffer_slice.get_mapped_range();
+            let depth = f32::from_ne_bytes([data[0], data[1], data[2], data[3]]);
+            drop(data);
+            buffer.unmap();
+            Some(depth)
+        } else {
+            None
         }
     }
 
@@ -130,16 +687,25 @@ impl Renderer {
             self.config.width = new_size.0;
             self.config.height = new_size.1;
             self.surface.configure(&self.device, &self.config);
-            self.depth_texture = Arc::new(Texture::create_depth_texture(
+            self.depth_texture = Arc::new(Texture::create_depth_texture_with_format(
                 &self.device,
-                &self.config,
+                new_size,
+                self.sample_count,
+                self.depth_format,
                 "depth_texture",
             ));
+            self.msaa_view = Self::create_msaa_view(
+                &self.device,
+                self.scene_format,
+                new_size,
+                self.sample_count,
+            );
             self.smaa_target
                 .clone()
                 .lock()
                 .unwrap()
                 .resize(&self.device, new_size.0, new_size.1);
+            self.post_process.resize(&self.device, new_size);
 
             if let Some(viewport) = text_viewport {
                 viewport.update(
@@ -153,3 +719,80 @@ impl Renderer {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unsupported_alpha_modes_fall_back_to_the_preferred_one() {
+        let supported = [
+            wgpu::CompositeAlphaMode::Opaque,
+            wgpu::CompositeAlphaMode::PreMultiplied,
+        ];
+
+        assert_eq!(
+            Renderer::select_alpha_mode(&supported, Some(wgpu::CompositeAlphaMode::PreMultiplied)),
+            wgpu::CompositeAlphaMode::PreMultiplied
+        );
+        assert_eq!(
+            Renderer::select_alpha_mode(&supported, Some(wgpu::CompositeAlphaMode::PostMultiplied)),
+            wgpu::CompositeAlphaMode::Opaque
+        );
+        assert_eq!(
+            Renderer::select_alpha_mode(&supported, None),
+            wgpu::CompositeAlphaMode::Opaque
+        );
+    }
+
+    #[test]
+    fn frame_latency_is_clamped_to_the_supported_range() {
+        assert_eq!(Renderer::validate_frame_latency(0), 1);
+        assert_eq!(Renderer::validate_frame_latency(1), 1);
+        assert_eq!(
+            Renderer::validate_frame_latency(RendererSettings::default().max_frame_latency),
+            Renderer::DEFAULT_FRAME_LATENCY
+        );
+        assert_eq!(
+            Renderer::validate_frame_latency(Renderer::MAX_FRAME_LATENCY),
+            Renderer::MAX_FRAME_LATENCY
+        );
+        assert_eq!(
+            Renderer::validate_frame_latency(Renderer::MAX_FRAME_LATENCY + 5),
+            Renderer::MAX_FRAME_LATENCY
+        );
+    }
+
+    #[test]
+    fn adapters_come_from_the_requested_backends() {
+        let request = |backends: wgpu::Backends| {
+            let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
+                backends,
+                ..Default::default()
+            });
+            let settings = RendererSettings {
+                backends,
+                ..RendererSettings::default()
+            };
+            pollster::block_on(Renderer::request_adapter(&instance, &settings, None))
+                .map(|adapter| adapter.get_info().backend)
+        };
+
+        assert!(matches!(
+            request(wgpu::Backends::empty()),
+            Err(RenderError::NoAdapter(backends)) if backends.is_empty()
+        ));
+        for requested in [
+            wgpu::Backends::VULKAN,
+            wgpu::Backends::METAL,
+            wgpu::Backends::DX12,
+            wgpu::Backends::GL,
+        ] {
+            match request(requested) {
+                Ok(backend) => assert_eq!(wgpu::Backends::from(backend), requested),
+                Err(RenderError::NoAdapter(backends)) => assert_eq!(backends, requested),
+                Err(error) => panic!("{error:?}"),
+            }
+        }
+    }
+}
@@ -0,0 +1,138 @@
+use std::{
+    panic::AssertUnwindSafe,
+    path::PathBuf,
+    rc::Rc,
+    sync::{Arc, Mutex, OnceLock},
+};
+
+use sdl2::Sdl;
+use wgpu::{Device, Features, Queue};
+
+use crate::EngineSystems;
+
+/// Draws [crate::model::Vertex] meshes with their transform through the active camera
+pub const VERTEX_SHADER: &str = "
+    struct Camera {
+        view_pos: vec4<f32>,
+        view_proj: mat4x4<f32>,
+    };
+    @group(1) @binding(0)
+    var<uniform> camera: Camera;
+
+    @vertex
+    fn main(
+        @location(0) position: vec3<f32>,
+        @location(5) model_0: vec4<f32>,
+        @location(6) model_1: vec4<f32>,
+        @location(7) model_2: vec4<f32>,
+        @location(8) model_3: vec4<f32>,
+    ) -> @builtin(position) vec4<f32> {
+        let model = mat4x4<f32>(model_0, model_1, model_2, model_3);
+        return camera.view_proj * model * vec4<f32>(position, 1.0);
+    }
+";
+
+/// A fragment shader filling meshes with `color`
+pub fn fragment_shader(color: [f32; 4]) -> String {
+    format!(
+        "@fragment fn main() -> @location(0) vec4<f32> {{ return vec4<f32>({:?}, {:?}, {:?}, {:?}); }}",
+        color[0], color[1], color[2], color[3]
+    )
+}
+
+/// Writes `files` into a fresh directory for the test and returns it
+pub fn shader_dir(test_name: &str, files: &[(&str, &str)]) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("gamezap_{test_name}_{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    for (name, contents) in files {
+        let path = dir.join(name);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, contents).unwrap();
+    }
+    dir
+}
+
+type SdlJob = Box<dyn FnOnce(Option<&Sdl>) + Send>;
+
+/// SDL can only ever be started from one thread, so tests that need [EngineSystems] run one after
+/// another on a thread of their own with the `dummy` video driver. A panic in `test` fails the
+/// calling test. `None` where SDL can't start, so the tests skip instead of failing
+pub fn with_engine_systems<R: Send + 'static>(
+    test: impl FnOnce(Rc<Mutex<EngineSystems>>) -> R + Send + 'static,
+) -> Option<R> {
+    static SDL_THREAD: OnceLock<flume::Sender<SdlJob>> = OnceLock::new();
+    let sdl_thread = SDL_THREAD.get_or_init(|| {
+        let (sender, receiver) = flume::unbounded::<SdlJob>();
+        std::thread::spawn(move || {
+            std::env::set_var("SDL_VIDEODRIVER", "dummy");
+            let sdl_context = sdl2::init().ok();
+            for job in receiver.iter() {
+                job(sdl_context.as_ref());
+            }
+        });
+        sender
+    });
+
+    let (result_sender, result_receiver) = flume::bounded(1);
+    sdl_thread
+        .send(Box::new(move |sdl_context| {
+            let engine_systems =
+                sdl_context.and_then(|sdl_context| EngineSystems::new(sdl_context.clone()).ok());
+            let result = engine_systems.map(|engine_systems| {
+                std::panic::catch_unwind(AssertUnwindSafe(|| {
+                    test(Rc::new(Mutex::new(engine_systems)))
+                }))
+            });
+            let _ = result_sender.send(result);
+        }))
+        .unwrap();
+    match result_receiver.recv().unwrap()? {
+        Ok(result) => Some(result),
+        Err(panic) => std::panic::resume_unwind(panic),
+    }
+}
+
+/// A device without a window for GPU tests, `None` on machines without an adapter or without
+/// `features` so the tests skip instead of failing
+pub fn headless_device(features: Features) -> Option<(Arc<Device>, Arc<Queue>)> {
+    let instance = wgpu::Instance::default();
+    let adapter =
+        pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions::default()))?;
+    if !adapter.features().contains(features) {
+        return None;
+    }
+    let (device, queue) = pollster::block_on(adapter.request_device(
+        &wgpu::DeviceDescriptor {
+            label: Some("Test Device"),
+            required_features: features,
+            required_limits: adapter.limits(),
+            memory_hints: wgpu::MemoryHints::default(),
+        },
+        None,
+    ))
+    .ok()?;
+    Some((Arc::new(device), Arc::new(queue)))
+}
+
+/// Copies `buffer` into a mappable one and reads it back, the buffer needs
+/// [wgpu::BufferUsages::COPY_SRC]
+pub fn read_buffer(device: &Device, queue: &Queue, buffer: &wgpu::Buffer) -> Vec<u8> {
+    let staging = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("Test Readback Buffer"),
+        size: buffer.size(),
+        usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+        mapped_at_creation: false,
+    });
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+        label: Some("Test Readback Encoder"),
+    });
+    encoder.copy_buffer_to_buffer(buffer, 0, &staging, 0, buffer.size());
+    queue.submit(Some(encoder.finish()));
+
+    let slice = staging.slice(..);
+    slice.map_async(wgpu::MapMode::Read, |result| result.unwrap());
+    device.poll(wgpu::Maintain::Wait);
+    let data = slice.get_mapped_range().to_vec();
+    staging.unmap();
+    data
+}
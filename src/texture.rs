@@ -1,5 +1,78 @@
 use anyhow::*;
 
+#[derive(Debug)]
+pub enum TextureError {
+    FailedToReadImage(String),
+    InvalidImage(String),
+}
+
+/// Addressing and filtering used by a texture's sampler. The default clamps to the edge and uses
+/// nearest filtering, which keeps scaled up pixel art sharp
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SamplerOptions {
+    pub address_mode_u: wgpu::AddressMode,
+    pub address_mode_v: wgpu::AddressMode,
+    pub address_mode_w: wgpu::AddressMode,
+    pub mag_filter: wgpu::FilterMode,
+    pub min_filter: wgpu::FilterMode,
+    pub mipmap_filter: wgpu::FilterMode,
+    /// Maximum anisotropy, 1 disables anisotropic filtering
+    pub anisotropy_clamp: u16,
+}
+
+impl Default for SamplerOptions {
+    fn default() -> Self {
+        Self {
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Nearest,
+            min_filter: wgpu::FilterMode::Nearest,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            anisotropy_clamp: 1,
+        }
+    }
+}
+
+impl SamplerOptions {
+    pub const MAX_ANISOTROPY: u16 = 16;
+
+    /// Repeating addressing with linear filtering, for tiling textures
+    pub fn tiling() -> Self {
+        Self {
+            address_mode_u: wgpu::AddressMode::Repeat,
+            address_mode_v: wgpu::AddressMode::Repeat,
+            address_mode_w: wgpu::AddressMode::Repeat,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Linear,
+            anisotropy_clamp: 1,
+        }
+    }
+
+    /// wgpu only accepts an anisotropy between 1 and 16, and only when every filter is linear
+    fn validated_anisotropy(&self) -> u16 {
+        let anisotropy = self.anisotropy_clamp.clamp(1, Self::MAX_ANISOTROPY);
+        if anisotropy != self.anisotropy_clamp {
+            log::warn!(
+                "Anisotropy of {} is outside the supported range of 1 to {}, using {anisotropy}",
+                self.anisotropy_clamp,
+                Self::MAX_ANISOTROPY
+            );
+        }
+
+        let all_linear = [self.mag_filter, self.min_filter, self.mipmap_filter]
+            .iter()
+            .all(|filter| *filter == wgpu::FilterMode::Linear);
+        if anisotropy > 1 && !all_linear {
+            log::warn!("Anisotropic filtering requires linear filters, disabling it");
+            return 1;
+        }
+
+        anisotropy
+    }
+}
+
 #[derive(Debug)]
 pub struct Texture {
     pub texture: wgpu::Texture,
@@ -8,6 +81,8 @@ pub struct Texture {
 }
 
 impl Texture {
+    /// Preferred depth format, the renderer falls back to others the adapter supports better,
+    /// see [crate::renderer::RendererSettings::depth_format]
     pub const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
 
     pub fn blank_texture(
@@ -33,9 +108,11 @@ impl Texture {
         queue: &wgpu::Queue,
         renderer: &mut imgui_wgpu::Renderer,
         path: String,
-    ) -> (imgui::TextureId, [f32; 2]) {
-        let bytes = std::fs::read(&path).unwrap();
-        let image = image::load_from_memory(&bytes).expect("Invalid image");
+    ) -> std::result::Result<(imgui::TextureId, [f32; 2]), TextureError> {
+        let bytes =
+            std::fs::read(&path).map_err(|_| TextureError::FailedToReadImage(path.clone()))?;
+        let image = image::load_from_memory(&bytes)
+            .map_err(|_| TextureError::InvalidImage(path.clone()))?;
         let image = image.to_rgba8();
         let (width, height) = image.dimensions();
         let raw_data = image.into_raw();
@@ -55,10 +132,10 @@ impl Texture {
 
         texture.write(queue, &raw_data, width, height);
 
-        (
+        Ok((
             renderer.textures.insert(texture),
             [width as f32, height as f32],
-        )
+        ))
     }
 
     pub async fn load_binary(file_name: &str, absolute_path: bool) -> anyhow::Result<Vec<u8>> {
@@ -78,9 +155,85 @@ impl Texture {
         device: &wgpu::Device,
         queue: &wgpu::Queue,
         is_normal_map: bool,
+    ) -> anyhow::Result<Texture> {
+        Self::load_texture_with_sampler(
+            file_name,
+            absolute_path,
+            device,
+            queue,
+            is_normal_map,
+            &SamplerOptions::default(),
+        )
+        .await
+    }
+
+    pub async fn load_texture_with_sampler(
+        file_name: &str,
+        absolute_path: bool,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        is_normal_map: bool,
+        sampler_options: &SamplerOptions,
     ) -> anyhow::Result<Texture> {
         let data = Self::load_binary(file_name, absolute_path).await?;
-        Texture::from_bytes(device, queue, &data, file_name, is_normal_map)
+        Texture::from_bytes_with_sampler(
+            device,
+            queue,
+            &data,
+            file_name,
+            is_normal_map,
+            sampler_options,
+        )
+    }
+
+    /// Loads several textures, reading and decoding the files concurrently on tokio's blocking
+    /// thread pool before uploading them. Needs a tokio runtime
+    pub async fn load_textures(
+        file_names: &[&str],
+        absolute_path: bool,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        is_normal_map: bool,
+    ) -> anyhow::Result<Vec<Texture>> {
+        let paths = file_names
+            .iter()
+            .map(|file_name| {
+                if absolute_path {
+                    std::path::Path::new(file_name).to_path_buf()
+                } else {
+                    std::path::Path::new(&std::env::current_dir().unwrap()).join(file_name)
+                }
+            })
+            .collect();
+        let images = Self::load_concurrently(paths, |path| {
+            Ok(image::load_from_memory(&std::fs::read(path)?)?)
+        })
+        .await;
+
+        file_names
+            .iter()
+            .zip(images)
+            .map(|(file_name, image)| {
+                Texture::from_image(device, queue, &image?, Some(*file_name), is_normal_map)
+            })
+            .collect()
+    }
+
+    /// Runs `load` for every path at once on tokio's blocking thread pool
+    async fn load_concurrently<T: Send + 'static>(
+        paths: Vec<std::path::PathBuf>,
+        load: impl Fn(std::path::PathBuf) -> Result<T> + Send + Sync + 'static,
+    ) -> Vec<Result<T>> {
+        let load = std::sync::Arc::new(load);
+        let loads = paths.into_iter().map(|path| {
+            let load = load.clone();
+            tokio::task::spawn_blocking(move || load(path))
+        });
+        futures::future::join_all(loads)
+            .await
+            .into_iter()
+            .map(|result| result?)
+            .collect()
     }
 
     pub fn from_bytes(
@@ -94,6 +247,26 @@ impl Texture {
         Self::from_image(device, queue, &img, Some(label), is_normal_map)
     }
 
+    pub fn from_bytes_with_sampler(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        bytes: &[u8],
+        label: &str,
+        is_normal_map: bool,
+        sampler_options: &SamplerOptions,
+    ) -> Result<Self> {
+        let img = image::load_from_memory(bytes)?;
+        Self::from_rgba_with_sampler(
+            device,
+            queue,
+            &img.to_rgba8(),
+            Some(label),
+            is_normal_map,
+            false,
+            sampler_options,
+        )
+    }
+
     pub fn from_rgba(
         device: &wgpu::Device,
         queue: &wgpu::Queue,
@@ -101,6 +274,26 @@ impl Texture {
         label: Option<&str>,
         is_normal_map: bool,
         is_storage_texture: bool,
+    ) -> Result<Self> {
+        Self::from_rgba_with_sampler(
+            device,
+            queue,
+            img,
+            label,
+            is_normal_map,
+            is_storage_texture,
+            &SamplerOptions::default(),
+        )
+    }
+
+    pub fn from_rgba_with_sampler(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        img: &image::RgbaImage,
+        label: Option<&str>,
+        is_normal_map: bool,
+        is_storage_texture: bool,
+        sampler_options: &SamplerOptions,
     ) -> Result<Self> {
         let dimensions = img.dimensions();
 
@@ -124,9 +317,11 @@ impl Texture {
             dimension: wgpu::TextureDimension::D2,
             format,
             usage: if is_storage_texture {
+                // Copy source so compute results can be read back
                 wgpu::TextureUsages::STORAGE_BINDING
                     | wgpu::TextureUsages::TEXTURE_BINDING
                     | wgpu::TextureUsages::COPY_DST
+                    | wgpu::TextureUsages::COPY_SRC
             } else {
                 wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST
             },
@@ -151,12 +346,13 @@ impl Texture {
 
         let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
         let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
-            address_mode_u: wgpu::AddressMode::ClampToEdge,
-            address_mode_v: wgpu::AddressMode::ClampToEdge,
-            address_mode_w: wgpu::AddressMode::ClampToEdge,
-            mag_filter: wgpu::FilterMode::Nearest,
-            min_filter: wgpu::FilterMode::Nearest,
-            mipmap_filter: wgpu::FilterMode::Nearest,
+            address_mode_u: sampler_options.address_mode_u,
+            address_mode_v: sampler_options.address_mode_v,
+            address_mode_w: sampler_options.address_mode_w,
+            mag_filter: sampler_options.mag_filter,
+            min_filter: sampler_options.min_filter,
+            mipmap_filter: sampler_options.mipmap_filter,
+            anisotropy_clamp: sampler_options.validated_anisotropy(),
             ..Default::default()
         });
 
@@ -178,24 +374,287 @@ impl Texture {
         Self::from_rgba(device, queue, &rgba, label, is_normal_map, false)
     }
 
+    /// Uploads an image decoded by the `gltf` crate, only 8 bit RGB and RGBA images are supported
+    pub fn from_gltf_image(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        image: &gltf::image::Data,
+        label: Option<&str>,
+    ) -> Result<Self> {
+        let pixels = match image.format {
+            gltf::image::Format::R8G8B8A8 => image.pixels.clone(),
+            gltf::image::Format::R8G8B8 => image
+                .pixels
+                .chunks_exact(3)
+                .flat_map(|pixel| [pixel[0], pixel[1], pixel[2], u8::MAX])
+                .collect(),
+            format => bail!("Unsupported glTF image format {format:?}"),
+        };
+
+        let rgba = image::RgbaImage::from_raw(image.width, image.height, pixels)
+            .context("glTF image data does not match its dimensions")?;
+
+        Self::from_rgba(device, queue, &rgba, label, false, false)
+    }
+
+    /// Loads six square face images into a cube texture. Faces are in the order wgpu expects:
+    /// +X, -X, +Y, -Y, +Z, -Z
+    pub fn load_cubemap(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        face_paths: [&str; 6],
+    ) -> Result<Self> {
+        let faces = face_paths
+            .iter()
+            .map(|path| {
+                let full_path = std::path::Path::new(&std::env::current_dir().unwrap()).join(path);
+                let bytes = std::fs::read(full_path)?;
+                Ok(image::load_from_memory(&bytes)?.to_rgba8())
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let dimensions = faces[0].dimensions();
+        if dimensions.0 != dimensions.1 {
+            bail!(
+                "Cubemap faces must be square, got {}x{}",
+                dimensions.0,
+                dimensions.1
+            );
+        }
+        if faces.iter().any(|face| face.dimensions() != dimensions) {
+            bail!("Cubemap faces must all have the same size");
+        }
+
+        let face_size = wgpu::Extent3d {
+            width: dimensions.0,
+            height: dimensions.1,
+            depth_or_array_layers: 1,
+        };
+
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Cubemap Texture"),
+            size: wgpu::Extent3d {
+                depth_or_array_layers: 6,
+                ..face_size
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+
+        for (layer, face) in faces.iter().enumerate() {
+            queue.write_texture(
+                wgpu::ImageCopyTexture {
+                    texture: &texture,
+                    mip_level: 0,
+                    origin: wgpu::Origin3d {
+                        x: 0,
+                        y: 0,
+                        z: layer as u32,
+                    },
+                    aspect: wgpu::TextureAspect::All,
+                },
+                face,
+                wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(4 * dimensions.0),
+                    rows_per_image: Some(dimensions.1),
+                },
+                face_size,
+            );
+        }
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor {
+            label: Some("Cubemap View"),
+            dimension: Some(wgpu::TextureViewDimension::Cube),
+            ..Default::default()
+        });
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+
+        Ok(Self {
+            texture,
+            view,
+            sampler,
+        })
+    }
+
+    /// Loads a BC3 or BC7 compressed KTX2 file and uploads every mip level as is. Requires the
+    /// device to have [wgpu::Features::TEXTURE_COMPRESSION_BC], which the renderer enables
+    /// whenever the adapter supports it
+    pub fn load_ktx2(device: &wgpu::Device, queue: &wgpu::Queue, path: &str) -> Result<Self> {
+        if !device
+            .features()
+            .contains(wgpu::Features::TEXTURE_COMPRESSION_BC)
+        {
+            bail!("Cannot load {path}, the adapter does not support BC texture compression");
+        }
+
+        let full_path = std::path::Path::new(&std::env::current_dir().unwrap()).join(path);
+        let bytes = std::fs::read(full_path)?;
+        let reader = ktx2::Reader::new(&bytes[..])
+            .map_err(|err| anyhow!("Invalid KTX2 file {path}: {err:?}"))?;
+        let header = reader.header();
+
+        if header.supercompression_scheme.is_some() {
+            bail!("Supercompressed KTX2 files are not supported ({path})");
+        }
+        if header.layer_count > 1 || header.face_count > 1 || header.pixel_depth > 1 {
+            bail!("Only single 2D KTX2 textures are supported ({path})");
+        }
+
+        let format = match header.format {
+            Some(ktx2::Format::BC7_UNORM_BLOCK) => wgpu::TextureFormat::Bc7RgbaUnorm,
+            Some(ktx2::Format::BC7_SRGB_BLOCK) => wgpu::TextureFormat::Bc7RgbaUnormSrgb,
+            Some(ktx2::Format::BC3_UNORM_BLOCK) => wgpu::TextureFormat::Bc3RgbaUnorm,
+            Some(ktx2::Format::BC3_SRGB_BLOCK) => wgpu::TextureFormat::Bc3RgbaUnormSrgb,
+            format => bail!("Unsupported KTX2 format {format:?} in {path}, expected BC3 or BC7"),
+        };
+
+        let (block_width, block_height) = format.block_dimensions();
+        let block_size = format.block_copy_size(None).unwrap();
+
+        if header.pixel_width % block_width != 0 || header.pixel_height % block_height != 0 {
+            bail!(
+                "{path} is {}x{}, compressed textures must be a multiple of {block_width}x{block_height}",
+                header.pixel_width,
+                header.pixel_height
+            );
+        }
+
+        let size = wgpu::Extent3d {
+            width: header.pixel_width,
+            height: header.pixel_height,
+            depth_or_array_layers: 1,
+        };
+        let mip_level_count = header.level_count.max(1);
+
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(path),
+            size,
+            mip_level_count,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+
+        for (mip_level, level_data) in reader.levels().enumerate() {
+            let mip_size = size.mip_level_size(mip_level as u32, wgpu::TextureDimension::D2);
+            // Mips smaller than a block still occupy a whole block
+            let physical_size = mip_size.physical_size(format);
+            let blocks_per_row = physical_size.width / block_width;
+            let block_rows = physical_size.height / block_height;
+
+            queue.write_texture(
+                wgpu::ImageCopyTexture {
+                    texture: &texture,
+                    mip_level: mip_level as u32,
+                    origin: wgpu::Origin3d::ZERO,
+                    aspect: wgpu::TextureAspect::All,
+                },
+                level_data,
+                wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(blocks_per_row * block_size),
+                    rows_per_image: Some(block_rows),
+                },
+                physical_size,
+            );
+        }
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        Ok(Self {
+            texture,
+            view,
+            sampler,
+        })
+    }
+
     pub fn create_depth_texture(
         device: &wgpu::Device,
         config: &wgpu::SurfaceConfiguration,
         label: &str,
     ) -> Self {
+        Self::create_depth_texture_with_size(device, (config.width, config.height), label)
+    }
+
+    pub fn create_depth_texture_with_size(
+        device: &wgpu::Device,
+        dimensions: (u32, u32),
+        label: &str,
+    ) -> Self {
+        Self::create_depth_texture_with_samples(device, dimensions, 1, label)
+    }
+
+    /// Depth texture for a multisampled render target, `sample_count` has to match the color
+    /// target's. Multisampled depth can't be copied out, only read with `textureLoad`
+    pub fn create_depth_texture_with_samples(
+        device: &wgpu::Device,
+        dimensions: (u32, u32),
+        sample_count: u32,
+        label: &str,
+    ) -> Self {
+        Self::create_depth_texture_with_format(
+            device,
+            dimensions,
+            sample_count,
+            Self::DEPTH_FORMAT,
+            label,
+        )
+    }
+
+    /// Same as [Texture::create_depth_texture_with_samples] in another depth format, e.g.
+    /// [crate::renderer::Renderer::depth_format]. [wgpu::TextureFormat::Depth24Plus] textures
+    /// can't be copied out
+    pub fn create_depth_texture_with_format(
+        device: &wgpu::Device,
+        dimensions: (u32, u32),
+        sample_count: u32,
+        format: wgpu::TextureFormat,
+        label: &str,
+    ) -> Self {
+        let copy_usage = if format == wgpu::TextureFormat::Depth24Plus {
+            wgpu::TextureUsages::empty()
+        } else {
+            wgpu::TextureUsages::COPY_SRC
+        };
         let size = wgpu::Extent3d {
-            width: config.width,
-            height: config.height,
+            width: dimensions.0,
+            height: dimensions.1,
             depth_or_array_layers: 1,
         };
         let desc = wgpu::TextureDescriptor {
             label: Some(label),
             size,
             mip_level_count: 1,
-            sample_count: 1,
+            sample_count,
             dimension: wgpu::TextureDimension::D2,
-            format: Self::DEPTH_FORMAT,
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                | wgpu::TextureUsages::TEXTURE_BINDING
+                | copy_usage,
             view_formats: &[],
         };
         let texture = device.create_texture(&desc);
@@ -220,4 +679,299 @@ impl Texture {
             sampler,
         }
     }
+
+    /// Layout for reading a depth texture at binding 0 as `texture_depth_2d` with `textureLoad`,
+    /// e.g. for fog, SSAO or soft particles. A texture can't be sampled in the same pass that
+    /// writes to it, so shaders reading the scene's depth have to run in a separate pass after
+    /// the scene has been drawn
+    pub fn depth_bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Depth Bind Group Layout"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    sample_type: wgpu::TextureSampleType::Depth,
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    multisampled: false,
+                },
+                count: None,
+            }],
+        })
+    }
+
+    pub fn create_depth_bind_group(
+        &self,
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Depth Bind Group"),
+            layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(&self.view),
+            }],
+        })
+    }
+
+    /// Layout for a depth texture at binding 0 and its comparison sampler at binding 1
+    /// (`texture_depth_2d` and `sampler_comparison` in WGSL), letting shadow shaders do
+    /// hardware PCF with `textureSampleCompare`
+    pub fn depth_comparison_bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Depth Comparison Bind Group Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Depth,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Comparison),
+                    count: None,
+                },
+            ],
+        })
+    }
+
+    /// Binds a depth texture made with [Texture::create_depth_texture] (whose sampler already
+    /// compares with `LessEqual`) to a [Texture::depth_comparison_bind_group_layout]
+    pub fn create_depth_comparison_bind_group(
+        &self,
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Depth Comparison Bind Group"),
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&self.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&self.sampler),
+                },
+            ],
+        })
+    }
+
+    /// Copies an 8 bit RGBA or BGRA texture (which needs `COPY_SRC`) into an image after the
+    /// commands already recorded in `encoder`, then submits and waits for the GPU. The bytes are
+    /// kept as they are, so sRGB textures give an sRGB image like the one shown on screen.
+    /// Returns `None` for other formats or if the buffer couldn't be mapped
+    pub fn read_to_image(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        mut encoder: wgpu::CommandEncoder,
+        texture: &wgpu::Texture,
+    ) -> Option<image::RgbaImage> {
+        let is_bgra = match texture.format() {
+            wgpu::TextureFormat::Rgba8Unorm | wgpu::TextureFormat::Rgba8UnormSrgb => false,
+            wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb => true,
+            _ => return None,
+        };
+        let (width, height) = (texture.width(), texture.height());
+
+        // Rows in the readback buffer are padded to the copy alignment
+        let unpadded_bytes_per_row = width * 4;
+        let padded_bytes_per_row = unpadded_bytes_per_row
+            .div_ceil(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT)
+            * wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Texture Readback Buffer"),
+            size: (padded_bytes_per_row * height) as u64,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        encoder.copy_texture_to_buffer(
+            texture.as_image_copy(),
+            wgpu::ImageCopyBuffer {
+                buffer: &buffer,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(padded_bytes_per_row),
+                    rows_per_image: Some(height),
+                },
+            },
+            texture.size(),
+        );
+        queue.submit(std::iter::once(encoder.finish()));
+
+        let buffer_slice = buffer.slice(..);
+        let (sender, receiver) = flume::bounded(1);
+        buffer_slice.map_async(wgpu::MapMode::Read, move |v| {
+            let _ = sender.send(v);
+        });
+        device.poll(wgpu::Maintain::Wait);
+        if !matches!(receiver.recv(), Ok(Ok(()))) {
+            return None;
+        }
+
+        let padded_data = buffer_slice.get_mapped_range();
+        let mut pixels = padded_data
+            .chunks_exact(padded_bytes_per_row as usize)
+            .flat_map(|row| &row[..unpadded_bytes_per_row as usize])
+            .copied()
+            .collect::<Vec<u8>>();
+        drop(padded_data);
+        buffer.unmap();
+
+        if is_bgra {
+            for pixel in pixels.chunks_exact_mut(4) {
+                pixel.swap(0, 2);
+            }
+        }
+
+        image::RgbaImage::from_raw(width, height, pixels)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        path::PathBuf,
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+        time::Duration,
+    };
+
+    use super::*;
+    use crate::{
+        renderer::Renderer,
+        test_utils::{headless_device, shader_dir, with_engine_systems},
+        ui_manager::UiManager,
+    };
+
+    #[test]
+    fn loads_overlap() {
+        let running = Arc::new(AtomicUsize::new(0));
+        let most_running = Arc::new(AtomicUsize::new(0));
+        let paths = (0..4)
+            .map(|index| PathBuf::from(index.to_string()))
+            .collect();
+
+        let load = {
+            let running = running.clone();
+            let most_running = most_running.clone();
+            move |path: PathBuf| {
+                most_running
+                    .fetch_max(running.fetch_add(1, Ordering::SeqCst) + 1, Ordering::SeqCst);
+                std::thread::sleep(Duration::from_millis(50));
+                running.fetch_sub(1, Ordering::SeqCst);
+                Ok(path)
+            }
+        };
+        let loaded = tokio::runtime::Runtime::new()
+            .unwrap()
+            .block_on(Texture::load_concurrently(paths, load));
+
+        assert!(most_running.load(Ordering::SeqCst) > 1);
+        let loaded = loaded
+            .into_iter()
+            .map(|path| path.unwrap().to_str().unwrap().to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(loaded, ["0", "1", "2", "3"]);
+    }
+
+    #[test]
+    fn depth_textures_bind_for_comparison_sampling() {
+        let Some((device, _)) = headless_device(wgpu::Features::empty()) else {
+            return;
+        };
+        device.push_error_scope(wgpu::ErrorFilter::Validation);
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Shadow Test Shader"),
+            source: wgpu::ShaderSource::Wgsl(
+                "
+                @group(0) @binding(0) var shadow_map: texture_depth_2d;
+                @group(0) @binding(1) var shadow_sampler: sampler_comparison;
+
+                @vertex
+                fn vs_main(@builtin(vertex_index) index: u32) -> @builtin(position) vec4<f32> {
+                    return vec4<f32>(f32(index % 2u), f32(index / 2u), 0.0, 1.0);
+                }
+
+                @fragment
+                fn fs_main() -> @location(0) vec4<f32> {
+                    let lit = textureSampleCompare(shadow_map, shadow_sampler, vec2<f32>(0.5, 0.5), 0.5);
+                    return vec4<f32>(lit, lit, lit, 1.0);
+                }
+                "
+                .into(),
+            ),
+        });
+        let depth_texture =
+            Texture::create_depth_texture_with_size(&device, (16, 16), "Shadow Map");
+        let layout = Texture::depth_comparison_bind_group_layout(&device);
+        let _bind_group = depth_texture.create_depth_comparison_bind_group(&device, &layout);
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Shadow Test Pipeline Layout"),
+            bind_group_layouts: &[&layout],
+            push_constant_ranges: &[],
+        });
+        let _pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Shadow Test Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                compilation_options: Default::default(),
+                buffers: &[],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                compilation_options: Default::default(),
+                targets: &[Some(wgpu::TextureFormat::Rgba8Unorm.into())],
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        });
+        let error = pollster::block_on(device.pop_error_scope());
+
+        assert!(error.is_none(), "{error:?}");
+    }
+
+    #[test]
+    fn bad_ui_images_are_errors() {
+        // imgui contexts can't be created on several threads at once
+        with_engine_systems(|_| {
+            let Some((device, queue)) = headless_device(wgpu::Features::empty()) else {
+                return;
+            };
+            let ui_manager =
+                UiManager::without_window(Renderer::HEADLESS_FORMAT, device.clone(), queue.clone());
+            let mut renderer = ui_manager.imgui_renderer.lock().unwrap();
+            let dir = shader_dir("bad_ui_images", &[("not_an_image.png", "not an image")]);
+            let path = |name: &str| dir.join(name).to_str().unwrap().to_string();
+
+            let missing =
+                Texture::load_ui_image(&device, &queue, &mut renderer, path("missing.png"));
+            assert!(
+                matches!(&missing, Err(TextureError::FailedToReadImage(failed)) if *failed == path("missing.png")),
+                "{missing:?}"
+            );
+            let invalid =
+                Texture::load_ui_image(&device, &queue, &mut renderer, path("not_an_image.png"));
+            assert!(
+                matches!(&invalid, Err(TextureError::InvalidImage(failed)) if *failed == path("not_an_image.png")),
+                "{invalid:?}"
+            );
+        });
+    }
 }
@@ -7,10 +7,10 @@ use std::{
     sync::{Arc, Mutex},
 };
 
-use imgui::{Context, FontId};
+use imgui::{ClipboardBackend, Context, FontId};
 use imgui_sdl2_support::SdlPlatform;
 use imgui_wgpu::{Renderer, RendererConfig};
-use sdl2::video::Window;
+use sdl2::{clipboard::ClipboardUtil, video::Window};
 use wgpu::{Device, Queue, TextureFormat};
 
 #[derive(Debug)]
@@ -18,6 +18,24 @@ pub enum UiError {
     FontFileLoadingError,
 }
 
+/// Lets imgui's text widgets copy and paste through the system clipboard
+struct SdlClipboard(ClipboardUtil);
+
+impl ClipboardBackend for SdlClipboard {
+    fn get(&mut self) -> Option<String> {
+        if !self.0.has_clipboard_text() {
+            return None;
+        }
+        self.0.clipboard_text().ok()
+    }
+
+    fn set(&mut self, value: &str) {
+        if let Err(err) = self.0.set_clipboard_text(value) {
+            log::warn!("Failed to set the clipboard: {err}");
+        }
+    }
+}
+
 #[allow(unused)]
 pub struct UiManager {
     pub imgui_context: Rc<Mutex<Context>>,
@@ -25,6 +43,10 @@ pub struct UiManager {
     pub imgui_platform: Rc<Mutex<SdlPlatform>>,
 
     pub font_ids: HashMap<String, FontId>,
+
+    texture_format: TextureFormat,
+    device: Arc<Device>,
+    queue: Arc<Queue>,
 }
 
 impl UiManager {
@@ -33,6 +55,21 @@ impl UiManager {
         device: Arc<Device>,
         queue: Arc<Queue>,
         window: &Window,
+    ) -> Self {
+        let ui_manager = Self::without_window(texture_format, device, queue);
+        ui_manager
+            .imgui_context
+            .lock()
+            .unwrap()
+            .set_clipboard_backend(SdlClipboard(window.subsystem().clipboard()));
+        ui_manager
+    }
+
+    /// Leaves imgui without the system clipboard, for running without a window
+    pub(crate) fn without_window(
+        texture_format: TextureFormat,
+        device: Arc<Device>,
+        queue: Arc<Queue>,
     ) -> Self {
         let config = RendererConfig {
             texture_format,
@@ -55,26 +92,53 @@ impl UiManager {
             imgui_renderer: Rc::new(Mutex::new(imgui_renderer)),
             imgui_platform: Rc::new(Mutex::new(imgui_platform)),
             font_ids: HashMap::new(),
+            texture_format,
+            device,
+            queue,
         }
     }
 
+    /// Format of the target the UI is drawn into, the scenes' text goes into the same one
+    pub fn texture_format(&self) -> TextureFormat {
+        self.texture_format
+    }
+
+    /// Adds a font to the atlas and rebuilds the font texture so it can be used right away
     pub fn load_font(
         &mut self,
         font_name: &str,
         path: String,
         size_pixels: f32,
     ) -> Result<FontId, UiError> {
-        let mut imgui_context = self.imgui_context.lock().unwrap();
         let bytes = Self::read_ttf_bytes(path)?;
-        let font_id = imgui_context
-            .fonts()
-            .add_font(&[imgui::FontSource::TtfData {
+        Ok(self.add_font(
+            font_name,
+            &[imgui::FontSource::TtfData {
                 data: &bytes,
                 size_pixels,
                 config: None,
-            }]);
+            }],
+        ))
+    }
+
+    /// Adds a font made of `sources` to the atlas under `font_name` and rebuilds the font
+    /// texture, like [UiManager::load_font] for fonts that aren't in a file
+    pub fn add_font(&mut self, font_name: &str, sources: &[imgui::FontSource]) -> FontId {
+        let font_id = self.imgui_context.lock().unwrap().fonts().add_font(sources);
         self.font_ids.insert(font_name.to_string(), font_id);
-        Ok(font_id)
+        self.rebuild_font_atlas();
+        font_id
+    }
+
+    /// Recreates the imgui font texture from the current font atlas. Needed after adding fonts
+    /// to the atlas directly, [UiManager::load_font] already does it
+    pub fn rebuild_font_atlas(&self) {
+        let mut imgui_context = self.imgui_context.lock().unwrap();
+        self.imgui_renderer.lock().unwrap().reload_font_texture(
+            &mut imgui_context,
+            &self.device,
+            &self.queue,
+        );
     }
 
     pub fn read_ttf_bytes(path: String) -> Result<Vec<u8>, UiError> {
@@ -114,3 +178,36 @@ impl std::fmt::Debug for UiManager {
             .finish()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::headless_device;
+
+    #[test]
+    fn adding_a_font_rebuilds_the_atlas_texture() {
+        let Some((device, queue)) = headless_device(wgpu::Features::empty()) else {
+            return;
+        };
+        let mut ui_manager =
+            UiManager::without_window(TextureFormat::Rgba8UnormSrgb, device, queue);
+        ui_manager.rebuild_font_atlas();
+        let initial_texture = ui_manager.imgui_context.lock().unwrap().fonts().tex_id;
+
+        let font_id = ui_manager.add_font(
+            "large",
+            &[imgui::FontSource::DefaultFontData {
+                config: Some(imgui::FontConfig {
+                    size_pixels: 26.0,
+                    ..Default::default()
+                }),
+            }],
+        );
+
+        let mut imgui_context = ui_manager.imgui_context.lock().unwrap();
+        let fonts = imgui_context.fonts();
+        assert_ne!(fonts.tex_id, initial_texture);
+        assert!(fonts.get_font(font_id).is_some());
+        assert_eq!(ui_manager.font_ids.get("large"), Some(&font_id));
+    }
+}
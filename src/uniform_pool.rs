@@ -0,0 +1,230 @@
+use std::{cell::RefCell, num::NonZeroU64, rc::Rc};
+
+use wgpu::{BindGroup, BindGroupLayout, Buffer, Device, Queue, ShaderStages};
+
+use crate::ecs::material::MaterialError;
+
+/// One uniform buffer shared by many materials, each writing to its own slot that's bound with
+/// a dynamic offset. Saves a buffer and a bind group per material when there are many small
+/// uniforms, see [crate::ecs::material::Material::new_with_pooled_uniform]
+#[derive(Debug)]
+pub struct UniformPool {
+    buffer: Buffer,
+    bind_group: BindGroup,
+    /// Largest uniform a slot holds, in bytes
+    slot_size: u64,
+    /// Distance between slots, the slot size rounded up to the device's
+    /// `min_uniform_buffer_offset_alignment`
+    stride: u64,
+    free_slots: RefCell<Vec<u32>>,
+}
+
+impl UniformPool {
+    /// A pool of `capacity` slots holding uniforms of up to `slot_size` bytes, rounded up to a
+    /// multiple of 16
+    pub fn new(device: &Device, slot_size: u64, capacity: u32) -> Rc<Self> {
+        let alignment = device.limits().min_uniform_buffer_offset_alignment as u64;
+        // Uniform structs are 16 byte aligned in WGSL, so their sizes are multiples of 16
+        let slot_size = slot_size.max(1).div_ceil(16) * 16;
+        let stride = slot_size.div_ceil(alignment) * alignment;
+
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Uniform Pool Buffer"),
+            size: stride * capacity.max(1) as u64,
+            usage: wgpu::BufferUsages::UNIFORM
+                | wgpu::BufferUsages::COPY_DST
+                | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Uniform Pool Bind Group"),
+            layout: &Self::bind_group_layout(device),
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                    buffer: &buffer,
+                    offset: 0,
+                    size: NonZeroU64::new(slot_size),
+                }),
+            }],
+        });
+
+        Rc::new(UniformPool {
+            buffer,
+            bind_group,
+            slot_size,
+            stride,
+            free_slots: RefCell::new((0..capacity).rev().collect()),
+        })
+    }
+
+    /// Matches the uniform bind group of pipelines for pooled materials
+    pub fn bind_group_layout(device: &Device) -> BindGroupLayout {
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Uniform Pool Bind Group Layout"),
+            entries: &[Self::bind_group_layout_entry()],
+        })
+    }
+
+    pub(crate) fn bind_group_layout_entry() -> wgpu::BindGroupLayoutEntry {
+        wgpu::BindGroupLayoutEntry {
+            binding: 0,
+            visibility: ShaderStages::VERTEX_FRAGMENT,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: true,
+                min_binding_size: None,
+            },
+            count: None,
+        }
+    }
+
+    /// Takes a free slot and writes `data` to it
+    pub fn allocate(
+        self: &Rc<Self>,
+        queue: &Queue,
+        data: &[u8],
+    ) -> Result<PooledUniform, MaterialError> {
+        let size = data.len() as u64;
+        if size > self.slot_size {
+            return Err(MaterialError::UniformTooLarge {
+                slot_size: self.slot_size,
+                actual: size,
+            });
+        }
+        let slot = self
+            .free_slots
+            .borrow_mut()
+            .pop()
+            .ok_or(MaterialError::UniformPoolFull)?;
+
+        let uniform = PooledUniform {
+            pool: self.clone(),
+            slot,
+            size,
+        };
+        uniform.write(queue, data);
+        Ok(uniform)
+    }
+
+    pub fn free_slot_count(&self) -> usize {
+        self.free_slots.borrow().len()
+    }
+
+    pub fn slot_size(&self) -> u64 {
+        self.slot_size
+    }
+
+    pub fn buffer(&self) -> &Buffer {
+        &self.buffer
+    }
+
+    pub fn bind_group(&self) -> &BindGroup {
+        &self.bind_group
+    }
+}
+
+/// A slot of a [UniformPool], freed again when dropped
+#[derive(Debug)]
+pub struct PooledUniform {
+    pool: Rc<UniformPool>,
+    slot: u32,
+    /// Size of the uniform written at allocation, in bytes
+    size: u64,
+}
+
+impl PooledUniform {
+    /// Byte offset of the slot in the pool's buffer, the dynamic offset it's bound with
+    pub fn offset(&self) -> u64 {
+        self.slot as u64 * self.pool.stride
+    }
+
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    pub fn pool(&self) -> &UniformPool {
+        &self.pool
+    }
+
+    pub(crate) fn write(&self, queue: &Queue, data: &[u8]) {
+        queue.write_buffer(&self.pool.buffer, self.offset(), data);
+    }
+}
+
+impl Drop for PooledUniform {
+    fn drop(&mut self) {
+        self.pool.free_slots.borrow_mut().push(self.slot);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{headless_device, read_buffer};
+
+    #[test]
+    fn slot_sizes_round_up_to_16_bytes() {
+        let Some((device, _)) = headless_device(wgpu::Features::empty()) else {
+            return;
+        };
+        assert_eq!(UniformPool::new(&device, 0, 1).slot_size(), 16);
+        assert_eq!(UniformPool::new(&device, 16, 1).slot_size(), 16);
+        assert_eq!(UniformPool::new(&device, 20, 1).slot_size(), 32);
+    }
+
+    #[test]
+    fn sub_allocations_hold_their_own_values() {
+        let Some((device, queue)) = headless_device(wgpu::Features::empty()) else {
+            return;
+        };
+        let pool = UniformPool::new(&device, 20, 4);
+        let first_data = [1.0_f32, 2.0, 3.0, 4.0, 5.0];
+        let second_data = [-1.0_f32, -2.0, -3.0, -4.0, -5.0];
+        let first = pool
+            .allocate(&queue, bytemuck::cast_slice(&first_data))
+            .unwrap();
+        let second = pool
+            .allocate(&queue, bytemuck::cast_slice(&second_data))
+            .unwrap();
+        assert_ne!(first.offset(), second.offset());
+        assert_eq!(
+            first.offset() % device.limits().min_uniform_buffer_offset_alignment as u64,
+            0
+        );
+        assert_eq!(pool.free_slot_count(), 2);
+
+        let contents = read_buffer(&device, &queue, pool.buffer());
+        let read = |uniform: &PooledUniform| {
+            let start = uniform.offset() as usize;
+            bytemuck::pod_collect_to_vec::<u8, f32>(
+                &contents[start..start + uniform.size() as usize],
+            )
+        };
+        assert_eq!(read(&first), first_data);
+        assert_eq!(read(&second), second_data);
+
+        drop(first);
+        assert_eq!(pool.free_slot_count(), 3);
+    }
+
+    #[test]
+    fn oversized_uniforms_are_rejected() {
+        let Some((device, queue)) = headless_device(wgpu::Features::empty()) else {
+            return;
+        };
+        let pool = UniformPool::new(&device, 16, 1);
+        assert!(matches!(
+            pool.allocate(&queue, &[0; 17]),
+            Err(MaterialError::UniformTooLarge {
+                slot_size: 16,
+                actual: 17
+            })
+        ));
+        let _slot = pool.allocate(&queue, &[0; 16]).unwrap();
+        assert!(matches!(
+            pool.allocate(&queue, &[0; 16]),
+            Err(MaterialError::UniformPoolFull)
+        ));
+    }
+}